@@ -0,0 +1,459 @@
+//! This module provides `Config`, the user's settings for the program,
+//! which are stored as TOML in `config.toml` in the configuration directory
+//! chosen by `app_dirs` (e.g. `~/.config/reading` on Linux). A different
+//! file can be used by setting the `READING_CONFIG` environment variable.
+//!
+//! The settings only give defaults: options given on the command line always
+//! take precedence over them. A missing configuration file is the same as an
+//! empty one, and every setting is optional (`reading init` writes a file
+//! listing them all, commented out; see `DEFAULT_CONFIG`). The supported
+//! settings are:
+//!
+//! * `no_ansi`: whether to disable fancy text output (`true` or `false`)
+//! * `count`: the number of entries shown by `reading view`
+//! * `plans_dir`: the directory containing the plans (see
+//!   `files::plans_dir`)
+//! * `export_format`: the format plans are exported in (`text`, `csv` or `html`)
+//! * `theme`: the name of the color theme used for text output
+//! * `sync_url`: the URL of the directory plans are synced with by `reading
+//!   sync` (only available with the `sync` feature)
+//! * `auto_archive`: whether `reading done` archives a plan when its last
+//!   entry is finished (`true` or `false`)
+//! * `encryption_key`: the key encrypted plans are read and written with, as
+//!   64 hexadecimal digits (only used with the `encryption` feature; see the
+//!   `crypto` module)
+//! * `encrypt`: whether new plans are encrypted (`true` or `false`; set by
+//!   `reading encrypt`)
+//! * `reader`: the name of the reader whose progress is shown and changed
+//!   by `reading view`, `next`, `previous` and `skip` (see the
+//!   `plan::reader` module)
+//! * `backups`: the number of backups kept of each plan (0 to keep none;
+//!   see the `files` module)
+//!
+//! Reminders for `reading remind` can be given in the configuration file as
+//! a list of rules (see the `remind` module), for example:
+//!
+//! ```toml
+//! reminders = ["daily at 21:00 if no entry completed today", "Sunday at 09:00"]
+//! ```
+//!
+//! Color themes can be defined in the configuration file as tables under
+//! `themes`, giving the style of each kind of text (see `Theme::ROLES`) as
+//! a description such as `bold yellow`, for example:
+//!
+//! ```toml
+//! theme = "dark"
+//!
+//! [themes.dark]
+//! title = "bold cyan"
+//! muted = "dimmed"
+//! ```
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use app_dirs::{self, AppDataType};
+use toml;
+
+use super::errors::*;
+use super::files::APP_INFO;
+use super::remind::Reminder;
+
+/// The environment variable which can be used to override the location of
+/// the configuration file.
+pub const CONFIG_VAR: &str = "READING_CONFIG";
+
+/// The formats plans can be exported in, as used by the `export_format`
+/// setting.
+pub const EXPORT_FORMATS: &[&str] = &["text", "csv", "html"];
+
+/// The configuration file written by `Config::init`, which lists the
+/// settings with their defaults, all commented out, so that it reads the
+/// same as an empty file.
+pub const DEFAULT_CONFIG: &str = r#"# Settings for `reading`. Each setting is optional: uncomment a line to
+# change it, or use `reading config set {key} {value}`.
+
+# Whether to disable fancy text output
+# no_ansi = false
+
+# The number of entries shown by `reading view`
+# count = 5
+
+# The directory containing the plans
+# plans_dir = "/path/to/plans"
+
+# The format plans are exported in ("text", "csv" or "html")
+# export_format = "text"
+
+# The name of the color theme used for text output
+# theme = "default"
+
+# Whether `reading done` archives a plan when its last entry is finished
+# auto_archive = false
+
+# The name of the reader whose progress is shown and changed
+# reader = "me"
+
+# The number of backups kept of each plan
+# backups = 10
+
+# Reminders for `reading remind`
+# reminders = ["daily at 21:00 if no entry completed today"]
+"#;
+
+/// The user's settings.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone)]
+#[serde(default)]
+pub struct Config {
+    no_ansi: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    plans_dir: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    export_format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    theme: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sync_url: Option<String>,
+    auto_archive: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    encryption_key: Option<String>,
+    encrypt: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reader: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backups: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    reminders: Vec<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    themes: BTreeMap<String, Theme>,
+}
+
+/// A color theme defined in the configuration file, giving the style of
+/// each kind of text as a description such as `bold red` or `italic`.
+///
+/// Kinds of text which aren't given keep their default style. The styles
+/// are only descriptions: they are interpreted by the program printing the
+/// text.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone)]
+#[serde(default)]
+pub struct Theme {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    normal: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    highlight: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    muted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    progress: Option<String>,
+}
+
+impl Config {
+    /// The names of all the settings.
+    pub const KEYS: &'static [&'static str] = &["no_ansi", "count", "plans_dir", "export_format", "theme", "sync_url",
+                                                       "auto_archive", "encryption_key", "encrypt", "reader",
+                                                       "backups"];
+
+    /// Reads the configuration file (see `config_path`), returning the
+    /// default settings if it doesn't exist.
+    pub fn load() -> Result<Config> {
+        Config::load_from(&config_path()?)
+    }
+
+    /// Reads the configuration file at the given path, returning the default
+    /// settings if it doesn't exist.
+    pub fn load_from(path: &Path) -> Result<Config> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let file = File::open(path).chain_err(|| format!("could not open config file {}", path.display()))?;
+        Config::read(file).chain_err(|| format!("invalid config file {}", path.display()))
+    }
+
+    /// Writes the default configuration file (`DEFAULT_CONFIG`) if there
+    /// isn't one yet, creating its directory if necessary, and returns
+    /// whether it was written.
+    pub fn init() -> Result<bool> {
+        Config::init_at(&config_path()?)
+    }
+
+    /// Writes the default configuration file to the given path, as for
+    /// `init`.
+    pub fn init_at(path: &Path) -> Result<bool> {
+        if path.exists() {
+            return Ok(false);
+        }
+        write_config_file(path, DEFAULT_CONFIG.as_bytes())?;
+        Ok(true)
+    }
+
+    /// Writes the settings to the configuration file, creating its
+    /// directory if necessary.
+    ///
+    /// Since the file may hold the only copy of the encryption key, it is
+    /// replaced all at once (see `write_config_file`), so that the old
+    /// settings are kept if writing the new ones fails.
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&config_path()?)
+    }
+
+    /// Writes the settings to the configuration file at the given path, as
+    /// for `save`.
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        let mut text = Vec::new();
+        self.write(&mut text)?;
+        write_config_file(path, &text)
+    }
+
+    /// Reads settings from TOML input.
+    pub fn read<R: Read>(mut input: R) -> Result<Config> {
+        let mut text = String::new();
+        input.read_to_string(&mut text).chain_err(|| "could not read config")?;
+        let config: Config = toml::from_str(&text).chain_err(|| "invalid toml")?;
+
+        if let Some(format) = config.export_format() {
+            if !EXPORT_FORMATS.contains(&format) {
+                bail!(ErrorKind::InvalidSettingValue("export_format".into(), format.into()));
+            }
+        }
+        config.reminders()?;
+        Ok(config)
+    }
+
+    /// Writes the settings as TOML.
+    pub fn write<W: Write>(&self, mut output: W) -> Result<()> {
+        let text = toml::to_string(self).chain_err(|| "could not serialize config")?;
+        output.write_all(text.as_bytes()).chain_err(|| "could not write config")
+    }
+
+    /// Returns whether fancy text output is disabled.
+    pub fn no_ansi(&self) -> bool {
+        self.no_ansi
+    }
+
+    /// Returns the number of entries shown by `reading view`, if set.
+    pub fn count(&self) -> Option<u32> {
+        self.count
+    }
+
+    /// Returns the plans directory, if set.
+    pub fn plans_dir(&self) -> Option<&Path> {
+        self.plans_dir.as_deref()
+    }
+
+    /// Returns the format plans are exported in, if set (one of
+    /// `EXPORT_FORMATS`).
+    pub fn export_format(&self) -> Option<&str> {
+        self.export_format.as_deref()
+    }
+
+    /// Returns the name of the color theme, if set.
+    pub fn theme(&self) -> Option<&str> {
+        self.theme.as_deref()
+    }
+
+    /// Returns the URL of the directory plans are synced with, if set.
+    pub fn sync_url(&self) -> Option<&str> {
+        self.sync_url.as_deref()
+    }
+
+    /// Returns whether plans are archived when they are finished with
+    /// `reading done`.
+    pub fn auto_archive(&self) -> bool {
+        self.auto_archive
+    }
+
+    /// Returns the key encrypted plans are read and written with, if set.
+    pub fn encryption_key(&self) -> Option<&str> {
+        self.encryption_key.as_deref()
+    }
+
+    /// Returns whether new plans are encrypted.
+    pub fn encrypt(&self) -> bool {
+        self.encrypt
+    }
+
+    /// Returns the name of the reader whose progress is used by default, if
+    /// set.
+    pub fn reader(&self) -> Option<&str> {
+        self.reader.as_deref()
+    }
+
+    /// Returns the number of backups kept of each plan, if set.
+    pub fn backups(&self) -> Option<u32> {
+        self.backups
+    }
+
+    /// Returns the reminders given in the configuration file, returning an
+    /// `InvalidReminder` error if any of them is invalid.
+    pub fn reminders(&self) -> Result<Vec<Reminder>> {
+        self.reminders.iter().map(|r| r.parse()).collect()
+    }
+
+    /// Returns the color themes defined in the configuration file, by name.
+    pub fn themes(&self) -> &BTreeMap<String, Theme> {
+        &self.themes
+    }
+
+    /// Returns the value of the setting with the given name (one of
+    /// `KEYS`) as text, or `None` if it isn't set.
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        Ok(match key {
+            "no_ansi" => Some(self.no_ansi.to_string()),
+            "count" => self.count.map(|c| c.to_string()),
+            "plans_dir" => {
+                match self.plans_dir {
+                    Some(ref dir) => Some(dir.to_str().ok_or_else(|| ErrorKind::NonUtf8Path(dir.clone()))?.to_owned()),
+                    None => None,
+                }
+            }
+            "export_format" => self.export_format.clone(),
+            "theme" => self.theme.clone(),
+            "sync_url" => self.sync_url.clone(),
+            "auto_archive" => Some(self.auto_archive.to_string()),
+            "encryption_key" => self.encryption_key.clone(),
+            "encrypt" => Some(self.encrypt.to_string()),
+            "reader" => self.reader.clone(),
+            "backups" => self.backups.map(|b| b.to_string()),
+            _ => return Err(ErrorKind::UnknownSetting(key.to_owned()).into()),
+        })
+    }
+
+    /// Changes the setting with the given name (one of `KEYS`), given its
+    /// value as text. An empty value (or `none`) unsets the setting.
+    ///
+    /// An error is returned if the setting doesn't exist or the value is
+    /// invalid for it, in which case the settings are left unchanged.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let invalid = || -> Error { ErrorKind::InvalidSettingValue(key.to_owned(), value.to_owned()).into() };
+        let value = match value.trim() {
+            "" | "none" => None,
+            v => Some(v),
+        };
+
+        let flag = || match value {
+            Some("true") | Some("yes") => Ok(true),
+            Some("false") | Some("no") | None => Ok(false),
+            _ => Err(invalid()),
+        };
+
+        match key {
+            "no_ansi" => self.no_ansi = flag()?,
+            "count" => {
+                self.count = match value {
+                    Some(v) => Some(v.parse().map_err(|_| invalid())?),
+                    None => None,
+                }
+            }
+            "plans_dir" => self.plans_dir = value.map(PathBuf::from),
+            "export_format" => {
+                if value.is_some_and(|v| !EXPORT_FORMATS.contains(&v)) {
+                    return Err(invalid());
+                }
+                self.export_format = value.map(str::to_owned);
+            }
+            "theme" => self.theme = value.map(str::to_owned),
+            "sync_url" => self.sync_url = value.map(str::to_owned),
+            "auto_archive" => self.auto_archive = flag()?,
+            "encryption_key" => {
+                if value.is_some_and(|v| v.len() != 64 || !v.chars().all(|c| c.is_ascii_hexdigit())) {
+                    return Err(invalid());
+                }
+                self.encryption_key = value.map(str::to_owned);
+            }
+            "encrypt" => self.encrypt = flag()?,
+            "reader" => self.reader = value.map(str::to_owned),
+            "backups" => {
+                self.backups = match value {
+                    Some(v) => Some(v.parse().map_err(|_| invalid())?),
+                    None => None,
+                }
+            }
+            _ => return Err(ErrorKind::UnknownSetting(key.to_owned()).into()),
+        }
+
+        Ok(())
+    }
+}
+
+impl Theme {
+    /// The kinds of text which can be styled: normal text, titles,
+    /// descriptions (and labels), errors, highlighted text (e.g. search
+    /// matches), muted text (less important details) and the filled part of
+    /// progress bars.
+    pub const ROLES: &'static [&'static str] = &["normal", "title", "description", "error", "highlight", "muted",
+                                                 "progress"];
+
+    /// Returns the style of the given kind of text (one of `ROLES`), if the
+    /// theme gives one.
+    pub fn style(&self, role: &str) -> Option<&str> {
+        match role {
+            "normal" => self.normal.as_deref(),
+            "title" => self.title.as_deref(),
+            "description" => self.description.as_deref(),
+            "error" => self.error.as_deref(),
+            "highlight" => self.highlight.as_deref(),
+            "muted" => self.muted.as_deref(),
+            "progress" => self.progress.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// Replaces the configuration file at the given path by one with the given
+/// contents, creating its directory if necessary.
+///
+/// As with plan files, the contents are written to a temporary file, which
+/// reaches the disk before it is renamed over the old file. On Unix, the
+/// file can only be read by its owner, since it may hold the encryption
+/// key.
+fn write_config_file(path: &Path, contents: &[u8]) -> Result<()> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    fs::create_dir_all(dir).chain_err(|| "could not create config directory")?;
+    let filename = path.file_name().map(|f| f.to_string_lossy().into_owned()).unwrap_or_default();
+    let temp_path = dir.join(format!(".{}.tmp", filename));
+
+    // A temporary file left behind could have been created by someone else
+    if temp_path.exists() {
+        fs::remove_file(&temp_path).chain_err(|| "could not remove old temporary config file")?;
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&temp_path)
+        .chain_err(|| format!("could not create config file {}", temp_path.display()))?;
+    file.write_all(contents).chain_err(|| "could not write config")?;
+    file.sync_all().chain_err(|| "could not write config")?;
+    fs::rename(&temp_path, path).chain_err(|| format!("could not replace config file {}", path.display()))
+}
+
+/// Returns the location of the configuration file.
+///
+/// This is the value of the `READING_CONFIG` environment variable, if it is
+/// set (and not empty); otherwise, it is `config.toml` in a directory chosen
+/// based on the operating system.
+pub fn config_path() -> Result<PathBuf> {
+    if let Some(path) = env::var_os(CONFIG_VAR) {
+        if !path.is_empty() {
+            return Ok(PathBuf::from(path));
+        }
+    }
+
+    app_dirs::app_root(AppDataType::UserConfig, &APP_INFO)
+        .map(|dir| dir.join("config.toml"))
+        .chain_err(|| ErrorKind::CannotLocateConfig)
+}
@@ -0,0 +1,146 @@
+//! This module provides the encryption used for plan files stored as
+//! `.plan.json.enc` (see the `files` module), which is only available with
+//! the `encryption` feature.
+//!
+//! Plans are encrypted with XChaCha20-Poly1305, using a 256-bit key which is
+//! kept in the `encryption_key` setting (see the `config` module) as 64
+//! hexadecimal digits, or given by the `READING_ENCRYPTION_KEY` environment
+//! variable, which takes precedence. An encrypted file starts with a short
+//! header identifying it, followed by the random nonce used for it and the
+//! encrypted contents. Anyone with the key can read the plans, so it should
+//! be kept as private as the plans themselves.
+
+use std::cell::RefCell;
+use std::env;
+use std::fmt;
+
+use chacha20poly1305::{Key as CipherKey, XChaCha20Poly1305, XNonce};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+
+use super::config::Config;
+use super::errors::*;
+
+/// The environment variable which can be used to give the key, instead of
+/// the `encryption_key` setting.
+pub const KEY_VAR: &str = "READING_ENCRYPTION_KEY";
+
+thread_local! {
+    /// The key set by `with_key`, if any
+    static KEY_OVERRIDE: RefCell<Option<Key>> = const { RefCell::new(None) };
+}
+
+/// The start of every encrypted file.
+const HEADER: &[u8] = b"reading-encrypted-v1\n";
+
+/// The length of a nonce, in bytes.
+const NONCE_LEN: usize = 24;
+
+/// A key used to encrypt and decrypt plans.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Key(CipherKey);
+
+impl Key {
+    /// Returns a new random key.
+    pub fn generate() -> Key {
+        Key(XChaCha20Poly1305::generate_key(&mut OsRng))
+    }
+
+    /// Returns the key written as hexadecimal digits (as by `to_hex`),
+    /// returning an `InvalidEncryptionKey` error if it isn't a valid key.
+    pub fn from_hex(hex: &str) -> Result<Key> {
+        let hex = hex.trim();
+        if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            bail!(ErrorKind::InvalidEncryptionKey);
+        }
+        let mut key = CipherKey::default();
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).chain_err(|| ErrorKind::InvalidEncryptionKey)?;
+        }
+        Ok(Key(key))
+    }
+
+    /// Returns the key as 64 hexadecimal digits.
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Returns the key set by `with_key`, if any, or else the one given by
+    /// the `READING_ENCRYPTION_KEY` environment variable, if it is set (and
+    /// not empty), or otherwise by the `encryption_key` setting, returning a
+    /// `NoEncryptionKey` error if none of them is set.
+    pub fn load() -> Result<Key> {
+        if let Some(key) = KEY_OVERRIDE.with(|o| o.borrow().clone()) {
+            return Ok(key);
+        }
+        if let Ok(hex) = env::var(KEY_VAR) {
+            if !hex.is_empty() {
+                return Key::from_hex(&hex);
+            }
+        }
+        match Config::load()?.encryption_key() {
+            Some(hex) => Key::from_hex(hex),
+            None => Err(ErrorKind::NoEncryptionKey.into()),
+        }
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The key itself is never shown, in case it ends up in a log
+        f.write_str("Key(..)")
+    }
+}
+
+/// Calls the given function with the key used to encrypt and decrypt plans
+/// set to the given one, restoring the previous key afterwards.
+///
+/// This only affects the current thread, and takes precedence over the
+/// `READING_ENCRYPTION_KEY` environment variable.
+pub fn with_key<F, T>(key: &Key, f: F) -> T
+    where F: FnOnce() -> T
+{
+    /// Restores the previous override when dropped (even if `f` panics)
+    struct Restore(Option<Key>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            KEY_OVERRIDE.with(|o| *o.borrow_mut() = previous);
+        }
+    }
+
+    let previous = KEY_OVERRIDE.with(|o| o.borrow_mut().replace(key.clone()));
+    let _restore = Restore(previous);
+    f()
+}
+
+/// Returns whether the given data was encrypted by `encrypt`.
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(HEADER)
+}
+
+/// Encrypts the given data with the given key, using a new random nonce.
+pub fn encrypt(key: &Key, data: &[u8]) -> Result<Vec<u8>> {
+    let cipher = XChaCha20Poly1305::new(&key.0);
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let encrypted = cipher.encrypt(&nonce, data).map_err(|_| Error::from("could not encrypt data"))?;
+
+    let mut output = Vec::with_capacity(HEADER.len() + NONCE_LEN + encrypted.len());
+    output.extend_from_slice(HEADER);
+    output.extend_from_slice(&nonce);
+    output.extend_from_slice(&encrypted);
+    Ok(output)
+}
+
+/// Decrypts data encrypted by `encrypt`, returning a `DecryptionFailed`
+/// error if it was encrypted with a different key (or has been changed
+/// since).
+pub fn decrypt(key: &Key, data: &[u8]) -> Result<Vec<u8>> {
+    if !is_encrypted(data) || data.len() < HEADER.len() + NONCE_LEN {
+        bail!(ErrorKind::DecryptionFailed);
+    }
+    let (nonce, encrypted) = data[HEADER.len()..].split_at(NONCE_LEN);
+    XChaCha20Poly1305::new(&key.0)
+        .decrypt(XNonce::from_slice(nonce), encrypted)
+        .map_err(|_| ErrorKind::DecryptionFailed.into())
+}
@@ -0,0 +1,122 @@
+//! The notification daemon used by the `daemon` subcommand, which is only
+//! available when the `daemon` feature is enabled.
+//!
+//! The daemon runs until it is killed, checking the plans once a minute and
+//! whenever the plans directory changes. It sends a desktop notification
+//! when a scheduled entry is due (or overdue), and a reminder if no plan has
+//! been advanced by a certain time of day. Each notification is sent at most
+//! once a day.
+
+use std::collections::HashSet;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveTime};
+use notify::{self, RecursiveMode, Watcher};
+use notify_rust::Notification;
+
+use reading::{Plan, PlanStore};
+use reading::files::FsStore;
+use reading::errors::*;
+
+/// How often to check the plans when nothing changes.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// How long to wait for changes to the plans directory to settle before
+/// checking the plans again.
+const DEBOUNCE_DELAY: Duration = Duration::from_secs(2);
+
+/// The notifications which have been sent today.
+struct Sent {
+    /// The day the notifications were sent
+    date: NaiveDate,
+    /// The due entries which have been notified, as plan names and entry
+    /// indexes
+    entries: HashSet<(String, usize)>,
+    /// Whether today's reminder has been sent
+    reminder: bool,
+}
+
+impl Sent {
+    /// Returns an empty record of notifications for the given day.
+    fn new(date: NaiveDate) -> Sent {
+        Sent {
+            date,
+            entries: HashSet::new(),
+            reminder: false,
+        }
+    }
+}
+
+/// Runs the daemon on the plans in the given store, sending a reminder at
+/// the given time of day if nothing has been read yet.
+pub fn run(store: &FsStore, remind_at: NaiveTime) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::watcher(tx, DEBOUNCE_DELAY).chain_err(|| "could not watch plans directory")?;
+    watcher.watch(store.dir(), RecursiveMode::NonRecursive)
+        .chain_err(|| format!("could not watch plans directory {}", store.dir().display()))?;
+    println!("Watching plans in {} (press Ctrl-C to stop)", store.dir().display());
+
+    let mut sent = Sent::new(Local::now().date_naive());
+    loop {
+        if let Err(e) = check(store, &Local::now(), remind_at, &mut sent) {
+            eprintln!("Error: {}", e);
+        }
+        match rx.recv_timeout(CHECK_INTERVAL) {
+            Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => bail!("stopped watching plans directory"),
+        }
+    }
+}
+
+/// Checks the plans in the store, sending any notifications that are due.
+fn check(store: &FsStore, now: &DateTime<Local>, remind_at: NaiveTime, sent: &mut Sent) -> Result<()> {
+    let today = now.date_naive();
+    if sent.date != today {
+        *sent = Sent::new(today);
+    }
+    let plans: Vec<_> = store.plans()
+        .chain_err(|| "could not read plans")?
+        .into_iter()
+        .filter_map(|p| p.ok())
+        .collect();
+
+    for plan in plans.iter().filter(|p| !p.is_archived()) {
+        for due in plan.due_entries(today) {
+            // Unscheduled entries are always due, so they aren't worth a
+            // notification
+            if due.entry().schedule().is_none() ||
+               !sent.entries.insert((plan.name().to_owned(), due.index())) {
+                continue;
+            }
+            let summary = if due.is_overdue() {
+                format!("Overdue in {}", plan.name())
+            } else {
+                format!("Due today in {}", plan.name())
+            };
+            notify(&summary, due.entry().title());
+        }
+    }
+
+    if !sent.reminder && now.time() >= remind_at && !read_today(&plans, today) &&
+       plans.iter().any(|p| !p.is_archived() && !p.is_ended()) {
+        sent.reminder = true;
+        notify("Time to read", "You haven't advanced any plans today");
+    }
+
+    Ok(())
+}
+
+/// Returns whether any of the plans were advanced on the given day.
+fn read_today(plans: &[Plan], today: NaiveDate) -> bool {
+    plans.iter()
+        .flat_map(|p| p.history())
+        .any(|e| e.completed_at().with_timezone(&Local).date_naive() == today)
+}
+
+/// Shows a desktop notification. Failures are only reported, since the
+/// notification server may become available later.
+fn notify(summary: &str, body: &str) {
+    if let Err(e) = Notification::new().appname("reading").summary(summary).body(body).show() {
+        eprintln!("Error: could not show notification: {}", e);
+    }
+}
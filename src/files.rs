@@ -6,26 +6,439 @@
 //!
 //! All plan files should be stored in the plans directory with
 //! the extension `.plan.json`. Files with a different extension
-//! will not be recognized, e.g. by the `plans` iterator function.
+//! will not be recognized, e.g. by the `plans` iterator function or
+//! `plan_names` (which lists the names of the plans from the names of their
+//! files alone, without reading them).
 //! In general, this should not be a problem; the provided methods
 //! for adding/removing plans will provide this extension automatically.
+//!
+//! Since JSON is awkward to edit by hand, plans can also be stored as TOML
+//! (`.plan.toml`) or YAML (`.plan.yaml`) if the `toml-format` or
+//! `yaml-format` features are enabled. A plan stays in the format of its
+//! file when it is changed; new plans are always written as JSON.
+//!
+//! With the `encryption` feature, plans can also be stored encrypted, as
+//! `.plan.json.enc` (see the `crypto` module), and are decrypted whenever
+//! they are read. If the `encrypt` setting is enabled, new plans are
+//! written encrypted instead of as JSON. The undo journal of an encrypted
+//! plan is encrypted as well. Existing plans can be encrypted (or
+//! decrypted) all at once using `FsStore::set_encrypted`.
+//!
+//! The plans directory is accessed through `FsStore`, which implements
+//! the `PlanStore` trait; the free functions in this module are shortcuts
+//! for using the `FsStore` for the default plans directory. The default
+//! directory can be changed by setting the `READING_PLANS_DIR` environment
+//! variable or the `plans_dir` setting (see the `config` module), or
+//! temporarily (for the current thread) using `with_plans_dir`.
+//!
+//! Plan files are never written in place: a plan is first written to a
+//! temporary file in the plans directory, which is then renamed over the
+//! old file, so that a plan file can't be left half-written if the program
+//! is interrupted.
+//!
+//! Every change to a plan made through `FsStore` (including removing it) is
+//! also recorded in an undo journal, `{name}.plan.journal`, which holds the
+//! previous states of the plan (one JSON object per line, oldest first). The
+//! most recent change can be reverted using `undo_last`.
+//!
+//! Before a plan file is replaced or moved to the trash, the old version is
+//! copied into the `backups` subdirectory of the plans directory (as
+//! `{time}.{name}.plan.json`, in the same way as in the trash), where the
+//! last 10 backups of each plan are kept (see `FsStore::set_backup_count`).
+//! A plan can be restored to any of its backups using
+//! `FsStore::restore_backup`.
+//!
+//! A plan file which can't be read (e.g. one broken by a bad edit) can be
+//! found using `FsStore::diagnose` or `diagnose`, which report why each
+//! plan can't be read and whether it has a backup which can. Such a plan can
+//! then be restored from its latest readable backup using `FsStore::repair`,
+//! or moved out of the way using `FsStore::quarantine`; either way, the
+//! broken file is kept in the `quarantine` subdirectory of the plans
+//! directory (named in the same way as in the trash).
+//!
+//! Removed plans are not deleted straight away, but moved into the `trash`
+//! subdirectory of the plans directory (as `{time}.{name}.plan.json`, where
+//! `{time}` is when the plan was removed, in milliseconds since the Unix
+//! epoch), from which they can be restored using `FsStore::restore`. Plans
+//! are deleted from the trash for good after 30 days.
+//!
+//! The creation and modification times of a plan (see `Plan::created_at`
+//! and `Plan::modified_at`) are kept up to date whenever it is written
+//! through `FsStore`, but not when it is restored by `undo` or rewritten by
+//! `migrate`.
+//!
+//! Changes to several plans which must be made together (or not at all) can
+//! be made in a transaction, using `FsStore::transaction` or `transaction`:
+//! the new versions of the plans are written to a hidden
+//! `.transaction.{pid}.{n}` directory inside the plans directory, and only
+//! moved into place once all of them have been written.
+//!
+//! All the plans in a store can also be bundled into a single JSON file
+//! (a "collection"), including their progress, using `export_collection`,
+//! and restored elsewhere using `import_collection`.
+//!
+//! Plans are upgraded to the current version of the file format (see the
+//! `migrations` module) whenever they are read, but the files themselves
+//! are only rewritten when the plans are next changed, or by
+//! `FsStore::migrate`.
+//!
+//! For overviews of many plans, `FsStore::summary` and `plan_summaries`
+//! describe plans without reading their entries into memory (see the
+//! `plan::summary` module), which matters for plans with many entries;
+//! `plan_summaries` also sorts them (e.g. by progress or by when they were
+//! last modified).
+//!
+//! Plans can be kept in separate collections, called profiles (e.g. one for
+//! work and one for personal reading). The plans directory itself holds the
+//! plans of the `default` profile, and every other profile has a directory
+//! of its own in the `profiles` subdirectory of the plans directory (see
+//! `Profile`).
+//!
+//! To stop simultaneous changes to a plan (e.g. by two instances of the
+//! program) from overwriting each other, every change takes an advisory
+//! lock on the hidden file `.{name}.plan.lock` in the plans directory.
+//! Changes that depend on the current state of the plan should be made
+//! using `PlanStore::update`, which holds the lock while the plan is read,
+//! changed and written back. If the lock is held by someone else, it is
+//! retried for a few seconds before giving up with a `PlanLocked` error.
+//!
+//! Every time a plan is written, its revision (see `Plan::revision`) goes
+//! up by one. A plan which was read without holding its lock (e.g. while
+//! the user decides what to do with it) can be written back using
+//! `FsStore::overwrite` or `overwrite_plan`, which return a
+//! `ConcurrentModification` error instead if the stored plan has a
+//! different revision, i.e. if it was changed in the meantime. `update`
+//! checks the revision in the same way, in case the plan was changed by
+//! something which doesn't respect the lock.
+//!
+//! An `FsStore` can also do a dry run (see `FsStore::set_dry_run`), in which
+//! it goes through the motions of every change without touching the plans
+//! directory, and keeps a list of the plan files it would have written or
+//! moved to the trash instead (see `FileChange`).
 
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::env;
 use std::fs::{self, File, ReadDir};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
 use std::iter::Iterator;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use app_dirs::{self, AppInfo, AppDataType, AppDirsError};
+use app_dirs::{self, AppInfo, AppDataType};
+use chrono::{DateTime, Utc};
+use fs2::FileExt;
 use serde_json;
+#[cfg(feature = "yaml-format")]
+use serde_yaml;
+#[cfg(feature = "toml-format")]
+use toml;
 
 use super::Plan;
+use super::config::Config;
+#[cfg(feature = "encryption")]
+use super::crypto::{self, Key};
 use super::errors::*;
+use super::migrations;
+use super::plan::summary::{PlanSummary, SortOrder};
+use super::search::{Query, SearchHit};
+use super::store::{PlanStore, Selection};
 
 /// The information for app_dirs
-const APP_INFO: AppInfo = AppInfo {
+pub(crate) const APP_INFO: AppInfo = AppInfo {
     name: "reading",
     author: "Ian Johnson",
 };
 
+/// The subdirectory of the plans directory containing the directories of
+/// profiles other than the default one.
+const PROFILES_DIR: &str = "profiles";
+
+/// The environment variable which can be used to override the default
+/// plans directory.
+pub const PLANS_DIR_VAR: &str = "READING_PLANS_DIR";
+
+thread_local! {
+    /// The plans directory set by `with_plans_dir`, if any
+    static PLANS_DIR_OVERRIDE: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// The subdirectory of a plans directory containing the plans which have
+/// been removed from it.
+const TRASH_DIR: &str = "trash";
+
+/// The subdirectory of a plans directory containing backups of the previous
+/// versions of its plans.
+const BACKUPS_DIR: &str = "backups";
+
+/// The subdirectory of a plans directory containing the plan files which
+/// couldn't be read, and were moved out of the way.
+const QUARANTINE_DIR: &str = "quarantine";
+
+/// The number of backups kept of each plan, unless the store is told
+/// otherwise (see `FsStore::set_backup_count`).
+pub const DEFAULT_BACKUP_COUNT: usize = 10;
+
+/// How long a removed plan is kept in the trash before it is deleted for
+/// good.
+const TRASH_EXPIRY: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// The maximum number of previous states kept in the undo journal of each
+/// plan.
+const JOURNAL_LENGTH: usize = 50;
+
+/// The number of times to try to take the lock on a plan before giving up.
+const LOCK_ATTEMPTS: u32 = 50;
+/// How long to wait between attempts to take the lock on a plan.
+const LOCK_RETRY_DELAY_MS: u64 = 100;
+
+/// The number of transactions started by this process, used to give each
+/// one its own directory.
+static TRANSACTION_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// The version of the collection format written by `write_collection`.
+const COLLECTION_VERSION: u32 = 1;
+
+/// A format in which plan files can be stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileFormat {
+    Json,
+    #[cfg(feature = "toml-format")]
+    Toml,
+    #[cfg(feature = "yaml-format")]
+    Yaml,
+    /// JSON, encrypted (see the `crypto` module)
+    #[cfg(feature = "encryption")]
+    Encrypted,
+}
+
+/// All the supported formats of plan files, in order of preference (if a
+/// plan has files in more than one format, the first one is used).
+const FILE_FORMATS: &[FileFormat] = &[
+    FileFormat::Json,
+    #[cfg(feature = "toml-format")]
+    FileFormat::Toml,
+    #[cfg(feature = "yaml-format")]
+    FileFormat::Yaml,
+    #[cfg(feature = "encryption")]
+    FileFormat::Encrypted,
+];
+
+impl FileFormat {
+    /// Returns the format of the file with the given name, if it is a plan
+    /// file.
+    fn for_filename(filename: &str) -> Option<FileFormat> {
+        FILE_FORMATS.iter().cloned().find(|f| filename.ends_with(f.extension()))
+    }
+
+    /// Returns the extension of plan files in this format.
+    fn extension(self) -> &'static str {
+        match self {
+            FileFormat::Json => ".plan.json",
+            #[cfg(feature = "toml-format")]
+            FileFormat::Toml => ".plan.toml",
+            #[cfg(feature = "yaml-format")]
+            FileFormat::Yaml => ".plan.yaml",
+            #[cfg(feature = "encryption")]
+            FileFormat::Encrypted => ".plan.json.enc",
+        }
+    }
+
+    /// Returns the format new plans are written in: encrypted, if the
+    /// `encrypt` setting is enabled, and otherwise JSON.
+    fn for_new_plans() -> FileFormat {
+        #[cfg(feature = "encryption")]
+        {
+            // An invalid config file is reported as soon as the program
+            // starts, so there is no need to report it for every plan
+            if Config::load().is_ok_and(|c| c.encrypt()) {
+                return FileFormat::Encrypted;
+            }
+        }
+        FileFormat::Json
+    }
+
+    /// Reads a plan in this format as it is stored (i.e. without migrating
+    /// it), as JSON.
+    fn read_value<R: Read>(self, input: R) -> Result<serde_json::Value> {
+        match self {
+            FileFormat::Json => serde_json::from_reader(input).chain_err(|| "json error in plan file"),
+            #[cfg(feature = "toml-format")]
+            FileFormat::Toml => {
+                let mut input = input;
+                let mut text = String::new();
+                input.read_to_string(&mut text).chain_err(|| "could not read plan file")?;
+                toml::from_str(&text).chain_err(|| "toml error in plan file")
+            }
+            #[cfg(feature = "yaml-format")]
+            FileFormat::Yaml => serde_yaml::from_reader(input).chain_err(|| "yaml error in plan file"),
+            #[cfg(feature = "encryption")]
+            FileFormat::Encrypted => {
+                let mut input = input;
+                let mut data = Vec::new();
+                input.read_to_end(&mut data).chain_err(|| "could not read plan file")?;
+                let data = crypto::decrypt(&Key::load()?, &data).chain_err(|| "could not decrypt plan file")?;
+                serde_json::from_slice(&data).chain_err(|| "json error in plan file")
+            }
+        }
+    }
+
+    /// Reads a plan in this format, migrating it to the current version of
+    /// the plan format if necessary.
+    fn read<R: Read>(self, input: R) -> Result<Plan> {
+        migrations::plan_from_value(self.read_value(input)?)
+    }
+
+    /// Writes a plan in this format.
+    fn write<W: Write>(self, plan: &Plan, output: W) -> Result<()> {
+        match self {
+            FileFormat::Json => {
+                serde_json::to_writer(output, plan).chain_err(|| "could not serialize plan to json")
+            }
+            #[cfg(feature = "toml-format")]
+            FileFormat::Toml => plan.to_toml(output),
+            #[cfg(feature = "yaml-format")]
+            FileFormat::Yaml => plan.to_yaml(output),
+            #[cfg(feature = "encryption")]
+            FileFormat::Encrypted => {
+                let mut output = output;
+                let data = serde_json::to_vec(plan).chain_err(|| "could not serialize plan to json")?;
+                let data = crypto::encrypt(&Key::load()?, &data)?;
+                output.write_all(&data).chain_err(|| "could not write plan file")
+            }
+        }
+    }
+
+    /// Writes a plan in this format to a new file, making sure that it
+    /// actually reaches the disk.
+    fn write_file(self, plan: &Plan, path: &Path) -> Result<()> {
+        let f = File::create(path).chain_err(|| "could not create temporary plan file")?;
+        let mut w = BufWriter::new(f);
+        self.write(plan, &mut w)?;
+        let f = w.into_inner()
+            .map_err(|e| e.into_error())
+            .chain_err(|| "could not write temporary plan file")?;
+        f.sync_all().chain_err(|| "could not write temporary plan file")
+    }
+}
+
+/// The contents of a collection file.
+///
+/// The plans are read as plain JSON, so that they can be migrated from older
+/// versions of the plan format before they are used.
+#[derive(Serialize, Deserialize, Debug)]
+struct Collection<P> {
+    /// The version of the format
+    version: u32,
+    /// All the plans in the collection
+    plans: Vec<P>,
+}
+
+/// What to do when importing a plan with the same name as an existing one.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Conflict {
+    /// Import nothing, returning a `PlanAlreadyExists` error.
+    Fail,
+    /// Replace the existing plan.
+    Overwrite,
+    /// Keep the existing plan, skipping the imported one.
+    Skip,
+}
+
+/// The result of importing a collection.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// The names of the plans which were imported
+    pub imported: Vec<String>,
+    /// The names of the plans which were skipped because they already
+    /// existed
+    pub skipped: Vec<String>,
+}
+
+/// The result of checking whether a plan file can be read (see
+/// `FsStore::diagnose`).
+#[derive(Debug)]
+pub struct Diagnosis {
+    /// The name of the plan
+    pub name: String,
+    /// The path of the plan file
+    pub path: PathBuf,
+    /// Why the plan couldn't be read, or `None` if it could
+    pub error: Option<Error>,
+    /// When the latest backup of the plan which can be read was made (see
+    /// `FsStore::backups`), if the plan couldn't be read and there is such a
+    /// backup
+    pub backup: Option<DateTime<Utc>>,
+}
+
+impl Diagnosis {
+    /// Returns whether the plan could be read.
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// An advisory lock on a plan, which is released when dropped.
+#[derive(Debug)]
+pub struct PlanLock {
+    /// The open lock file (there is none in a dry run, which doesn't even
+    /// create lock files)
+    file: Option<File>,
+}
+
+impl Drop for PlanLock {
+    fn drop(&mut self) {
+        // The lock would be released when the file is closed anyway, so
+        // there's nothing to be done if this fails
+        if let Some(ref file) = self.file {
+            let _ = file.unlock();
+        }
+    }
+}
+
+/// Changes to the plans in an `FsStore` which are only made if they can all
+/// be made (see `FsStore::transaction`).
+///
+/// A transaction is a `PlanStore` itself, which shows the plans as they
+/// will be once the changes are made. Every plan which is written or removed
+/// stays locked until the transaction is over, so that nobody else can
+/// change it in the meantime; plans which are only read are not locked.
+pub struct Transaction<'a> {
+    /// The store being changed
+    store: &'a FsStore,
+    /// The directory holding the new versions of the plans written so far
+    staging_dir: PathBuf,
+    /// The changes made so far, by plan name
+    changes: BTreeMap<String, Change>,
+    /// The locks on the plans which have been changed
+    locks: HashMap<String, PlanLock>,
+}
+
+/// A change to a single plan in a `Transaction`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    /// The plan was written, in the given format
+    Write(FileFormat),
+    /// The plan was removed
+    Remove,
+}
+
+/// A separate collection of plans, stored in its own directory.
+///
+/// The default profile (named `default`) uses the plans directory itself;
+/// other profiles use `profiles/{name}` inside it. Profile names can't be
+/// empty, start with a dot or contain path separators.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    /// The name of the profile
+    name: String,
+    /// The directory containing the plan files of the profile
+    dir: PathBuf,
+}
+
 /// An iterator over all the plans in the plan directory.
 ///
 /// The iterator returns items of type `Result<Plan, Error>`
@@ -40,146 +453,1555 @@ impl Iterator for Plans {
     type Item = Result<Plan>;
 
     fn next(&mut self) -> Option<Result<Plan>> {
-        let entry = match self.read_dir.next() {
-            Some(e) => e,
-            None => return None,
-        };
+        let entry = self.read_dir.next()?;
         let path =
-            match entry.chain_err(|| ErrorKind::Io("could not read directory item".into())) {
+            match entry.chain_err(|| "could not read directory item") {
                 Ok(e) => e.path(),
                 Err(e) => return Some(Err(e)),
             };
 
         // Make sure we skip over things that aren't files or
-        // don't have the proper extension ('.plan.json')
-        let path_str = match path.to_str() {
-            Some(s) => s.to_owned(),
-            None => return Some(Err(ErrorKind::Utf8("path is not valid utf8".into()).into())),
+        // don't have the proper extension (e.g. '.plan.json'), including
+        // files whose names aren't valid UTF-8 (which can't be plans)
+        let format = match path.to_str() {
+            Some(s) => FileFormat::for_filename(s),
+            None => {
+                debug!("ignoring {}, since its name isn't valid UTF-8", path.display());
+                return self.next();
+            }
+        };
+        let format = match format {
+            Some(f) if path.is_file() => f,
+            _ => return self.next(),
         };
-        if !path.is_file() || !path_str.ends_with(".plan.json") {
-            return self.next();
-        }
         // Now try to open the plan and read in its data
         let f = match File::open(&path)
-            .chain_err(|| ErrorKind::Io(format!("could not open file '{}'", path.display()))) {
+            .chain_err(|| format!("could not open file '{}'", path.display())) {
             Ok(f) => f,
             Err(e) => return Some(Err(e)),
         };
-        Some(serde_json::from_reader(&f)
-            .chain_err(|| ErrorKind::Json(format!("json error in file '{}'", path.display()))))
+        Some(format.read(f).chain_err(|| format!("could not read file '{}'", path.display())))
+    }
+}
+
+/// A `PlanStore` which keeps each plan as a JSON file in a directory.
+///
+/// The file for each plan is named `{name}.plan.json` (or `{name}.plan.toml`
+/// or `{name}.plan.yaml`, if those formats are enabled); any other files in
+/// the directory are ignored.
+#[derive(Debug, Clone)]
+pub struct FsStore {
+    /// The directory containing the plan files
+    dir: PathBuf,
+    /// Whether nothing is actually written (see `set_dry_run`)
+    dry_run: bool,
+    /// The changes to plan files which were held back in a dry run
+    held_back: RefCell<Vec<FileChange>>,
+    /// The number of backups kept of each plan
+    backup_count: usize,
+}
+
+/// A change to a plan file which an `FsStore` held back because it was
+/// doing a dry run (see `FsStore::set_dry_run`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileChange {
+    /// The file at the given path would have been written (created, or
+    /// replaced by a new version)
+    Write(PathBuf),
+    /// The file at the given path would have been moved to the trash
+    Trash(PathBuf),
+}
+
+impl FsStore {
+    /// Returns a store using the given directory.
+    ///
+    /// The directory does not need to exist yet; it will be created
+    /// when the first plan is written.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> FsStore {
+        FsStore {
+            dir: dir.into(),
+            dry_run: false,
+            held_back: RefCell::new(Vec::new()),
+            backup_count: DEFAULT_BACKUP_COUNT,
+        }
+    }
+
+    /// Returns a store using the default plans directory (as given by
+    /// `plans_dir`).
+    ///
+    /// The directory does not need to exist yet; it will be created
+    /// when the first plan is written.
+    pub fn default_location() -> Result<FsStore> {
+        Ok(FsStore::new(plans_dir()?))
+    }
+
+    /// Sets whether the store does a dry run, in which nothing in the plans
+    /// directory is changed: plans can still be read, but writing or
+    /// removing a plan only records the change to its file (see
+    /// `held_back`), and changes to undo journals and backups are skipped.
+    /// Plans are read as they were before the held back changes.
+    ///
+    /// Anything which can't be done without changing files directly (such
+    /// as `undo` or `restore`) returns an error instead.
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    /// Returns whether the store does a dry run (see `set_dry_run`).
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Returns the changes to plan files which were held back because the
+    /// store is doing a dry run, in the order they would have been made.
+    pub fn held_back(&self) -> Vec<FileChange> {
+        self.held_back.borrow().clone()
+    }
+
+    /// Sets the number of backups kept of each plan (see `backups`), after
+    /// which the oldest ones are deleted as new ones are made. A count of 0
+    /// stops backups from being made at all.
+    pub fn set_backup_count(&mut self, count: usize) {
+        self.backup_count = count;
+    }
+
+    /// Returns whether the given change may be made, which it may unless the
+    /// store is doing a dry run, in which case it is held back instead.
+    fn allow(&self, change: FileChange) -> bool {
+        if self.dry_run {
+            info!("dry run: holding back {:?}", change);
+            self.held_back.borrow_mut().push(change);
+        }
+        !self.dry_run
+    }
+
+    /// Returns an error if the store is doing a dry run, for changes which
+    /// can't be held back.
+    fn must_write(&self) -> Result<()> {
+        if self.dry_run {
+            bail!("this can't be done in a dry run");
+        }
+        Ok(())
+    }
+
+    /// Returns the directory containing the plan files.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns an iterator over the plans in the directory.
+    ///
+    /// Unlike `PlanStore::plans`, this reads each plan only as the
+    /// iterator is advanced.
+    pub fn iter(&self) -> Result<Plans> {
+        let dir = self.dir_must_exist()?;
+
+        Ok(Plans { read_dir: fs::read_dir(dir).chain_err(|| "could not read from plans directory")? })
+    }
+
+    /// Returns the names of the plans in the directory, in alphabetical
+    /// order, which are taken from the names of their files without opening
+    /// any of them (so this is much cheaper than reading the plans).
+    ///
+    /// Only files whose names end in the extension of a plan file (such as
+    /// `.plan.json`) and have a name before it count as plans. Files whose
+    /// names aren't valid UTF-8 are passed over, since they can't be named
+    /// as plans anyway.
+    pub fn plan_names(&self) -> Result<Vec<String>> {
+        let dir = self.dir_must_exist()?;
+        let mut names = Vec::new();
+
+        for entry in fs::read_dir(dir).chain_err(|| "could not read from plans directory")? {
+            let path = entry.chain_err(|| "could not read directory item")?
+                .path();
+            let filename = match path.file_name().map(|f| f.to_str()) {
+                Some(Some(f)) => f,
+                Some(None) => {
+                    debug!("ignoring {}, since its name isn't valid UTF-8", path.display());
+                    continue;
+                }
+                None => continue,
+            };
+            // Skip over anything that isn't a plan file
+            match FileFormat::for_filename(filename) {
+                Some(format) if filename.len() > format.extension().len() && path.is_file() => {
+                    names.push(filename[..filename.len() - format.extension().len()].to_owned());
+                }
+                Some(_) => {}
+                // Journals, hidden files (such as locks) and the backups left
+                // by older versions are expected, but anything else might be a misnamed plan
+                None if filename.starts_with('.') || filename.ends_with(".journal") || filename.ends_with(".bak") => {}
+                None if path.is_file() => {
+                    debug!("ignoring {}, since plan files must end in one of {}",
+                           path.display(),
+                           FILE_FORMATS.iter().map(|f| f.extension()).collect::<Vec<_>>().join(", "));
+                }
+                None => {}
+            }
+        }
+        names.sort();
+        // A plan may have files in more than one format
+        names.dedup();
+
+        Ok(names)
+    }
+
+    /// Summarizes the plan with the given name (see `PlanSummary`), reading
+    /// it in full only if it can't be summarized directly.
+    pub fn summary(&self, name: &str) -> Result<PlanSummary> {
+        self.dir_must_exist()?;
+        let (filename, format) = self.plan_file(name)?;
+        if !filename.exists() {
+            return Err(ErrorKind::PlanDoesNotExist(name.into()).into());
+        }
+        if format == FileFormat::Json {
+            let f = File::open(&filename).chain_err(|| format!("could not open file '{}'", filename.display()))?;
+            if let Some(summary) = PlanSummary::from_json(BufReader::new(f))? {
+                return Ok(summary);
+            }
+        }
+        self.read(name).map(|plan| PlanSummary::from(&plan))
+    }
+
+    /// Summarizes every plan in the directory (as `PlanStore::summaries`
+    /// does), sorted in the given order, or the reverse of it. The plans
+    /// which couldn't be read come last.
+    ///
+    /// Plans are sorted by when they were last modified according to their
+    /// own timestamps, or else (for plans stored before those were
+    /// recorded) when their files were last changed.
+    pub fn sorted_summaries(&self, order: SortOrder, reverse: bool) -> Result<Vec<Result<PlanSummary>>> {
+        let (readable, failures): (Vec<_>, Vec<_>) = self.summaries()?.into_iter().partition(|s| s.is_ok());
+        let mut readable = readable.into_iter()
+            .map(|s| {
+                let summary = s.unwrap();
+                let modified = match summary.modified_at() {
+                    Some(time) => Some(time),
+                    None => self.modified(summary.name())?.map(DateTime::<Utc>::from),
+                };
+                Ok((summary, modified))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        readable.sort_by(|&(ref a, a_modified), &(ref b, b_modified)| {
+            let ordering = order.compare(a, a_modified, b, b_modified);
+            if reverse { ordering.reverse() } else { ordering }
+        });
+        Ok(readable.into_iter().map(|(s, _)| Ok(s)).chain(failures).collect())
+    }
+
+    /// Returns when the file of the plan with the given name was last
+    /// changed, or `None` if the plan doesn't exist.
+    pub fn modified(&self, name: &str) -> Result<Option<SystemTime>> {
+        let filename = self.plan_path(name)?;
+        if !filename.is_file() {
+            return Ok(None);
+        }
+        let metadata = fs::metadata(&filename)
+            .chain_err(|| format!("could not read metadata of '{}'", filename.display()))?;
+        metadata.modified().map(Some).chain_err(|| "could not get modification time of plan file")
+    }
+
+    /// Returns the path and format of the file for the plan with the given
+    /// name. If the plan doesn't exist yet, this is where it would be
+    /// written.
+    ///
+    /// An `InvalidPlanName` error is returned for a name which could lead
+    /// outside the plans directory (see `is_valid_plan_name`).
+    fn plan_file(&self, name: &str) -> Result<(PathBuf, FileFormat)> {
+        check_plan_name(name)?;
+        let (path, format) = FILE_FORMATS.iter()
+            .map(|&f| (self.dir.join(format!("{}{}", name, f.extension())), f))
+            .find(|(path, _)| path.is_file())
+            .unwrap_or_else(|| {
+                let format = FileFormat::for_new_plans();
+                (self.dir.join(format!("{}{}", name, format.extension())), format)
+            });
+        trace!("using {} ({:?}) for plan '{}'", path.display(), format, name);
+        Ok((path, format))
+    }
+
+    /// Returns the path of the file for the plan with the given name. If the
+    /// plan doesn't exist yet, this is where it would be written (as JSON,
+    /// or encrypted if the `encrypt` setting is enabled).
+    pub fn plan_path(&self, name: &str) -> Result<PathBuf> {
+        self.plan_file(name).map(|(path, _)| path)
+    }
+
+    /// Returns the path of the undo journal for the plan with the given
+    /// name.
+    pub fn journal_path(&self, name: &str) -> Result<PathBuf> {
+        check_plan_name(name)?;
+        Ok(self.dir.join(format!("{}.plan.journal", name)))
+    }
+
+    /// Takes the lock on the plan with the given name, waiting for a few
+    /// seconds if someone else holds it before returning a `PlanLocked`
+    /// error.
+    ///
+    /// The plan does not need to exist, but the plans directory does.
+    pub fn lock(&self, name: &str) -> Result<PlanLock> {
+        check_plan_name(name)?;
+        if self.dry_run {
+            return Ok(PlanLock { file: None });
+        }
+        let dir = self.dir_must_exist()?;
+        let file = File::create(dir.join(format!(".{}.plan.lock", name)))
+            .chain_err(|| "could not open plan lock file")?;
+
+        for attempt in 0..LOCK_ATTEMPTS {
+            if attempt > 0 {
+                thread::sleep(Duration::from_millis(LOCK_RETRY_DELAY_MS));
+            }
+            match file.try_lock_exclusive() {
+                Ok(()) => return Ok(PlanLock { file: Some(file) }),
+                Err(ref e) if e.kind() == fs2::lock_contended_error().kind() => continue,
+                Err(e) => return Err(e).chain_err(|| ErrorKind::LockFailed(name.into())),
+            }
+        }
+
+        Err(ErrorKind::PlanLocked(name.into()).into())
+    }
+
+    /// Reverts the most recent change to the plan with the given name,
+    /// returning the restored plan.
+    ///
+    /// If the plan was removed, it will be restored. A `NothingToUndo` error
+    /// is returned if there are no changes in the plan's journal.
+    pub fn undo(&mut self, name: &str) -> Result<Plan> {
+        self.must_write()?;
+        self.dir_must_exist()?;
+        let _lock = self.lock(name)?;
+        self.undo_locked(name)
+    }
+
+    /// Reverts the most recent change to the plan with the given name, as
+    /// with `undo`, when its lock is already held.
+    fn undo_locked(&self, name: &str) -> Result<Plan> {
+        let mut states = self.read_journal(name)?;
+        let state = match states.pop() {
+            Some(s) => s,
+            None => return Err(ErrorKind::NothingToUndo(name.into()).into()),
+        };
+        let state = serde_json::from_str(&state).chain_err(|| "json error in undo journal")?;
+        let mut plan = migrations::plan_from_value(state)?;
+        // The plan may have been renamed since this state was recorded
+        plan.set_name(name);
+        // Going back to an earlier version is still a change, as far as
+        // anyone who read the plan since then is concerned
+        let stored = self.stored_revision(name)?.unwrap_or(0);
+        plan.set_revision(stored.max(plan.revision()) + 1);
+
+        self.write_plan_file(&plan)?;
+        self.write_journal(name, &states)?;
+
+        Ok(plan)
+    }
+
+    /// Restores the most recently removed plan with the given name from the
+    /// trash, returning the restored plan.
+    ///
+    /// A `NotInTrash` error is returned if there is no such plan in the
+    /// trash, and a `PlanAlreadyExists` error if a plan with the same name
+    /// has been added since it was removed.
+    pub fn restore(&mut self, name: &str) -> Result<Plan> {
+        self.must_write()?;
+        self.dir_must_exist()?;
+        let _lock = self.lock(name)?;
+        if self.exists(name)? {
+            return Err(ErrorKind::PlanAlreadyExists(name.into()).into());
+        }
+        let (path, filename) = match self.trashed(name)?.pop() {
+            Some((_, path, filename)) => (path, filename),
+            None => return Err(ErrorKind::NotInTrash(name.into()).into()),
+        };
+
+        let dest = self.dir.join(filename);
+        fs::rename(&path, &dest).chain_err(|| "could not restore plan file")?;
+        self.read(name)
+    }
+
+    /// Returns the times at which the backups of the plan with the given
+    /// name were made, oldest first. Each backup holds the version of the
+    /// plan from before the change made at that time.
+    pub fn backups(&self, name: &str) -> Result<Vec<DateTime<Utc>>> {
+        Ok(self.backup_files(name)?.into_iter().map(|(time, _)| DateTime::from(time)).collect())
+    }
+
+    /// Restores the plan with the given name to the version it had at the
+    /// given time, which is its oldest backup made at or after that time,
+    /// returning the restored plan, or a `NoBackup` error if there is no
+    /// such backup.
+    ///
+    /// This is a change to the plan like any other, so the version being
+    /// replaced is backed up (and recorded in the undo journal) in turn. A
+    /// plan which has been removed is brought back.
+    pub fn restore_backup(&mut self, name: &str, time: DateTime<Utc>) -> Result<Plan> {
+        self.must_write()?;
+        self.dir_must_exist()?;
+        let _lock = self.lock(name)?;
+        let time = SystemTime::from(time);
+        let path = match self.backup_files(name)?.into_iter().find(|&(t, _)| t >= time) {
+            Some((_, path)) => path,
+            None => return Err(ErrorKind::NoBackup(name.into()).into()),
+        };
+        let mut plan = read_plan_file(&path).chain_err(|| format!("could not read backup {}", path.display()))?;
+        // The plan may have been renamed since the backup was made
+        plan.set_name(name);
+        let stored = self.stored_revision(name)?.unwrap_or(0);
+        plan.set_revision(stored.max(plan.revision()) + 1);
+
+        self.record(name)?;
+        self.write_plan_file(&plan)?;
+        Ok(plan)
+    }
+
+    /// Checks whether every plan in the directory can be read, returning
+    /// the result for each plan, in the order of `list`.
+    ///
+    /// A plan which can't be read has its error, along with the time of its
+    /// latest backup which can be read, if any (see `repair`). An error is
+    /// only returned if the plans can't be listed.
+    pub fn diagnose(&self) -> Result<Vec<Diagnosis>> {
+        let mut diagnoses = Vec::new();
+        for name in self.list()? {
+            let error = self.read(&name).err();
+            let backup = match error {
+                Some(_) => self.readable_backup(&name)?.map(|(time, _)| DateTime::from(time)),
+                None => None,
+            };
+            diagnoses.push(Diagnosis {
+                path: self.plan_path(&name)?,
+                name,
+                error,
+                backup,
+            });
+        }
+        Ok(diagnoses)
+    }
+
+    /// Restores the plan with the given name, which can't be read, from its
+    /// latest backup which can, returning the restored plan. The broken
+    /// plan file is moved to the quarantine directory first.
+    ///
+    /// A `NoBackup` error is returned if there is no such backup, in which
+    /// case the plan file is left alone.
+    pub fn repair(&mut self, name: &str) -> Result<Plan> {
+        self.must_write()?;
+        self.dir_must_exist()?;
+        let _lock = self.lock(name)?;
+        let (_, path) = match self.readable_backup(name)? {
+            Some(backup) => backup,
+            None => return Err(ErrorKind::NoBackup(name.into()).into()),
+        };
+        let mut plan = read_plan_file(&path)?;
+        self.quarantine_file(name)?;
+        // Nobody can have read the broken version, so the revision of the
+        // backup is as good as any
+        plan.set_revision(plan.revision() + 1);
+        self.write_plan_file(&plan)?;
+        Ok(plan)
+    }
+
+    /// Moves the file of the plan with the given name (which should be one
+    /// that can't be read) into the quarantine directory, returning its new
+    /// path. The plan no longer exists afterwards, but can still be restored
+    /// from its backups.
+    pub fn quarantine(&mut self, name: &str) -> Result<PathBuf> {
+        self.must_write()?;
+        self.dir_must_exist()?;
+        let _lock = self.lock(name)?;
+        if !self.plan_path(name)?.exists() {
+            return Err(ErrorKind::PlanDoesNotExist(name.into()).into());
+        }
+        self.quarantine_file(name)
+    }
+
+    /// Moves the file of the plan with the given name into the quarantine
+    /// directory, returning its new path.
+    fn quarantine_file(&self, name: &str) -> Result<PathBuf> {
+        let dir = self.dir.join(QUARANTINE_DIR);
+        fs::create_dir_all(&dir).chain_err(|| "could not create quarantine directory")?;
+        let (path, format) = self.plan_file(name)?;
+        let dest = timestamped_path(&dir, name, format, SystemTime::now());
+        info!("quarantining plan file {} as {}", path.display(), dest.display());
+        fs::rename(&path, &dest).chain_err(|| "could not move plan file to quarantine")?;
+        Ok(dest)
+    }
+
+    /// Returns the latest backup of the plan with the given name which can
+    /// be read, as the time it was made and its path, if there is one.
+    fn readable_backup(&self, name: &str) -> Result<Option<(SystemTime, PathBuf)>> {
+        Ok(self.backup_files(name)?.into_iter().rev().find(|(_, path)| read_plan_file(path).is_ok()))
+    }
+
+    /// Copies the file of the plan with the given name (if there is one)
+    /// into the backups directory, deleting the oldest backups of the plan
+    /// beyond the number which are kept.
+    fn back_up(&self, name: &str) -> Result<()> {
+        let (path, format) = self.plan_file(name)?;
+        if self.backup_count == 0 || !path.is_file() {
+            return Ok(());
+        }
+        let dir = self.dir.join(BACKUPS_DIR);
+        fs::create_dir_all(&dir).chain_err(|| "could not create backups directory")?;
+        let dest = timestamped_path(&dir, name, format, SystemTime::now());
+        debug!("backing up plan '{}' to {}", name, dest.display());
+        fs::copy(&path, &dest).chain_err(|| "could not back up plan file")?;
+
+        let backups = self.backup_files(name)?;
+        let excess = backups.len().saturating_sub(self.backup_count);
+        for (_, path) in &backups[..excess] {
+            fs::remove_file(path).chain_err(|| "could not delete old backup")?;
+        }
+        Ok(())
+    }
+
+    /// Returns the backup files of the plan with the given name, oldest
+    /// first, as the time each was made and its path.
+    fn backup_files(&self, name: &str) -> Result<Vec<(SystemTime, PathBuf)>> {
+        let mut backups: Vec<_> = timestamped_files(&self.dir.join(BACKUPS_DIR))?
+            .into_iter()
+            .filter(|(_, backup_name, _)| backup_name == name)
+            .map(|(time, _, path)| (time, path))
+            .collect();
+        backups.sort();
+        Ok(backups)
+    }
+
+    /// Returns the names of the plans in the trash (each name is only
+    /// included once, even if several plans with that name were removed).
+    pub fn trash(&self) -> Result<Vec<String>> {
+        let mut names: Vec<_> = self.trash_files()?.into_iter().map(|(_, name, _)| name).collect();
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    /// Moves the file of the plan with the given name into the trash,
+    /// deleting any plans which have been in the trash for too long.
+    fn move_to_trash(&self, name: &str) -> Result<()> {
+        if !self.allow(FileChange::Trash(self.plan_path(name)?)) {
+            return Ok(());
+        }
+        self.back_up(name)?;
+        let dir = self.dir.join(TRASH_DIR);
+        debug!("moving plan '{}' to {}", name, dir.display());
+        fs::create_dir_all(&dir).chain_err(|| "could not create trash directory")?;
+        let now = SystemTime::now();
+        for (removed, _, path) in self.trash_files()? {
+            if now.duration_since(removed).is_ok_and(|age| age > TRASH_EXPIRY) {
+                fs::remove_file(&path).chain_err(|| "could not delete expired plan from trash")?;
+            }
+        }
+
+        let (path, format) = self.plan_file(name)?;
+        let dest = timestamped_path(&dir, name, format, now);
+        fs::rename(&path, &dest).chain_err(|| "could not move plan file to trash")
+    }
+
+    /// Returns the trashed files of the plan with the given name, oldest
+    /// first, as the time each was removed, its path and the filename it had
+    /// before it was removed.
+    fn trashed(&self, name: &str) -> Result<Vec<(SystemTime, PathBuf, String)>> {
+        let mut trashed = Vec::new();
+        for (removed, trashed_name, path) in self.trash_files()? {
+            if trashed_name == name {
+                let filename = path.file_name().and_then(|f| f.to_str()).unwrap_or_default();
+                // The filename had the time of removal added to its start
+                let original = filename.split_once('.').map_or(filename, |(_, f)| f).to_owned();
+                trashed.push((removed, path, original));
+            }
+        }
+        trashed.sort_by_key(|&(removed, _, _)| removed);
+        Ok(trashed)
+    }
+
+    /// Returns every plan file in the trash, as the time it was removed,
+    /// the name of the plan and the path of the file.
+    fn trash_files(&self) -> Result<Vec<(SystemTime, String, PathBuf)>> {
+        timestamped_files(&self.dir.join(TRASH_DIR))
+    }
+
+    /// Makes changes to several plans at once using the given function,
+    /// which makes them through a `Transaction`, returning its result.
+    ///
+    /// The changes are only made once the function returns successfully: new
+    /// versions of plans are written to a temporary directory inside the
+    /// plans directory, and then renamed over the old ones. If the function
+    /// (or writing any of the plans) fails, no plans are changed. If putting
+    /// one of the new versions in place fails, the plans already changed are
+    /// changed back (using the undo journal, which otherwise records each
+    /// change as usual).
+    pub fn transaction<F, T>(&mut self, f: F) -> Result<T>
+        where F: FnOnce(&mut Transaction) -> Result<T>
+    {
+        // A dry run stages the new versions of the plans outside the plans
+        // directory, so that it isn't changed at all
+        let dir = if self.dry_run { env::temp_dir() } else { self.dir_ensure()?.to_owned() };
+        let staging_dir = dir.join(format!(".transaction.{}.{}",
+                                           process::id(),
+                                           TRANSACTION_COUNT.fetch_add(1, Ordering::SeqCst)));
+        fs::create_dir(&staging_dir).chain_err(|| "could not create transaction directory")?;
+        debug!("staging transaction in {}", staging_dir.display());
+        let mut transaction = Transaction {
+            store: self,
+            staging_dir,
+            changes: BTreeMap::new(),
+            locks: HashMap::new(),
+        };
+
+        let result = f(&mut transaction)?;
+        transaction.commit()?;
+        Ok(result)
+    }
+
+    /// Rewrites every plan which is stored in an older version of the file
+    /// format in the current version, returning the names of the plans
+    /// which were rewritten.
+    ///
+    /// The old version of each rewritten plan is kept in its undo journal.
+    pub fn migrate(&mut self) -> Result<Vec<String>> {
+        self.must_write()?;
+        let mut migrated = Vec::new();
+
+        for name in self.list()? {
+            let _lock = self.lock(&name)?;
+            let (filename, format) = self.plan_file(&name)?;
+            let f = File::open(filename).chain_err(|| "could not open plan file")?;
+            let plan = format.read_value(f).chain_err(|| format!("could not read plan '{}'", name))?;
+
+            if migrations::version(&plan) < migrations::CURRENT_VERSION {
+                let plan = migrations::plan_from_value(plan)
+                    .chain_err(|| format!("could not migrate plan '{}'", name))?;
+                self.record(&name)?;
+                self.write_plan_file(&plan)?;
+                migrated.push(name);
+            }
+        }
+
+        Ok(migrated)
+    }
+
+    /// Records the current state of the plan with the given name (if it
+    /// exists) in its undo journal.
+    fn record(&self, name: &str) -> Result<()> {
+        let (filename, format) = self.plan_file(name)?;
+        if !filename.exists() {
+            return Ok(());
+        }
+        let f = File::open(filename).chain_err(|| "could not open plan file")?;
+        // The plan is stored in the journal exactly as it was in its file,
+        // minus any formatting (since every state must fit on one line);
+        // plans in other formats are converted to JSON
+        let state = format.read_value(f)?;
+
+        let mut states = self.read_journal(name)?;
+        states.push(state.to_string());
+        if states.len() > JOURNAL_LENGTH {
+            let excess = states.len() - JOURNAL_LENGTH;
+            states.drain(..excess);
+        }
+
+        self.write_journal(name, &states)
+    }
+
+    /// Reads the states in the undo journal of the plan with the given name,
+    /// oldest first.
+    fn read_journal(&self, name: &str) -> Result<Vec<String>> {
+        let filename = self.journal_path(name)?;
+        if !filename.exists() {
+            return Ok(Vec::new());
+        }
+        let mut journal = Vec::new();
+        File::open(filename)
+            .and_then(|mut f| f.read_to_end(&mut journal))
+            .chain_err(|| "could not read undo journal")?;
+        #[cfg(feature = "encryption")]
+        {
+            if crypto::is_encrypted(&journal) {
+                journal = crypto::decrypt(&Key::load()?, &journal).chain_err(|| "could not decrypt undo journal")?;
+            }
+        }
+        let mut states = Vec::new();
+        for line in journal.lines() {
+            let line = line.chain_err(|| "could not read undo journal")?;
+            if !line.trim().is_empty() {
+                states.push(line);
+            }
+        }
+
+        Ok(states)
+    }
+
+    /// Replaces the undo journal of the plan with the given name, removing
+    /// it entirely if there are no states left.
+    fn write_journal(&self, name: &str, states: &[String]) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+        let filename = self.journal_path(name)?;
+        if states.is_empty() {
+            if filename.exists() {
+                fs::remove_file(&filename).chain_err(|| "could not remove undo journal")?;
+            }
+            return Ok(());
+        }
+
+        let mut journal = Vec::new();
+        for state in states {
+            writeln!(journal, "{}", state).chain_err(|| "could not write undo journal")?;
+        }
+        // The journal of an encrypted plan would give away its contents
+        #[cfg(feature = "encryption")]
+        {
+            if self.plan_file(name)?.1 == FileFormat::Encrypted {
+                journal = crypto::encrypt(&Key::load()?, &journal)?;
+            }
+        }
+        // As with plan files, the new journal reaches the disk before it
+        // replaces the old one, so that a failed write can't lose it
+        let temp_filename = self.dir.join(format!(".{}.plan.journal.tmp", name));
+        let mut f = File::create(&temp_filename).chain_err(|| "could not create temporary undo journal")?;
+        f.write_all(&journal)
+            .and_then(|_| f.sync_all())
+            .chain_err(|| "could not write temporary undo journal")?;
+        fs::rename(&temp_filename, &filename).chain_err(|| "could not replace undo journal")
+    }
+
+    /// Rewrites every plan (including those in the trash, backups and
+    /// quarantined plans) encrypted, if `encrypted` is true, or otherwise
+    /// rewrites every encrypted plan as JSON, returning the names of the
+    /// plans which were rewritten. The undo journals of the plans are
+    /// rewritten to match.
+    ///
+    /// A file which can't be read (such as a quarantined plan) can't be
+    /// rewritten, so it is removed when encrypting, rather than being left
+    /// unencrypted, and left alone when decrypting.
+    ///
+    /// This doesn't change the format new plans are written in, which is
+    /// given by the `encrypt` setting.
+    #[cfg(feature = "encryption")]
+    pub fn set_encrypted(&mut self, encrypted: bool) -> Result<Vec<String>> {
+        self.must_write()?;
+        let dir = self.dir_must_exist()?.to_owned();
+        let needs_rewrite = |format: FileFormat| (format == FileFormat::Encrypted) != encrypted;
+        let target = if encrypted { FileFormat::Encrypted } else { FileFormat::Json };
+        let mut rewritten = Vec::new();
+
+        for name in self.list()? {
+            let _lock = self.lock(&name)?;
+            let (filename, format) = self.plan_file(&name)?;
+            if !needs_rewrite(format) {
+                continue;
+            }
+            let states = self.read_journal(&name)?;
+            let plan = self.read(&name).chain_err(|| format!("could not read plan '{}'", name))?;
+            // Older versions kept a backup next to the plan file
+            let mut old_backup = filename.clone().into_os_string();
+            old_backup.push(".bak");
+            let old_backup = PathBuf::from(old_backup);
+            let temp_filename = dir.join(format!(".{}{}.tmp", name, target.extension()));
+            target.write_file(&plan, &temp_filename)?;
+            fs::rename(&temp_filename, dir.join(format!("{}{}", name, target.extension())))
+                .chain_err(|| "could not replace plan file")?;
+            fs::remove_file(&filename).chain_err(|| "could not remove old plan file")?;
+            if old_backup.exists() {
+                fs::remove_file(&old_backup).chain_err(|| "could not remove old backup")?;
+            }
+            self.write_journal(&name, &states)?;
+            rewritten.push(name);
+        }
+
+        for &subdir in &[TRASH_DIR, BACKUPS_DIR, QUARANTINE_DIR] {
+            for (_, name, path) in timestamped_files(&dir.join(subdir))? {
+                let filename = path.file_name().and_then(|f| f.to_str()).unwrap_or_default().to_owned();
+                let format = match FileFormat::for_filename(&filename) {
+                    Some(f) if needs_rewrite(f) => f,
+                    _ => continue,
+                };
+                let read = File::open(&path).chain_err(|| "could not open plan file").and_then(|f| format.read(f));
+                let plan = match read {
+                    Ok(plan) => plan,
+                    Err(e) => {
+                        info!("could not read plan '{}' in {}: {}", name, path.display(), e);
+                        if encrypted {
+                            fs::remove_file(&path).chain_err(|| "could not remove unreadable plan file")?;
+                        }
+                        continue;
+                    }
+                };
+                let new_path = path.with_file_name(format!("{}{}",
+                                                           &filename[..filename.len() - format.extension().len()],
+                                                           target.extension()));
+                target.write_file(&plan, &new_path)?;
+                fs::remove_file(&path).chain_err(|| "could not remove old plan file")?;
+            }
+        }
+
+        Ok(rewritten)
+    }
+
+    /// Writes the given plan, as with `write`, but only if the stored plan
+    /// hasn't been changed since the given one was read, i.e. if it has the
+    /// same revision (or, for a plan which has never been stored, if it
+    /// still doesn't exist). Otherwise, a `ConcurrentModification` error is
+    /// returned and nothing is written.
+    pub fn overwrite(&mut self, plan: &Plan) -> Result<()> {
+        self.dir_ensure()?;
+        let _lock = self.lock(plan.name())?;
+        self.check_revision(plan.name(), plan.revision())?;
+        self.record(plan.name())?;
+        self.write_plan_file(&next_version(plan, plan.revision()))
+    }
+
+    /// Returns the revision of the stored plan with the given name, or
+    /// `None` if it doesn't exist.
+    fn stored_revision(&self, name: &str) -> Result<Option<u64>> {
+        if self.exists(name)? {
+            self.summary(name).map(|s| Some(s.revision()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns a `ConcurrentModification` error unless the stored plan with
+    /// the given name has the given revision (where a plan which doesn't
+    /// exist can only match revision 0).
+    fn check_revision(&self, name: &str, revision: u64) -> Result<()> {
+        match self.stored_revision(name)? {
+            Some(r) if r == revision => Ok(()),
+            None if revision == 0 => Ok(()),
+            _ => Err(ErrorKind::ConcurrentModification(name.into()).into()),
+        }
+    }
+
+    /// Writes the given plan to its file (as described in the module
+    /// documentation), without recording anything in the journal.
+    fn write_plan_file(&self, plan: &Plan) -> Result<()> {
+        if !self.allow(FileChange::Write(self.plan_path(plan.name())?)) {
+            return Ok(());
+        }
+        let dir = self.dir_ensure()?;
+        let (_, format) = self.plan_file(plan.name())?;
+        let temp_filename = dir.join(format!(".{}{}.tmp", plan.name(), format.extension()));
+
+        // Write the plan to a temporary file first, making sure it actually
+        // reaches the disk before it replaces the old version
+        format.write_file(plan, &temp_filename)?;
+        self.replace_plan_file(plan.name(), &temp_filename)
+    }
+
+    /// Replaces the file of the plan with the given name by the given file
+    /// (which must be in the same format), backing up the old version first.
+    fn replace_plan_file(&self, name: &str, new_filename: &Path) -> Result<()> {
+        let filename = self.plan_path(name)?;
+        if !self.allow(FileChange::Write(filename.clone())) {
+            return Ok(());
+        }
+        if filename.exists() {
+            self.back_up(name)?;
+        }
+        debug!("writing plan '{}' to {}", name, filename.display());
+        fs::rename(new_filename, &filename)
+            .chain_err(|| "could not replace plan file")
+    }
+
+    /// Returns the plans directory, ensuring that it actually exists
+    /// (the directory will be created if it does not).
+    fn dir_ensure(&self) -> Result<&Path> {
+        if !self.dir.is_dir() && !self.dry_run {
+            info!("creating plans directory {}", self.dir.display());
+            fs::create_dir_all(&self.dir)
+                .map(|_| self.dir.as_path())
+                .chain_err(|| "could not create plans directory")
+        } else {
+            Ok(&self.dir)
+        }
+    }
+
+    /// Returns the plans directory, returning an error if it doesn't exist.
+    fn dir_must_exist(&self) -> Result<&Path> {
+        if !self.dir.is_dir() {
+            Err(ErrorKind::NoConfigDirectory.into())
+        } else {
+            Ok(&self.dir)
+        }
+    }
+}
+
+impl PlanStore for FsStore {
+    fn read(&self, name: &str) -> Result<Plan> {
+        self.dir_must_exist()?;
+        let (filename, format) = self.plan_file(name)?;
+
+        if !filename.exists() {
+            return Err(ErrorKind::PlanDoesNotExist(name.into()).into());
+        }
+        let f = File::open(filename).chain_err(|| "could not open plan file")?;
+
+        format.read(f)
+    }
+
+    fn write(&mut self, plan: &Plan) -> Result<()> {
+        self.dir_ensure()?;
+        let _lock = self.lock(plan.name())?;
+        let stored = self.stored_revision(plan.name())?.unwrap_or(0);
+        self.record(plan.name())?;
+        self.write_plan_file(&next_version(plan, stored))
+    }
+
+    fn update<F, T>(&mut self, name: &str, f: F) -> Result<T>
+        where F: FnOnce(&mut Plan) -> Result<T>
+    {
+        self.dir_must_exist()?;
+        let _lock = self.lock(name)?;
+        let mut plan = self.read(name)?;
+        let revision = plan.revision();
+        let result = f(&mut plan)?;
+
+        if plan.name() != name {
+            bail!("cannot rename plan '{}' while updating it", name);
+        }
+        self.check_revision(name, revision)?;
+        self.record(name)?;
+        self.write_plan_file(&next_version(&plan, revision))?;
+
+        Ok(result)
+    }
+
+    fn remove(&mut self, name: &str) -> Result<()> {
+        self.dir_must_exist()?;
+        let filename = self.plan_path(name)?;
+
+        let _lock = self.lock(name)?;
+        if !filename.exists() {
+            Err(ErrorKind::PlanDoesNotExist(name.to_owned()).into())
+        } else {
+            self.record(name)?;
+            self.move_to_trash(name)
+        }
+    }
+
+    /// Renames the plan, keeping it in the same format. Its undo journal and
+    /// backups go along with it, so that its earlier changes can still be
+    /// undone, and nothing is put in the trash under its old name.
+    fn rename(&mut self, name: &str, new_name: &str) -> Result<Plan> {
+        self.must_write()?;
+        self.dir_must_exist()?;
+        let mut plan = self.read(name)?;
+        plan.set_property("name", new_name)?;
+        if new_name == name {
+            return Ok(plan);
+        }
+        let _lock = self.lock(name)?;
+        let _new_lock = self.lock(new_name)?;
+        if self.exists(new_name)? {
+            return Err(ErrorKind::PlanAlreadyExists(new_name.into()).into());
+        }
+        // The plan is read again now that nobody else can change it
+        let mut plan = self.read(name)?;
+        plan.set_name(new_name);
+        let stored = self.stored_revision(name)?.unwrap_or(0);
+        let plan = next_version(&plan, stored);
+
+        let (path, format) = self.plan_file(name)?;
+        let temp_filename = self.dir.join(format!(".{}{}.tmp", new_name, format.extension()));
+        format.write_file(&plan, &temp_filename)?;
+        let new_path = self.dir.join(format!("{}{}", new_name, format.extension()));
+        debug!("renaming plan '{}' to {}", name, new_path.display());
+        fs::rename(&temp_filename, &new_path).chain_err(|| "could not write renamed plan file")?;
+
+        let journal = self.journal_path(name)?;
+        if journal.exists() {
+            fs::rename(&journal, self.journal_path(new_name)?).chain_err(|| "could not move undo journal")?;
+        }
+        // Each backup keeps the time it was made (and its format)
+        for (time, backup) in self.backup_files(name)? {
+            let backup_format = backup.to_str().and_then(FileFormat::for_filename).unwrap_or(format);
+            let dest = timestamped_path(&self.dir.join(BACKUPS_DIR), new_name, backup_format, time);
+            fs::rename(&backup, &dest).chain_err(|| "could not move backup")?;
+        }
+        fs::remove_file(&path).chain_err(|| "could not remove plan file under its old name")?;
+
+        Ok(plan)
+    }
+
+    fn list(&self) -> Result<Vec<String>> {
+        self.plan_names()
+    }
+
+    fn exists(&self, name: &str) -> Result<bool> {
+        Ok(self.plan_path(name)?.is_file())
+    }
+
+    /// Summarizes every plan in the directory, without reading the entries
+    /// of plans stored as JSON in the current version of the format.
+    fn summaries(&self) -> Result<Vec<Result<PlanSummary>>> {
+        Ok(self.list()?.iter().map(|name| self.summary(name)).collect())
+    }
+}
+
+impl<'a> Transaction<'a> {
+    /// Returns the path of the new version of the plan with the given name.
+    fn staged_path(&self, name: &str, format: FileFormat) -> PathBuf {
+        self.staging_dir.join(format!("{}{}", name, format.extension()))
+    }
+
+    /// Takes the lock on the plan with the given name, unless it is already
+    /// held by the transaction.
+    fn lock(&mut self, name: &str) -> Result<()> {
+        if !self.locks.contains_key(name) {
+            let lock = self.store.lock(name)?;
+            self.locks.insert(name.to_owned(), lock);
+        }
+        Ok(())
+    }
+
+    /// Puts all the changes in place, changing back the plans already
+    /// changed if that fails.
+    fn commit(&self) -> Result<()> {
+        let mut changed = Vec::new();
+        let result = self.apply_changes(&mut changed);
+
+        if result.is_err() {
+            // This is the best that can be done: if changing a plan back
+            // fails as well, there's nothing left to try
+            for &(name, existed) in changed.iter().rev() {
+                let _ = if existed {
+                    self.store.undo_locked(name).map(|_| ())
+                } else {
+                    self.store
+                        .plan_path(name)
+                        .and_then(|path| fs::remove_file(path).chain_err(|| "could not remove plan file"))
+                };
+            }
+        }
+        result.chain_err(|| "could not commit transaction")
+    }
+
+    /// Puts the changes in place, adding the name of each plan to `changed`
+    /// (along with whether it existed before) once its previous state has
+    /// been recorded in its undo journal.
+    fn apply_changes<'b>(&'b self, changed: &mut Vec<(&'b str, bool)>) -> Result<()> {
+        for (name, &change) in &self.changes {
+            let existed = self.store.exists(name)?;
+            self.store.record(name)?;
+            changed.push((name, existed));
+            match change {
+                Change::Write(format) => self.store.replace_plan_file(name, &self.staged_path(name, format))?,
+                Change::Remove => self.store.move_to_trash(name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> PlanStore for Transaction<'a> {
+    fn read(&self, name: &str) -> Result<Plan> {
+        match self.changes.get(name) {
+            Some(&Change::Write(format)) => {
+                let f = File::open(self.staged_path(name, format)).chain_err(|| "could not open plan file")?;
+                format.read(f)
+            }
+            Some(&Change::Remove) => Err(ErrorKind::PlanDoesNotExist(name.into()).into()),
+            None => self.store.read(name),
+        }
+    }
+
+    fn write(&mut self, plan: &Plan) -> Result<()> {
+        self.lock(plan.name())?;
+        // The plan is kept in the format it is already stored in
+        let (_, format) = self.store.plan_file(plan.name())?;
+        let stored = self.store.stored_revision(plan.name())?.unwrap_or(0);
+        let plan = next_version(plan, stored);
+        format.write_file(&plan, &self.staged_path(plan.name(), format))?;
+        self.changes.insert(plan.name().to_owned(), Change::Write(format));
+        Ok(())
+    }
+
+    fn remove(&mut self, name: &str) -> Result<()> {
+        if !self.exists(name)? {
+            return Err(ErrorKind::PlanDoesNotExist(name.into()).into());
+        }
+        self.lock(name)?;
+        // A plan which was only added by the transaction can simply be
+        // forgotten
+        if self.store.exists(name)? {
+            self.changes.insert(name.to_owned(), Change::Remove);
+        } else {
+            self.changes.remove(name);
+        }
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<String>> {
+        let mut names: BTreeSet<_> = self.store.list()?.into_iter().collect();
+        for (name, &change) in &self.changes {
+            match change {
+                Change::Write(_) => names.insert(name.clone()),
+                Change::Remove => names.remove(name),
+            };
+        }
+        Ok(names.into_iter().collect())
+    }
+
+    fn exists(&self, name: &str) -> Result<bool> {
+        match self.changes.get(name) {
+            Some(&Change::Write(_)) => Ok(true),
+            Some(&Change::Remove) => Ok(false),
+            None => self.store.exists(name),
+        }
+    }
+
+    fn update<F, T>(&mut self, name: &str, f: F) -> Result<T>
+        where F: FnOnce(&mut Plan) -> Result<T>
+    {
+        // The plan is locked first, so that it can't change between being
+        // read and written
+        self.lock(name)?;
+        let mut plan = self.read(name)?;
+        let result = f(&mut plan)?;
+
+        if plan.name() != name {
+            bail!("cannot rename plan '{}' while updating it", name);
+        }
+        self.write(&plan)?;
+
+        Ok(result)
+    }
+}
+
+impl<'a> Drop for Transaction<'a> {
+    fn drop(&mut self) {
+        // Anything left in the staging directory is no longer needed (and
+        // there's nothing to be done if it can't be removed)
+        let _ = fs::remove_dir_all(&self.staging_dir);
+    }
+}
+
+impl Profile {
+    /// The name of the default profile.
+    pub const DEFAULT: &'static str = "default";
+
+    /// Returns the profile with the given name, for the given plans
+    /// directory. The profile doesn't need to exist yet.
+    pub fn new<P: Into<PathBuf>>(plans_dir: P, name: &str) -> Result<Profile> {
+        let plans_dir = plans_dir.into();
+        if name == Profile::DEFAULT {
+            return Ok(Profile {
+                name: name.to_owned(),
+                dir: plans_dir,
+            });
+        }
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            bail!("invalid profile name '{}'", name);
+        }
+
+        Ok(Profile {
+            name: name.to_owned(),
+            dir: plans_dir.join(PROFILES_DIR).join(name),
+        })
+    }
+
+    /// Returns the profile with the given name, for the default plans
+    /// directory (see `plans_dir`).
+    pub fn named(name: &str) -> Result<Profile> {
+        Profile::new(plans_dir()?, name)
+    }
+
+    /// Returns the names of all the profiles for the given plans directory,
+    /// starting with the default profile (which always exists).
+    pub fn list<P: AsRef<Path>>(plans_dir: P) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        let dir = plans_dir.as_ref().join(PROFILES_DIR);
+
+        if dir.is_dir() {
+            for entry in fs::read_dir(&dir).chain_err(|| "could not read from profiles directory")? {
+                let path = entry.chain_err(|| "could not read directory item")?.path();
+                let name = match path.file_name() {
+                    Some(n) => n.to_str().ok_or_else(|| ErrorKind::NonUtf8Path(path.clone()))?,
+                    None => continue,
+                };
+                if path.is_dir() && !name.starts_with('.') && name != Profile::DEFAULT {
+                    names.push(name.to_owned());
+                }
+            }
+        }
+        names.sort();
+        names.insert(0, Profile::DEFAULT.to_owned());
+
+        Ok(names)
+    }
+
+    /// Returns the name of the profile.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the directory containing the plan files of the profile.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns whether this is the default profile.
+    pub fn is_default(&self) -> bool {
+        self.name == Profile::DEFAULT
+    }
+
+    /// Returns whether the profile exists. The default profile always
+    /// exists, even if its directory hasn't been created yet.
+    pub fn exists(&self) -> bool {
+        self.is_default() || self.dir.is_dir()
+    }
+
+    /// Returns a store for the plans of the profile.
+    pub fn store(&self) -> FsStore {
+        FsStore::new(&self.dir)
+    }
+
+    /// Creates the profile, returning a `ProfileAlreadyExists` error if it
+    /// already exists.
+    pub fn create(&self) -> Result<()> {
+        if self.exists() {
+            bail!(ErrorKind::ProfileAlreadyExists(self.name.clone()));
+        }
+        fs::create_dir_all(&self.dir).chain_err(|| "could not create profile directory")
+    }
+
+    /// Deletes the profile, along with all of its plans, returning a
+    /// `ProfileDoesNotExist` error if it doesn't exist. The default profile
+    /// can't be deleted.
+    pub fn delete(&self) -> Result<()> {
+        if self.is_default() {
+            bail!("the default profile cannot be deleted");
+        }
+        if !self.exists() {
+            bail!(ErrorKind::ProfileDoesNotExist(self.name.clone()));
+        }
+        fs::remove_dir_all(&self.dir).chain_err(|| "could not remove profile directory")
+    }
+}
+
+/// Returns a copy of the given plan ready to be written over a stored plan
+/// with the given revision (0 if there is none): marked as modified now,
+/// and with a revision after both its own and the stored one.
+fn next_version(plan: &Plan, stored_revision: u64) -> Plan {
+    let mut plan = plan.clone();
+    plan.mark_modified(Utc::now());
+    plan.set_revision(stored_revision.max(plan.revision()) + 1);
+    plan
+}
+
+/// Reads the plan in the given file, in the format given by its extension
+/// (JSON if it doesn't have a known one).
+fn read_plan_file(path: &Path) -> Result<Plan> {
+    let format = path.to_str().and_then(FileFormat::for_filename).unwrap_or(FileFormat::Json);
+    let f = File::open(path).chain_err(|| format!("could not open file {}", path.display()))?;
+    format.read(f)
+}
+
+/// Returns a path in the given directory for a file of the plan with the
+/// given name and format named after the given time (see `timestamped_files`),
+/// which no other file has.
+fn timestamped_path(dir: &Path, name: &str, format: FileFormat, time: SystemTime) -> PathBuf {
+    let mut millis = time.duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+    // A plan can be removed or backed up more than once in the same instant
+    // (e.g. when it is renamed and then removed)
+    loop {
+        let path = dir.join(format!("{}.{}{}", millis, name, format.extension()));
+        if !path.exists() {
+            return path;
+        }
+        millis += 1;
+    }
+}
+
+/// Returns every plan file named `{time}.{name}{extension}` in the given
+/// directory (such as the trash), as its time, the name of the plan and the
+/// path of the file. A directory which doesn't exist has no files.
+fn timestamped_files(dir: &Path) -> Result<Vec<(SystemTime, String, PathBuf)>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).chain_err(|| format!("could not read from directory {}", dir.display()))? {
+        let path = entry.chain_err(|| "could not read directory item")?.path();
+        let filename = match path.file_name().and_then(|f| f.to_str()) {
+            Some(f) => f,
+            None => continue,
+        };
+        // Skip over anything that isn't a plan file
+        let (millis, rest) = match filename.split_once('.') {
+            Some((millis, rest)) => (millis, rest),
+            None => continue,
+        };
+        let (millis, format) = match (millis.parse(), FileFormat::for_filename(rest)) {
+            (Ok(millis), Some(format)) => (millis, format),
+            _ => continue,
+        };
+        let name = rest[..rest.len() - format.extension().len()].to_owned();
+        files.push((UNIX_EPOCH + Duration::from_millis(millis), name, path));
     }
+    Ok(files)
+}
+
+/// Checks whether every plan in the plans directory can be read (see
+/// `FsStore::diagnose`).
+pub fn diagnose() -> Result<Vec<Diagnosis>> {
+    FsStore::default_location()?.diagnose()
 }
 
 /// Returns an iterator over the plans in the plan directory if possible,
 /// or an error if this cannot be done.
 ///
 /// As noted in the module documentation, plans must have the extension
-/// `.plan.json` to be recognized; the iterator will pass over any files
-/// that do not have this extension.
+/// `.plan.json` (or that of another enabled format) to be recognized; the
+/// iterator will pass over any files that do not have such an extension.
 pub fn plans() -> Result<Plans> {
-    let dir = plans_dir_must_exist()?;
+    FsStore::default_location()?.iter()
+}
 
-    Ok(Plans { read_dir: fs::read_dir(&dir).chain_err(|| ErrorKind::Io("could not read from plans directory".into()))? })
+/// Returns the names of the plans in the plans directory, without reading
+/// any of them (see `FsStore::plan_names`).
+pub fn plan_names() -> Result<Vec<String>> {
+    FsStore::default_location()?.plan_names()
 }
 
-/// Returns the location of the plans directory if possible.
-pub fn plans_dir() -> Result<PathBuf> {
-    match app_dirs::get_app_dir(AppDataType::UserData, &APP_INFO, "plans") {
-        Ok(p) => Ok(p),
-        Err(AppDirsError::NotSupported) => Err(ErrorKind::CannotLocateConfig.into()),
-        Err(AppDirsError::Io(e)) => {
-            Err(e).chain_err(|| ErrorKind::Io("could not find plans directory".into()))
+/// Returns all the plans in the plans directory that are part of the
+/// given selection (see `PlanStore::select`).
+pub fn select_plans(selection: &Selection) -> Result<Vec<Plan>> {
+    FsStore::default_location()?.select(selection)
+}
+
+/// Returns whether the given name can be the name of a plan stored in a
+/// plans directory: one which isn't empty and can't lead outside the
+/// directory, since it contains no `/`, `\` or `..`.
+pub fn is_valid_plan_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['/', '\\']) && !name.contains("..")
+}
+
+/// Returns an `InvalidPlanName` error if the given name can't be the name
+/// of a stored plan (see `is_valid_plan_name`).
+fn check_plan_name(name: &str) -> Result<()> {
+    if !is_valid_plan_name(name) {
+        bail!(ErrorKind::InvalidPlanName(name.to_owned()));
+    }
+    Ok(())
+}
+
+/// Returns whether the file at the given path is a plan file, judging by
+/// its name (e.g. `name.plan.json`), as opposed to another file that may be
+/// kept in a plans directory, such as a lock or an undo journal.
+pub fn is_plan_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|f| f.to_str())
+        .and_then(FileFormat::for_filename)
+        .is_some()
+}
+
+/// Summarizes every plan in the plans directory, without reading in the
+/// entries of plans when possible (see `FsStore::summary`), sorted in the
+/// given order, or the reverse of it (see `FsStore::sorted_summaries`).
+pub fn plan_summaries(order: SortOrder, reverse: bool) -> Result<Vec<Result<PlanSummary>>> {
+    FsStore::default_location()?.sorted_summaries(order, reverse)
+}
+
+/// Searches the entries of every plan in the plans directory (see
+/// `PlanStore::search`).
+pub fn search(query: &Query) -> Result<Vec<SearchHit>> {
+    FsStore::default_location()?.search(query)
+}
+
+/// Returns the name of the plan in the plans directory whose name is most
+/// similar to the given one, if any (see `PlanStore::closest_plan`).
+pub fn closest_plan(name: &str) -> Result<Option<String>> {
+    FsStore::default_location()?.closest_plan(name)
+}
+
+/// Calls the given function with the default plans directory set to the
+/// given path, restoring the previous default afterwards.
+///
+/// This only affects the current thread, and takes precedence over the
+/// `READING_PLANS_DIR` environment variable.
+pub fn with_plans_dir<P, F, T>(path: P, f: F) -> T
+    where P: Into<PathBuf>,
+          F: FnOnce() -> T
+{
+    /// Restores the previous override when dropped (even if `f` panics)
+    struct Restore(Option<PathBuf>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            PLANS_DIR_OVERRIDE.with(|o| *o.borrow_mut() = previous);
         }
-        // This should properly be a panic, since there really isn't any way
-        // this can happen (unless `app_dirs` changes in a breaking way).
-        Err(AppDirsError::InvalidAppInfo) => panic!("invalid app info"),
     }
+
+    let previous = PLANS_DIR_OVERRIDE.with(|o| o.borrow_mut().replace(path.into()));
+    let _restore = Restore(previous);
+    f()
+}
+
+/// Writes every plan in the given store to the output as a collection,
+/// returning the number of plans written.
+///
+/// Since a collection is meant to be a complete backup, an error is
+/// returned if any plan can't be read.
+pub fn write_collection<S: PlanStore, W: Write>(store: &S, output: W) -> Result<usize> {
+    let plans = store.plans()?.into_iter().collect::<Result<Vec<_>>>()?;
+    let collection = Collection {
+        version: COLLECTION_VERSION,
+        plans,
+    };
+
+    serde_json::to_writer_pretty(output, &collection)
+        .chain_err(|| "could not write collection")?;
+    Ok(collection.plans.len())
 }
 
-/// Returns the location of the plans directory, ensuring that it
-/// actually exists (the directory will be created if it does not).
-fn plans_dir_ensure() -> Result<PathBuf> {
-    let path = plans_dir()?;
-    if !path.exists() || !path.is_dir() {
-        fs::create_dir(&path)
-            .map(|_| path)
-            .chain_err(|| ErrorKind::Io("could not create plans directory".into()))
-    } else {
-        Ok(path)
+/// Reads a collection from the input and adds its plans to the given
+/// store, resolving conflicts with existing plans as specified.
+///
+/// When conflicts are to be treated as errors, all the plans are checked
+/// before any are imported, so that a failed import changes nothing.
+pub fn read_collection<S: PlanStore, R: Read>(store: &mut S,
+                                              input: R,
+                                              conflict: Conflict)
+                                              -> Result<ImportReport> {
+    let collection: Collection<serde_json::Value> = serde_json::from_reader(input)
+        .chain_err(|| "could not read collection")?;
+    if collection.version > COLLECTION_VERSION {
+        bail!(ErrorKind::UnsupportedVersion(collection.version, COLLECTION_VERSION));
+    }
+    let plans = collection.plans
+        .into_iter()
+        .map(migrations::plan_from_value)
+        .collect::<Result<Vec<_>>>()?;
+
+    if conflict == Conflict::Fail {
+        for plan in &plans {
+            if store.exists(plan.name())? {
+                return Err(ErrorKind::PlanAlreadyExists(plan.name().into()).into());
+            }
+        }
+    }
+    let mut report = ImportReport::default();
+    for plan in &plans {
+        if conflict == Conflict::Skip && store.exists(plan.name())? {
+            report.skipped.push(plan.name().to_owned());
+        } else {
+            store.write(plan)?;
+            report.imported.push(plan.name().to_owned());
+        }
     }
+
+    Ok(report)
+}
+
+/// Writes every plan in the plans directory to the output as a collection
+/// (see `write_collection`).
+pub fn export_collection<W: Write>(output: W) -> Result<usize> {
+    write_collection(&FsStore::default_location()?, output)
 }
 
-/// Returns the location of the plans directory, returning an error
-/// if it doesn't exist.
-fn plans_dir_must_exist() -> Result<PathBuf> {
-    let path = plans_dir()?;
-    if !path.exists() || !path.is_dir() {
-        Err(ErrorKind::NoConfigDirectory.into())
-    } else {
-        Ok(path)
+/// Imports the plans in a collection into the plans directory (see
+/// `read_collection`).
+pub fn import_collection<R: Read>(input: R, conflict: Conflict) -> Result<ImportReport> {
+    read_collection(&mut FsStore::default_location()?, input, conflict)
+}
+
+/// Returns the location of the plans directory if possible.
+///
+/// This is the directory set by `with_plans_dir`, if any, or else the value
+/// of the `READING_PLANS_DIR` environment variable, if it is set (and not
+/// empty), or else the `plans_dir` setting (see the `config` module);
+/// otherwise, it is a directory chosen based on the operating system.
+pub fn plans_dir() -> Result<PathBuf> {
+    if let Some(dir) = PLANS_DIR_OVERRIDE.with(|o| o.borrow().clone()) {
+        return Ok(dir);
+    }
+    if let Some(dir) = env::var_os(PLANS_DIR_VAR) {
+        if !dir.is_empty() {
+            return Ok(PathBuf::from(dir));
+        }
     }
+    if let Some(dir) = Config::load()?.plans_dir() {
+        return Ok(dir.to_owned());
+    }
+
+    app_dirs::get_app_dir(AppDataType::UserData, &APP_INFO, "plans")
+        .chain_err(|| ErrorKind::CannotLocateConfig)
+}
+
+/// Returns the names of all the profiles in the plans directory (see
+/// `Profile::list`).
+pub fn profiles() -> Result<Vec<String>> {
+    Profile::list(plans_dir()?)
 }
 
 /// Reads the plan with the given name.
 ///
-/// The filename of the plan must be `{name}.plan.json`, or it will
-/// not be recognized.
+/// The filename of the plan must be `{name}.plan.json` (or use the
+/// extension of another enabled format), or it will not be recognized.
 pub fn read_plan(name: &str) -> Result<Plan> {
-    let mut filename = plans_dir_must_exist()?;
-    filename.push(name);
-    filename.set_extension("plan.json");
-
-    if !filename.exists() {
-        return Err(ErrorKind::PlanDoesNotExist(name.into()).into());
-    }
-    // We need to map the error into the correct type (wrap it in the
-    // Io variant of our custom error)
-    let f = File::open(filename).chain_err(|| ErrorKind::Io("could not open plan file".into()))?;
+    FsStore::default_location()?.read(name)
+}
 
-    serde_json::from_reader(f).chain_err(|| ErrorKind::Json("json error in plan file".into()))
+/// Returns the path of the file for the plan with the given name in the
+/// plans directory (see `FsStore::plan_path`).
+pub fn plan_path(name: &str) -> Result<PathBuf> {
+    FsStore::default_location()?.plan_path(name)
 }
 
 /// Writes the given plan to the plans directory, or will return
 /// an error if the plan already exists there.
 pub fn add_plan(p: &Plan) -> Result<()> {
-    let mut filename = plans_dir_ensure()?;
-    filename.push(p.name());
-    filename.set_extension("plan.json");
+    FsStore::default_location()?.add(p)
+}
 
-    if filename.exists() {
-        return Err(ErrorKind::PlanAlreadyExists(p.name().into()).into());
-    }
-    let mut f =
-        File::create(filename).chain_err(|| ErrorKind::Io("could not create plan file".into()))?;
+/// Writes the given plan to the plans directory, overwriting it if it
+/// already exists, unless it was changed since the given plan was read (see
+/// `FsStore::overwrite`).
+pub fn overwrite_plan(p: &Plan) -> Result<()> {
+    FsStore::default_location()?.overwrite(p)
+}
 
-    serde_json::to_writer(&mut f, &p)
-        .chain_err(|| ErrorKind::Json("could not serialize plan to json".into()))
+/// Copies the plan named `src` in the plans directory to a new plan named
+/// `dest`, with its progress reset (see `PlanStore::copy`).
+pub fn copy_plan(src: &str, dest: &str) -> Result<Plan> {
+    FsStore::default_location()?.copy(src, dest)
 }
 
-/// Writes the given plan to the plans directory, overwriting it if
-/// it already exists.
-pub fn overwrite_plan(p: &Plan) -> Result<()> {
-    let mut filename = plans_dir_ensure()?;
-    filename.push(p.name());
-    filename.set_extension("plan.json");
+/// Rewrites every plan in the plans directory which is stored in an older
+/// version of the file format (see `FsStore::migrate`).
+pub fn migrate_plans() -> Result<Vec<String>> {
+    FsStore::default_location()?.migrate()
+}
 
-    let mut f =
-        File::create(filename).chain_err(|| ErrorKind::Io("could not overwrite plan file".into()))?;
+/// Reverts the most recent change to the plan with the given name (see
+/// `FsStore::undo`), returning the restored plan.
+pub fn undo_last(name: &str) -> Result<Plan> {
+    FsStore::default_location()?.undo(name)
+}
 
-    serde_json::to_writer(&mut f, &p)
-        .chain_err(|| ErrorKind::Json("could not serialize plan to json".into()))
+/// Changes the plan with the given name using the given function, holding
+/// its lock while it is read and written (see `PlanStore::update`).
+pub fn update_plan<F, T>(name: &str, f: F) -> Result<T>
+    where F: FnOnce(&mut Plan) -> Result<T>
+{
+    FsStore::default_location()?.update(name, f)
 }
 
 /// Attempts to remove the plan with the given name, returning
 /// an error if it doesn't exist.
 pub fn remove_plan(name: &str) -> Result<()> {
-    let mut filename = plans_dir_must_exist()?;
-    filename.push(name);
-    filename.set_extension("plan.json");
+    FsStore::default_location()?.remove(name)
+}
 
-    if !filename.exists() {
-        Err(ErrorKind::PlanDoesNotExist(name.to_owned()).into())
-    } else {
-        fs::remove_file(&filename).chain_err(|| ErrorKind::Io("could not remove plan file".into()))
-    }
+/// Changes several plans in the plans directory at once, so that either all
+/// of the changes are made or none of them are (see `FsStore::transaction`).
+pub fn transaction<F, T>(f: F) -> Result<T>
+    where F: FnOnce(&mut Transaction) -> Result<T>
+{
+    FsStore::default_location()?.transaction(f)
 }
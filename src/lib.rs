@@ -1,17 +1,24 @@
 //! When used as a library, `reading` provides all the features of the binary
 //! program, exposed in a way that they can be reused by others (e.g. eventually
 //! I might like to make a GUI interface without rewriting all this code).
-//! The library is split into three modules: `errors`, which provides
+//! The library is split into several modules: `errors`, which provides
 //! all the error types (provided by `error_chain`); `plan`, which provides
-//! the basic types for working with plans, such as `Plan`; and `files`,
-//! which provides methods for working with plans stored in a system-dependent
-//! configuration directory.
+//! the basic types for working with plans, such as `Plan`; `store`, which
+//! provides the `PlanStore` trait for abstracting over where plans are
+//! stored; `files`, which provides methods for working with plans
+//! stored in a system-dependent configuration directory; `config`, which
+//! reads and writes the user's settings; `migrations`, which upgrades
+//! plans stored in older versions of the file format;
+//! `stats`, which computes statistics about progress through plans; and
+//! `search`, which finds entries matching a query. There are also `remind`,
+//! which decides when to remind the user to read, and `status`, which gives
+//! the exit status of the program for each error. With the `encryption`
+//! feature, there is also `crypto`, which encrypts plan files.
 //!
 //! More information on each module (except `errors`, which is self-explanatory)
 //! is provided in the module-level documentation for each. Several fundamental
-//! types,
-//! such as `Plan` and `Error`, are re-exported as members of this module for
-//! convenient use.
+//! types, such as `Plan`, `PlanStore` and `Error`, are re-exported as members
+//! of this module for convenient use.
 
 // For `error_chain!`
 #![recursion_limit = "1024"]
@@ -22,9 +29,23 @@ extern crate serde_derive;
 extern crate serde_json;
 
 extern crate app_dirs;
+extern crate chrono;
+extern crate csv;
+extern crate fs2;
+#[macro_use]
+extern crate log;
+extern crate regex;
+extern crate xml;
+#[cfg(feature = "yaml-format")]
+extern crate serde_yaml;
+extern crate toml;
 #[macro_use]
 extern crate error_chain;
+#[cfg(feature = "encryption")]
+extern crate chacha20poly1305;
 
+// `error_chain` uses the deprecated `Error` methods to handle foreign errors
+#[allow(deprecated)]
 pub mod errors {
     error_chain!{
         errors {
@@ -32,15 +53,35 @@ pub mod errors {
             CannotLocateConfig {
                 description("cannot locate config directory")
             }
-            /// The config directory does not exist (has not been created yet).
+            /// The plans directory does not exist (has not been created yet).
             NoConfigDirectory {
-                description("config directory does not exist")
+                description("plans directory does not exist")
+                display("the plans directory does not exist yet (use `reading init` to create it)")
             }
             /// The specified plan does not exist (includes the name of the plan).
             PlanDoesNotExist(name: String) {
                 description("plan does not exist")
                 display("plan '{}' does not exist", name)
             }
+            /// The specified plan does not exist, but there is one with a
+            /// similar name (includes the name of the plan and the similar
+            /// name).
+            MisspelledPlan(name: String, suggestion: String) {
+                description("plan does not exist")
+                display("plan '{}' does not exist (did you mean '{}'?)", name, suggestion)
+            }
+            /// The specified profile does not exist (includes the name of the
+            /// profile).
+            ProfileDoesNotExist(name: String) {
+                description("profile does not exist")
+                display("profile '{}' does not exist", name)
+            }
+            /// The specified profile already exists (includes the name of the
+            /// profile).
+            ProfileAlreadyExists(name: String) {
+                description("profile already exists")
+                display("profile '{}' already exists", name)
+            }
             /// The specified plan already exists (includes the name of the plan).
             /// This may be an error if, for example, the user tries to add
             /// a plan with the same name as one previously existing.
@@ -48,26 +89,174 @@ pub mod errors {
                 description("plan already exists")
                 display("plan '{}' already exists", name)
             }
-            /// Indicates an error in UTF8 format (probably a filename).
-            Utf8(t: String) {
-                description("utf8 error")
-                    display("utf8 error: {}", t)
+            /// The given name can't be the name of a stored plan, e.g. since
+            /// it would lead outside the plans directory (includes the name).
+            InvalidPlanName(name: String) {
+                description("invalid plan name")
+                display("invalid plan name '{}'", name)
+            }
+            /// Some of several plans being changed at once couldn't be
+            /// changed (includes how many of them, and how many plans there
+            /// were); the error for the first of them is its cause.
+            PlansNotChanged(failed: usize, total: usize) {
+                description("some plans could not be changed")
+                display("could not change {} of {} plans", failed, total)
+            }
+            /// The specified plan is locked by someone else, so it can't be
+            /// changed right now (includes the name of the plan).
+            PlanLocked(name: String) {
+                description("plan is locked")
+                display("plan '{}' is being changed by another program", name)
+            }
+            /// The specified plan has already ended, so it can't be advanced
+            /// (includes the name of the plan).
+            PlanEnded(name: String) {
+                description("plan has ended")
+                display("plan '{}' has already ended (use `reading previous` to go back)", name)
+            }
+            /// There are no changes to the specified plan that can be undone
+            /// (includes the name of the plan).
+            NothingToUndo(name: String) {
+                description("nothing to undo")
+                display("there are no changes to plan '{}' to undo", name)
+            }
+            /// There is no plan with the specified name in the trash, so it
+            /// can't be restored (includes the name of the plan).
+            NotInTrash(name: String) {
+                description("plan is not in the trash")
+                display("there is no plan named '{}' in the trash", name)
+            }
+            /// There is no backup of the specified plan from the time it
+            /// was to be restored to (includes the name of the plan).
+            NoBackup(name: String) {
+                description("no backup of plan")
+                display("there is no backup of plan '{}' from that time", name)
+            }
+            /// The specified plan property does not exist (includes the name
+            /// of the property).
+            UnknownProperty(key: String) {
+                description("unknown plan property")
+                display("unknown plan property '{}'", key)
+            }
+            /// The value given for a plan property is not valid for that
+            /// property (includes the property name and value).
+            InvalidPropertyValue(key: String, value: String) {
+                description("invalid plan property value")
+                display("invalid value '{}' for plan property '{}'", value, key)
+            }
+            /// The specified setting does not exist (includes the name of the
+            /// setting).
+            UnknownSetting(key: String) {
+                description("unknown setting")
+                display("unknown setting '{}'", key)
+            }
+            /// The value given for a setting is not valid for that setting
+            /// (includes the setting name and value).
+            InvalidSettingValue(key: String, value: String) {
+                description("invalid setting value")
+                display("invalid value '{}' for setting '{}'", value, key)
+            }
+            /// A search query is not a valid regular expression (includes the
+            /// query).
+            InvalidQuery(query: String) {
+                description("invalid search query")
+                display("invalid search query '{}'", query)
+            }
+            /// The parameters given for generating a plan are invalid
+            /// (includes a description of the problem).
+            InvalidGenerator(t: String) {
+                description("invalid plan generator")
+                display("invalid plan generator: {}", t)
+            }
+            /// The specified entry number is outside of the plan (includes
+            /// the entry number and the number of entries in the plan).
+            EntryOutOfRange(number: usize, len: usize) {
+                description("entry number out of range")
+                display("entry {} is out of range (the plan has {} entries)", number, len)
+            }
+            /// A plan could not be locked for some reason other than it
+            /// being locked by someone else (includes the name of the plan).
+            LockFailed(name: String) {
+                description("could not lock plan")
+                display("could not lock plan '{}'", name)
+            }
+            /// Some stored data is in a newer format than this version of
+            /// the program understands (includes the version found and the
+            /// newest supported version).
+            UnsupportedVersion(version: u32, supported: u32) {
+                description("unsupported format version")
+                display("unsupported format version {} (the newest supported version is {})",
+                        version, supported)
+            }
+            /// A plan is not internally consistent, e.g. because its file was
+            /// edited by hand (includes the name of the plan and the problem).
+            InvalidPlan(name: String, problem: String) {
+                description("invalid plan")
+                display("plan '{}' is invalid: {}", name, problem)
+            }
+            /// Some entries of a plan would each have to come after the
+            /// other because of their prerequisites (includes the name of
+            /// the plan and the title of one of the entries).
+            PrerequisiteCycle(name: String, title: String) {
+                description("entries are prerequisites of each other")
+                display("'{}' in plan '{}' would have to come after itself because of its prerequisites", title, name)
+            }
+            /// A path (probably a filename) is not valid UTF-8, so it can't be
+            /// used as a plan name (includes the path).
+            NonUtf8Path(path: ::std::path::PathBuf) {
+                description("path is not valid utf-8")
+                display("path '{}' is not valid utf-8", path.display())
+            }
+            /// A plan was changed by someone else between being read and
+            /// written back, so writing it would lose their changes
+            /// (includes the name of the plan).
+            ConcurrentModification(name: String) {
+                description("plan was changed concurrently")
+                display("plan '{}' was changed by another program while it was being changed", name)
+            }
+            /// The specified bookmark does not exist in a plan (includes the
+            /// name of the plan and the name of the bookmark).
+            BookmarkDoesNotExist(plan: String, name: String) {
+                description("bookmark does not exist")
+                display("plan '{}' has no bookmark named '{}'", plan, name)
+            }
+            /// Plans are to be encrypted or decrypted, but no encryption key
+            /// has been set.
+            NoEncryptionKey {
+                description("no encryption key")
+                display("no encryption key has been set (use `reading encrypt on` to create one)")
+            }
+            /// The encryption key is not valid (it must be 64 hexadecimal
+            /// digits).
+            InvalidEncryptionKey {
+                description("invalid encryption key")
+                display("invalid encryption key (it must be 64 hexadecimal digits)")
+            }
+            /// Encrypted data could not be decrypted, because it was
+            /// encrypted with a different key or has been damaged.
+            DecryptionFailed {
+                description("could not decrypt data")
+                display("could not decrypt data (the encryption key may be wrong)")
+            }
+            /// A reminder rule is not valid (includes the rule and the
+            /// problem with it).
+            InvalidReminder(rule: String, problem: String) {
+                description("invalid reminder")
+                display("invalid reminder '{}': {}", rule, problem)
             }
             /// An error in plan text format.
             TextFormat(t: String) {
                 description("text format error")
                 display("text format error: {}", t)
             }
-            /// An IO error (usually caused by `std::io::Error`).
-            Io(t: String) {
-                description("io error")
-                display("{}", t)
-            }
-            /// A JSON error (usually caused by `serde_json::Error`).
-            Json(t: String) {
-                description("json error")
-                display("{}", t)
-            }
+        }
+
+        // The errors of the libraries we use are kept as they are, so that
+        // the underlying cause of a problem is never lost
+        foreign_links {
+            Io(::std::io::Error);
+            Json(::serde_json::Error);
+            Csv(::csv::Error);
         }
     }
 }
@@ -75,14 +264,57 @@ pub mod errors {
 pub use errors::*;
 
 pub mod plan;
+pub mod store;
 pub mod files;
+pub mod config;
+pub mod migrations;
+pub mod stats;
+pub mod search;
+pub mod remind;
+pub mod status;
+#[cfg(feature = "encryption")]
+pub mod crypto;
 
-pub use plan::{Plan, Entry};
+pub use plan::{Plan, Bookmark, Entry, EntryStatus, DueEntry, Estimate, HistoryEvent, Note, Schedule, Section};
+pub use store::PlanStore;
 
 #[cfg(test)]
 mod tests {
+    use chrono::{Duration, Months, NaiveDate, TimeZone, Utc, Weekday};
+    use serde_json;
+
+    use config::{Config, DEFAULT_CONFIG};
+    use errors::{Error, ErrorKind};
     use Plan;
+    use Bookmark;
     use Entry;
+    use EntryStatus;
+    use Estimate;
+    use Schedule;
+    use Section;
+    use std::cell::RefCell;
+    use std::env;
+    use std::fs;
+    use std::process;
+    use std::rc::Rc;
+    use std::slice;
+
+    use files::{self, Conflict, FileChange, FsStore, Profile};
+    use migrations;
+    use remind::{self, Condition, Reminder};
+    use plan::{self, Warning};
+    use plan::validate::{PlanWarning, WarningKind};
+    use plan::builder::PlanBuilder;
+    use plan::dialect::{BlankLines, TextFormatOptions};
+    use plan::summary::{PlanSummary, SortOrder};
+    use plan::track::Track;
+    use plan::generator::{Generator, Split};
+    use plan::pace::Pace;
+    use plan::weekdays::ActiveDays;
+    use search::{self, Field, Query};
+    use store::{MemoryStore, ObservedStore, PlanStore, Selection, StoreEvent};
+    use stats::{self, Calendar, PlanStats, Report, Summary};
+    use status;
 
     #[test]
     fn plan_from_text() {
@@ -126,6 +358,75 @@ Entry 3
                    buf_string);
     }
 
+    #[test]
+    fn format_round_trips() {
+        let text = "tags: bible\npace: 1 per day\n---\n# Part I\n[2017-05-01] One (25m)\n    The first\n    \
+                    https://example.com/1\n[2017-05-02 to 2017-05-03] Two\nThree\n# Part II\n[every Sunday] Four\n";
+        let mut plan = Plan::from_text("test", text.as_bytes()).expect("could not parse plan");
+        let time = |h| Utc.with_ymd_and_hms(2017, 5, 1, h, 0, 0).unwrap();
+        plan.mark_modified(time(9));
+        plan.set_pace(plan.pace(), NaiveDate::from_ymd_opt(2017, 5, 1).unwrap());
+        plan.next_at(1, time(10));
+        assert!(plan.skip_current());
+        plan.add_note_at(0, "two lines\nand a \\ backslash", time(11)).unwrap();
+        plan.set_bookmark("Part II", 3).unwrap();
+        plan.set_archived(true);
+        plan.set_pinned(true);
+
+        // The plain text format only keeps the progress through the plan if
+        // asked to
+        let round_trip = |plan: &Plan| {
+            let mut buffer = Vec::new();
+            plan.to_text_with_progress(&mut buffer).expect("could not write plan");
+            Plan::from_text("test", &buffer[..]).expect("could not reparse plan")
+        };
+        assert_eq!(round_trip(&plan), plan);
+        let mut buffer = Vec::new();
+        plan.to_text(&mut buffer).expect("could not write plan");
+        let reparsed = Plan::from_text("test", &buffer[..]).expect("could not reparse plan");
+        assert_eq!(reparsed.current_entry_number(), 1);
+        assert!(reparsed.history().is_empty(), "progress written without asking");
+
+        let mut ended = plan.clone();
+        ended.next_at(2, time(12));
+        assert!(ended.completed_at().is_some());
+        assert_eq!(round_trip(&ended), ended);
+        let mut cyclic = plan.clone();
+        cyclic.set_cyclic(true);
+        cyclic.next_at(3, time(12));
+        assert_eq!(cyclic.cycles_completed(), 1);
+        assert_eq!(round_trip(&cyclic), cyclic);
+
+        for field in &["current: 5", "current: end\ncyclic: true", "read: 0 2017-05-01T12:00:00Z", "note: 1 May 1"] {
+            let text = format!("{}\n---\nOne\nTwo\n", field);
+            assert!(Plan::from_text("test", text.as_bytes()).is_err(), "invalid field accepted: {:?}", field);
+        }
+
+        // CSV only keeps the entries' titles, descriptions and schedules
+        let mut buffer = Vec::new();
+        plan.to_csv(&mut buffer).expect("could not write plan");
+        let reparsed = Plan::from_csv("test", &buffer[..]).expect("could not reparse plan");
+        let summary = |p: &Plan| {
+            p.entries().map(|e| (e.title().to_owned(), e.description().to_owned(), e.schedule())).collect::<Vec<_>>()
+        };
+        assert_eq!(summary(&reparsed), summary(&plan));
+
+        let json = serde_json::to_string(&plan).expect("could not serialize plan");
+        assert_eq!(serde_json::from_str::<Plan>(&json).expect("could not parse plan"), plan);
+        #[cfg(feature = "toml-format")]
+        {
+            let mut buffer = Vec::new();
+            plan.to_toml(&mut buffer).expect("could not write plan");
+            assert_eq!(Plan::from_toml(&buffer[..]).expect("could not reparse plan"), plan);
+        }
+        #[cfg(feature = "yaml-format")]
+        {
+            let mut buffer = Vec::new();
+            plan.to_yaml(&mut buffer).expect("could not write plan");
+            assert_eq!(Plan::from_yaml(&buffer[..]).expect("could not reparse plan"), plan);
+        }
+    }
+
     #[test]
     fn cyclic() {
         let mut plan = Plan::from_entries("test", vec![Entry::new("entry"); 3]);
@@ -133,8 +434,31 @@ Entry 3
         assert!(plan.is_cyclic(), "plan is not cyclic");
         plan.next(3);
         assert_eq!(plan.current_entry_number(), 1);
+        assert_eq!(plan.cycles_completed(), 1);
         plan.previous(2);
         assert_eq!(plan.current_entry_number(), 2);
+        assert_eq!(plan.cycles_completed(), 0);
+        plan.previous(4);
+        assert_eq!(plan.current_entry_number(), 1);
+        assert_eq!(plan.cycles_completed(), 0);
+        plan.next(7);
+        assert_eq!(plan.cycles_completed(), 2);
+        plan.reset();
+        assert_eq!(plan.cycles_completed(), 0);
+
+        // Only the last time round is recorded, however far the plan goes
+        let recorded = plan.history().len();
+        plan.next(1);
+        plan.next(3001);
+        assert_eq!(plan.current_entry_number(), 3);
+        assert_eq!(plan.cycles_completed(), 1000);
+        let history: Vec<_> = plan.history()[recorded..].iter().map(|e| e.entry_index()).collect();
+        assert_eq!(history, [0, 2, 0, 1]);
+
+        let mut tracks = Plan::from_text("test", "@ A\na\nb\n@ B\nc\n".as_bytes()).unwrap();
+        tracks.set_cyclic(true);
+        tracks.next(1000);
+        assert_eq!(tracks.history().len(), 3);
     }
 
     #[test]
@@ -146,4 +470,2267 @@ Entry 3
         plan.previous(100);
         assert_eq!(plan.current_entry_number(), 1);
     }
+
+    #[test]
+    fn set_current_entry() {
+        let mut plan = Plan::from_entries("test", vec![Entry::new("entry"); 3]);
+        plan.set_current_entry(2).expect("could not set entry");
+        assert_eq!(plan.current_entry_number(), 2);
+        plan.set_current_entry(4).expect("could not move to end");
+        assert!(plan.is_ended(), "plan did not end");
+        assert!(plan.set_current_entry(0).is_err(), "entry 0 accepted");
+        assert!(plan.set_current_entry(5).is_err(), "entry past end accepted");
+        assert!(plan.history().is_empty(), "history recorded");
+
+        plan.set_cyclic(true);
+        plan.set_current_entry(5).expect("could not wrap around");
+        assert_eq!(plan.current_entry_number(), 2);
+    }
+
+    #[test]
+    fn search() {
+        let text = "Genesis 1\n    The generations of the heavens\nExodus 1\nGenesis 2";
+        let plan = Plan::from_text("test", text.as_bytes()).expect("could not parse plan");
+
+        let hits = search::search_plan(&plan, &Query::substring("gen").expect("invalid query"));
+        let found: Vec<_> = hits.iter().map(|h| (h.entry_number(), h.field())).collect();
+        assert_eq!(found, [(1, Field::Title), (1, Field::Description), (3, Field::Title)]);
+        assert_eq!(hits[0].parts(), ("", "Gen", "esis 1"));
+
+        let hits = search::search_plan(&plan, &Query::regex(r"s \d$").expect("invalid regex"));
+        assert_eq!(hits.len(), 3);
+        assert!(Query::regex("(").is_err(), "invalid regex accepted");
+    }
+
+    #[test]
+    fn fs_store() {
+        let dir = env::temp_dir().join(format!("reading-test-{}", process::id()));
+        let mut store = FsStore::new(&dir);
+        let plan = Plan::from_entries("test", vec![Entry::new("entry"); 3]);
+        store.add(&plan).expect("could not add plan");
+        assert!(store.add(&plan).is_err(), "plan added twice");
+        assert_eq!(store.list().expect("could not list plans"), ["test"]);
+
+        store.update("test", |p| {
+                p.next(2);
+                Ok(())
+            })
+            .expect("could not update plan");
+        store.remove("test").expect("could not remove plan");
+        assert!(!store.exists("test").expect("could not check plan"), "plan not removed");
+        let journal = fs::read_to_string(store.journal_path("test").unwrap()).expect("could not read journal");
+        assert_eq!(journal.lines().count(), 2);
+        assert!(!dir.join(".test.plan.journal.tmp").exists(), "temporary journal left behind");
+        let restored = store.undo("test").expect("could not undo removal");
+        assert_eq!(restored.current_entry_number(), 3);
+        let restored = store.undo("test").expect("could not undo change");
+        assert_eq!(restored.current_entry_number(), 1);
+        assert!(store.undo("test").is_err(), "undid the addition of the plan");
+
+        let read = files::with_plans_dir(&dir, || files::read_plan("test"));
+        assert_eq!(read.expect("could not read plan").current_entry_number(), 1);
+        assert!(files::is_plan_file(&dir.join("test.plan.json")));
+        let path = files::with_plans_dir(&dir, || files::plan_path("test"));
+        assert_eq!(path.expect("could not get plan path"), dir.join("test.plan.json"));
+        assert!(!files::is_plan_file(&store.journal_path("test").unwrap()), "journal taken for a plan");
+
+        fs::remove_dir_all(&dir).expect("could not remove test directory");
+    }
+
+    #[test]
+    fn plan_locks() {
+        let dir = env::temp_dir().join(format!("reading-test-locks-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let store = FsStore::new(&dir);
+
+        let lock = store.lock("x").expect("could not lock plan");
+        // Another plan can still be locked
+        store.lock("y").expect("could not lock another plan");
+        match store.lock("x") {
+            Err(Error(ErrorKind::PlanLocked(ref name), _)) if name == "x" => {}
+            other => panic!("locked plan locked again: {:?}", other.map(|_| ())),
+        }
+        drop(lock);
+        store.lock("x").expect("lock not released when dropped");
+
+        fs::remove_dir_all(&dir).expect("could not remove test directory");
+    }
+
+    #[test]
+    fn invalid_plan_names() {
+        let dir = env::temp_dir().join(format!("reading-test-names-{}", process::id()));
+        let mut store = FsStore::new(dir.join("plans"));
+        store.add(&Plan::from_entries("inside", vec![Entry::new("entry")])).expect("could not add plan");
+        fs::write(dir.join("outside.plan.json"), fs::read(dir.join("plans/inside.plan.json")).unwrap()).unwrap();
+
+        for name in &["../outside", "..", "a/b", "a\\b", ""] {
+            assert!(!files::is_valid_plan_name(name), "'{}' accepted", name);
+            match store.read(name) {
+                Err(Error(ErrorKind::InvalidPlanName(_), _)) => {}
+                other => panic!("read '{}' gave {:?}", name, other.map(|p| p.name().to_owned())),
+            }
+            assert!(store.exists(name).is_err(), "'{}' checked", name);
+            assert!(store.lock(name).is_err(), "'{}' locked", name);
+        }
+        let plan = Plan::from_entries("../escaped", vec![Entry::new("entry")]);
+        assert!(store.add(&plan).is_err(), "plan written outside the plans directory");
+        assert!(!dir.join("escaped.plan.json").exists());
+        let updated = store.update("../outside", |p| {
+            p.next(1);
+            Ok(())
+        });
+        assert!(updated.is_err(), "plan outside updated");
+        assert!(files::is_valid_plan_name("my plan.2"));
+
+        fs::remove_dir_all(&dir).expect("could not remove test directory");
+    }
+
+    #[test]
+    fn generator() {
+        let plan = Generator::new("page", 1, 612, Split::Entries(30))
+            .generate("book")
+            .expect("could not generate plan");
+        let titles: Vec<_> = plan.entries().map(|e| e.title()).collect();
+        assert_eq!(titles.len(), 30);
+        assert_eq!(titles[0], "Pages 1–21");
+        assert_eq!(titles[12], "Pages 253–272");
+        assert_eq!(titles[29], "Pages 593–612");
+
+        let entries = Generator::new("chapter", 1, 5, Split::PerEntry(2)).entries().expect("could not generate entries");
+        let titles: Vec<_> = entries.iter().map(|e| e.title()).collect();
+        assert_eq!(titles, ["Chapters 1–2", "Chapters 3–4", "Chapter 5"]);
+
+        assert!(Generator::new("page", 10, 1, Split::Entries(1)).entries().is_err(), "backwards range accepted");
+        assert!(Generator::new("page", 1, 3, Split::Entries(4)).entries().is_err(), "too many entries accepted");
+        assert!(Generator::new("page", 1, 3, Split::PerEntry(0)).entries().is_err(), "empty entries accepted");
+    }
+
+    #[test]
+    fn notes() {
+        let mut plan = Plan::from_entries("test", vec![Entry::new("a"), Entry::new("b")]);
+        let time = Utc.with_ymd_and_hms(2017, 5, 1, 12, 0, 0).unwrap();
+        plan.add_note_at(1, "a note", time).expect("could not add note");
+        assert!(plan.add_note(2, "out of range").is_err(), "note added past the end");
+        let notes = plan.entries().nth(1).unwrap().notes();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].text(), "a note");
+        assert_eq!(notes[0].created_at(), time);
+
+        // Notes should survive a round trip through JSON and editing the
+        // entries (which loses notes)
+        let json = serde_json::to_string(&plan).expect("could not serialize plan");
+        let mut plan: Plan = serde_json::from_str(&json).expect("could not parse plan");
+        plan.replace_entries(vec![Entry::new("b"), Entry::new("c")]);
+        assert_eq!(plan.entries().next().unwrap().notes().len(), 1);
+    }
+
+    #[test]
+    fn collection() {
+        let mut store = MemoryStore::new();
+        let mut plan = Plan::from_entries("test", vec![Entry::new("entry"); 3]);
+        plan.next(1);
+        store.add(&plan).expect("could not add plan");
+        let mut bundle = Vec::new();
+        assert_eq!(files::write_collection(&store, &mut bundle).expect("could not export"), 1);
+
+        let mut other = MemoryStore::new();
+        let report = files::read_collection(&mut other, &bundle[..], Conflict::Fail).expect("could not import");
+        assert_eq!(report.imported, ["test"]);
+        assert_eq!(other.read("test").expect("could not read plan"), plan);
+        assert!(files::read_collection(&mut other, &bundle[..], Conflict::Fail).is_err(),
+                "conflicting plan imported");
+        let report = files::read_collection(&mut other, &bundle[..], Conflict::Skip).expect("could not import");
+        assert_eq!(report.skipped, ["test"]);
+    }
+
+    #[test]
+    fn plan_names() {
+        let dir = env::temp_dir().join(format!("reading-names-test-{}", process::id()));
+        let mut store = FsStore::new(&dir);
+        store.add(&Plan::from_entries("b", vec![Entry::new("entry")])).expect("could not add plan");
+        // Plan files aren't opened, so even an invalid plan is listed
+        fs::write(dir.join("a.plan.json"), "not a plan").unwrap();
+        fs::write(dir.join(".plan.json"), "{}").unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        fs::create_dir(dir.join("c.plan.json")).unwrap();
+        #[cfg(unix)]
+        {
+            use std::ffi::OsStr;
+            use std::os::unix::ffi::OsStrExt;
+            fs::write(dir.join(OsStr::from_bytes(b"bad\xff.plan.json")), "{}").unwrap();
+        }
+        assert_eq!(store.plan_names().expect("could not list plans"), ["a", "b"]);
+
+        let plans: Vec<_> = store.iter().expect("could not read plans").collect();
+        assert_eq!(plans.len(), 3);
+        assert_eq!(plans.iter().filter(|p| p.is_err()).count(), 2);
+
+        fs::remove_dir_all(&dir).expect("could not remove test directory");
+    }
+
+    #[test]
+    fn pinned() {
+        let mut store = MemoryStore::new();
+        for name in &["a", "b", "c"] {
+            let mut plan = Plan::from_entries(name, vec![Entry::new("entry")]);
+            plan.set_pinned(*name != "b");
+            plan.set_archived(*name == "c");
+            store.add(&plan).unwrap();
+        }
+        // Archived plans aren't used by default, even if they are pinned
+        assert_eq!(store.pinned().unwrap(), ["a"]);
+        assert!(!store.read("a").unwrap().fresh_copy("d").is_pinned(), "copy of plan pinned");
+        store.update("a", |p| p.set_property("pinned", "false")).unwrap();
+        assert!(store.pinned().unwrap().is_empty());
+    }
+
+    #[test]
+    fn memory_store() {
+        let mut store = MemoryStore::new();
+        let mut plan = Plan::from_entries("b", vec![Entry::new("entry")]);
+        plan.add_tag("tag").expect("could not add tag");
+        store.add(&plan).expect("could not add plan");
+        store.add(&Plan::from_entries("a", vec![Entry::new("entry")])).expect("could not add plan");
+        assert!(store.add(&plan).is_err(), "plan added twice");
+        assert_eq!(store.list().expect("could not list plans"), ["a", "b"]);
+
+        let tagged = store.select(&Selection::Tag("tag".into())).expect("could not select plans");
+        assert_eq!(tagged, [plan]);
+        store.update("b", |p| {
+                p.next(1);
+                Ok(())
+            })
+            .expect("could not update plan");
+        assert!(store.read("b").expect("could not read plan").is_ended());
+
+        // A failure doesn't stop the other plans from being changed, and
+        // archived plans are left out
+        let mut archived = Plan::from_entries("c", vec![Entry::new("entry")]);
+        archived.set_archived(true);
+        store.add(&archived).expect("could not add plan");
+        let results = store.update_each(&Selection::All, |p| {
+                if p.is_ended() {
+                    bail!("plan has ended");
+                }
+                p.next(1);
+                Ok(p.current_entry_number())
+            })
+            .expect("could not update plans");
+        let results: Vec<_> = results.into_iter().map(|(name, r)| (name, r.ok())).collect();
+        assert_eq!(results, [("a".to_owned(), Some(2)), ("b".to_owned(), None)]);
+        assert!(store.read("a").expect("could not read plan").is_ended());
+
+        store.remove("a").expect("could not remove plan");
+        assert!(store.remove("a").is_err(), "plan removed twice");
+        assert!(store.read("a").is_err(), "removed plan read");
+    }
+
+    #[test]
+    fn config() {
+        let mut config = Config::read("no_ansi = true\ncount = 3\n".as_bytes()).expect("could not read config");
+        assert!(config.no_ansi());
+        assert_eq!(config.count(), Some(3));
+        assert_eq!(config.theme(), None);
+
+        config.set("export_format", "csv").expect("could not change setting");
+        config.set("count", "none").expect("could not unset setting");
+        assert_eq!(config.get("export_format").unwrap(), Some("csv".into()));
+        assert_eq!(config.get("count").unwrap(), None);
+        config.set("sync_url", "https://example.com/dav/").expect("could not change setting");
+        assert_eq!(config.sync_url(), Some("https://example.com/dav/"));
+        config.set("auto_archive", "yes").expect("could not change setting");
+        assert!(config.auto_archive());
+        config.set("reader", "alice").expect("could not change setting");
+        assert_eq!(config.reader(), Some("alice"));
+        assert!(config.set("auto_archive", "sometimes").is_err(), "invalid flag accepted");
+        assert!(config.set("export_format", "pdf").is_err(), "invalid format accepted");
+        assert!(config.set("count", "many").is_err(), "invalid count accepted");
+        assert!(config.get("colour").is_err(), "unknown setting accepted");
+
+        let mut buffer = Vec::new();
+        config.write(&mut buffer).expect("could not write to buffer");
+        assert_eq!(Config::read(&buffer[..]).expect("could not reread config"), config);
+        assert!(Config::read("export_format = \"pdf\"".as_bytes()).is_err(),
+                "invalid config accepted");
+        assert_eq!(Config::read("".as_bytes()).expect("could not read empty config"),
+                   Config::default());
+
+        let text = "theme = \"dark\"\n[themes.dark]\ntitle = \"bold cyan\"\nprogress = \"blue\"\n";
+        let config = Config::read(text.as_bytes()).expect("could not read config with theme");
+        let theme = &config.themes()["dark"];
+        assert_eq!(theme.style("title"), Some("bold cyan"));
+        assert_eq!(theme.style("muted"), None);
+        assert_eq!(theme.style("progress"), Some("blue"));
+        let mut buffer = Vec::new();
+        config.write(&mut buffer).expect("could not write to buffer");
+        assert_eq!(Config::read(&buffer[..]).expect("could not reread config"), config);
+    }
+
+    #[test]
+    fn default_config() {
+        assert_eq!(Config::read(DEFAULT_CONFIG.as_bytes()).expect("could not read default config"),
+                   Config::default());
+        // Every example setting should be valid once uncommented
+        let uncommented: String = DEFAULT_CONFIG.lines()
+            .filter(|l| l.contains(" = "))
+            .map(|l| format!("{}\n", l.trim_start_matches("# ")))
+            .collect();
+        let config = Config::read(uncommented.as_bytes()).expect("could not read uncommented config");
+        assert_eq!(config.count(), Some(5));
+        assert_eq!(config.reminders().expect("invalid reminders").len(), 1);
+    }
+
+    #[test]
+    fn exit_statuses() {
+        let error = |kind: ErrorKind| -> Error { kind.into() };
+        assert_eq!(status::exit_status(&error(ErrorKind::PlanDoesNotExist("a".into()))), status::NOT_FOUND);
+        assert_eq!(status::exit_status(&error(ErrorKind::EntryOutOfRange(5, 3))), status::INVALID_INPUT);
+        assert_eq!(status::exit_status(&error(ErrorKind::InvalidPlan("a".into(), "bad".into()))),
+                   status::INVALID_INPUT);
+        assert_eq!(status::exit_status(&error(ErrorKind::UnknownProperty("colour".into()))),
+                   status::INVALID_INPUT);
+        assert_eq!(status::exit_status(&"something else".into()), status::ERROR);
+
+        // The status comes from the first cause which has one
+        let locked = Error::with_chain(error(ErrorKind::PlanLocked("a".into())), "could not change plan");
+        assert_eq!(status::exit_status(&locked), status::BUSY);
+        let failed = Error::with_chain(error(ErrorKind::PlanEnded("a".into())), ErrorKind::PlansNotChanged(1, 2));
+        assert_eq!(status::exit_status(&failed), status::PLAN_ENDED);
+
+        let json: Error = serde_json::from_str::<serde_json::Value>("{").expect_err("invalid json accepted").into();
+        assert_eq!(status::exit_status(&json), status::INVALID_INPUT);
+        let toml = Config::read("count = [".as_bytes()).expect_err("invalid toml accepted");
+        assert_eq!(status::exit_status(&toml), status::INVALID_INPUT);
+    }
+
+    #[test]
+    fn config_file() {
+        let dir = env::temp_dir().join(format!("reading-test-config-{}", process::id()));
+        let path = dir.join("config.toml");
+        assert!(Config::init_at(&path).expect("could not write default config"), "default config not written");
+        assert!(!Config::init_at(&path).expect("could not check config"), "config written twice");
+        assert_eq!(Config::load_from(&path).expect("could not load config"), Config::default());
+
+        let mut config = Config::default();
+        config.set("count", "3").unwrap();
+        config.save_to(&path).expect("could not save config");
+        assert_eq!(Config::load_from(&path).expect("could not reload config"), config);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1, "temporary file left behind");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600, "config file readable by others");
+        }
+        fs::remove_dir_all(&dir).expect("could not remove test directory");
+    }
+
+    #[test]
+    fn profiles() {
+        let dir = env::temp_dir().join(format!("reading-test-profiles-{}", process::id()));
+        let default = Profile::new(dir.clone(), "default").expect("could not get default profile");
+        let work = Profile::new(dir.clone(), "work").expect("could not get profile");
+        assert_eq!(default.dir(), dir);
+        assert!(default.exists() && !work.exists());
+        assert!(Profile::new(dir.clone(), "../work").is_err(), "invalid name accepted");
+
+        work.create().expect("could not create profile");
+        assert!(work.create().is_err(), "profile created twice");
+        work.store().add(&Plan::from_entries("plan", vec![Entry::new("entry")])).expect("could not add plan");
+        assert_eq!(Profile::list(&dir).expect("could not list profiles"), ["default", "work"]);
+        // The plans of other profiles aren't part of the default profile
+        assert_eq!(default.store().list().expect("could not list plans"), Vec::<String>::new());
+
+        work.delete().expect("could not delete profile");
+        assert!(!work.exists());
+        assert!(work.delete().is_err(), "profile deleted twice");
+        assert!(default.delete().is_err(), "default profile deleted");
+        fs::remove_dir_all(&dir).expect("could not remove test directory");
+    }
+
+    #[test]
+    fn closest_plan() {
+        let mut store = MemoryStore::new();
+        for name in &["mybook", "Gospels", "ab", "ac"] {
+            store.add(&Plan::from_entries(name, vec![Entry::new("entry")])).expect("could not add plan");
+        }
+        let closest = |name| store.closest_plan(name).expect("could not list plans");
+
+        assert_eq!(closest("mybok"), Some("mybook".into()));
+        assert_eq!(closest("gospel"), Some("Gospels".into()));
+        assert_eq!(closest("a"), None, "ambiguous name matched");
+        assert_eq!(closest("novel"), None, "unrelated name matched");
+    }
+
+    #[test]
+    fn streaks() {
+        let mut plans = [Plan::from_entries("a", vec![Entry::new("entry"); 10]),
+                         Plan::from_entries("b", vec![Entry::new("entry"); 10])];
+        for &(day, i) in &[(1, 0), (2, 1), (3, 0), (5, 1), (6, 0)] {
+            plans[i].next_at(1, Utc.with_ymd_and_hms(2017, 5, day, 12, 0, 0).unwrap());
+        }
+
+        let now = Utc.with_ymd_and_hms(2017, 5, 7, 12, 0, 0).unwrap();
+        assert_eq!(stats::current_streak(&plans, &now), 2);
+        assert_eq!(stats::longest_streak(&plans, &now), 3);
+        let later = Utc.with_ymd_and_hms(2017, 5, 8, 12, 0, 0).unwrap();
+        assert_eq!(stats::current_streak(&plans, &later), 0);
+    }
+
+    #[test]
+    fn reminders() {
+        let reminder: Reminder = "Daily at 21:00 if no entry completed today".parse().expect("invalid reminder");
+        assert_eq!(reminder.condition(), Some(Condition::NothingCompletedToday));
+        assert_eq!(reminder.to_string(), "daily at 21:00 if no entry completed today");
+
+        // 2017-05-01 was a Monday
+        let mut plans = vec![Plan::from_entries("a", vec![Entry::new("entry"); 3])];
+        let morning = Utc.with_ymd_and_hms(2017, 5, 1, 8, 0, 0).unwrap();
+        let evening = Utc.with_ymd_and_hms(2017, 5, 1, 21, 30, 0).unwrap();
+        assert!(!reminder.is_due(&plans, &morning), "reminder due before its time");
+        assert!(reminder.is_due(&plans, &evening));
+        plans[0].next_at(1, morning);
+        assert!(!reminder.is_due(&plans, &evening), "reminder due after reading");
+        let next_day = Utc.with_ymd_and_hms(2017, 5, 2, 21, 30, 0).unwrap();
+        assert!(reminder.is_due(&plans, &next_day));
+
+        let weekends: Reminder = "weekends at 09:00".parse().expect("invalid reminder");
+        let sunday: Reminder = "Sunday at 09:00 if entries due".parse().expect("invalid reminder");
+        let saturday = Utc.with_ymd_and_hms(2017, 5, 6, 10, 0, 0).unwrap();
+        assert_eq!(remind::due_reminders(&[weekends.clone(), sunday.clone()], &plans, &saturday), [&weekends]);
+        plans.push(Plan::from_text("b", "[2017-05-07] Genesis 1\n".as_bytes()).unwrap());
+        let sunday_morning = Utc.with_ymd_and_hms(2017, 5, 7, 10, 0, 0).unwrap();
+        assert!(sunday.is_due(&plans, &sunday_morning));
+        plans[1].set_archived(true);
+        assert!(!sunday.is_due(&plans, &sunday_morning), "reminder due for archived plan");
+
+        for rule in &["daily", "daily at 25:00", "someday at 09:00", "daily at 09:00 if raining"] {
+            assert!(rule.parse::<Reminder>().is_err(), "invalid reminder '{}' accepted", rule);
+        }
+        assert!(Config::read("reminders = ['daily at noon']".as_bytes()).is_err(), "invalid reminder in config");
+        let config = Config::read("reminders = ['daily at 12:00']".as_bytes()).expect("could not read config");
+        assert_eq!(config.reminders().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn report() {
+        let mut plans = [Plan::from_entries("a", vec![Entry::new("entry"); 10]),
+                         Plan::from_entries("b", vec![Entry::new("entry"); 10]),
+                         Plan::from_entries("c", vec![Entry::new("entry"); 10])];
+        for &(day, i) in &[(1, 0), (3, 0), (3, 1), (5, 1), (6, 0), (6, 0), (2, 2)] {
+            plans[i].next_at(1, Utc.with_ymd_and_hms(2017, 5, day, 12, 0, 0).unwrap());
+        }
+
+        let now = Utc.with_ymd_and_hms(2017, 5, 7, 12, 0, 0).unwrap();
+        let start = stats::period_start("5d", now.date_naive()).expect("could not parse period");
+        assert_eq!(start, NaiveDate::from_ymd_opt(2017, 5, 3).unwrap());
+        assert_eq!(stats::period_start("1w", now.date_naive()).unwrap(),
+                   NaiveDate::from_ymd_opt(2017, 5, 1).unwrap());
+        assert_eq!(stats::period_start("1m", now.date_naive()).unwrap(),
+                   NaiveDate::from_ymd_opt(2017, 4, 8).unwrap());
+        assert_eq!(stats::period_start("2017-04-30", now.date_naive()).unwrap(),
+                   NaiveDate::from_ymd_opt(2017, 4, 30).unwrap());
+        assert!(stats::period_start("0d", now.date_naive()).is_err(), "empty period accepted");
+        assert!(stats::period_start("2 fortnights", now.date_naive()).is_err(), "invalid period accepted");
+
+        let report = Report::for_plans(&plans, start, &now);
+        assert_eq!(report.end(), now.date_naive());
+        assert_eq!(report.daily(), &[2, 0, 1, 2, 0]);
+        assert_eq!(report.completed(), 5);
+        assert_eq!(report.days_read(), 3);
+        let names: Vec<_> = report.plans().iter().map(|p| p.name()).collect();
+        assert_eq!(names, ["a", "b"], "plan without entries in the period included");
+        assert_eq!(report.plans()[0].daily(), &[1, 0, 0, 2, 0]);
+        assert_eq!(report.plans()[1].completed(), 2);
+
+        assert_eq!(stats::sparkline(report.daily(), 10), "█▁▄█▁");
+        assert_eq!(stats::sparkline(report.daily(), 3), "▅█▁");
+        assert_eq!(stats::sparkline(&[0, 0], 10), "▁▁");
+    }
+
+    #[test]
+    fn calendar() {
+        let date = |month, day| NaiveDate::from_ymd_opt(2017, month, day).unwrap();
+        let mut entries = vec![Entry::new("entry"); 4];
+        entries[0].set_schedule(Some(Schedule::Date(date(5, 2))));
+        entries[1].set_schedule(Some(Schedule::Range(date(4, 30), date(5, 2))));
+        entries[2].set_schedule(Some(Schedule::Weekly(Weekday::Mon)));
+        let mut plan = Plan::from_entries("plan", entries);
+        plan.next_at(2, Utc.with_ymd_and_hms(2017, 5, 3, 12, 0, 0).unwrap());
+        let mut archived = plan.clone();
+        archived.set_archived(true);
+
+        let now = Utc.with_ymd_and_hms(2017, 5, 7, 12, 0, 0).unwrap();
+        let start = stats::month_start("+0", now.date_naive()).expect("could not parse month");
+        assert_eq!(start, date(5, 1));
+        assert_eq!(stats::month_start("-5", now.date_naive()).unwrap(), date(5, 1) - Months::new(5));
+        assert_eq!(stats::month_start("2017-02", now.date_naive()).unwrap(), date(2, 1));
+        assert!(stats::month_start("2017-13", now.date_naive()).is_err(), "invalid month accepted");
+
+        let calendar = Calendar::for_plans(&[plan, archived], start, &now);
+        assert_eq!(calendar.start(), start);
+        assert_eq!(calendar.scheduled().len(), 31);
+        assert_eq!(&calendar.scheduled()[..9], &[2, 2, 0, 0, 0, 0, 0, 1, 0]);
+        assert_eq!(calendar.completed().iter().sum::<usize>(), 4);
+        assert_eq!(calendar.completed()[2], 4);
+    }
+
+    #[test]
+    fn plan_without_tags() {
+        // Plans saved before tags were introduced should still be readable
+        let json = r#"{"name":"test","cyclic":false,"current_entry":0,
+                       "entries":[{"title":"entry","description":""}]}"#;
+        let plan: Plan = serde_json::from_str(json).expect("could not parse plan");
+        assert!(plan.tags().is_empty(), "plan has tags");
+        assert!(!plan.has_tag("tag"), "plan has tag 'tag'");
+        assert!(!plan.is_archived(), "plan is archived");
+        plan.check().expect("plan is invalid");
+        assert_eq!(plan.cycles_completed(), 0);
+    }
+
+    #[test]
+    fn migrations() {
+        let json = r#"{"name":"test","cyclic":false,"current_entry":0,
+                       "entries":[{"title":"entry","description":""}]}"#;
+        let mut value: serde_json::Value = serde_json::from_str(json).expect("could not parse json");
+        assert_eq!(migrations::version(&value), 0);
+        let plan = migrations::plan_from_value(value.clone()).expect("could not migrate plan");
+        assert_eq!(plan, Plan::from_entries("test", vec![Entry::new("entry")]));
+
+        assert!(migrations::migrate(&mut value).expect("could not migrate plan"), "nothing migrated");
+        assert_eq!(migrations::version(&value), migrations::CURRENT_VERSION);
+        assert_eq!(value["tags"], serde_json::Value::Array(Vec::new()));
+        assert!(!migrations::migrate(&mut value).expect("could not migrate plan"), "migrated twice");
+
+        // Plans from the future can't be read
+        value["version"] = serde_json::Value::from(migrations::CURRENT_VERSION + 1);
+        match migrations::plan_from_value(value) {
+            Err(Error(ErrorKind::UnsupportedVersion(..), _)) => (),
+            r => panic!("plan from a newer version accepted: {:?}", r),
+        }
+
+        // Plans are always written in the current version
+        let written = serde_json::to_value(&plan).expect("could not serialize plan");
+        assert_eq!(migrations::version(&written), migrations::CURRENT_VERSION);
+    }
+
+    #[test]
+    fn invalid_plan() {
+        // A cyclic plan can never be at its end
+        let json = r#"{"name":"test","cyclic":true,"current_entry":1,
+                       "entries":[{"title":"entry","description":""}]}"#;
+        let plan: Plan = serde_json::from_str(json).expect("could not parse plan");
+        match plan.check() {
+            Err(Error(ErrorKind::InvalidPlan(..), _)) => (),
+            r => panic!("cyclic plan at its end accepted: {:?}", r),
+        }
+
+        let mut empty = Plan::from_entries("empty", Vec::new());
+        empty.set_cyclic(true);
+        assert!(empty.check().is_err(), "empty plan accepted");
+        // Moving through an empty plan should do nothing (not panic)
+        empty.next(3);
+        assert_eq!(empty.current_entry_number(), 1);
+    }
+
+    #[test]
+    fn set_property() {
+        let mut plan = Plan::from_entries("test", vec![Entry::new("entry"); 3]);
+        plan.set_property("cyclic", "yes").expect("could not set cyclic");
+        assert!(plan.is_cyclic(), "plan is not cyclic");
+        plan.set_property("tags", "a, b,,c").expect("could not set tags");
+        assert_eq!(plan.tags(), ["a", "b", "c"]);
+        plan.set_property("priority", "-2").expect("could not set priority");
+        assert_eq!(plan.priority(), -2);
+        plan.set_property("archived", "true").expect("could not set archived");
+        assert!(plan.is_archived(), "plan is not archived");
+
+        assert!(plan.set_property("cyclic", "maybe").is_err(),
+                "invalid value accepted");
+        assert!(plan.set_property("name", "a/b").is_err(),
+                "name with path separator accepted");
+        assert!(plan.set_property("colour", "blue").is_err(),
+                "unknown property accepted");
+        assert_eq!(plan.name(), "test");
+    }
+
+    #[test]
+    fn scheduled_entries() {
+        let plan_text = "[2017-05-01] Entry 1
+[every Sunday] Entry 2
+    Description
+[not a schedule] Entry 3
+[2017-05-07] Entry 4";
+        let plan = Plan::from_text("test", plan_text.as_bytes()).expect("could not parse plan");
+        let entries: Vec<_> = plan.entries().collect();
+        assert_eq!(entries[0].title(), "Entry 1");
+        assert_eq!(entries[0].schedule(),
+                   Some(Schedule::Date(NaiveDate::from_ymd_opt(2017, 5, 1).unwrap())));
+        assert_eq!(entries[1].schedule(), Some(Schedule::Weekly(Weekday::Sun)));
+        assert_eq!(entries[2].title(), "[not a schedule] Entry 3");
+        assert_eq!(entries[2].schedule(), None);
+
+        // 2017-05-07 was a Sunday, but the specific date takes precedence
+        let date = |d| NaiveDate::from_ymd_opt(2017, 5, d).unwrap();
+        assert_eq!(plan.entry_for_date(date(7)).map(Entry::title), Some("Entry 4"));
+        assert_eq!(plan.entry_for_date(date(14)).map(Entry::title), Some("Entry 2"));
+        assert_eq!(plan.entry_for_date(date(2)), None);
+
+        // Schedules should survive a round trip through the text format
+        let mut buffer = Vec::new();
+        plan.to_text(&mut buffer).expect("could not write to buffer");
+        let reparsed = Plan::from_text("test", &buffer[..]).expect("could not reparse plan");
+        assert_eq!(reparsed, plan);
+    }
+
+    #[test]
+    fn due_entries() {
+        let text = "[2017-05-01] a\n[every Wednesday] b\n[2017-05-03] c\n[2017-05-04] d\ne";
+        let mut plan = Plan::from_text("test", text.as_bytes()).expect("could not parse plan");
+        // 2017-05-03 is a Wednesday
+        let date = NaiveDate::from_ymd_opt(2017, 5, 3).unwrap();
+        let due: Vec<_> = plan.due_entries(date).iter().map(|d| (d.index(), d.is_overdue())).collect();
+        assert_eq!(due, [(0, true), (1, false), (2, false)]);
+
+        // Completed entries are no longer due
+        plan.next(1);
+        let due: Vec<_> = plan.due_entries(date).iter().map(|d| d.index()).collect();
+        assert_eq!(due, [1, 2]);
+
+        // Without a schedule, only the current entry is due
+        let mut plan = Plan::from_entries("test", vec![Entry::new("entry"); 3]);
+        plan.next(1);
+        let due: Vec<_> = plan.due_entries(date).iter().map(|d| d.index()).collect();
+        assert_eq!(due, [1]);
+        plan.next(2);
+        assert!(plan.due_entries(date).is_empty(), "ended plan has due entries");
+    }
+
+    #[test]
+    fn entry_iterators() {
+        let mut plan = Plan::from_entries("test", (1..5).map(|i| Entry::new(&i.to_string())).collect());
+        let titles = |entries: slice::Iter<Entry>| entries.map(|e| e.title().to_owned()).collect::<Vec<_>>();
+        assert_eq!(titles(plan.remaining()), ["1", "2", "3", "4"]);
+        assert!(plan.completed().next().is_none(), "new plan has completed entries");
+        plan.next(1);
+        plan.skip_current();
+        assert_eq!(titles(plan.completed()), ["1", "2"]);
+        assert_eq!(titles(plan.remaining()), ["3", "4"]);
+        assert_eq!(titles(plan.entries_from(1)), ["2", "3", "4"]);
+        assert!(plan.entries_from(5).next().is_none(), "entries past the end");
+        assert_eq!(plan.entry(3).map(Entry::title), Some("4"));
+        assert_eq!(plan.entry(4), None);
+
+        plan.next(2);
+        assert!(plan.remaining().next().is_none(), "ended plan has remaining entries");
+        assert_eq!(plan.completed().len(), 4);
+    }
+
+    #[test]
+    fn date_ranges() {
+        let text = "[2017-05-01 to 2017-05-03] a\n[2017-05-04 to 2017-05-06] b\n[every Sunday] c\n\
+                    [2017-05-08] d\n[2017-05-09 to 2017-05-10] e";
+        let mut plan = Plan::from_text("test", text.as_bytes()).expect("could not parse plan");
+        let date = |d| NaiveDate::from_ymd_opt(2017, 5, d).unwrap();
+        let entry = plan.entries().next().unwrap();
+        assert_eq!(entry.schedule(), Some(Schedule::Range(date(1), date(3))));
+        assert_eq!((entry.start_date(), entry.end_date()), (Some(date(1)), Some(date(3))));
+        assert_eq!(plan.entry_for_date(date(2)).map(Entry::title), Some("a"));
+        assert!("2017-05-03 to 2017-05-01".parse::<Schedule>().is_err(),
+                "range ending before it starts accepted");
+
+        // An entry is only behind schedule once its whole range has passed
+        assert_eq!(plan.behind_by(date(3)), 0);
+        assert_eq!(plan.catch_up_index(date(3)), None);
+        let due: Vec<_> = plan.due_entries(date(5)).iter().map(|d| (d.index(), d.is_overdue())).collect();
+        assert_eq!(due, [(0, true), (1, false)]);
+        // Weekly entries are never behind schedule
+        assert_eq!(plan.behind_by(date(10)), 3);
+        assert_eq!(plan.catch_up_index(date(10)), Some(4));
+        plan.next(1);
+        assert_eq!(plan.behind_by(date(10)), 2);
+        assert_eq!(plan.behind_by(date(11)), 3);
+        assert_eq!(plan.catch_up_index(date(11)), Some(5));
+
+        let mut buffer = Vec::new();
+        plan.to_text(&mut buffer).expect("could not write to buffer");
+        assert!(String::from_utf8(buffer).unwrap().starts_with("[2017-05-01 to 2017-05-03] a"));
+    }
+
+    #[test]
+    fn replace_entries() {
+        let titles = |ts: &[&str]| ts.iter().map(|t| Entry::new(t)).collect::<Vec<_>>();
+        let mut plan = Plan::from_entries("test", titles(&["a", "b", "c"]));
+        plan.next(1);
+
+        // The current entry should follow its title
+        plan.replace_entries(titles(&["z", "a", "y", "b", "c"]));
+        assert_eq!(plan.current_entry_number(), 4);
+        // Otherwise, the entry number should stay the same where possible
+        plan.replace_entries(titles(&["a", "c", "d", "e"]));
+        assert_eq!(plan.current_entry_number(), 4);
+        plan.next(1);
+        plan.replace_entries(titles(&["a", "b", "c"]));
+        assert!(plan.is_ended(), "plan did not stay ended");
+
+        // The history should follow the titles of the completed entries
+        let mut plan = Plan::from_entries("test", titles(&["a", "b", "c", "d"]));
+        plan.next(2);
+        let completed = |plan: &Plan| {
+            let titles: Vec<_> = plan.entries().map(|e| e.title().to_owned()).collect();
+            plan.history().iter().map(|e| titles[e.entry_index()].clone()).collect::<Vec<_>>()
+        };
+        plan.replace_entries(titles(&["z", "a", "b", "c", "d"]));
+        assert_eq!(completed(&plan), ["a", "b"]);
+        assert_eq!(plan.current_entry_number(), 4);
+        // Completed entries which were removed are forgotten
+        plan.replace_entries(titles(&["z", "b", "c"]));
+        assert_eq!(completed(&plan), ["b"]);
+        plan.check().expect("plan is invalid");
+    }
+
+    #[test]
+    fn apply_update() {
+        use plan::diff::{EntryChange, UpdateStrategy};
+
+        let titles = |ts: &[&str]| ts.iter().map(|t| Entry::new(t)).collect::<Vec<_>>();
+        let time = Utc.with_ymd_and_hms(2017, 5, 1, 12, 0, 0).unwrap();
+        let mut plan = Plan::from_entries("test", titles(&["a", "b", "c", "d"]));
+        plan.next_at(2, time);
+        let new = Plan::from_entries("new", titles(&["b", "C", "d", "e"]));
+
+        let diff = plan.diff(&new);
+        assert_eq!(diff.changes(),
+                   &[EntryChange::Removed { index: 0, title: "a".into() },
+                     EntryChange::Renamed { old_index: 2, new_index: 1, old_title: "c".into(), new_title: "C".into() },
+                     EntryChange::Added { index: 3, title: "e".into() }]);
+        assert_eq!(diff.new_index(3), Some(2));
+        assert_eq!(diff.new_index(0), None);
+
+        // The current entry stays after the last one read
+        let mut merged = plan.clone();
+        merged.apply_update(&new, UpdateStrategy::Merge);
+        assert_eq!(merged.entries().map(Entry::title).collect::<Vec<_>>(), ["a", "b", "C", "d", "e"]);
+        assert_eq!(merged.current_entry().map(Entry::title), Some("C"));
+        assert_eq!(merged.history().len(), 2);
+
+        let diff = plan.apply_update(&new, UpdateStrategy::Replace);
+        assert_eq!((diff.added(), diff.removed(), diff.renamed()), (1, 1, 1));
+        assert_eq!(plan.name(), "test");
+        assert_eq!(plan.current_entry().map(Entry::title), Some("C"));
+        assert_eq!(plan.history().iter().map(|e| e.entry_index()).collect::<Vec<_>>(), [0]);
+
+        // An ended plan continues with the new entries
+        plan.next(4);
+        plan.apply_update(&Plan::from_entries("new", titles(&["b", "C", "d", "e", "g"])),
+                          UpdateStrategy::Replace);
+        assert_eq!(plan.current_entry().map(Entry::title), Some("g"));
+    }
+
+    #[test]
+    fn insert_entries() {
+        let time = Utc.with_ymd_and_hms(2017, 5, 1, 12, 0, 0).unwrap();
+        let mut plan = Plan::from_entries("test", vec![Entry::new("a"), Entry::new("b"), Entry::new("c")]);
+        plan.next_at(1, time);
+
+        // Entries before the current entry count as read
+        plan.insert_entry(0, Entry::new("before")).expect("could not insert entry");
+        assert_eq!(plan.current_entry().map(Entry::title), Some("b"));
+        assert_eq!(plan.history()[0].entry_index(), 1);
+        plan.insert_entry(2, Entry::new("now")).expect("could not insert entry");
+        assert_eq!(plan.current_entry().map(Entry::title), Some("now"));
+        assert!(plan.insert_entry(6, Entry::new("past end")).is_err(), "entry inserted past the end");
+
+        assert_eq!(plan.remove_entry(0).expect("could not remove entry").title(), "before");
+        assert_eq!(plan.history()[0].entry_index(), 0);
+        assert_eq!(plan.remove_entry(1).expect("could not remove entry").title(), "now");
+        assert_eq!(plan.current_entry().map(Entry::title), Some("b"));
+        assert!(plan.remove_entry(3).is_err(), "entry removed past the end");
+
+        // An ended plan continues with an appended entry
+        plan.next(3);
+        assert!(plan.is_ended());
+        plan.push_entry(Entry::new("d"));
+        assert_eq!(plan.current_entry().map(Entry::title), Some("d"));
+        assert_eq!(plan.len(), 4);
+    }
+
+    #[test]
+    fn pace() {
+        let pace: Pace = "5 chapters per week".parse().expect("could not parse pace");
+        assert_eq!(pace, Pace::new(5, 7).unwrap());
+        assert_eq!(pace.to_string(), "5 per week");
+        assert_eq!("2 per 3 days".parse::<Pace>().expect("could not parse pace").days(), 3);
+        assert!("0 per day".parse::<Pace>().is_err(), "empty pace accepted");
+        assert!("5 per year".parse::<Pace>().is_err(), "unknown period accepted");
+
+        let date = |d| NaiveDate::from_ymd_opt(2017, 5, d).unwrap();
+        let mut plan = Plan::from_entries("test", vec![Entry::new("entry"); 11]);
+        assert_eq!(plan.projection(date(1)), None);
+        plan.next(1);
+        plan.set_pace(Some(Pace::new(2, 1).unwrap()), date(1));
+        let projection = plan.projection(date(1)).expect("no projection");
+        assert_eq!(projection.scheduled_finish(), date(5));
+        assert_eq!(projection.to_read_today(), 2);
+        assert_eq!(projection.days_ahead(), 0);
+
+        // Two days later, with only one more entry read
+        plan.next(1);
+        let projection = plan.projection(date(3)).expect("no projection");
+        assert_eq!(projection.days_ahead(), -1);
+        assert_eq!(projection.to_read_today(), 5);
+        assert_eq!(projection.projected_finish(), Some(date(7)));
+
+        plan.next(9);
+        assert_eq!(plan.projection(date(3)).expect("no projection").projected_finish(), None);
+    }
+
+    #[test]
+    fn active_days() {
+        let weekdays: ActiveDays = "mon-fri".parse().expect("could not parse days");
+        assert_eq!(weekdays, "Weekdays".parse().unwrap());
+        assert_eq!(weekdays.to_string(), "weekdays");
+        let long_weekend: ActiveDays = "fri-mon".parse().expect("could not parse days");
+        assert_eq!(long_weekend.count(), 4);
+        assert_eq!(long_weekend.to_string(), "Mon, Fri, Sat, Sun");
+        assert!("".parse::<ActiveDays>().is_err(), "no days accepted");
+        assert!("mon, funday".parse::<ActiveDays>().is_err(), "unknown day accepted");
+
+        // 2017-05-01 is a Monday
+        let date = |d| NaiveDate::from_ymd_opt(2017, 5, d).unwrap();
+        let mut plan = Plan::from_entries("test", vec![Entry::new("entry"); 10]);
+        plan.set_property("days", "weekdays").unwrap();
+        assert!(plan.due_entries(date(6)).is_empty(), "entry due on a day off");
+        assert_eq!(plan.due_entries(date(8)).len(), 1);
+
+        // Five entries a week are one each weekday
+        plan.set_pace(Some(Pace::new(5, 7).unwrap()), date(1));
+        assert_eq!(plan.projection(date(1)).expect("no projection").scheduled_finish(), date(12));
+        plan.next(5);
+        let projection = plan.projection(date(6)).expect("no projection");
+        assert_eq!(projection.days_ahead(), 0);
+        assert_eq!(projection.to_read_today(), 0);
+        assert_eq!(projection.projected_finish(), Some(date(12)));
+        let projection = plan.projection(date(9)).expect("no projection");
+        assert_eq!(projection.days_ahead(), -1);
+        assert_eq!(projection.to_read_today(), 2);
+
+        assert_eq!(weekdays.nth_from(date(6), 1), Some(date(8)));
+        assert_eq!(weekdays.count_between(date(3), date(13)), 8);
+        // Dates past the end of the calendar can't be projected
+        assert_eq!(weekdays.nth_from(date(1), u64::MAX), None);
+        assert_eq!(weekdays.nth_from(NaiveDate::MAX, 10), None);
+        plan.set_pace(Some(Pace::new(1, 365).unwrap()), NaiveDate::MAX.pred_opt().unwrap());
+        assert_eq!(plan.projection(NaiveDate::MAX), None);
+
+        let mut buffer = Vec::new();
+        plan.to_text(&mut buffer).expect("could not write plan");
+        let reparsed = Plan::from_text("test", &buffer[..]).expect("could not reparse plan");
+        assert_eq!(reparsed.active_days(), weekdays);
+    }
+
+    #[test]
+    fn history() {
+        let time = Utc.with_ymd_and_hms(2017, 5, 1, 12, 0, 0).unwrap();
+        let mut plan = Plan::from_entries("test", vec![Entry::new("entry"); 3]);
+        plan.next_at(2, time);
+        plan.previous(1);
+        plan.next_at(5, time);
+        let completed: Vec<_> = plan.history().iter().map(|e| e.entry_index()).collect();
+        assert_eq!(completed, [0, 1, 1, 2]);
+        assert!(plan.history().iter().all(|e| e.completed_at() == time));
+
+        // Cyclic plans wrap around in the history as well, but only the last
+        // time round is recorded
+        let mut plan = Plan::from_entries("test", vec![Entry::new("entry"); 3]);
+        plan.set_cyclic(true);
+        plan.next_at(2, time);
+        plan.next_at(4, time);
+        let completed: Vec<_> = plan.history().iter().map(|e| e.entry_index()).collect();
+        assert_eq!(completed, [0, 1, 0, 1, 2]);
+    }
+
+    #[test]
+    fn tags() {
+        let mut plan = Plan::from_entries("test", vec![Entry::new("entry")]);
+        plan.add_tag("fiction").expect("could not add tag");
+        plan.add_tag(" work ").expect("could not add tag");
+        plan.add_tag("fiction").expect("could not add tag");
+        assert_eq!(plan.tags(), ["fiction", "work"]);
+        assert!(plan.add_tag("a,b").is_err(), "tag with comma accepted");
+
+        assert!(plan.remove_tag("fiction"), "tag was not removed");
+        assert!(!plan.remove_tag("fiction"), "tag was removed twice");
+        assert_eq!(plan.tags(), ["work"]);
+    }
+
+    #[test]
+    fn stats() {
+        let start = Utc.with_ymd_and_hms(2017, 5, 1, 12, 0, 0).unwrap();
+        let mut plan = Plan::from_entries("test", vec![Entry::new("entry"); 10]);
+        // Two entries a day for a week, then nothing for two days
+        for day in 0..7 {
+            plan.next_at(2, start + Duration::days(day));
+        }
+        let now = start + Duration::days(8);
+
+        let stats = PlanStats::for_plan(&plan, &now);
+        assert_eq!(stats.completed(), 10);
+        assert_eq!(stats.remaining(), 0);
+        assert_eq!(stats.percent_complete(), 100.0);
+        // Only 10 entries could actually be completed, over 9 days
+        assert_eq!(stats.entries_per_week(), Some(10.0 * 7.0 / 9.0));
+        assert_eq!(stats.estimated_completion(), None);
+
+        let mut other = Plan::from_entries("other", vec![Entry::new("entry"); 30]);
+        other.next_at(5, start);
+        let stats = PlanStats::for_plan(&other, &now);
+        assert_eq!(stats.entries_per_week(), Some(5.0 * 7.0 / 9.0));
+        assert_eq!(stats.estimated_completion(),
+                   Some(NaiveDate::from_ymd_opt(2017, 6, 23).unwrap()));
+
+        let summary = Summary::for_plans(&[plan, other], &now);
+        assert_eq!(summary.plans(), 2);
+        assert_eq!(summary.remaining(), 25);
+        assert_eq!(summary.entries_per_week(), Some(15.0 * 7.0 / 9.0));
+
+        // History older than four weeks doesn't count towards the pace
+        let later = now + Duration::days(60);
+        assert_eq!(Summary::for_plans(&[], &later).entries_per_week(), None);
+    }
+
+    #[test]
+    fn plan_csv() {
+        let csv = "Title,Description,Date
+Entry 1,\"Description, with a comma\",2017-05-01
+Entry 2
+Entry 3,,every Monday\n";
+        let plan = Plan::from_csv("test", csv.as_bytes()).expect("could not parse plan");
+        let entries: Vec<_> = plan.entries().collect();
+        assert_eq!(plan.len(), 3);
+        assert_eq!(entries[0].description(), "Description, with a comma");
+        assert_eq!(entries[1], &Entry::new("Entry 2"));
+        assert_eq!(entries[2].schedule(), Some(Schedule::Weekly(Weekday::Mon)));
+
+        let mut buffer = Vec::new();
+        plan.to_csv(&mut buffer).expect("could not write to buffer");
+        let reparsed = Plan::from_csv("test", &buffer[..]).expect("could not reparse plan");
+        assert_eq!(reparsed, plan);
+
+        assert!(Plan::from_csv("test", "title\n".as_bytes()).is_err(),
+                "empty plan accepted");
+        assert!(Plan::from_csv("test", "Entry,,tomorrow\n".as_bytes()).is_err(),
+                "invalid schedule accepted");
+    }
+
+    #[test]
+    fn plan_html() {
+        let plan_text = "Read <this> & that\n    A \"quoted\" description\n# Part II\nLinked (10m)\n    https://example.com/a?b=1&c=2\n";
+        let mut plan = Plan::from_text("Tom's plan", plan_text.as_bytes()).expect("could not parse plan");
+        plan.next(1);
+
+        let mut buffer = Vec::new();
+        plan.to_html(&mut buffer).expect("could not write to buffer");
+        let html = String::from_utf8(buffer).unwrap();
+        assert!(html.contains("<title>Tom&#39;s plan</title>"), "unescaped title: {}", html);
+        assert!(html.contains("<input type=\"checkbox\" checked> <span class=\"title\">Read &lt;this&gt; &amp; that</span>"),
+                "completed entry not checked: {}", html);
+        assert!(html.contains("<p>A &quot;quoted&quot; description</p>"));
+        assert!(html.contains("<h2>Part II</h2>\n<ul>\n<li><label><input type=\"checkbox\"> <span class=\"title\">Linked</span> \
+                               <span class=\"details\">(about 10m)</span>"),
+                "pending entry not in its section: {}", html);
+        assert!(html.contains("<a href=\"https://example.com/a?b=1&amp;c=2\">"));
+        assert_eq!(html.matches("<ul>").count(), html.matches("</ul>").count());
+    }
+
+    #[test]
+    fn plan_metadata() {
+        let plan_text = "author: Robert Murray M'Cheyne\nsource: https://example.com/plan\n---\nGenesis 1\nGenesis 2\n";
+        let mut plan = Plan::from_text("test", plan_text.as_bytes()).expect("could not parse plan");
+        assert_eq!(plan.author(), Some("Robert Murray M'Cheyne"));
+        assert_eq!(plan.source_url(), Some("https://example.com/plan"));
+        assert_eq!(plan.len(), 2);
+        // A `---` line is an entry unless everything before it is a header
+        let plain = Plan::from_text("test", "Genesis 1\n---\n".as_bytes()).expect("could not parse plan");
+        assert_eq!(plain.entries().nth(1).unwrap().title(), "---");
+        assert!(Plan::from_text("test", "authr: me\n---\nGenesis 1\n".as_bytes()).is_err(),
+                "unknown header field accepted");
+        assert!(Plan::from_text("test", "created: yesterday\n---\nGenesis 1\n".as_bytes()).is_err(),
+                "invalid timestamp accepted");
+
+        let created = Utc.with_ymd_and_hms(2017, 5, 1, 12, 0, 0).unwrap();
+        plan.mark_modified(created);
+        plan.mark_modified(Utc::now());
+        assert_eq!(plan.created_at(), Some(created));
+        let mut buffer = Vec::new();
+        plan.to_text(&mut buffer).expect("could not write to buffer");
+        let reparsed = Plan::from_text("test", &buffer[..]).expect("could not reparse plan");
+        assert_eq!(reparsed, plan);
+        assert!(plan.fresh_copy("copy").created_at().is_none());
+
+        // The store keeps the times up to date
+        let dir = env::temp_dir().join(format!("reading-test-metadata-{}", process::id()));
+        let mut store = FsStore::new(&dir);
+        store.add(&Plan::from_entries("new", vec![Entry::new("a")])).expect("could not add plan");
+        let stored = store.read("new").expect("could not read plan");
+        assert!(stored.created_at().is_some());
+        assert_eq!(stored.modified_at(), stored.created_at());
+        let updated = store.update("new", |p| {
+                p.next(1);
+                Ok(p.clone())
+            })
+            .expect("could not update plan");
+        assert_eq!(updated.created_at(), stored.created_at());
+        assert!(updated.modified_at() >= stored.modified_at());
+        fs::remove_dir_all(&dir).expect("could not remove test directory");
+    }
+
+    #[test]
+    fn plan_text_header() {
+        let plan_text = "name: mcheyne\ncyclic: true\ntags: bible, daily\nunit: day\npriority: 2\npace: 1 per day\n---\n\
+                         Genesis 1\nGenesis 2\n";
+        let plan = Plan::from_text("file", plan_text.as_bytes()).expect("could not parse plan");
+        assert_eq!(plan.name(), "mcheyne");
+        assert!(plan.is_cyclic());
+        assert_eq!(plan.tags(), ["bible", "daily"]);
+        assert_eq!(plan.unit_label(), "day");
+        assert_eq!(plan.priority(), 2);
+        assert_eq!(plan.pace(), Some(Pace::new(1, 1).unwrap()));
+
+        // Everything but the name is written back out
+        let mut buffer = Vec::new();
+        plan.to_text(&mut buffer).expect("could not write to buffer");
+        assert!(buffer.starts_with(b"cyclic: true\n"), "unexpected header: {}", String::from_utf8_lossy(&buffer));
+        assert_eq!(Plan::from_text("mcheyne", &buffer[..]).expect("could not reparse plan"), plan);
+
+        assert!(Plan::from_text("test", "cyclic: maybe\n---\nGenesis 1\n".as_bytes()).is_err(),
+                "invalid property accepted");
+        assert!(Plan::from_text("test", "name: a/b\n---\nGenesis 1\n".as_bytes()).is_err(),
+                "invalid name accepted");
+
+        let mut other = Plan::from_entries("other", vec![Entry::new("a")]);
+        other.copy_properties(&plan);
+        assert_eq!(other.name(), "other");
+        assert!(other.is_cyclic());
+        assert_eq!(other.tags(), plan.tags());
+        assert_eq!(other.pace(), plan.pace());
+    }
+
+    #[test]
+    fn text_dialects() {
+        let expected = vec![Entry::with_description("Genesis 1", "Creation"),
+                            Entry::new("Genesis 2")];
+        let dialects = [("bullets", "// Comment\n- Genesis 1\n  Creation\n\n  https://example.com/gen\n* Genesis 2\n"),
+                        ("definitions", "Genesis 1\n: Creation\n\n: https://example.com/gen\nGenesis 2\n"),
+                        ("paragraphs", "Genesis 1\nCreation\nhttps://example.com/gen\n\n// Comment\nGenesis 2\n")];
+        for &(dialect, text) in &dialects {
+            let options = TextFormatOptions::dialect(dialect).expect("missing dialect");
+            let plan = Plan::from_text_with("test", text.as_bytes(), &options).expect("could not parse plan");
+            let mut entries = plan.entries().cloned().collect::<Vec<_>>();
+            entries[0].set_url(None);
+            assert_eq!(entries, expected, "in the {} dialect", dialect);
+            assert_eq!(plan.entries().next().unwrap().url(), Some("https://example.com/gen"));
+        }
+
+        // The standard dialect is the same as `from_text`
+        let standard = TextFormatOptions::dialect("standard").expect("missing dialect");
+        assert!(standard.is_standard());
+        assert!(Plan::from_text_with("test", "Genesis 1\n# Part I\n\n  Creation\n".as_bytes(), &standard).is_err(),
+                "description after section header accepted");
+        assert!(TextFormatOptions::dialect("nonsense").is_none());
+
+        let mut options = TextFormatOptions::default();
+        options.bullets(&["1. "]).comment_prefix(";").blank_lines(BlankLines::Paragraphs);
+        assert!(!options.is_standard());
+        let plan = Plan::from_text_with("test", "name: custom\n---\n# Part I\n1. Genesis 1 (5m)\n; no\n\n1. Genesis 2\n"
+                                            .as_bytes(),
+                                        &options)
+            .expect("could not parse plan");
+        assert_eq!(plan.name(), "custom");
+        assert_eq!(plan.sections()[0].title(), "Part I");
+        assert_eq!(plan.entries().map(Entry::title).collect::<Vec<_>>(), ["Genesis 1", "Genesis 2"]);
+        assert!(plan.entries().next().unwrap().estimate().is_some());
+    }
+
+    #[test]
+    fn plan_opml() {
+        let opml = r#"<?xml version="1.0"?>
+<opml version="2.0">
+  <head><title>Queue</title></head>
+  <body>
+    <outline text="Podcasts">
+      <outline text="Show &amp; Tell" xmlUrl="https://example.com/feed" htmlUrl="https://example.com/"/>
+      <outline title="Other" xmlUrl="https://example.org/feed"/>
+    </outline>
+    <outline text="Article"/>
+  </body>
+</opml>"#;
+        let plan = Plan::from_opml("test", opml.as_bytes()).expect("could not parse plan");
+        let entries: Vec<_> = plan.entries().collect();
+        assert_eq!(plan.len(), 3);
+        assert_eq!(entries[0], &Entry::with_description("Show & Tell", "https://example.com/"));
+        assert_eq!(entries[1], &Entry::with_description("Other", "https://example.org/feed"));
+        assert_eq!(entries[2], &Entry::new("Article"));
+
+        assert!(Plan::from_opml("test", "<opml><body></body></opml>".as_bytes()).is_err(),
+                "empty plan accepted");
+        assert!(Plan::from_opml("test", "<opml><body><outline/></body></opml>".as_bytes()).is_err(),
+                "untitled item accepted");
+        assert!(Plan::from_opml("test", "<opml><body>".as_bytes()).is_err(),
+                "invalid xml accepted");
+    }
+
+    #[test]
+    fn plan_goodreads() {
+        let export = "Book Id,Title,Author,My Rating,Date Added,Bookshelves,Bookshelves with positions,\
+                      Exclusive Shelf,My Review\n\
+                      3,Dune,Frank Herbert,0,2024/03/01,\"to-read, sci-fi\",\"to-read (#2), sci-fi (#1)\",\
+                      to-read,Everyone says<br/>it's great\n\
+                      2,Emma,Jane Austen,5,2023/01/01,,,read,\n\
+                      1,Middlemarch,George Eliot,0,2024/05/01,,to-read (#1),to-read,\n";
+        let plan = Plan::from_goodreads("books", export.as_bytes()).expect("could not parse plan");
+        let entries: Vec<_> = plan.entries().collect();
+        assert_eq!(plan.unit_label(), "book");
+        assert_eq!(entries,
+                   vec![&Entry::with_description("Middlemarch by George Eliot", "Shelves: to-read"),
+                        &Entry::with_description("Dune by Frank Herbert",
+                                                 "Shelves: to-read, sci-fi\nEveryone says\nit's great")]);
+
+        assert!(Plan::from_csv("books", export.as_bytes()).is_err(), "goodreads export read as csv");
+        assert!(Plan::from_goodreads("books", "Name\nDune\n".as_bytes()).is_err(), "missing title accepted");
+        assert!(Plan::from_goodreads("books", "Title,Exclusive Shelf\nEmma,read\n".as_bytes()).is_err(),
+                "empty plan accepted");
+    }
+
+    #[cfg(feature = "feed")]
+    #[test]
+    fn plan_feed() {
+        let rss = r#"<?xml version="1.0"?>
+<rss version="2.0" xmlns:media="http://search.yahoo.com/mrss/">
+  <channel>
+    <title>Blog</title>
+    <link>https://example.com/</link>
+    <item>
+      <title>Second &amp; last</title>
+      <link>https://example.com/2</link>
+      <media:title>Thumbnail</media:title>
+    </item>
+    <item><title><![CDATA[First]]></title></item>
+    <item><link>https://example.com/0</link></item>
+  </channel>
+</rss>"#;
+        let plan = Plan::from_feed("test", rss.as_bytes()).expect("could not parse plan");
+        let entries: Vec<_> = plan.entries().collect();
+        assert_eq!(plan.len(), 3);
+        assert_eq!(entries[0], &Entry::with_description("https://example.com/0", "https://example.com/0"));
+        assert_eq!(entries[1], &Entry::new("First"));
+        assert_eq!(entries[2], &Entry::with_description("Second & last", "https://example.com/2"));
+
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Blog</title>
+  <link href="https://example.com/"/>
+  <entry>
+    <title>Post</title>
+    <link rel="edit" href="https://example.com/edit/1"/>
+    <link rel="alternate" href="https://example.com/1"/>
+  </entry>
+</feed>"#;
+        let plan = Plan::from_feed("test", atom.as_bytes()).expect("could not parse plan");
+        assert_eq!(plan.entries().collect::<Vec<_>>(), [&Entry::with_description("Post", "https://example.com/1")]);
+
+        assert!(Plan::from_feed("test", "<rss><channel></channel></rss>".as_bytes()).is_err(),
+                "empty plan accepted");
+        assert!(Plan::from_feed("test", "<rss><channel><item/></channel></rss>".as_bytes()).is_err(),
+                "untitled item accepted");
+    }
+
+    #[cfg(feature = "toml-format")]
+    #[test]
+    fn plan_toml() {
+        let mut plan = Plan::from_text("test", "Entry 1\n    Description\n[every Monday] Entry 2\n".as_bytes())
+            .expect("could not parse plan");
+        plan.add_tag("tag").expect("could not add tag");
+        plan.next_at(1, Utc.with_ymd_and_hms(2017, 5, 1, 12, 0, 0).unwrap());
+
+        let mut buffer = Vec::new();
+        plan.to_toml(&mut buffer).expect("could not write to buffer");
+        let reparsed = Plan::from_toml(&buffer[..]).expect("could not reparse plan");
+        assert_eq!(reparsed, plan);
+
+        // Plans in the store can be written by hand in TOML
+        let dir = env::temp_dir().join(format!("reading-test-toml-{}", process::id()));
+        fs::create_dir_all(&dir).expect("could not create test directory");
+        fs::write(dir.join("test.plan.toml"), &buffer).expect("could not write plan file");
+        let mut store = FsStore::new(&dir);
+        assert_eq!(store.list().expect("could not list plans"), ["test"]);
+        store.update("test", |p| {
+                p.next(1);
+                Ok(())
+            })
+            .expect("could not update plan");
+        assert!(dir.join("test.plan.toml").is_file(), "plan not kept as toml");
+        assert!(!dir.join("test.plan.json").exists(), "plan written as json");
+        assert!(store.read("test").expect("could not read plan").is_ended());
+        fs::remove_dir_all(&dir).expect("could not remove test directory");
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn encrypted_store() {
+        use crypto::{self, Key};
+
+        let key = Key::generate();
+        let data = crypto::encrypt(&key, b"private").expect("could not encrypt data");
+        assert!(crypto::is_encrypted(&data));
+        assert_eq!(crypto::decrypt(&key, &data).expect("could not decrypt data"), b"private");
+        match crypto::decrypt(&Key::generate(), &data) {
+            Err(Error(ErrorKind::DecryptionFailed, _)) => {}
+            r => panic!("unexpected result of decrypting with the wrong key: {:?}", r),
+        }
+        assert_eq!(Key::from_hex(&key.to_hex()).unwrap(), key);
+        assert!(Key::from_hex("abc").is_err(), "short key accepted");
+
+        // The key only applies to this thread, so other tests aren't affected
+        crypto::with_key(&key, || {
+            let dir = env::temp_dir().join(format!("reading-test-encrypted-{}", process::id()));
+            let mut store = FsStore::new(&dir);
+            let plan = Plan::from_entries("secret", vec![Entry::new("Entry 1"), Entry::new("Entry 2")]);
+            store.add(&plan).expect("could not add plan");
+            store.update("secret", |p| {
+                    p.next(1);
+                    Ok(())
+                })
+                .expect("could not update plan");
+            fs::create_dir_all(dir.join("quarantine")).unwrap();
+            fs::write(dir.join("quarantine/1.broken.plan.json"), "{").unwrap();
+
+            assert_eq!(store.set_encrypted(true).expect("could not encrypt plans"), ["secret"]);
+            assert!(dir.join("secret.plan.json.enc").is_file(), "plan not encrypted");
+            assert!(!dir.join("secret.plan.json").exists(), "unencrypted plan left behind");
+            assert!(!dir.join("secret.plan.json.bak").exists(), "unencrypted backup left behind");
+            let backups: Vec<_> = fs::read_dir(dir.join("backups")).unwrap().map(|e| e.unwrap().path()).collect();
+            assert!(!backups.is_empty(), "backups removed");
+            for backup in &backups {
+                assert!(crypto::is_encrypted(&fs::read(backup).unwrap()), "{} not encrypted", backup.display());
+            }
+            assert!(!dir.join("quarantine/1.broken.plan.json").exists(), "unencrypted quarantined plan left behind");
+            let journal = fs::read(store.journal_path("secret").unwrap()).expect("could not read journal");
+            assert!(crypto::is_encrypted(&journal), "journal not encrypted");
+            assert_eq!(store.read("secret").expect("could not read plan").current_entry_number(), 2);
+            assert_eq!(store.list().unwrap(), ["secret"]);
+
+            store.update("secret", |p| {
+                    p.next(1);
+                    Ok(())
+                })
+                .expect("could not update encrypted plan");
+            assert!(!dir.join("secret.plan.json").exists(), "encrypted plan written as json");
+            assert_eq!(store.undo("secret").expect("could not undo change").current_entry_number(), 2);
+
+            assert_eq!(store.set_encrypted(false).expect("could not decrypt plans"), ["secret"]);
+            assert!(dir.join("secret.plan.json").is_file(), "plan not decrypted");
+            assert!(!dir.join("secret.plan.json.enc").exists(), "encrypted plan left behind");
+            assert_eq!(store.undo("secret").expect("could not undo change").current_entry_number(), 1);
+            fs::remove_dir_all(&dir).expect("could not remove test directory");
+        });
+    }
+
+    #[cfg(feature = "yaml-format")]
+    #[test]
+    fn plan_yaml() {
+        let mut plan = Plan::from_text("test", "Entry 1\n    Description\n[2017-05-01] Entry 2\n".as_bytes())
+            .expect("could not parse plan");
+        plan.set_cyclic(true);
+        plan.next_at(3, Utc.with_ymd_and_hms(2017, 5, 1, 12, 0, 0).unwrap());
+
+        let mut buffer = Vec::new();
+        plan.to_yaml(&mut buffer).expect("could not write to buffer");
+        let reparsed = Plan::from_yaml(&buffer[..]).expect("could not reparse plan");
+        assert_eq!(reparsed, plan);
+        assert!(Plan::from_yaml("name: [".as_bytes()).is_err(), "invalid yaml accepted");
+    }
+
+    #[cfg(feature = "bible")]
+    #[test]
+    fn bible() {
+        use plan::bible::{self, Preset};
+
+        let chapters = |books: &[bible::Book]| books.iter().map(|b| b.chapters()).sum::<u32>();
+        assert_eq!(chapters(bible::old_testament()), 929);
+        assert_eq!(chapters(bible::new_testament()), 260);
+
+        let plan = Preset::WholeBible.generate("bible", None).expect("could not generate plan");
+        assert_eq!(plan.len(), 365);
+        let titles: Vec<_> = plan.entries().map(|e| e.title().to_owned()).collect();
+        assert_eq!(titles[0], "Genesis 1–4");
+        assert!(titles.iter().any(|t| t.contains("; ")), "no entry spans two books");
+        assert_eq!(titles[364], "Revelation 20–22");
+
+        let plan = "nt-90".parse::<Preset>().unwrap().generate("nt", None).unwrap();
+        assert_eq!(plan.len(), 90);
+        assert!(!plan.is_cyclic());
+        let plan = Preset::PsalmsProverbs.generate("pp", None).unwrap();
+        assert_eq!(plan.len(), 31);
+        assert!(plan.is_cyclic());
+        assert!(Preset::PsalmsProverbs.generate("pp", Some(30)).is_err(), "wrong length accepted");
+        assert!(Preset::NewTestament.generate("nt", Some(261)).is_err(), "too many days accepted");
+        assert!("bogus".parse::<Preset>().is_err(), "unknown preset accepted");
+    }
+
+    #[test]
+    fn sections() {
+        let plan_text = "Preface\n# Part I\nOne\n    The first\nTwo\n#Part II\nThree\n";
+        let mut plan = Plan::from_text("test", plan_text.as_bytes()).expect("could not parse plan");
+        assert_eq!(plan.len(), 4);
+        assert_eq!(plan.sections(), &[Section::new("Part I", 1), Section::new("Part II", 3)]);
+        assert_eq!(plan.current_section(), None);
+        plan.next(2);
+        assert_eq!(plan.current_section().map(|s| s.title()), Some("Part I"));
+        assert_eq!(plan.section_of(3).map(|s| s.title()), Some("Part II"));
+        plan.check().expect("plan with sections is invalid");
+
+        let mut buffer = Vec::new();
+        plan.to_text(&mut buffer).expect("could not write plan");
+        assert_eq!(String::from_utf8(buffer).unwrap(),
+                   "Preface\n# Part I\nOne\n    The first\nTwo\n# Part II\nThree\n");
+
+        // Sections follow their entries as entries are added and removed
+        plan.insert_entry(1, Entry::new("Introduction")).unwrap();
+        assert_eq!(plan.sections(), &[Section::new("Part I", 1), Section::new("Part II", 4)]);
+        plan.remove_entry(4).unwrap();
+        assert_eq!(plan.sections(), &[Section::new("Part I", 1)]);
+        assert!(plan.set_sections(vec![Section::new("Empty", 4)]).is_err(), "empty section accepted");
+
+        for text in &["# Empty\n# Part I\nOne\n", "One\n# Empty\n"] {
+            assert!(Plan::from_text("test", text.as_bytes()).is_err(), "empty section accepted: {:?}", text);
+        }
+    }
+
+    #[test]
+    fn tracks() {
+        let current = |plan: &Plan| plan.tracks().iter().map(Track::current_index).collect::<Vec<_>>();
+        let plan_text = "@ Old Testament\nGenesis 1\nGenesis 2\nGenesis 3\n@ New Testament\nMatthew 1\n@ Psalms\nPsalm 1\n\
+                         Psalm 2\n";
+        let mut plan = Plan::from_text("test", plan_text.as_bytes()).expect("could not parse plan");
+        assert_eq!(plan.len(), 6);
+        assert_eq!(plan.tracks(), &[Track::new("Old Testament", 0..3), Track::new("New Testament", 3..4),
+                                    Track::new("Psalms", 4..6)]);
+        assert_eq!(plan.track_of(4).map(|t| t.name()), Some("Psalms"));
+        plan.check().expect("plan with tracks is invalid");
+
+        // Every track moves on, and the plan ends once they all have
+        assert_eq!(plan.current_indices(), [0, 3, 4]);
+        plan.next(1);
+        assert_eq!(current(&plan), [Some(1), None, Some(5)]);
+        assert_eq!(plan.current_entry_number(), 2);
+        assert_eq!(plan.history().len(), 3);
+        assert_eq!(plan.current_indices(), [1, 5]);
+        plan.next(1);
+        assert_eq!(current(&plan), [Some(2), None, None]);
+        assert_eq!(plan.current_indices(), [2]);
+        assert_eq!(PlanSummary::from(&plan).percent_complete().round(), 83.0);
+        assert!(!plan.is_ended());
+
+        let mut buffer = Vec::new();
+        plan.to_text_with_progress(&mut buffer).expect("could not write plan");
+        let reparsed = Plan::from_text("test", &buffer[..]).expect("could not parse written plan");
+        assert_eq!(reparsed.tracks(), plan.tracks());
+
+        // Tracks follow their entries as entries are added and removed
+        plan.insert_entry(3, Entry::new("Introduction")).unwrap();
+        assert_eq!(plan.tracks()[1].entries(), 3..5);
+        assert_eq!(plan.tracks()[2].entries(), 5..7);
+        assert_eq!(current(&plan), [Some(2), None, None]);
+        plan.remove_entry(3).unwrap();
+        plan.remove_entry(3).unwrap();
+        assert_eq!(plan.tracks().len(), 2);
+        plan.next(1);
+        assert!(plan.is_ended());
+        assert!(plan.current_indices().is_empty());
+
+        // A plan without tracks only has one current entry
+        let mut plan = Plan::from_entries("test", vec![Entry::new("a"), Entry::new("b")]);
+        assert_eq!(plan.current_indices(), [0]);
+        plan.next(2);
+        assert!(plan.current_indices().is_empty());
+
+        assert!(Plan::from_text("test", "Preface\n@ Track\nOne\n".as_bytes()).is_err(),
+                "entry before first track accepted");
+    }
+
+    #[test]
+    fn readers() {
+        let mut plan = Plan::from_entries("test", vec![Entry::new("a"), Entry::new("b"), Entry::new("c")]);
+        let mut alice = plan.for_reader("alice");
+        assert_eq!(alice.current_entry_number(), 1);
+        alice.next(2);
+        plan.set_reader_progress("alice", &alice);
+        let mut bob = plan.for_reader("bob");
+        assert!(bob.skip_current());
+        plan.set_reader_progress("bob", &bob);
+        plan.next(1);
+        assert_eq!(plan.readers(), ["alice", "bob"]);
+
+        // Every reader keeps their own progress
+        assert_eq!(plan.current_entry_number(), 2);
+        assert_eq!(plan.history().len(), 1);
+        assert_eq!(plan.skipped_count(), 0);
+        let alice = plan.for_reader("alice");
+        assert_eq!(alice.current_entry_number(), 3);
+        assert_eq!(alice.history().len(), 2);
+        assert!(alice.readers().is_empty());
+        assert_eq!(plan.for_reader("bob").skipped_count(), 1);
+
+        // Changes to the entries are made to every reader's progress
+        plan.insert_entry(0, Entry::new("intro")).unwrap();
+        plan.remove_entry(2).unwrap();
+        assert_eq!(plan.current_entry_number(), 3);
+        let alice = plan.for_reader("alice");
+        assert_eq!(alice.current_entry().map(|e| e.title()), Some("c"));
+        assert_eq!(alice.history().len(), 1);
+        plan.check().expect("plan with readers is invalid");
+
+        let json = serde_json::to_string(&plan).unwrap();
+        assert_eq!(serde_json::from_str::<Plan>(&json).unwrap(), plan);
+    }
+
+    #[test]
+    fn find_entry() {
+        let plan = Plan::from_text("test", "Genesis 1\nExodus 1\ngenesis 2\n".as_bytes()).unwrap();
+        let found: Vec<_> = plan.find_entry("GENESIS").into_iter().map(|(i, e)| (i, e.title())).collect();
+        assert_eq!(found, [(0, "Genesis 1"), (2, "genesis 2")]);
+        assert!(plan.find_entry("Leviticus").is_empty());
+    }
+
+    #[test]
+    fn content_hash() {
+        let text = "Genesis 1\n    In the beginning\nGenesis 2 [2024-01-02]\n";
+        let mut plan = Plan::from_text("test", text.as_bytes()).unwrap();
+        let hash = plan.content_hash();
+        assert_eq!(hash.len(), 16);
+        // Only the entries count, not the progress or the rest of the plan
+        plan.next(1);
+        plan.set_name("other");
+        assert_eq!(plan.content_hash(), hash);
+        assert_eq!(Plan::from_text("again", text.as_bytes()).unwrap().content_hash(), hash);
+        assert_ne!(Plan::from_text("test", "Genesis 1\nGenesis 2\n".as_bytes()).unwrap().content_hash(), hash);
+        // Fields can't run together
+        let joined = Plan::from_entries("test", vec![Entry::new("ab")]);
+        let split = Plan::from_entries("test", vec![Entry::with_description("a", "b")]);
+        assert_ne!(joined.content_hash(), split.content_hash());
+
+        assert!(!plan.is_changed_from_source());
+        plan.set_source_hash(Some(&hash));
+        assert!(!plan.is_changed_from_source());
+        plan.insert_entry(1, Entry::new("Interlude")).unwrap();
+        assert!(plan.is_changed_from_source());
+    }
+
+    #[test]
+    fn entry_urls() {
+        let plan_text = "Article\n    A long read\n    https://example.com/a\nLinked\n    http://example.com/b\n    \
+                         more description\nPlain\n    httpd setup\n";
+        let plan = Plan::from_text("test", plan_text.as_bytes()).expect("could not parse plan");
+        let urls: Vec<_> = plan.entries().map(|e| e.url()).collect();
+        assert_eq!(urls, vec![Some("https://example.com/a"), None, None]);
+        let descriptions: Vec<_> = plan.entries().map(|e| e.description()).collect();
+        assert_eq!(descriptions,
+                   vec!["A long read", "http://example.com/b\nmore description", "httpd setup"]);
+
+        let mut buffer = Vec::new();
+        plan.to_text(&mut buffer).expect("could not write plan");
+        let reparsed = Plan::from_text("test", &buffer[..]).expect("could not reparse plan");
+        assert_eq!(reparsed, plan);
+    }
+
+    #[test]
+    fn description_paragraphs() {
+        let plan_text = "Poem\n    Roses are red,\n    violets are blue\n\n    Second stanza\n    \
+                         https://example.com/poem\n\nNext\n";
+        let plan = Plan::from_text("test", plan_text.as_bytes()).expect("could not parse plan");
+        assert_eq!(plan.len(), 2);
+        let poem = plan.entries().next().unwrap();
+        assert_eq!(poem.description(), "Roses are red,\nviolets are blue\n\nSecond stanza");
+        assert_eq!(poem.description_paragraphs().collect::<Vec<_>>(),
+                   vec!["Roses are red,\nviolets are blue", "Second stanza"]);
+        assert_eq!(poem.url(), Some("https://example.com/poem"));
+
+        let mut buffer = Vec::new();
+        plan.to_text(&mut buffer).expect("could not write plan");
+        let reparsed = Plan::from_text("test", &buffer[..]).expect("could not reparse plan");
+        assert_eq!(reparsed, plan);
+    }
+
+    #[test]
+    fn plan_builder() {
+        let plan = PlanBuilder::new("psalms")
+            .cyclic(true)
+            .entry("Introduction", "")
+            .section("Book I")
+            .entry("Psalm 1", "Blessed is the man")
+            .entry("Psalm 2", "")
+            .start_at(3)
+            .build()
+            .expect("could not build plan");
+        assert!(plan.is_cyclic());
+        assert_eq!(plan.current_entry().unwrap().title(), "Psalm 2");
+        assert_eq!(plan.entries().nth(1).unwrap().description(), "Blessed is the man");
+        assert_eq!(plan.sections(), &[Section::new("Book I", 1)][..]);
+
+        let text = "Introduction\n# Book I\nPsalm 1\n    Blessed is the man\nPsalm 2\n";
+        let parsed = Plan::from_text("psalms", text.as_bytes()).unwrap();
+        assert!(parsed.entries().eq(plan.entries()));
+        assert_eq!(parsed.sections(), plan.sections());
+
+        let mut builder = PlanBuilder::new("invalid");
+        assert!(builder.build().is_err(), "plan with no entries built");
+        builder.entry("One", "").section("Empty");
+        assert!(builder.build().is_err(), "plan with an empty section built");
+        builder.entry("Two", "").start_at(0);
+        assert!(builder.build().is_err(), "plan starting before its first entry built");
+        builder.start_at(3);
+        assert!(builder.build().unwrap().is_ended());
+        builder.cyclic(true);
+        assert!(builder.build().is_err(), "cyclic plan starting past its end built");
+    }
+
+    #[test]
+    fn entry_estimates() {
+        let plan_text = "[2017-05-01] Genesis 1-3 (25m)\nGenesis 4-7 (1h 5m)\nChapter (2)\nExodus (1h)\n";
+        let mut plan = Plan::from_text("test", plan_text.as_bytes()).expect("could not parse plan");
+        let titles: Vec<_> = plan.entries().map(|e| e.title()).collect();
+        assert_eq!(titles, vec!["Genesis 1-3", "Genesis 4-7", "Chapter (2)", "Exodus"]);
+        let estimates: Vec<_> = plan.entries().map(|e| e.estimate().map(|e| e.minutes())).collect();
+        assert_eq!(estimates, vec![Some(25), Some(65), None, Some(60)]);
+        assert_eq!(plan.entries().next().unwrap().schedule(),
+                   Some(Schedule::Date(NaiveDate::from_ymd_opt(2017, 5, 1).unwrap())));
+
+        assert_eq!(plan.total_remaining_time(), Some(Estimate::from_minutes(150)));
+        assert_eq!(plan.total_remaining_time().unwrap().to_string(), "2h 30m");
+        plan.next(2);
+        assert_eq!(plan.total_remaining_time(), Some(Estimate::from_minutes(60)));
+        plan.next(2);
+        assert_eq!(plan.total_remaining_time(), None);
+
+        assert_eq!("1h30m".parse::<Estimate>().unwrap(), Estimate::from_minutes(90));
+        assert!("25 minutes".parse::<Estimate>().is_err(), "invalid estimate accepted");
+        assert!("".parse::<Estimate>().is_err(), "empty estimate accepted");
+
+        let mut buffer = Vec::new();
+        plan.to_text(&mut buffer).expect("could not write plan");
+        let reparsed = Plan::from_text("test", &buffer[..]).expect("could not reparse plan");
+        assert!(reparsed.entries().eq(plan.entries()), "estimates lost in round trip");
+    }
+
+    #[test]
+    fn text_warnings() {
+        let plan_text = "[2017-05-01] One\n    https://example.com\n    more\n[May 1] Two\n#\nOne\n\t Three\n";
+        let warnings = plan::text_warnings(plan_text.as_bytes()).expect("could not check plan");
+        let lines: Vec<_> = warnings.iter().map(Warning::line).collect();
+        assert_eq!(lines, vec![2, 4, 5, 6, 7]);
+        assert!(warnings[1].message().contains("[May 1]"), "unexpected warning: {}", warnings[1]);
+        assert!(warnings[3].message().contains("line 1"), "unexpected warning: {}", warnings[3]);
+
+        let plan_text = "# Part I\n[2017-05-01] One\n    Description\n    https://example.com\nTwo\n";
+        assert_eq!(plan::text_warnings(plan_text.as_bytes()).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn bookmarks() {
+        let entries = (1..7).map(|i| Entry::new(&format!("Entry {}", i))).collect();
+        let mut plan = Plan::from_entries("plan", entries);
+        plan.set_bookmark(" Part II ", 3).unwrap();
+        plan.set_bookmark("Part I", 0).unwrap();
+        let names: Vec<_> = plan.bookmarks().iter().map(Bookmark::name).collect();
+        assert_eq!(names, vec!["Part I", "Part II"]);
+        assert_eq!(plan.next_bookmark().map(Bookmark::name), Some("Part I"));
+        plan.next(1);
+        assert_eq!(plan.next_bookmark().map(Bookmark::entry_index), Some(3));
+
+        // Bookmarking another entry with the same name moves the bookmark
+        plan.set_bookmark("Part II", 4).unwrap();
+        assert_eq!(plan.bookmarks().len(), 2);
+        assert_eq!(plan.bookmark("Part II").map(Bookmark::entry_index), Some(4));
+
+        plan.insert_entry(1, Entry::new("New")).unwrap();
+        assert_eq!(plan.bookmark("Part I").map(Bookmark::entry_index), Some(0));
+        assert_eq!(plan.bookmark("Part II").map(Bookmark::entry_index), Some(5));
+        plan.remove_entry(0).unwrap();
+        assert_eq!(plan.bookmark("Part II").map(Bookmark::entry_index), Some(4));
+        plan.remove_entry(plan.len() - 1).unwrap();
+        plan.check().expect("plan with bookmarks is invalid");
+
+        for name in &["", " ", "12"] {
+            assert!(plan.set_bookmark(name, 0).is_err(), "bookmark name {:?} was accepted", name);
+        }
+        match plan.set_bookmark("Late", plan.len()) {
+            Err(Error(ErrorKind::EntryOutOfRange(..), _)) => {}
+            r => panic!("unexpected result of bookmarking past the end: {:?}", r),
+        }
+        assert_eq!(plan.remove_bookmark("Part I").unwrap().entry_index(), 0);
+        match plan.remove_bookmark("Part I") {
+            Err(Error(ErrorKind::BookmarkDoesNotExist(_, ref name), _)) if name == "Part I" => {}
+            r => panic!("unexpected result of removing a missing bookmark: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn validate() {
+        let plan_text = "[2017-05-01] One\n    First\n[2017-05-02] Two\n    Second\n[2017-05-03] Three\n    Third\n\
+                         [2017-05-04] Four\n[2017-05-20] Five\n    Fifth\n[2017-05-19] Two\n    Again\n";
+        let plan = Plan::from_text("plan", plan_text.as_bytes()).unwrap();
+        let warnings: Vec<_> = plan.validate().iter().map(|w| (w.kind(), w.entry_index())).collect();
+        assert_eq!(warnings,
+                   vec![(WarningKind::MissingDescription, 3),
+                        (WarningKind::DateGap, 4),
+                        (WarningKind::DuplicateTitle, 5),
+                        (WarningKind::DateOverlap, 5)]);
+
+        let long_title = "Word ".repeat(30);
+        let plan = Plan::from_entries("plan", vec![Entry::new("One"), Entry::new(&long_title)]);
+        let warnings: Vec<_> = plan.validate().iter().map(PlanWarning::kind).collect();
+        assert_eq!(warnings, vec![WarningKind::LongTitle]);
+        assert!(Plan::from_entries("plan", vec![Entry::new("One")]).validate().is_empty());
+    }
+
+    #[test]
+    fn prerequisites() {
+        let titles = ["Intro", "Borrowing", "Lifetimes", "Ownership", "Traits"];
+        let mut plan = Plan::from_entries("rust", titles.iter().map(|t| Entry::new(t)).collect());
+        plan.add_prerequisite(1, 3).unwrap();
+        plan.add_prerequisite(2, 1).unwrap();
+        match plan.add_prerequisite(3, 2) {
+            Err(Error(ErrorKind::PrerequisiteCycle(..), _)) => (),
+            r => panic!("expected a prerequisite cycle, got {:?}", r),
+        }
+        assert_eq!(plan.prerequisites_of(2), vec![1]);
+        let warnings: Vec<_> = plan.validate().iter().map(|w| (w.kind(), w.entry_index())).collect();
+        assert_eq!(warnings, vec![(WarningKind::PrerequisiteOrder, 1)]);
+
+        // The prerequisites survive the plain text format
+        let mut text = Vec::new();
+        plan.to_text(&mut text).unwrap();
+        assert_eq!(Plan::from_text("rust", &text[..]).unwrap().entries().collect::<Vec<_>>(),
+                   plan.entries().collect::<Vec<_>>());
+
+        plan.next(1);
+        assert!(plan.reorder_respecting_dependencies().unwrap());
+        let titles: Vec<_> = plan.entries().map(Entry::title).collect();
+        assert_eq!(titles, vec!["Intro", "Ownership", "Borrowing", "Lifetimes", "Traits"]);
+        assert!(plan.validate().is_empty());
+        assert!(!plan.reorder_respecting_dependencies().unwrap());
+
+        assert!(plan.remove_prerequisite(2, 1));
+        assert!(!plan.remove_prerequisite(2, 1));
+        assert!(plan.entry(2).unwrap().prerequisites().is_empty());
+    }
+
+    #[test]
+    fn add_all() {
+        let mut store = MemoryStore::new();
+        store.add(&Plan::from_entries("taken", vec![Entry::new("entry")])).unwrap();
+        let sources = ["first\nOne\n", "taken\nTwo\n", "broken\n", "second\nThree\nFour\n"];
+        let results = store.add_all(sources.iter(), |text| {
+            let (name, entries) = text.split_once('\n').unwrap();
+            Plan::from_text(name, entries.as_bytes())
+        });
+
+        assert_eq!(results.len(), sources.len());
+        match results[1].1 {
+            Err(Error(ErrorKind::PlanAlreadyExists(ref name), _)) => assert_eq!(name, "taken"),
+            ref r => panic!("expected the plan to exist already, got {:?}", r),
+        }
+        assert!(results[2].1.is_err(), "plan without entries added");
+        let added: Vec<_> = results.iter().filter_map(|(_, r)| r.as_ref().ok()).map(Plan::name).collect();
+        assert_eq!(added, ["first", "second"]);
+        assert_eq!(store.read("second").unwrap().len(), 2);
+        assert_eq!(store.read("taken").unwrap().entry(0).unwrap().title(), "entry");
+    }
+
+    #[test]
+    fn observed_store() {
+        let mut store = ObservedStore::new(MemoryStore::new());
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let seen = Rc::clone(&events);
+        store.subscribe(move |event| seen.borrow_mut().push(event.clone()));
+
+        let plan = Plan::from_entries("test", vec![Entry::new("One"), Entry::new("Two")]);
+        store.add(&plan).unwrap();
+        store.update("test", |p| {
+            p.next(2);
+            Ok(())
+        }).unwrap();
+        store.update("test", |p| p.add_tag("done")).unwrap();
+        // Neither a failed change nor one which changes nothing is reported
+        assert!(store.update("test", |_| -> ::Result<()> { bail!("failed") }).is_err());
+        store.write(&store.read("test").unwrap()).unwrap();
+        store.remove("test").unwrap();
+
+        let added = StoreEvent::PlanAdded("test".into());
+        let advanced = StoreEvent::PlanAdvanced {
+            plan: "test".into(),
+            from: Some(1),
+            to: None,
+        };
+        let changed = StoreEvent::PlanChanged("test".into());
+        assert_eq!(*events.borrow(), [added, advanced, changed, StoreEvent::PlanRemoved("test".into())]);
+        assert!(store.into_inner().list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn split_plan() {
+        let entries = (1..7).map(|i| Entry::new(&format!("Entry {}", i))).collect();
+        let mut plan = Plan::from_entries("backlog", entries);
+        plan.set_sections(vec![Section::new("Part I", 0), Section::new("Part II", 3)]).unwrap();
+        plan.set_bookmark("late", 4).unwrap();
+        plan.add_tag("articles").unwrap();
+        plan.set_cyclic(true);
+        plan.next(4);
+        assert!(plan.clone().split_at(0).is_err(), "split left a plan without entries");
+        assert!(plan.clone().split_at(6).is_err(), "split out of range");
+
+        let (first, rest) = plan.split_at(2).unwrap();
+        assert_eq!(first.len(), 2);
+        assert!(first.is_ended() && !first.is_cyclic());
+        assert!(first.completed_at().is_some());
+        assert_eq!(first.history().len(), 2);
+        assert_eq!(first.sections(), &[Section::new("Part I", 0)]);
+        assert!(first.bookmarks().is_empty());
+
+        assert_eq!(rest.entry(0).unwrap().title(), "Entry 3");
+        assert_eq!(rest.current_entry_number(), 3);
+        assert!(rest.is_cyclic());
+        assert_eq!(rest.history().iter().map(|e| e.entry_index()).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(rest.sections(), &[Section::new("Part I", 0), Section::new("Part II", 1)]);
+        let bookmarks: Vec<_> = rest.bookmarks().iter().map(|b| (b.name(), b.entry_index())).collect();
+        assert_eq!(bookmarks, vec![("late", 2)]);
+        assert_eq!(rest.tags(), first.tags());
+    }
+
+    #[test]
+    fn copy_plan() {
+        let mut store = MemoryStore::new();
+        let entries = (1..5).map(|i| Entry::new(&format!("Entry {}", i))).collect();
+        let mut plan = Plan::from_entries("original", entries);
+        plan.add_tag("book").unwrap();
+        plan.next(2);
+        plan.skip_current();
+        plan.add_note(0, "Good start").unwrap();
+        plan.set_archived(true);
+        store.add(&plan).unwrap();
+
+        let copy = store.copy("original", "again").expect("could not copy plan");
+        assert_eq!(store.read("again").unwrap(), copy);
+        assert_eq!(copy.name(), "again");
+        assert_eq!(copy.current_entry_number(), 1);
+        assert!(copy.history().is_empty());
+        assert!(copy.entries().all(|e| e.notes().is_empty()), "notes were copied");
+        assert!(!copy.is_archived());
+        assert_eq!(copy.tags(), plan.tags());
+        assert_eq!(copy.len(), plan.len());
+        assert_eq!(store.read("original").unwrap(), plan, "original plan was changed");
+
+        match store.copy("original", "again") {
+            Err(Error(ErrorKind::PlanAlreadyExists(ref name), _)) if name == "again" => {}
+            r => panic!("unexpected result of copying over a plan: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn plan_summaries() {
+        let dir = env::temp_dir().join(format!("reading-test-summaries-{}", process::id()));
+        let mut store = FsStore::new(&dir);
+        let plan_text = "One\n# Part II\nTwo\nThree\n";
+        let mut plan = Plan::from_text("current", plan_text.as_bytes()).unwrap();
+        plan.add_tag("book").unwrap();
+        plan.set_property("unit", "day").unwrap();
+        plan.next(1);
+        store.add(&plan).unwrap();
+        // The stored plan is the first revision
+        plan.set_revision(1);
+        // Plans in older versions of the format are read in full
+        fs::write(dir.join("old.plan.json"),
+                  r#"{"name":"old","cyclic":true,"current_entry":1,
+                      "entries":[{"title":"a","description":""},{"title":"b","description":""}]}"#)
+            .unwrap();
+
+        let summary = store.summary("current").expect("could not summarize plan");
+        plan.mark_modified(summary.modified_at().expect("modification time not recorded"));
+        assert_eq!(summary, PlanSummary::from(&plan));
+        assert_eq!(summary.len(), 3);
+        assert_eq!(summary.current_entry_number(), 2);
+        assert!(summary.has_tag("book"));
+        assert_eq!(summary.unit_label(), "day");
+        assert_eq!(summary.current_section().map(|s| s.title()), Some("Part II"));
+        assert_eq!(summary.history().len(), 1);
+
+        let sorted = |order, reverse| {
+            files::with_plans_dir(&dir, || files::plan_summaries(order, reverse))
+                .expect("could not summarize plans")
+                .into_iter()
+                .map(|s| s.expect("could not summarize plan"))
+                .collect::<Vec<_>>()
+        };
+        let names = |order, reverse| sorted(order, reverse).iter().map(|s| s.name().to_owned()).collect::<Vec<_>>();
+        // The old plan has no modification time of its own, but its file was
+        // written last
+        assert_eq!(names(SortOrder::Recent, false), ["old", "current"]);
+        assert_eq!(names(SortOrder::Progress, false), ["old", "current"]);
+        assert_eq!(names(SortOrder::Length, false), ["current", "old"]);
+        assert_eq!(names(SortOrder::Length, true), ["old", "current"]);
+        assert_eq!(summary.percent_complete().round(), 33.0);
+        let summaries = sorted(SortOrder::Name, false);
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0], summary);
+        assert_eq!((summaries[1].name(), summaries[1].len(), summaries[1].is_cyclic()), ("old", 2, true));
+        assert_eq!(summaries[1].unit_label(), "entry");
+        // Summarizing the plans in full gives the same result
+        let mut memory = MemoryStore::new();
+        for name in &["current", "old"] {
+            memory.write(&store.read(name).unwrap()).unwrap();
+        }
+        let full: Vec<_> = memory.summaries().unwrap().into_iter().map(Result::unwrap).collect();
+        assert_eq!(summaries, full);
+        assert!(store.summary("missing").is_err(), "missing plan summarized");
+
+        fs::remove_dir_all(&dir).expect("could not remove test directory");
+    }
+
+    #[test]
+    fn trash() {
+        let dir = env::temp_dir().join(format!("reading-test-trash-{}", process::id()));
+        let mut store = FsStore::new(&dir);
+        let mut plan = Plan::from_entries("book", vec![Entry::new("One"), Entry::new("Two")]);
+        store.add(&plan).unwrap();
+        plan.next(1);
+        store.write(&plan).unwrap();
+
+        store.remove("book").expect("could not remove plan");
+        assert!(!store.exists("book").unwrap());
+        assert_eq!(store.list().unwrap(), Vec::<String>::new());
+        assert_eq!(store.trash().unwrap(), vec!["book".to_owned()]);
+
+        // A plan added under the same name stops the old one being restored
+        store.add(&Plan::from_entries("book", vec![Entry::new("Other")])).unwrap();
+        match store.restore("book") {
+            Err(Error(ErrorKind::PlanAlreadyExists(_), _)) => {}
+            r => panic!("unexpected result of restoring over a plan: {:?}", r),
+        }
+        store.remove("book").unwrap();
+
+        // Both are in the trash, but the most recent one is restored first
+        let restored = store.restore("book").expect("could not restore plan");
+        assert_eq!(restored.entries().next().unwrap().title(), "Other");
+        assert!(store.exists("book").unwrap());
+        fs::remove_file(dir.join("book.plan.json")).unwrap();
+        let restored = store.restore("book").expect("could not restore plan");
+        assert!(restored.entries().eq(plan.entries()), "wrong plan restored");
+        assert_eq!(store.trash().unwrap(), Vec::<String>::new());
+        match store.restore("missing") {
+            Err(Error(ErrorKind::NotInTrash(_), _)) => {}
+            r => panic!("unexpected result of restoring a missing plan: {:?}", r),
+        }
+
+        fs::remove_dir_all(&dir).expect("could not remove test directory");
+    }
+
+    #[test]
+    fn rename() {
+        let dir = env::temp_dir().join(format!("reading-test-rename-{}", process::id()));
+        let mut store = FsStore::new(&dir);
+        let mut plan = Plan::from_entries("old", vec![Entry::new("One"), Entry::new("Two")]);
+        store.add(&plan).unwrap();
+        plan.next(1);
+        store.write(&plan).unwrap();
+        store.add(&Plan::from_entries("taken", vec![Entry::new("Other")])).unwrap();
+        match store.rename("old", "taken") {
+            Err(Error(ErrorKind::PlanAlreadyExists(_), _)) => {}
+            r => panic!("unexpected result of renaming over a plan: {:?}", r),
+        }
+        assert!(store.rename("old", "a/b").is_err(), "invalid name accepted");
+
+        let renamed = store.rename("old", "new").expect("could not rename plan");
+        assert_eq!(renamed.name(), "new");
+        assert_eq!(store.list().unwrap(), ["new", "taken"]);
+        assert_eq!(store.read("new").unwrap().current_entry_number(), 2);
+        assert!(store.trash().unwrap().is_empty(), "renamed plan put in the trash");
+        assert!(!store.journal_path("old").unwrap().exists(), "journal left under the old name");
+        assert!(store.backups("old").unwrap().is_empty(), "backups left under the old name");
+        assert_eq!(store.backups("new").unwrap().len(), 1);
+
+        // The changes made before the plan was renamed can still be undone
+        let undone = store.undo("new").expect("could not undo change");
+        assert_eq!(undone.name(), "new");
+        assert_eq!(undone.current_entry_number(), 1);
+        assert!(!store.exists("old").unwrap());
+
+        fs::remove_dir_all(&dir).expect("could not remove test directory");
+    }
+
+    #[test]
+    fn backups() {
+        let dir = env::temp_dir().join(format!("reading-test-backups-{}", process::id()));
+        let mut store = FsStore::new(&dir);
+        store.set_backup_count(2);
+        let mut plan = Plan::from_entries("book", vec![Entry::new("entry"); 4]);
+        store.add(&plan).unwrap();
+        assert!(store.backups("book").unwrap().is_empty());
+        for _ in 0..3 {
+            plan.next(1);
+            store.write(&plan).unwrap();
+        }
+
+        // Only the last two versions before the current one are kept
+        let backups = store.backups("book").unwrap();
+        assert_eq!(backups.len(), 2);
+        let restored = store.restore_backup("book", backups[0]).expect("could not restore backup");
+        assert_eq!(restored.current_entry_number(), 2);
+        assert_eq!(store.read("book").unwrap().current_entry_number(), 2);
+
+        // Restoring is a change like any other, and removed plans can be
+        // restored too
+        let backups = store.backups("book").unwrap();
+        assert_eq!(store.restore_backup("book", backups[1]).unwrap().current_entry_number(), 4);
+        store.remove("book").unwrap();
+        let backups = store.backups("book").unwrap();
+        assert_eq!(store.restore_backup("book", backups[1]).unwrap().current_entry_number(), 4);
+        match store.restore_backup("missing", backups[0]) {
+            Err(Error(ErrorKind::NoBackup(_), _)) => {}
+            r => panic!("unexpected result of restoring a missing backup: {:?}", r),
+        }
+
+        // No backups are made with a count of 0
+        store.set_backup_count(0);
+        let backups = store.backups("book").unwrap();
+        let plan = store.read("book").unwrap();
+        store.write(&plan).unwrap();
+        assert_eq!(store.backups("book").unwrap(), backups);
+
+        fs::remove_dir_all(&dir).expect("could not remove test directory");
+    }
+
+    #[test]
+    fn diagnose() {
+        let dir = env::temp_dir().join(format!("reading-test-diagnose-{}", process::id()));
+        let mut store = FsStore::new(&dir);
+        let mut plan = Plan::from_entries("backed-up", vec![Entry::new("entry"); 3]);
+        store.add(&plan).unwrap();
+        plan.next(1);
+        store.write(&plan).unwrap();
+        store.add(&Plan::from_entries("fine", vec![Entry::new("entry")])).unwrap();
+        store.add(&Plan::from_entries("lost", vec![Entry::new("entry")])).unwrap();
+        fs::write(dir.join("backed-up.plan.json"), "{ broken").unwrap();
+        fs::write(dir.join("lost.plan.json"), "[]").unwrap();
+
+        let diagnoses = store.diagnose().expect("could not diagnose plans");
+        let results: Vec<_> = diagnoses.iter().map(|d| (d.name.as_str(), d.is_ok(), d.backup.is_some())).collect();
+        assert_eq!(results, [("backed-up", false, true), ("fine", true, false), ("lost", false, false)]);
+        assert_eq!(diagnoses[0].path, dir.join("backed-up.plan.json"));
+
+        // The latest readable backup is from before the plan was advanced
+        let repaired = store.repair("backed-up").expect("could not repair plan");
+        assert_eq!(repaired.current_entry_number(), 1);
+        assert_eq!(store.read("backed-up").unwrap(), repaired);
+        match store.repair("lost") {
+            Err(Error(ErrorKind::NoBackup(_), _)) => {}
+            r => panic!("unexpected result of repairing a plan without a backup: {:?}", r),
+        }
+        let quarantined = store.quarantine("lost").expect("could not quarantine plan");
+        assert_eq!(fs::read_to_string(quarantined).unwrap(), "[]");
+        assert!(store.diagnose().unwrap().iter().all(|d| d.is_ok()), "broken plans left behind");
+
+        fs::remove_dir_all(&dir).expect("could not remove test directory");
+    }
+
+    #[test]
+    fn reorder() {
+        let titles = |plan: &Plan| plan.entries().map(|e| e.title().to_owned()).collect::<Vec<_>>();
+        let plan_text = "a\nb\n# Later\nc\nd\ne\n";
+        let mut plan = Plan::from_text("test", plan_text.as_bytes()).unwrap();
+        plan.set_cyclic(true);
+        plan.next(5);
+        plan.next(1);
+        assert_eq!(plan.current_entry_number(), 2);
+
+        plan.reverse();
+        assert_eq!(titles(&plan), ["a", "e", "d", "c", "b"]);
+        assert_eq!(plan.current_entry_number(), 2);
+        assert!(plan.sections().is_empty(), "section kept after reordering");
+        // The history follows the entries to their new places
+        let completed: Vec<_> = plan.history().iter().map(|e| titles(&plan)[e.entry_index()].clone()).collect();
+        assert_eq!(completed, ["a", "b", "c", "d", "e", "a"]);
+
+        let mut shuffled = plan.clone();
+        shuffled.shuffle(42);
+        assert_eq!(titles(&shuffled)[0], "a");
+        let mut sorted = titles(&shuffled);
+        sorted.sort();
+        assert_eq!(sorted, ["a", "b", "c", "d", "e"]);
+        let mut again = plan.clone();
+        again.shuffle(42);
+        assert_eq!(again, shuffled, "same seed gave a different order");
+
+        // A plan which has ended has nothing left to reorder
+        let mut ended = Plan::from_entries("ended", vec![Entry::new("a"), Entry::new("b")]);
+        ended.next(2);
+        ended.shuffle(1);
+        ended.reverse();
+        assert!(ended.is_ended());
+        assert_eq!(titles(&ended), ["a", "b"]);
+    }
+
+    #[test]
+    fn reorder_stale_history() {
+        // A plan file may have history for entries which no longer exist
+        let mut plan = Plan::from_entries("test", vec![Entry::new("entry"); 6]);
+        plan.next(5);
+        let mut value = serde_json::to_value(&plan).expect("could not serialize plan");
+        value["entries"].as_array_mut().unwrap().truncate(2);
+        value["current_entry"] = serde_json::Value::from(0);
+        match migrations::plan_from_value(value.clone()) {
+            Err(Error(ErrorKind::InvalidPlan(..), _)) => (),
+            r => panic!("plan with history past its end accepted: {:?}", r),
+        }
+
+        let mut stale: Plan = serde_json::from_value(value).expect("could not parse plan");
+        stale.reverse();
+        let history: Vec<_> = stale.history().iter().map(|e| e.entry_index()).collect();
+        assert_eq!(history, [1, 0, 2, 3, 4]);
+    }
+
+    #[test]
+    fn random_plan() {
+        let entries = (1..7).map(|i| Entry::new(&format!("Entry {}", i))).collect();
+        let mut plan = Plan::from_entries("test", entries);
+        plan.set_property("random", "true").expect("could not set random");
+        assert!(plan.is_random(), "plan is not random");
+        let time = |n| Utc.with_ymd_and_hms(2017, 5, 1, 12, 0, n).unwrap();
+
+        let mut partway = plan.clone();
+        partway.next_at(2, time(0));
+        plan.next_at(2, time(0));
+        for n in 2..6 {
+            plan.next_at(1, time(n));
+        }
+        // Every entry is read exactly once, in some order
+        let mut read: Vec<_> = plan.history()
+            .iter()
+            .map(|e| plan.entries().nth(e.entry_index()).unwrap().title())
+            .collect();
+        assert_ne!(read, ["Entry 1", "Entry 2", "Entry 3", "Entry 4", "Entry 5", "Entry 6"],
+                   "entries read in order");
+        read.sort();
+        assert_eq!(read, ["Entry 1", "Entry 2", "Entry 3", "Entry 4", "Entry 5", "Entry 6"]);
+        assert!(plan.is_ended(), "plan not ended after reading every entry");
+        assert_eq!(plan.random_entry(1), None);
+        // Only the entries which haven't been read can be picked
+        assert_eq!(partway.current_entry_number(), 3);
+        for seed in 0..20 {
+            assert!(partway.random_entry(seed).unwrap() >= 2, "read entry picked");
+        }
+    }
+
+    #[test]
+    fn skip() {
+        let entries = (1..5).map(|i| Entry::new(&format!("Entry {}", i))).collect();
+        let mut plan = Plan::from_entries("skip", entries);
+
+        plan.next(1);
+        assert!(plan.skip_current());
+        plan.next(1);
+        assert!(plan.skip_current());
+        assert!(plan.is_ended());
+        assert!(!plan.skip_current(), "skipped past the end");
+        assert_eq!(plan.history().len(), 2, "skipped entries recorded in history");
+        assert_eq!(plan.skipped_count(), 2);
+        let statuses: Vec<_> = (0..4).map(|i| plan.entry_status(i).unwrap()).collect();
+        assert_eq!(statuses,
+                   vec![EntryStatus::Completed, EntryStatus::Skipped, EntryStatus::Completed, EntryStatus::Skipped]);
+        assert_eq!(plan.entry_status(4), None);
+
+        // Going back makes the skipped entries pending again, and reading
+        // them normally clears the skip
+        plan.previous(3);
+        assert_eq!(plan.entry_status(1), Some(EntryStatus::Pending));
+        assert_eq!(plan.skipped_count(), 0);
+        plan.next(2);
+        assert_eq!(plan.entry_status(1), Some(EntryStatus::Completed));
+        assert_eq!(plan.skipped_count(), 0);
+
+        let stats = PlanStats::for_plan(&plan, &Utc::now());
+        assert_eq!(stats.skipped(), 0);
+        plan.skip_current();
+        assert_eq!(PlanStats::for_plan(&plan, &Utc::now()).skipped(), 1);
+        assert_eq!(Summary::for_plans(&[plan], &Utc::now()).skipped(), 1);
+    }
+
+    #[test]
+    fn completion() {
+        let mut plan = Plan::from_entries("test", vec![Entry::new("entry"); 3]);
+        let time = Utc.with_ymd_and_hms(2017, 5, 1, 12, 0, 0).unwrap();
+        plan.next_at(2, time);
+        assert_eq!(plan.completed_at(), None);
+        plan.next_at(1, time + Duration::days(2));
+        assert_eq!(plan.completed_at(), Some(time + Duration::days(2)));
+        // Going past the end doesn't finish the plan again
+        plan.next_at(1, time + Duration::days(3));
+        assert_eq!(plan.completed_at(), Some(time + Duration::days(2)));
+
+        // Moving back from the end undoes the completion, and skipping the
+        // last entry doesn't count
+        plan.previous(1);
+        assert_eq!(plan.completed_at(), None);
+        plan.skip_current();
+        assert!(plan.is_ended());
+        assert_eq!(plan.completed_at(), None);
+
+        let mut cyclic = Plan::from_entries("test", vec![Entry::new("entry"); 2]);
+        cyclic.set_cyclic(true);
+        cyclic.next_at(2, time);
+        assert_eq!(cyclic.completed_at(), None);
+    }
+
+    #[test]
+    fn transaction() {
+        let dir = env::temp_dir().join(format!("reading-test-transaction-{}", process::id()));
+        let mut store = FsStore::new(&dir);
+        store.add(&Plan::from_entries("a", vec![Entry::new("entry"); 3])).unwrap();
+        store.add(&Plan::from_entries("b", vec![Entry::new("entry"); 3])).unwrap();
+
+        let moved = store.transaction(|tx| {
+                tx.update("a", |p| {
+                        p.next(1);
+                        Ok(())
+                    })?;
+                tx.remove("b")?;
+                tx.add(&Plan::from_entries("c", vec![Entry::new("entry")]))?;
+                // The transaction sees its own changes before they are made
+                assert_eq!(tx.list()?, ["a", "c"]);
+                assert_eq!(tx.read("a")?.current_entry_number(), 2);
+                Ok(tx.read("a")?.current_entry_number())
+            })
+            .expect("could not commit transaction");
+        assert_eq!(moved, 2);
+        assert_eq!(store.list().unwrap(), ["a", "c"]);
+        assert_eq!(store.read("a").unwrap().current_entry_number(), 2);
+        assert_eq!(store.trash().unwrap(), ["b"]);
+
+        // A failed transaction changes nothing
+        let result: Result<(), Error> = store.transaction(|tx| {
+            tx.update("a", |p| {
+                    p.next(1);
+                    Ok(())
+                })?;
+            tx.remove("c")?;
+            tx.update("missing", |_| Ok(()))
+        });
+        assert!(result.is_err(), "transaction with a missing plan succeeded");
+        assert_eq!(store.list().unwrap(), ["a", "c"]);
+        assert_eq!(store.read("a").unwrap().current_entry_number(), 2);
+        // Nothing is left behind by either transaction (besides the trash and
+        // the backups)
+        assert_eq!(fs::read_dir(&dir).unwrap().filter(|e| e.as_ref().unwrap().path().is_dir()).count(),
+                   2,
+                   "transaction directory left behind");
+
+        // Committed changes can be undone as usual
+        let undone = files::with_plans_dir(&dir, || files::undo_last("a")).expect("could not undo change");
+        assert_eq!(undone.current_entry_number(), 1);
+        fs::remove_dir_all(&dir).expect("could not remove test directory");
+    }
+
+    #[test]
+    fn dry_run() {
+        let dir = env::temp_dir().join(format!("reading-test-dry-run-{}", process::id()));
+        let mut store = FsStore::new(&dir);
+        store.add(&Plan::from_entries("a", vec![Entry::new("entry"); 3])).unwrap();
+        store.add(&Plan::from_entries("b", vec![Entry::new("entry"); 3])).unwrap();
+        let contents = || {
+            let mut files: Vec<_> = fs::read_dir(&dir)
+                .unwrap()
+                .map(|e| {
+                    let path = e.unwrap().path();
+                    let contents = if path.is_file() { fs::read(&path).unwrap() } else { Vec::new() };
+                    (path, contents)
+                })
+                .collect();
+            files.sort();
+            files
+        };
+        let before = contents();
+
+        store.set_dry_run(true);
+        store.update("a", |p| {
+                p.next(1);
+                Ok(())
+            })
+            .unwrap();
+        store.add(&Plan::from_entries("c", vec![Entry::new("entry")])).unwrap();
+        store.remove("b").unwrap();
+        store.transaction(|tx| {
+                tx.update("a", |p| {
+                    p.next(2);
+                    Ok(())
+                })
+            })
+            .unwrap();
+        assert!(store.undo("a").is_err(), "undo allowed in a dry run");
+
+        assert_eq!(contents(), before);
+        assert_eq!(store.read("a").unwrap().current_entry_number(), 1);
+        assert_eq!(store.held_back(),
+                   [FileChange::Write(store.plan_path("a").unwrap()),
+                    FileChange::Write(store.plan_path("c").unwrap()),
+                    FileChange::Trash(store.plan_path("b").unwrap()),
+                    FileChange::Write(store.plan_path("a").unwrap())]);
+
+        store.set_dry_run(false);
+        store.remove("b").unwrap();
+        assert_eq!(store.list().unwrap(), ["a"]);
+        fs::remove_dir_all(&dir).expect("could not remove test directory");
+    }
+
+    #[test]
+    fn concurrent_modification() {
+        let dir = env::temp_dir().join(format!("reading-test-revisions-{}", process::id()));
+        let mut store = FsStore::new(&dir);
+        store.add(&Plan::from_entries("a", vec![Entry::new("entry"); 3])).unwrap();
+        assert_eq!(store.read("a").unwrap().revision(), 1);
+
+        // Two copies of the plan are read, but only the first one written
+        // back is kept
+        let mut first = store.read("a").unwrap();
+        let mut second = first.clone();
+        first.next(1);
+        store.overwrite(&first).expect("could not overwrite unchanged plan");
+        second.next(2);
+        match store.overwrite(&second) {
+            Err(Error(ErrorKind::ConcurrentModification(ref name), _)) if name == "a" => {}
+            r => panic!("unexpected result of overwriting a changed plan: {:?}", r),
+        }
+        let stored = store.read("a").unwrap();
+        assert_eq!((stored.current_entry_number(), stored.revision()), (2, 2));
+        // A new plan can only be overwritten if it still doesn't exist
+        assert!(store.overwrite(&Plan::from_entries("a", vec![Entry::new("entry")])).is_err(),
+                "existing plan overwritten by new plan");
+        store.overwrite(&Plan::from_entries("b", vec![Entry::new("entry")])).expect("could not add plan");
+
+        // Plain writes and updates always keep the revision going up, even
+        // for plans which didn't come from the store
+        store.write(&Plan::from_entries("a", vec![Entry::new("entry")])).unwrap();
+        assert_eq!(store.read("a").unwrap().revision(), 3);
+        // Someone ignoring the lock changes the plan during an update
+        let result = store.update("a", |_| {
+            let mut plan = Plan::from_entries("a", vec![Entry::new("other")]);
+            plan.set_revision(10);
+            fs::write(dir.join("a.plan.json"), serde_json::to_vec(&plan)?)?;
+            Ok(())
+        });
+        match result {
+            Err(Error(ErrorKind::ConcurrentModification(_), _)) => {}
+            r => panic!("unexpected result of updating a changed plan: {:?}", r),
+        }
+        assert_eq!(store.read("a").unwrap().entries().next(), Some(&Entry::new("other")));
+        fs::remove_dir_all(&dir).expect("could not remove test directory");
+    }
 }
@@ -0,0 +1,89 @@
+//! This module upgrades plans stored in older versions of the JSON format
+//! to the current version, so that plan files written by older versions of
+//! the program keep working as the format changes.
+//!
+//! Every serialized plan has a `version` field; plans written before the
+//! format was versioned have none, and are treated as version 0. Each
+//! migration takes a plan from one version of the format to the next, and
+//! works on the raw JSON rather than on `Plan`, so that it doesn't depend on
+//! the current definition of `Plan`. Changes to the format should be made
+//! by adding a migration to the end of `MIGRATIONS`.
+
+use serde_json::{self, Map, Value};
+
+use super::Plan;
+use super::errors::*;
+
+/// A function which upgrades a plan (as a JSON object) by one version.
+type Migration = fn(&mut Map<String, Value>);
+
+/// All the migrations, in order: the migration at index `i` upgrades a plan
+/// from version `i` to version `i + 1`.
+const MIGRATIONS: &[Migration] = &[to_v1];
+
+/// The current version of the plan format.
+pub const CURRENT_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Returns the version of the format the given plan is in (0 if the plan
+/// has no version).
+pub fn version(plan: &Value) -> u32 {
+    plan.get("version")
+        .and_then(Value::as_u64)
+        .map_or(0, |v| v.min(u32::MAX as u64) as u32)
+}
+
+/// Upgrades the given plan to the current version of the format, returning
+/// whether any changes had to be made.
+///
+/// Plans from a newer version of the format than the current one can't be
+/// downgraded, and result in an `UnsupportedVersion` error.
+pub fn migrate(plan: &mut Value) -> Result<bool> {
+    let version = version(plan);
+    if version > CURRENT_VERSION {
+        bail!(ErrorKind::UnsupportedVersion(version, CURRENT_VERSION));
+    }
+    let object = match *plan {
+        Value::Object(ref mut object) => object,
+        _ => bail!("plan is not a JSON object"),
+    };
+
+    if version < CURRENT_VERSION {
+        debug!("upgrading plan {} from version {} of the format to version {}",
+               object.get("name").unwrap_or(&Value::Null),
+               version,
+               CURRENT_VERSION);
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(object);
+    }
+    object.insert("version".into(), Value::from(CURRENT_VERSION));
+
+    Ok(version < CURRENT_VERSION)
+}
+
+/// Converts the given JSON to a plan, upgrading it to the current version
+/// of the format first and checking that the result is consistent (see
+/// `Plan::check`).
+pub fn plan_from_value(mut plan: Value) -> Result<Plan> {
+    migrate(&mut plan)?;
+    let plan: Plan = serde_json::from_value(plan).chain_err(|| "invalid plan data")?;
+    plan.check()?;
+
+    Ok(plan)
+}
+
+/// Version 1 added the `version` field itself. The fields which were added
+/// to plans before then were given defaults when missing; these are now
+/// filled in explicitly.
+fn to_v1(plan: &mut Map<String, Value>) {
+    let defaults = [("tags", Value::Array(Vec::new())),
+                    ("unit_label", Value::from("entry")),
+                    ("priority", Value::from(0)),
+                    ("history", Value::Array(Vec::new())),
+                    ("cycles_completed", Value::from(0)),
+                    ("archived", Value::from(false))];
+
+    for (key, default) in defaults.iter() {
+        plan.entry(*key).or_insert_with(|| default.clone());
+    }
+}
@@ -0,0 +1,946 @@
+//! The output abstraction shared by all the subcommands, which allows their
+//! results to be printed either as styled text (the default) or as JSON
+//! (with the global `--json` flag), for use by scripts. With the global
+//! `--quiet` flag, nothing is printed but errors, which go to standard error
+//! (see the `status` module for the exit statuses scripts can check).
+//!
+//! In JSON mode, each subcommand prints exactly one JSON object to standard
+//! output, on a single line; errors are printed as an object with an
+//! `error` field (along with a list of `causes`). The helper functions in
+//! this module give the representations of plans and entries which are
+//! shared by the different subcommands, so that they are consistent.
+//!
+//! The main results of the subcommands which show or change plans (such as
+//! the plans listed by `reading list`, or the entries added to a plan by
+//! `reading insert`) are reported as `Event`s to a `Printer`, rather than
+//! printed directly, so that the same events can be shown in different ways
+//! (e.g. as a table in text mode, or as a list of objects in JSON mode).
+//! Only the subcommands which print reports about plans without changing
+//! them (such as `reading stats`), or which look after the plans directory
+//! and settings rather than particular plans (such as `reading config` or
+//! `reading sync`), print their results directly with `Output::print`.
+
+use std::env;
+use std::mem;
+
+use chrono::Local;
+use serde::Serialize;
+use serde_json::{self, Value};
+#[cfg(unix)]
+use libc;
+
+use reading::{Entry, HistoryEvent, Plan};
+use reading::plan::diff::EntryChange;
+use reading::plan::pace::Projection;
+use reading::plan::summary::PlanSummary;
+use reading::plan::track::Track;
+use reading::plan::validate::PlanWarning;
+use reading::errors::*;
+
+use theme::StyleSet;
+use entries;
+use ordinal;
+
+/// Where the results of a subcommand should be printed, and how.
+#[derive(Debug, Clone)]
+pub enum Output {
+    /// Styled text, intended to be read by people.
+    Text(StyleSet),
+    /// JSON, intended to be read by other programs.
+    Json,
+    /// Nothing but errors.
+    Quiet,
+}
+
+impl Output {
+    /// Returns whether JSON output is being used.
+    pub fn is_json(&self) -> bool {
+        match *self {
+            Output::Json => true,
+            Output::Text(_) | Output::Quiet => false,
+        }
+    }
+
+    /// Prints the result of a subcommand: in text mode, the given function
+    /// is called to print it, and in JSON mode, the given value is printed.
+    pub fn print<T, F>(&self, value: &T, text: F)
+        where T: Serialize,
+              F: FnOnce(&StyleSet)
+    {
+        match *self {
+            Output::Text(ref style_set) => text(style_set),
+            Output::Json => print_json(value),
+            Output::Quiet => {}
+        }
+    }
+
+    /// Prints something only in text mode (e.g. progress that will be
+    /// included in the final result in JSON mode).
+    pub fn text<F: FnOnce(&StyleSet)>(&self, text: F) {
+        if let Output::Text(ref style_set) = *self {
+            text(style_set);
+        }
+    }
+
+    /// Returns a printer for the events of a subcommand, which (in text
+    /// mode) truncates text that doesn't fit on a line of the terminal.
+    pub fn printer(&self) -> Box<dyn Printer> {
+        self.new_printer(false)
+    }
+
+    /// Returns a printer for the events of a subcommand which never
+    /// truncates text, however wide it is.
+    pub fn wide_printer(&self) -> Box<dyn Printer> {
+        self.new_printer(true)
+    }
+
+    fn new_printer(&self, wide: bool) -> Box<dyn Printer> {
+        match *self {
+            Output::Text(ref style_set) => {
+                Box::new(TextPrinter {
+                    style_set: style_set.clone(),
+                    wide,
+                    events: Vec::new(),
+                })
+            }
+            Output::Json => Box::new(JsonPrinter::default()),
+            Output::Quiet => Box::new(QuietPrinter),
+        }
+    }
+
+    /// Prints an error (and its causes).
+    pub fn error(&self, e: &Error) {
+        match *self {
+            Output::Text(ref style_set) => {
+                styleln!(style_set.error, "Error: {}", e);
+
+                for e in e.iter().skip(1) {
+                    styleln!(style_set.error, "Caused by: {}", e);
+                }
+
+                if let Some(backtrace) = e.backtrace() {
+                    styleln!(style_set.error, "Backtrace: {:?}", backtrace);
+                }
+            }
+            Output::Json => {
+                let causes: Vec<_> = e.iter().skip(1).map(|e| e.to_string()).collect();
+                print_json(&json!({ "error": e.to_string(), "causes": causes }));
+            }
+            Output::Quiet => {
+                eprintln!("Error: {}", e);
+                for e in e.iter().skip(1) {
+                    eprintln!("Caused by: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Something which happened in a subcommand, to be shown by a `Printer`.
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// A plan was listed (e.g. by `reading list`).
+    PlanListed(PlanSummary),
+    /// An entry was shown, along with its number (starting at 1) and how
+    /// many entries after the current one it comes.
+    EntryViewed { number: usize, offset: usize, entry: Entry },
+    /// A plan was moved from one entry to another (given by their numbers,
+    /// or `None` for the end of the plan); the entry it is now at is
+    /// included as well.
+    PlanAdvanced {
+        plan: String,
+        from: Option<usize>,
+        to: Option<usize>,
+        entry: Option<Entry>,
+    },
+    /// An entry of a plan was completed, given by its number.
+    EntryCompleted { plan: String, number: usize, entry: Entry },
+    /// A plan was added, with its number of entries (if known) and where it
+    /// came from (such as the file it was read from), if that is worth
+    /// showing.
+    PlanAdded {
+        plan: String,
+        entries: Option<usize>,
+        source: Option<String>,
+    },
+    /// A plan couldn't be added from the given source, because of the given
+    /// error (and its causes).
+    PlanNotAdded { source: String, causes: Vec<String> },
+    /// A plan was removed (to the trash).
+    PlanRemoved { plan: String },
+    /// A plan was restored to an earlier version (from the trash, a backup
+    /// or its undo journal), which is at the given entry.
+    PlanRestored { plan: String, to: Option<usize> },
+    /// A property of a plan was changed, as it would be by `reading set`.
+    PropertySet { plan: String, key: String, value: String },
+    /// An entry of a plan was added, removed or renamed.
+    EntryChanged { plan: String, change: EntryChange },
+    /// The remaining entries of a plan were put in a new order.
+    EntriesReordered { plan: String, count: usize, order: Order },
+    /// An entry of a plan was made to come after another (its prerequisite),
+    /// or no longer does if `added` is false; both are given by their
+    /// numbers.
+    PrerequisiteChanged {
+        plan: String,
+        number: usize,
+        prerequisite: usize,
+        added: bool,
+    },
+    /// A note was added to an entry of a plan, given by its number.
+    NoteAdded { plan: String, number: usize, text: String },
+    /// A bookmark of a plan was set to the entry with the given number, or
+    /// removed if there is none.
+    BookmarkSet {
+        plan: String,
+        bookmark: String,
+        number: Option<usize>,
+    },
+    /// Something about an entry of a plan is probably a mistake.
+    Warning { plan: String, warning: PlanWarning },
+}
+
+/// How the remaining entries of a plan were reordered (see
+/// `Event::EntriesReordered`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    /// The entries were reversed.
+    Reversed,
+    /// The entries were shuffled, using the given seed.
+    Shuffled(u64),
+    /// The entries were put after their prerequisites.
+    Dependencies,
+}
+
+/// Shows the events reported by a subcommand, along with the rest of its
+/// result.
+pub trait Printer {
+    /// Reports an event. The event might not be shown until the output is
+    /// finished, so that (for example) tables can be lined up.
+    fn event(&mut self, event: Event);
+
+    /// Finishes the output, given the rest of the result of the subcommand
+    /// as a JSON object. Any other text output should only be printed after
+    /// this is called.
+    fn finish(self: Box<Self>, result: Value);
+}
+
+/// A `Printer` for text mode, which prints the events as styled text.
+#[derive(Debug)]
+pub struct TextPrinter {
+    style_set: StyleSet,
+    /// Whether to show text in full, even if it doesn't fit on a line
+    wide: bool,
+    events: Vec<Event>,
+}
+
+/// A `Printer` for JSON mode, which adds the events to the result: each
+/// kind of event is added to a list in the result object (e.g. `plans` for
+/// listed plans, `upcoming` for viewed entries and `changes` for advanced
+/// plans; see `event_json`), which is created if it isn't there already.
+#[derive(Debug, Default)]
+pub struct JsonPrinter {
+    events: Vec<Event>,
+}
+
+/// A `Printer` for quiet mode, which ignores the events.
+#[derive(Debug)]
+pub struct QuietPrinter;
+
+impl Printer for TextPrinter {
+    fn event(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    fn finish(self: Box<Self>, _result: Value) {
+        let style_set = &self.style_set;
+        // The width lines are truncated to, unless the output is wide
+        let width = if self.wide { None } else { Some(terminal_width()) };
+        // The bars of listed plans line up after the longest name (or the
+        // widest a name is shown), leaving room for the position after them
+        let listed_width = self.events
+            .iter()
+            .filter_map(|e| match *e {
+                Event::PlanListed(ref plan) => Some(plan.name().chars().count()),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+        let listed_width = if self.wide { listed_width } else { listed_width.min(MAX_LIST_NAME_WIDTH) };
+        let list_bar_width = bar_width(listed_width + LIST_POSITION_WIDTH);
+        let advanced: Vec<_> = self.events
+            .iter()
+            .filter_map(|e| match *e {
+                Event::PlanAdvanced { ref plan, .. } => Some(plan.chars().count()),
+                _ => None,
+            })
+            .collect();
+        let advanced_width = advanced.iter().cloned().max().unwrap_or(0);
+        // Plans added from several sources are shown in a table, lined up
+        // after the widest source
+        let sources: Vec<_> = self.events
+            .iter()
+            .filter_map(|e| match *e {
+                Event::PlanAdded { source: Some(ref source), .. } |
+                Event::PlanNotAdded { ref source, .. } => Some(source.chars().count()),
+                _ => None,
+            })
+            .collect();
+        let source_width = if sources.len() > 1 { sources.iter().cloned().max() } else { None };
+        let entry_changes = self.events.iter().filter(|e| matches!(**e, Event::EntryChanged { .. })).count();
+
+        for event in &self.events {
+            match *event {
+                Event::PlanListed(ref plan) => print_listed(plan, listed_width, list_bar_width, width, style_set),
+                Event::EntryViewed { offset, ref entry, .. } => {
+                    let label = match offset {
+                        0 => "Current entry: ".to_owned(),
+                        1 => "Next entry: ".to_owned(),
+                        _ => format!("{} entries from now: ", offset),
+                    };
+                    print_entry(&label, entry, style_set);
+                }
+                // A single plan is described in a sentence, but several are
+                // shown in a table
+                Event::PlanAdvanced { ref plan, from, to, .. } if advanced.len() == 1 => {
+                    styleln!(style_set.normal,
+                             "Changed current entry of '{}': {} -> {}",
+                             plan,
+                             position_text(from),
+                             position_text(to))
+                }
+                Event::PlanAdvanced { ref plan, from, to, ref entry } => {
+                    let title = entry.as_ref().map_or("(end of plan)", |e| e.title());
+                    // The title comes after the plan and the two positions
+                    let title = match width {
+                        Some(width) => truncate_text(title, width.saturating_sub(advanced_width + 15)),
+                        None => title.to_owned(),
+                    };
+                    println!("{} {} {}",
+                             style!(style_set.title, "{:1$}", plan, advanced_width),
+                             style!(style_set.normal, "{:>5} -> {:<5}", position_text(from), position_text(to)),
+                             style!(style_set.description, "{}", title));
+                }
+                Event::EntryCompleted { ref plan, ref entry, .. } => {
+                    styleln!(style_set.normal, "Done with '{}' in '{}'", entry.title(), plan)
+                }
+                Event::PlanAdded { ref plan, entries: count, ref source } => {
+                    let added = match count {
+                        Some(count) => format!("{} ({})", plan, entries(count)),
+                        None => plan.clone(),
+                    };
+                    match (source, source_width) {
+                        (Some(source), Some(source_width)) => {
+                            println!("{} {:3$}  {}",
+                                     style!(style_set.normal, "{:6}", "Added"),
+                                     source,
+                                     style!(style_set.title, "{}", added),
+                                     source_width)
+                        }
+                        (Some(source), None) => styleln!(style_set.normal, "Added plan {} from {}", added, source),
+                        (None, _) => styleln!(style_set.normal, "Added plan {}", added),
+                    }
+                }
+                Event::PlanNotAdded { ref source, ref causes } => {
+                    match source_width {
+                        Some(source_width) => {
+                            println!("{} {:3$}  {}",
+                                     style!(style_set.error, "{:6}", "Failed"),
+                                     source,
+                                     style!(style_set.error, "{}", causes.join(": ")),
+                                     source_width)
+                        }
+                        None => styleln!(style_set.error, "Could not add {}: {}", source, causes.join(": ")),
+                    }
+                }
+                Event::PlanRemoved { ref plan } => {
+                    styleln!(style_set.normal,
+                             "Removed plan {} (use `reading restore {}` to get it back)",
+                             plan,
+                             plan)
+                }
+                Event::PlanRestored { ref plan, to } => {
+                    styleln!(style_set.normal, "Restored plan {} (current entry: {})", plan, position_text(to))
+                }
+                Event::PropertySet { ref plan, ref key, ref value } => {
+                    match (key.as_str(), value.as_str()) {
+                        ("archived", "true") => styleln!(style_set.normal, "Archived '{}'", plan),
+                        ("archived", _) => styleln!(style_set.normal, "Unarchived '{}'", plan),
+                        ("pinned", "true") => styleln!(style_set.normal, "Pinned '{}'", plan),
+                        ("pinned", _) => styleln!(style_set.normal, "Unpinned '{}'", plan),
+                        _ => styleln!(style_set.normal, "Set {} of '{}' to '{}'", key, plan, value),
+                    }
+                }
+                // A single change is described in a sentence, but several
+                // are listed one per line
+                Event::EntryChanged { ref plan, ref change } if entry_changes == 1 => {
+                    match *change {
+                        EntryChange::Added { index, ref title } => {
+                            styleln!(style_set.normal, "Added entry {} to '{}': {}", index + 1, plan, title)
+                        }
+                        EntryChange::Removed { index, ref title } => {
+                            styleln!(style_set.normal, "Removed entry {} from '{}': {}", index + 1, plan, title)
+                        }
+                        EntryChange::Renamed { new_index, ref old_title, ref new_title, .. } => {
+                            styleln!(style_set.normal,
+                                     "Renamed entry {} of '{}' to {} (was {})",
+                                     new_index + 1,
+                                     plan,
+                                     new_title,
+                                     old_title)
+                        }
+                    }
+                }
+                Event::EntryChanged { ref change, .. } => {
+                    match *change {
+                        EntryChange::Added { index, ref title } => {
+                            styleln!(style_set.normal, "  + {:>5} {}", index + 1, title)
+                        }
+                        EntryChange::Removed { index, ref title } => {
+                            styleln!(style_set.normal, "  - {:>5} {}", index + 1, title)
+                        }
+                        EntryChange::Renamed { new_index, ref old_title, ref new_title, .. } => {
+                            styleln!(style_set.normal, "  ~ {:>5} {} (was {})", new_index + 1, new_title, old_title)
+                        }
+                    }
+                }
+                Event::EntriesReordered { ref plan, count, order } => {
+                    match order {
+                        Order::Reversed => {
+                            styleln!(style_set.normal, "Reversed the remaining {} of '{}'", entries(count), plan)
+                        }
+                        Order::Shuffled(seed) => {
+                            styleln!(style_set.normal,
+                                     "Shuffled the remaining {} of '{}' (seed {})",
+                                     entries(count),
+                                     plan,
+                                     seed)
+                        }
+                        Order::Dependencies => {
+                            styleln!(style_set.normal,
+                                     "Put the remaining {} of '{}' after their prerequisites",
+                                     entries(count),
+                                     plan)
+                        }
+                    }
+                }
+                Event::PrerequisiteChanged { ref plan, number, prerequisite, added } => {
+                    let comes = if added { "now comes" } else { "no longer comes" };
+                    styleln!(style_set.normal, "Entry {} of '{}' {} after entry {}", number, plan, comes, prerequisite)
+                }
+                Event::NoteAdded { ref plan, number, .. } => {
+                    styleln!(style_set.normal, "Added note to entry {} of '{}'", number, plan)
+                }
+                Event::BookmarkSet { ref plan, ref bookmark, number } => {
+                    match number {
+                        Some(number) => {
+                            styleln!(style_set.normal, "Bookmarked entry {} of '{}' as '{}'", number, plan, bookmark)
+                        }
+                        None => styleln!(style_set.normal, "Removed bookmark '{}' from '{}'", bookmark, plan),
+                    }
+                }
+                Event::Warning { ref warning, .. } => styleln!(style_set.error, "Warning: {}", warning),
+            }
+        }
+    }
+}
+
+impl Printer for QuietPrinter {
+    fn event(&mut self, _event: Event) {}
+
+    fn finish(self: Box<Self>, _result: Value) {}
+}
+
+impl Printer for JsonPrinter {
+    fn event(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    fn finish(self: Box<Self>, mut result: Value) {
+        for event in self.events {
+            let (key, value) = event_json(event);
+            // The result of a subcommand is always an object
+            let list = result.as_object_mut()
+                .expect("result is not an object")
+                .entry(key)
+                .or_insert_with(|| json!([]));
+            if let Some(list) = list.as_array_mut() {
+                list.push(value);
+            }
+        }
+        print_json(&result);
+    }
+}
+
+/// Returns an event as JSON, along with the key of the list it belongs in
+/// (see `JsonPrinter`).
+fn event_json(event: Event) -> (&'static str, Value) {
+    match event {
+        Event::PlanListed(ref plan) => ("plans", summary_json(plan)),
+        Event::EntryViewed { number, ref entry, .. } => ("upcoming", entry_json(number, entry)),
+        Event::PlanAdvanced { plan, from, to, .. } => ("changes", json!({ "plan": plan, "from": from, "to": to })),
+        Event::EntryCompleted { plan, number, ref entry } => {
+            ("done", json!({ "plan": plan, "entry": entry_json(number, entry) }))
+        }
+        Event::PlanAdded { plan, entries, source } => {
+            ("added", json!({ "plan": plan, "entries": entries, "source": source }))
+        }
+        Event::PlanNotAdded { source, causes } => ("failures", json!({ "source": source, "error": causes })),
+        Event::PlanRemoved { plan } => ("removed", json!({ "plan": plan })),
+        Event::PlanRestored { plan, to } => ("restored", json!({ "plan": plan, "to": to })),
+        Event::PropertySet { plan, key, value } => ("set", json!({ "plan": plan, "key": key, "value": value })),
+        Event::EntryChanged { plan, ref change } => {
+            let mut json = entry_change_json(change);
+            json["plan"] = plan.into();
+            ("entry_changes", json)
+        }
+        Event::EntriesReordered { plan, count, order } => {
+            let (order, seed) = match order {
+                Order::Reversed => ("reversed", None),
+                Order::Shuffled(seed) => ("shuffled", Some(seed)),
+                Order::Dependencies => ("dependencies", None),
+            };
+            ("reordered", json!({ "plan": plan, "entries": count, "order": order, "seed": seed }))
+        }
+        Event::PrerequisiteChanged { plan, number, prerequisite, added } => {
+            ("prerequisites", json!({ "plan": plan, "entry": number, "after": prerequisite, "added": added }))
+        }
+        Event::NoteAdded { plan, number, text } => ("notes", json!({ "plan": plan, "entry": number, "note": text })),
+        Event::BookmarkSet { plan, bookmark, number } => {
+            ("bookmarks", json!({ "plan": plan, "bookmark": bookmark, "entry": number }))
+        }
+        Event::Warning { plan, ref warning } => {
+            let mut json = warning_json(warning);
+            json["plan"] = plan.into();
+            ("warnings", json)
+        }
+    }
+}
+
+/// Returns the position given by an entry number as text, or "end" for the
+/// end of a plan.
+fn position_text(number: Option<usize>) -> String {
+    number.map_or_else(|| "end".to_owned(), |n| n.to_string())
+}
+
+/// The room left for the position of each plan after its progress bar in
+/// `reading list`.
+const LIST_POSITION_WIDTH: usize = 40;
+/// The widest that the name of a plan is shown in `reading list`, unless the
+/// output is wide.
+const MAX_LIST_NAME_WIDTH: usize = 24;
+
+/// Prints a plan as a line of the table printed by `reading list`, with its
+/// name padded (or truncated) to the given width and a progress bar of the
+/// given width (if there is room for one). If a line width is given, the
+/// rest of the line is truncated to fit in it.
+fn print_listed(plan: &PlanSummary,
+                name_width: usize,
+                bar_width: Option<usize>,
+                width: Option<usize>,
+                style_set: &StyleSet) {
+    let section = match plan.current_section() {
+        Some(section) => format!(", {}", section.title()),
+        None => String::new(),
+    };
+    let progress = if plan.is_ended() {
+        "(end of plan)".to_owned()
+    } else if !plan.tracks().is_empty() {
+        let tracks: Vec<_> = plan.tracks()
+            .iter()
+            .map(|t| if t.is_ended() {
+                format!("{} done", t.name())
+            } else {
+                format!("{} {}/{}", t.name(), t.position(), t.len())
+            })
+            .collect();
+        format!("({}{})", tracks.join(", "), section)
+    } else if plan.cycles_completed() > 0 {
+        format!("({} {} of {}, {} pass{})",
+                plan.unit_label(),
+                plan.current_entry_number(),
+                plan.len(),
+                ordinal(plan.cycles_completed() + 1),
+                section)
+    } else {
+        format!("({} {} of {}{})",
+                plan.unit_label(),
+                plan.current_entry_number(),
+                plan.len(),
+                section)
+    };
+
+    let name = truncate_text(plan.name(), name_width);
+    let (mut line, mut line_width) = match bar_width {
+        Some(bar_width) => {
+            let percent = plan.percent_complete();
+            (format!("{} {} {:>3.0}%",
+                     style!(style_set.title, "{:1$}", name, name_width),
+                     progress_bar(percent / 100.0, bar_width, style_set),
+                     percent),
+             name_width + bar_width + 5)
+        }
+        None => (style!(style_set.title, "{}", name).to_string(), name.chars().count()),
+    };
+    let mut parts = vec![(progress, style_set.normal)];
+    if plan.is_pinned() {
+        parts.push(("(pinned)".to_owned(), style_set.muted));
+    }
+    if plan.is_archived() {
+        parts.push(("(archived)".to_owned(), style_set.muted));
+    }
+    if !plan.tags().is_empty() {
+        parts.push((format!("[{}]", plan.tags().join(", ")), style_set.description));
+    }
+    // The parts after the name are cut off once the line is full
+    for (text, style) in parts {
+        let room = width.map_or(usize::MAX, |w| w.saturating_sub(line_width + 1));
+        if room == 0 {
+            break;
+        }
+        let text = truncate_text(&text, room);
+        line += &format!(" {}", style.paint(text.as_str()));
+        line_width += 1 + text.chars().count();
+    }
+    println!("{}", line);
+}
+
+/// Prints a value as JSON on a single line.
+fn print_json<T: Serialize>(value: &T) {
+    // Serializing a `Value` (or one of our own types) can't fail
+    println!("{}", serde_json::to_string(value).expect("could not serialize output"));
+}
+
+/// The width of the terminal assumed if it can't be found out.
+const DEFAULT_TERMINAL_WIDTH: usize = 80;
+/// The widest that a progress bar is made.
+const MAX_BAR_WIDTH: usize = 30;
+/// The narrowest that a progress bar is made; if there isn't room for a bar
+/// this wide, none is shown.
+const MIN_BAR_WIDTH: usize = 10;
+
+/// Returns the width of the terminal in columns, which is taken from the
+/// `COLUMNS` environment variable if it can't be found out directly (e.g.
+/// because standard output isn't a terminal).
+pub fn terminal_width() -> usize {
+    stdout_width()
+        .or_else(|| env::var("COLUMNS").ok().and_then(|c| c.parse().ok()))
+        .unwrap_or(DEFAULT_TERMINAL_WIDTH)
+}
+
+/// Asks the terminal on standard output for its width.
+#[cfg(unix)]
+fn stdout_width() -> Option<usize> {
+    let mut size = libc::winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+    // The size is only filled in if standard output is a terminal
+    match unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } {
+        0 if size.ws_col > 0 => Some(size.ws_col as usize),
+        _ => None,
+    }
+}
+
+/// Asks the console on standard output for its width.
+#[cfg(windows)]
+fn stdout_width() -> Option<usize> {
+    use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+    use winapi::um::processenv::GetStdHandle;
+    use winapi::um::winbase::STD_OUTPUT_HANDLE;
+    use winapi::um::wincon::{CONSOLE_SCREEN_BUFFER_INFO, GetConsoleScreenBufferInfo};
+
+    unsafe {
+        let handle = GetStdHandle(STD_OUTPUT_HANDLE);
+        if handle.is_null() || handle == INVALID_HANDLE_VALUE {
+            return None;
+        }
+        let mut info: CONSOLE_SCREEN_BUFFER_INFO = mem::zeroed();
+        if GetConsoleScreenBufferInfo(handle, &mut info) == 0 {
+            return None;
+        }
+        let width = info.srWindow.Right - info.srWindow.Left + 1;
+        if width > 0 { Some(width as usize) } else { None }
+    }
+}
+
+/// There is no way to ask the terminal for its width on other platforms.
+#[cfg(not(any(unix, windows)))]
+fn stdout_width() -> Option<usize> {
+    None
+}
+
+/// Returns the width of a progress bar to fit on a line of the terminal
+/// alongside the given number of columns of other text, or `None` if there
+/// isn't room for one.
+pub fn bar_width(other_text: usize) -> Option<usize> {
+    let width = terminal_width().saturating_sub(other_text).min(MAX_BAR_WIDTH);
+    if width >= MIN_BAR_WIDTH { Some(width) } else { None }
+}
+
+/// Truncates text to the given width (in columns), replacing its end with
+/// an ellipsis if it doesn't fit.
+pub fn truncate_text(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_owned();
+    } else if width == 0 {
+        return String::new();
+    }
+    let mut truncated: String = text.chars().take(width.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// The narrowest that text is wrapped, however narrow the terminal is.
+const MIN_WRAP_WIDTH: usize = 20;
+
+/// Wraps a line of text at spaces into lines no wider than the given width
+/// (in columns), or `MIN_WRAP_WIDTH` if that is wider. A word which is too
+/// wide to fit on a line is left on a line of its own.
+pub fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(MIN_WRAP_WIDTH);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut line_width = 0;
+    for word in text.split_whitespace() {
+        let word_width = word.chars().count();
+        if line_width > 0 && line_width + 1 + word_width > width {
+            lines.push(mem::take(&mut line));
+            line_width = 0;
+        }
+        if line_width > 0 {
+            line.push(' ');
+            line_width += 1;
+        }
+        line += word;
+        line_width += word_width;
+    }
+    lines.push(line);
+    lines
+}
+
+/// Returns a progress bar of the given width (in columns), filled up to the
+/// given fraction (between 0 and 1) using Unicode block characters.
+pub fn progress_bar(fraction: f64, width: usize, style_set: &StyleSet) -> String {
+    // Partially filled columns, by the number of eighths filled
+    const PARTIAL: [&str; 8] = ["", "▏", "▎", "▍", "▌", "▋", "▊", "▉"];
+
+    let eighths = (fraction.clamp(0.0, 1.0) * (width * 8) as f64).round() as usize;
+    let (full, partial) = (eighths / 8, eighths % 8);
+    let empty = width - full - if partial > 0 { 1 } else { 0 };
+    format!("{}{}",
+            style!(style_set.progress, "{}{}", "█".repeat(full), PARTIAL[partial]),
+            style!(style_set.muted, "{}", "░".repeat(empty)))
+}
+
+/// Returns the position of the plan as JSON: the number of the current entry,
+/// or `null` if the plan has ended.
+pub fn position_json(plan: &Plan) -> Value {
+    if plan.is_ended() {
+        Value::Null
+    } else {
+        json!(plan.current_entry_number())
+    }
+}
+
+/// Returns a summary of the plan (without its entries) as JSON.
+pub fn plan_json(plan: &Plan) -> Value {
+    summary_json(&PlanSummary::from(plan))
+}
+
+/// Returns a plan summary as JSON, in the same form as `plan_json`.
+pub fn summary_json(plan: &PlanSummary) -> Value {
+    json!({
+        "name": plan.name(),
+        "cyclic": plan.is_cyclic(),
+        "random": plan.is_random(),
+        "current_entry": if plan.is_ended() { Value::Null } else { json!(plan.current_entry_number()) },
+        "entries": plan.len(),
+        "unit": plan.unit_label(),
+        "ended": plan.is_ended(),
+        "percent_complete": plan.percent_complete(),
+        "cycles_completed": plan.cycles_completed(),
+        "archived": plan.is_archived(),
+        "pinned": plan.is_pinned(),
+        "pace": plan.pace().map(|p| p.to_string()),
+        "days": plan.active_days().to_string(),
+        "tags": plan.tags(),
+        "section": plan.current_section().map(|s| s.title()),
+        "tracks": plan.tracks().iter().map(track_json).collect::<Vec<_>>(),
+        "modified_at": plan.modified_at().map(|t| t.to_rfc3339()),
+    })
+}
+
+/// Returns the position in a track of a plan as JSON: the number of its
+/// current entry within the track is `null` once it has ended.
+pub fn track_json(track: &Track) -> Value {
+    json!({
+        "name": track.name(),
+        "position": if track.is_ended() { Value::Null } else { json!(track.position()) },
+        "entries": track.len(),
+        "ended": track.is_ended(),
+    })
+}
+
+/// Returns the entry with the given number (starting at 1) as JSON.
+pub fn entry_json(number: usize, entry: &Entry) -> Value {
+    json!({
+        "number": number,
+        "title": entry.title(),
+        "description": entry.description(),
+        "schedule": entry.schedule().map(|s| s.to_string()),
+        "start_date": entry.start_date().map(|d| d.format("%Y-%m-%d").to_string()),
+        "end_date": entry.end_date().map(|d| d.format("%Y-%m-%d").to_string()),
+        "url": entry.url(),
+        "estimated_minutes": entry.estimate().map(|e| e.minutes()),
+        "notes": entry.notes()
+            .iter()
+            .map(|n| json!({ "text": n.text(), "created_at": n.created_at().to_rfc3339() }))
+            .collect::<Vec<_>>(),
+        "prerequisites": entry.prerequisites(),
+    })
+}
+
+/// Returns the completion of an entry of a plan (from its history) as JSON.
+pub fn history_json(plan: &Plan, event: &HistoryEvent) -> Value {
+    json!({
+        "entry": event.entry_index() + 1,
+        // The entry might not exist anymore if the plan was edited
+        "title": plan.entry(event.entry_index()).map(|e| e.title()),
+        "completed_at": event.completed_at().to_rfc3339(),
+    })
+}
+
+/// Returns the progress through a plan compared with its pace as JSON.
+pub fn projection_json(projection: &Projection) -> Value {
+    json!({
+        "pace": projection.pace().to_string(),
+        "scheduled_finish": projection.scheduled_finish().format("%Y-%m-%d").to_string(),
+        "projected_finish": projection.projected_finish().map(|d| d.format("%Y-%m-%d").to_string()),
+        "days_ahead": projection.days_ahead(),
+        "to_read_today": projection.to_read_today(),
+    })
+}
+
+/// Returns a change to the entries of a plan as JSON, with entry numbers
+/// starting at 1.
+fn entry_change_json(change: &EntryChange) -> Value {
+    match *change {
+        EntryChange::Added { index, ref title } => json!({ "change": "added", "entry": index + 1, "title": title }),
+        EntryChange::Removed { index, ref title } => json!({ "change": "removed", "entry": index + 1, "title": title }),
+        EntryChange::Renamed { old_index, new_index, ref old_title, ref new_title } => {
+            json!({
+                "change": "renamed",
+                "from": old_index + 1,
+                "to": new_index + 1,
+                "old_title": old_title,
+                "title": new_title,
+            })
+        }
+    }
+}
+
+/// Returns warnings about the entries of a plan as JSON, with entry numbers
+/// starting at 1.
+pub fn warnings_json(warnings: &[PlanWarning]) -> Value {
+    json!(warnings.iter().map(warning_json).collect::<Vec<_>>())
+}
+
+/// Returns a warning about an entry of a plan as JSON, with its entry number
+/// starting at 1.
+fn warning_json(warning: &PlanWarning) -> Value {
+    json!({ "kind": warning.kind().name(), "entry": warning.entry_index() + 1, "message": warning.message() })
+}
+
+/// Returns a change in the position of a plan as JSON, given the position
+/// before the change (as given by `position_json`).
+#[cfg(feature = "serve")]
+pub fn change_json(plan: &Plan, from: Value) -> Value {
+    json!({
+        "plan": plan.name(),
+        "from": from,
+        "to": position_json(plan),
+    })
+}
+
+/// Prints an entry (including its schedule and description, if present)
+/// with the given label.
+pub fn print_entry(label: &str, entry: &Entry, style_set: &StyleSet) {
+    let details: Vec<_> = entry.schedule()
+        .map(|s| s.to_string())
+        .into_iter()
+        .chain(entry.estimate().map(|e| format!("about {}", e)))
+        .collect();
+    // Everything is wrapped to fit beside the labels, with a hanging indent
+    let width = terminal_width().saturating_sub(21);
+    let title = wrap_text(entry.title(), width);
+    let mut lines: Vec<_> = title.iter().map(|l| style!(style_set.title, "{}", l).to_string()).collect();
+    if !details.is_empty() {
+        // The details go after the title if they fit, and on a line of their
+        // own otherwise
+        let details = format!("({})", details.join(", "));
+        let last_width = title.last().map_or(0, |l| l.chars().count());
+        if last_width + 1 + details.chars().count() <= width.max(MIN_WRAP_WIDTH) {
+            let last = lines.len() - 1;
+            lines[last] += &format!(" {}", style!(style_set.muted, "{}", details));
+        } else {
+            lines.push(style!(style_set.muted, "{}", details).to_string());
+        }
+    }
+    for (i, line) in lines.iter().enumerate() {
+        println!("{} {}", style!(style_set.normal, "{:20}", if i == 0 { label } else { "" }), line);
+    }
+    for (i, paragraph) in entry.description_paragraphs().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        for line in paragraph.lines().flat_map(|l| wrap_text(l, width)) {
+            styleln!(style_set.description, "{:20} {}", "", line);
+        }
+    }
+    if let Some(url) = entry.url() {
+        styleln!(style_set.muted, "{:20} {}", "", url);
+    }
+    for note in entry.notes() {
+        let label = format!("Note ({}):", note.created_at().with_timezone(&Local).format("%Y-%m-%d"));
+        let indent = label.chars().count() + 1;
+        for (i, line) in wrap_text(note.text(), width.saturating_sub(indent)).iter().enumerate() {
+            println!("{:20} {} {}",
+                     "",
+                     style!(style_set.muted, "{:1$}", if i == 0 { &label } else { "" }, indent - 1),
+                     style!(style_set.description, "{}", line));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MIN_WRAP_WIDTH, truncate_text, wrap_text};
+
+    #[test]
+    fn truncate() {
+        assert_eq!(truncate_text("Genesis", 7), "Genesis");
+        assert_eq!(truncate_text("Genesis", 6), "Genes…");
+        assert_eq!(truncate_text("Genesis", 1), "…");
+        assert_eq!(truncate_text("Genesis", 0), "");
+        assert_eq!(truncate_text("", 0), "");
+        // Widths are counted in characters, not bytes
+        assert_eq!(truncate_text("Ésaïe", 5), "Ésaïe");
+        assert_eq!(truncate_text("Ésaïe 1–3", 6), "Ésaïe…");
+    }
+
+    #[test]
+    fn wrap() {
+        assert_eq!(wrap_text("", 30), [""]);
+        assert_eq!(wrap_text("  In the  beginning ", 30), ["In the beginning"]);
+        // A line which is exactly as wide as allowed isn't wrapped
+        let exact = "aaaaaaaaaa bbbbbbbbbbbbbbbbbbb";
+        assert_eq!(wrap_text(exact, 30), [exact]);
+        assert_eq!(wrap_text(&format!("{} c", exact), 30), [exact, "c"]);
+        // A word which is too wide is left on a line of its own
+        let long = "x".repeat(40);
+        assert_eq!(wrap_text(&format!("a {} b", long), 30), ["a", &long, "b"]);
+        // Widths are counted in characters, not bytes
+        assert_eq!(wrap_text("ééééééééé ééééééééé ééééééééé", 20), ["ééééééééé ééééééééé", "ééééééééé"]);
+        // Text is never wrapped narrower than the minimum width
+        let words = "one two three four five six";
+        assert_eq!(wrap_text(words, 0), wrap_text(words, MIN_WRAP_WIDTH));
+        assert_eq!(wrap_text(words, 1), ["one two three four", "five six"]);
+    }
+}
@@ -0,0 +1,241 @@
+//! This module knows the books of the Bible and their chapters, and
+//! generates Bible reading plans from them, either for any group of books
+//! over a certain number of days or from one of a few common presets (see
+//! `Preset`).
+//!
+//! The chapters are divided as evenly as possible between the days (earlier
+//! days get an extra chapter if necessary), without regard to where each
+//! book starts, so a single day's reading may span two books.
+
+use std::str::FromStr;
+
+use super::{Entry, Plan};
+use super::super::errors::*;
+
+/// A book of the Bible.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Book {
+    name: &'static str,
+    chapters: u32,
+}
+
+/// All the books of the Bible (in the Protestant canon), in order. The
+/// first 39 are the Old Testament.
+pub const BOOKS: &[Book] = &[
+    Book { name: "Genesis", chapters: 50 },
+    Book { name: "Exodus", chapters: 40 },
+    Book { name: "Leviticus", chapters: 27 },
+    Book { name: "Numbers", chapters: 36 },
+    Book { name: "Deuteronomy", chapters: 34 },
+    Book { name: "Joshua", chapters: 24 },
+    Book { name: "Judges", chapters: 21 },
+    Book { name: "Ruth", chapters: 4 },
+    Book { name: "1 Samuel", chapters: 31 },
+    Book { name: "2 Samuel", chapters: 24 },
+    Book { name: "1 Kings", chapters: 22 },
+    Book { name: "2 Kings", chapters: 25 },
+    Book { name: "1 Chronicles", chapters: 29 },
+    Book { name: "2 Chronicles", chapters: 36 },
+    Book { name: "Ezra", chapters: 10 },
+    Book { name: "Nehemiah", chapters: 13 },
+    Book { name: "Esther", chapters: 10 },
+    Book { name: "Job", chapters: 42 },
+    Book { name: "Psalms", chapters: 150 },
+    Book { name: "Proverbs", chapters: 31 },
+    Book { name: "Ecclesiastes", chapters: 12 },
+    Book { name: "Song of Solomon", chapters: 8 },
+    Book { name: "Isaiah", chapters: 66 },
+    Book { name: "Jeremiah", chapters: 52 },
+    Book { name: "Lamentations", chapters: 5 },
+    Book { name: "Ezekiel", chapters: 48 },
+    Book { name: "Daniel", chapters: 12 },
+    Book { name: "Hosea", chapters: 14 },
+    Book { name: "Joel", chapters: 3 },
+    Book { name: "Amos", chapters: 9 },
+    Book { name: "Obadiah", chapters: 1 },
+    Book { name: "Jonah", chapters: 4 },
+    Book { name: "Micah", chapters: 7 },
+    Book { name: "Nahum", chapters: 3 },
+    Book { name: "Habakkuk", chapters: 3 },
+    Book { name: "Zephaniah", chapters: 3 },
+    Book { name: "Haggai", chapters: 2 },
+    Book { name: "Zechariah", chapters: 14 },
+    Book { name: "Malachi", chapters: 4 },
+    Book { name: "Matthew", chapters: 28 },
+    Book { name: "Mark", chapters: 16 },
+    Book { name: "Luke", chapters: 24 },
+    Book { name: "John", chapters: 21 },
+    Book { name: "Acts", chapters: 28 },
+    Book { name: "Romans", chapters: 16 },
+    Book { name: "1 Corinthians", chapters: 16 },
+    Book { name: "2 Corinthians", chapters: 13 },
+    Book { name: "Galatians", chapters: 6 },
+    Book { name: "Ephesians", chapters: 6 },
+    Book { name: "Philippians", chapters: 4 },
+    Book { name: "Colossians", chapters: 4 },
+    Book { name: "1 Thessalonians", chapters: 5 },
+    Book { name: "2 Thessalonians", chapters: 3 },
+    Book { name: "1 Timothy", chapters: 6 },
+    Book { name: "2 Timothy", chapters: 4 },
+    Book { name: "Titus", chapters: 3 },
+    Book { name: "Philemon", chapters: 1 },
+    Book { name: "Hebrews", chapters: 13 },
+    Book { name: "James", chapters: 5 },
+    Book { name: "1 Peter", chapters: 5 },
+    Book { name: "2 Peter", chapters: 3 },
+    Book { name: "1 John", chapters: 5 },
+    Book { name: "2 John", chapters: 1 },
+    Book { name: "3 John", chapters: 1 },
+    Book { name: "Jude", chapters: 1 },
+    Book { name: "Revelation", chapters: 22 },
+];
+
+/// The number of books in the Old Testament.
+const OLD_TESTAMENT_BOOKS: usize = 39;
+
+/// A common Bible reading plan.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Preset {
+    /// The whole Bible, in a year by default.
+    WholeBible,
+    /// The New Testament, in 90 days by default.
+    NewTestament,
+    /// The Psalms and Proverbs every month (as a cyclic plan): a chapter of
+    /// Proverbs each day, along with a few psalms.
+    PsalmsProverbs,
+}
+
+impl Book {
+    /// Returns the name of the book.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the number of chapters in the book.
+    pub fn chapters(&self) -> u32 {
+        self.chapters
+    }
+}
+
+impl Preset {
+    /// The names of all the presets, as used on the command line.
+    pub const NAMES: &'static [&'static str] = &["bible-year", "nt-90", "psalms-proverbs"];
+
+    /// Returns the number of days the preset takes by default.
+    pub fn default_days(self) -> u32 {
+        match self {
+            Preset::WholeBible => 365,
+            Preset::NewTestament => 90,
+            Preset::PsalmsProverbs => 31,
+        }
+    }
+
+    /// Generates a plan with the given name from the preset, taking the
+    /// given number of days (or the default for the preset).
+    ///
+    /// The Psalms and Proverbs preset always takes 31 days (one for each
+    /// chapter of Proverbs), so a different number of days is an error.
+    pub fn generate(self, name: &str, days: Option<u32>) -> Result<Plan> {
+        let days = days.unwrap_or_else(|| self.default_days());
+
+        match self {
+            Preset::WholeBible => Ok(Plan::from_entries(name, split_books(BOOKS, days)?)),
+            Preset::NewTestament => Ok(Plan::from_entries(name, split_books(new_testament(), days)?)),
+            Preset::PsalmsProverbs => {
+                if days != 31 {
+                    return Err(ErrorKind::InvalidGenerator("the Psalms and Proverbs plan always \
+                                                            takes 31 days"
+                            .into())
+                        .into());
+                }
+                let psalms: Vec<_> = book("Psalms").into_iter().collect();
+                let entries = split_books(&psalms, days)?
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, e)| Entry::new(&format!("{}; Proverbs {}", e.title(), i + 1)))
+                    .collect();
+                let mut plan = Plan::from_entries(name, entries);
+                plan.set_cyclic(true);
+                Ok(plan)
+            }
+        }
+    }
+}
+
+impl FromStr for Preset {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Preset> {
+        match s {
+            "bible-year" => Ok(Preset::WholeBible),
+            "nt-90" => Ok(Preset::NewTestament),
+            "psalms-proverbs" => Ok(Preset::PsalmsProverbs),
+            _ => {
+                Err(ErrorKind::InvalidGenerator(format!("unknown preset '{}' (expected one of {})",
+                                                        s,
+                                                        Preset::NAMES.join(", ")))
+                    .into())
+            }
+        }
+    }
+}
+
+/// Returns the book with the given name (ignoring case), if there is one.
+pub fn book(name: &str) -> Option<Book> {
+    BOOKS.iter().find(|b| b.name.eq_ignore_ascii_case(name.trim())).cloned()
+}
+
+/// Returns the books of the Old Testament.
+pub fn old_testament() -> &'static [Book] {
+    &BOOKS[..OLD_TESTAMENT_BOOKS]
+}
+
+/// Returns the books of the New Testament.
+pub fn new_testament() -> &'static [Book] {
+    &BOOKS[OLD_TESTAMENT_BOOKS..]
+}
+
+/// Returns entries covering the chapters of the given books (in order) in
+/// the given number of days, or an `InvalidGenerator` error if there are
+/// fewer chapters than days.
+pub fn split_books(books: &[Book], days: u32) -> Result<Vec<Entry>> {
+    let chapters: Vec<_> = books.iter()
+        .flat_map(|b| (1..=b.chapters).map(move |c| (b.name, c)))
+        .collect();
+    let total = chapters.len() as u32;
+    if days == 0 || days > total {
+        return Err(ErrorKind::InvalidGenerator(format!("cannot split {} chapters into {} days",
+                                                       total,
+                                                       days))
+            .into());
+    }
+
+    let mut rest = &chapters[..];
+    Ok((0..days)
+        .map(|i| {
+            let size = (total / days + if i < total % days { 1 } else { 0 }) as usize;
+            let (day, after) = rest.split_at(size);
+            rest = after;
+            Entry::new(&title(day))
+        })
+        .collect())
+}
+
+/// Returns the title of an entry covering the given chapters, e.g.
+/// `Genesis 50; Exodus 1–2`.
+fn title(chapters: &[(&str, u32)]) -> String {
+    let mut parts = Vec::new();
+    let mut rest = chapters;
+
+    while let Some(&(book, first)) = rest.first() {
+        let len = rest.iter().take_while(|&&(b, _)| b == book).count();
+        let last = rest[len - 1].1;
+        parts.push(if first == last {
+            format!("{} {}", book, first)
+        } else {
+            format!("{} {}–{}", book, first, last)
+        });
+        rest = &rest[len..];
+    }
+    parts.join("; ")
+}
@@ -0,0 +1,163 @@
+//! This module provides `PlanBuilder`, which constructs a plan step by step
+//! (e.g. in a program generating plans), rather than all at once from a
+//! list of entries or a file.
+//!
+//! Entries, sections and tracks are added in order, just as they would
+//! appear in the plain text format, and the result is only checked when the
+//! plan is built, so that an invalid plan (e.g. one with an empty section)
+//! is never returned.
+
+use chrono::{DateTime, Utc};
+
+use super::{Entry, Plan, Section};
+use super::track::Track;
+use super::super::errors::*;
+
+/// Constructs a plan from its entries and properties.
+///
+/// The methods which add to the plan return the builder, so that they can
+/// be chained, e.g. `builder.section("Book I").entry("Psalm 1", "")`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PlanBuilder {
+    name: String,
+    cyclic: bool,
+    entries: Vec<Entry>,
+    sections: Vec<Section>,
+    /// The tracks, whose ends are only known once the plan is built
+    tracks: Vec<Track>,
+    /// The number of the entry the plan starts at (starting at 1)
+    start_at: usize,
+    author: Option<String>,
+    source_url: Option<String>,
+    created_at: Option<DateTime<Utc>>,
+    modified_at: Option<DateTime<Utc>>,
+}
+
+impl PlanBuilder {
+    /// Returns a builder for an acyclic plan with the given name and no
+    /// entries, which will start at its first entry.
+    pub fn new(name: &str) -> PlanBuilder {
+        PlanBuilder {
+            name: name.to_owned(),
+            cyclic: false,
+            entries: Vec::new(),
+            sections: Vec::new(),
+            tracks: Vec::new(),
+            start_at: 1,
+            author: None,
+            source_url: None,
+            created_at: None,
+            modified_at: None,
+        }
+    }
+
+    /// Sets the name of the plan.
+    pub fn name(&mut self, name: &str) -> &mut PlanBuilder {
+        self.name = name.to_owned();
+        self
+    }
+
+    /// Sets whether the plan is cyclic.
+    pub fn cyclic(&mut self, cyclic: bool) -> &mut PlanBuilder {
+        self.cyclic = cyclic;
+        self
+    }
+
+    /// Sets the author of the plan.
+    pub fn author(&mut self, author: &str) -> &mut PlanBuilder {
+        self.author = Some(author.to_owned());
+        self
+    }
+
+    /// Sets the URL the plan came from.
+    pub fn source_url(&mut self, url: &str) -> &mut PlanBuilder {
+        self.source_url = Some(url.to_owned());
+        self
+    }
+
+    /// Sets when the plan was first stored (e.g. for a plan which was
+    /// written out as text and is now being read back).
+    pub fn created_at(&mut self, time: DateTime<Utc>) -> &mut PlanBuilder {
+        self.created_at = Some(time);
+        self
+    }
+
+    /// Sets when the plan was last stored.
+    pub fn modified_at(&mut self, time: DateTime<Utc>) -> &mut PlanBuilder {
+        self.modified_at = Some(time);
+        self
+    }
+
+    /// Adds an entry with the given title and description (which may be
+    /// empty) to the end of the plan.
+    pub fn entry(&mut self, title: &str, description: &str) -> &mut PlanBuilder {
+        self.push_entry(Entry::with_description(title, description))
+    }
+
+    /// Adds the given entry to the end of the plan.
+    pub fn push_entry(&mut self, entry: Entry) -> &mut PlanBuilder {
+        self.entries.push(entry);
+        self
+    }
+
+    /// Starts a section with the given title, which will contain the
+    /// entries added after it (until the next section is started).
+    pub fn section(&mut self, title: &str) -> &mut PlanBuilder {
+        self.sections.push(Section::new(title, self.entries.len()));
+        self
+    }
+
+    /// Starts a track with the given name, which will contain the entries
+    /// added after it (until the next track is started). If any tracks are
+    /// started, the first one must be started before the first entry.
+    pub fn track(&mut self, name: &str) -> &mut PlanBuilder {
+        let start = self.entries.len();
+        self.tracks.push(Track::new(name, start..start));
+        self
+    }
+
+    /// Sets the number of the entry (starting at 1) which will be the
+    /// current entry of the plan. For an acyclic plan, this can be one more
+    /// than the number of entries, to start at the end of the plan. In a
+    /// plan with tracks, the entries before this one count as read.
+    pub fn start_at(&mut self, number: usize) -> &mut PlanBuilder {
+        self.start_at = number;
+        self
+    }
+
+    /// Returns the number of entries added so far.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether no entries have been added yet.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the plan, or an `InvalidPlan` error if it would not be valid
+    /// (see `Plan::check`), e.g. because it has no entries, one of its
+    /// sections has no entries or the entry it starts at is out of range.
+    pub fn build(&self) -> Result<Plan> {
+        let mut plan = Plan::from_entries(&self.name, self.entries.clone());
+        plan.cyclic = self.cyclic;
+        plan.author = self.author.clone();
+        plan.source_url = self.source_url.clone();
+        plan.created_at = self.created_at;
+        plan.modified_at = self.modified_at;
+        plan.set_sections(self.sections.clone())?;
+        if self.start_at == 0 {
+            bail!(ErrorKind::InvalidPlan(self.name.clone(), "it starts before its first entry".into()));
+        }
+        plan.current_entry = self.start_at - 1;
+        let mut tracks = self.tracks.clone();
+        let ends: Vec<_> = tracks.iter().skip(1).map(|t| t.start).chain(Some(self.entries.len())).collect();
+        for (track, end) in tracks.iter_mut().zip(ends) {
+            track.end = end;
+        }
+        plan.set_tracks(tracks)?;
+        plan.check()?;
+
+        Ok(plan)
+    }
+}
@@ -0,0 +1,231 @@
+//! This module provides the prerequisites of the entries of a plan, for
+//! plans whose entries build on each other (e.g. a list of technical papers
+//! where one relies on another), so that an entry can be marked as coming
+//! after another.
+//!
+//! The prerequisites of an entry are other entries of the plan, which are
+//! kept by title, so that they stay the same when the entries are edited or
+//! reordered; a title shared by several entries refers to the first of
+//! them. Prerequisites don't change how a plan is read: they are used to
+//! put the remaining entries of a plan in an order which respects them (see
+//! `Plan::reorder_respecting_dependencies`), and `Plan::validate` warns
+//! about any entry which comes before one of its prerequisites.
+//!
+//! In the plain text format, each prerequisite is a header field giving the
+//! title of the entry and then that of its prerequisite, separated by `|`
+//! (a `|` in a title is escaped as `\|`), e.g. `after: Borrowing | Ownership`.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use super::{Entry, Plan, unescape_line};
+use super::super::errors::*;
+
+impl Entry {
+    /// Returns the titles of the prerequisites of the entry, in the order
+    /// they were added.
+    pub fn prerequisites(&self) -> &[String] {
+        &self.prerequisites
+    }
+}
+
+impl Plan {
+    /// Returns the indexes (0-based) of the prerequisites of the entry with
+    /// the given index, leaving out any whose titles don't belong to an entry
+    /// of the plan.
+    pub fn prerequisites_of(&self, index: usize) -> Vec<usize> {
+        let titles = self.first_indexes();
+        self.prerequisite_indexes(index, &titles)
+    }
+
+    /// Returns the index (0-based) of the entry a prerequisite with the
+    /// given title refers to (the first entry with that title), if there is
+    /// one.
+    pub fn title_index(&self, title: &str) -> Option<usize> {
+        self.entries.iter().position(|e| e.title == title)
+    }
+
+    /// Makes the entry with the index `prerequisite` a prerequisite of the
+    /// entry with the index `index` (both 0-based), so that it should come
+    /// before it.
+    ///
+    /// A `PrerequisiteCycle` error is returned if the entry is (directly or
+    /// not) already a prerequisite of its new prerequisite, or if they are
+    /// the same entry. An entry with the same title as an earlier entry
+    /// can't be a prerequisite, since its title refers to the earlier one.
+    pub fn add_prerequisite(&mut self, index: usize, prerequisite: usize) -> Result<()> {
+        for &i in &[index, prerequisite] {
+            if i >= self.len() {
+                return Err(ErrorKind::EntryOutOfRange(i + 1, self.len()).into());
+            }
+        }
+        let titles = self.first_indexes();
+        let title = self.entries[prerequisite].title.clone();
+        if titles[title.as_str()] != prerequisite {
+            bail!("entry {} has the same title as an earlier entry, so it can't be a prerequisite",
+                  prerequisite + 1);
+        }
+        if self.entries[index].prerequisites.contains(&title) {
+            return Ok(());
+        }
+
+        // The entries which must come before the prerequisite
+        let mut before = vec![prerequisite];
+        let mut seen = vec![false; self.len()];
+        while let Some(i) = before.pop() {
+            if i == index {
+                return Err(ErrorKind::PrerequisiteCycle(self.name.clone(), self.entries[index].title.clone())
+                    .into());
+            }
+            if !seen[i] {
+                seen[i] = true;
+                before.extend(self.prerequisite_indexes(i, &titles));
+            }
+        }
+        self.entries[index].prerequisites.push(title);
+        Ok(())
+    }
+
+    /// Removes the entry with the index `prerequisite` from the
+    /// prerequisites of the entry with the index `index` (both 0-based),
+    /// returning whether it was one of them.
+    pub fn remove_prerequisite(&mut self, index: usize, prerequisite: usize) -> bool {
+        let title = match self.entries.get(prerequisite) {
+            Some(entry) => entry.title.clone(),
+            None => return false,
+        };
+        match self.entries.get_mut(index) {
+            Some(entry) => {
+                let len = entry.prerequisites.len();
+                entry.prerequisites.retain(|t| *t != title);
+                entry.prerequisites.len() < len
+            }
+            None => false,
+        }
+    }
+
+    /// Puts the entries which haven't been read yet (the current entry and
+    /// the ones after it) in an order where every entry comes after its
+    /// prerequisites, in the same way as `reverse`, and returns whether any
+    /// of them moved.
+    ///
+    /// The entries are otherwise kept in their current order, so an entry
+    /// only moves if it came before one of its prerequisites (or one of
+    /// theirs). Prerequisites which have already been read are already in
+    /// order. If some of the entries are prerequisites of each other, a
+    /// `PrerequisiteCycle` error is returned, and the plan is left unchanged.
+    /// As with `reverse`, the entries of a plan with tracks or readers are
+    /// left alone.
+    pub fn reorder_respecting_dependencies(&mut self) -> Result<bool> {
+        if !self.tracks.is_empty() || !self.readers.is_empty() {
+            return Ok(false);
+        }
+        let start = self.current_entry;
+        let titles = self.first_indexes();
+        // The number of remaining prerequisites of each remaining entry, and
+        // the entries each one is a prerequisite of
+        let mut waiting = vec![0; self.len()];
+        let mut dependents = vec![Vec::new(); self.len()];
+        for (i, waiting) in waiting.iter_mut().enumerate().skip(start) {
+            for p in self.prerequisite_indexes(i, &titles).into_iter().filter(|&p| p >= start) {
+                *waiting += 1;
+                dependents[p].push(i);
+            }
+        }
+
+        // The earliest entry whose prerequisites have all been placed comes
+        // next, which keeps the order of the entries as far as possible
+        let mut ready: BinaryHeap<_> = (start..self.len()).filter(|&i| waiting[i] == 0).map(Reverse).collect();
+        let mut order = Vec::with_capacity(self.len() - start);
+        while let Some(Reverse(i)) = ready.pop() {
+            order.push(i);
+            for &d in &dependents[i] {
+                waiting[d] -= 1;
+                if waiting[d] == 0 {
+                    ready.push(Reverse(d));
+                }
+            }
+        }
+        if order.len() < self.len() - start {
+            let stuck = (start..self.len()).find(|&i| waiting[i] > 0).unwrap();
+            return Err(ErrorKind::PrerequisiteCycle(self.name.clone(), self.entries[stuck].title.clone()).into());
+        }
+
+        let moved = order.iter().enumerate().any(|(i, &old)| start + i != old);
+        if moved {
+            self.reorder_remaining(order);
+        }
+        Ok(moved)
+    }
+
+    /// Adds a prerequisite from a field in the header of the plain text
+    /// format (see the module documentation).
+    pub(super) fn set_prerequisite_field(&mut self, value: &str) -> Result<()> {
+        let invalid = || -> Error { ErrorKind::InvalidPropertyValue("after".into(), value.into()).into() };
+        let (title, prerequisite) = split_titles(value).ok_or_else(&invalid)?;
+        let entry = self.entries.iter_mut().find(|e| e.title == title).ok_or_else(&invalid)?;
+        if !entry.prerequisites.contains(&prerequisite) {
+            entry.prerequisites.push(prerequisite);
+        }
+        Ok(())
+    }
+
+    /// Returns the header fields for the prerequisites of the entries in the
+    /// plain text format (see `set_prerequisite_field`).
+    pub(super) fn prerequisite_fields(&self) -> Vec<(&'static str, String)> {
+        self.entries
+            .iter()
+            .flat_map(|e| {
+                e.prerequisites
+                    .iter()
+                    .map(move |p| ("after", format!("{} | {}", escape_title(&e.title), escape_title(p))))
+            })
+            .collect()
+    }
+
+    /// Returns the index of the first entry with each title.
+    pub(super) fn first_indexes(&self) -> HashMap<&str, usize> {
+        let mut titles = HashMap::new();
+        for (i, entry) in self.entries.iter().enumerate().rev() {
+            titles.insert(entry.title.as_str(), i);
+        }
+        titles
+    }
+
+    /// Returns the indexes of the prerequisites of the entry with the given
+    /// index, given the index of the first entry with each title.
+    fn prerequisite_indexes(&self, index: usize, titles: &HashMap<&str, usize>) -> Vec<usize> {
+        self.entries[index]
+            .prerequisites
+            .iter()
+            .filter_map(|t| titles.get(t.as_str()).cloned())
+            .filter(|&p| p != index)
+            .collect()
+    }
+}
+
+/// Escapes a title for a prerequisite header field.
+fn escape_title(title: &str) -> String {
+    title.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+/// Splits the value of a prerequisite header field into the title of the
+/// entry and that of its prerequisite, at the first unescaped `|`.
+fn split_titles(value: &str) -> Option<(String, String)> {
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '|' => {
+                let (title, prerequisite) = (value[..i].trim(), value[i + 1..].trim());
+                if title.is_empty() || prerequisite.is_empty() {
+                    return None;
+                }
+                return Some((unescape_line(title), unescape_line(prerequisite)));
+            }
+            _ => {}
+        }
+    }
+    None
+}
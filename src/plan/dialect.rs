@@ -0,0 +1,170 @@
+//! This module provides `TextFormatOptions`, which describe a dialect of
+//! the plain text format for `Plan::from_text_with`, for files which mark
+//! their entries and descriptions differently from the standard format
+//! (such as lists with bullets, or descriptions after a `: ` marker).
+//!
+//! The standard format (see `Plan::from_text`) is the default, and a few
+//! common dialects are available by name (see `TextFormatOptions::dialect`).
+//! Whatever the dialect, a plan may start with a header, entries may have
+//! schedules and estimates in their titles, unindented lines starting
+//! with `#` are section headers, and those starting with `@` start tracks.
+
+/// The names of the dialects available through `TextFormatOptions::dialect`.
+pub const DIALECTS: &[&str] = &["standard", "bullets", "definitions", "paragraphs"];
+
+/// What a blank line means in a plan in the plain text format.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BlankLines {
+    /// A blank line ends a paragraph of the description of the current
+    /// entry, so a description continuing after it starts a new one (as in
+    /// the standard format).
+    EndEntry,
+    /// Blank lines are ignored, so a description can continue after one.
+    Ignore,
+    /// Entries are paragraphs separated by blank lines: the first line of
+    /// each paragraph is the title, and the lines after it are the
+    /// description, whether or not they are indented.
+    Paragraphs,
+}
+
+/// Describes how entries and their descriptions are written in a dialect of
+/// the plain text format.
+///
+/// The methods which change the options return them, so that they can be
+/// chained, e.g. `options.bullets(&["- "]).comment_prefix("//")`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TextFormatOptions {
+    /// Prefixes which may start the title of an entry (and aren't part of
+    /// it), such as `- `
+    bullets: Vec<String>,
+    /// Prefixes which mark a line as part of the description of the entry
+    /// before it, as an alternative to indentation
+    description_markers: Vec<String>,
+    blank_lines: BlankLines,
+    /// The prefix of lines which are ignored, if there is one
+    comment_prefix: Option<String>,
+}
+
+/// A line of a plan in the plain text format, as classified by
+/// `TextFormatOptions::classify`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum Line<'a> {
+    Blank,
+    Comment,
+    /// A section header, with its title
+    Section(&'a str),
+    /// The start of a track, with its name
+    Track(&'a str),
+    /// A line of description, without its indentation or marker
+    Description(&'a str),
+    /// The title of an entry (which may be a description in a paragraph),
+    /// without its bullet
+    Title(&'a str),
+}
+
+impl Default for TextFormatOptions {
+    /// Returns the options for the standard format.
+    fn default() -> TextFormatOptions {
+        TextFormatOptions {
+            bullets: Vec::new(),
+            description_markers: Vec::new(),
+            blank_lines: BlankLines::EndEntry,
+            comment_prefix: None,
+        }
+    }
+}
+
+impl TextFormatOptions {
+    /// Returns the options for the dialect with the given name (one of
+    /// `DIALECTS`), or `None` if there is no such dialect.
+    ///
+    /// The `standard` dialect is the standard format. The others ignore
+    /// lines starting with `//`: in the `bullets` dialect, titles may start
+    /// with a `-`, `*` or `+` bullet (and blank lines are ignored); in the
+    /// `definitions` dialect, lines starting with `: ` are descriptions (and
+    /// blank lines are ignored); and in the `paragraphs` dialect, each
+    /// paragraph is an entry (see `BlankLines::Paragraphs`).
+    pub fn dialect(name: &str) -> Option<TextFormatOptions> {
+        let mut options = TextFormatOptions::default();
+        match name {
+            "standard" => return Some(options),
+            "bullets" => {
+                options.bullets(&["- ", "* ", "+ "]).blank_lines(BlankLines::Ignore);
+            }
+            "definitions" => {
+                options.description_markers(&[": "]).blank_lines(BlankLines::Ignore);
+            }
+            "paragraphs" => {
+                options.blank_lines(BlankLines::Paragraphs);
+            }
+            _ => return None,
+        }
+        options.comment_prefix("//");
+        Some(options)
+    }
+
+    /// Sets the prefixes which may start the title of an entry.
+    pub fn bullets(&mut self, bullets: &[&str]) -> &mut TextFormatOptions {
+        self.bullets = bullets.iter().map(|&b| b.to_owned()).collect();
+        self
+    }
+
+    /// Sets the prefixes which mark a line as part of a description.
+    pub fn description_markers(&mut self, markers: &[&str]) -> &mut TextFormatOptions {
+        self.description_markers = markers.iter().map(|&m| m.to_owned()).collect();
+        self
+    }
+
+    /// Sets what blank lines mean.
+    pub fn blank_lines(&mut self, blank_lines: BlankLines) -> &mut TextFormatOptions {
+        self.blank_lines = blank_lines;
+        self
+    }
+
+    /// Sets the prefix of lines which are ignored.
+    pub fn comment_prefix(&mut self, prefix: &str) -> &mut TextFormatOptions {
+        self.comment_prefix = Some(prefix.to_owned());
+        self
+    }
+
+    /// Returns what blank lines mean.
+    pub fn blank_lines_mean(&self) -> BlankLines {
+        self.blank_lines
+    }
+
+    /// Returns whether these are the options for the standard format.
+    pub fn is_standard(&self) -> bool {
+        *self == TextFormatOptions::default()
+    }
+
+    /// Classifies a line of a plan (after the header), which should have no
+    /// trailing whitespace.
+    pub(crate) fn classify<'a>(&self, line: &'a str) -> Line<'a> {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() {
+            return Line::Blank;
+        }
+        if self.comment_prefix.as_ref().is_some_and(|p| trimmed.starts_with(p.as_str())) {
+            return Line::Comment;
+        }
+        if line.starts_with(char::is_whitespace) {
+            return Line::Description(trimmed);
+        }
+        if let Some(description) = strip_any(line, &self.description_markers) {
+            return Line::Description(description.trim_start());
+        }
+        if let Some(title) = line.strip_prefix('#') {
+            return Line::Section(title.trim());
+        }
+        if let Some(name) = line.strip_prefix('@') {
+            return Line::Track(name.trim());
+        }
+        Line::Title(strip_any(line, &self.bullets).map_or(line, str::trim_start))
+    }
+}
+
+/// Removes the first of the given prefixes which the line starts with, if
+/// any.
+fn strip_any<'a>(line: &'a str, prefixes: &[String]) -> Option<&'a str> {
+    prefixes.iter().find_map(|p| line.strip_prefix(p.as_str()))
+}
@@ -0,0 +1,317 @@
+//! This module provides `PlanDiff`, which describes how the entries of a plan
+//! differ from those of another version of it (such as an updated copy of
+//! the file it was added from), and `Plan::apply_update`, which brings a
+//! plan up to date with a new version without losing its progress.
+//!
+//! Entries are matched up by their titles, keeping them in order, so that
+//! an entry which moved is treated as removed and added again. Where the
+//! same number of entries were removed and added between two matching
+//! entries, they are treated as renamed instead, so that a corrected title
+//! doesn't lose the entry's notes or progress.
+
+use std::cmp;
+
+use super::{Entry, Plan, Section};
+use super::track::Track;
+
+/// A change to the entries of a plan.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum EntryChange {
+    /// An entry was added (includes its index in the new version and its
+    /// title).
+    Added { index: usize, title: String },
+    /// An entry was removed (includes its index in the old version and its
+    /// title).
+    Removed { index: usize, title: String },
+    /// An entry was given a new title (includes its indexes and titles in
+    /// the old and new versions).
+    Renamed {
+        old_index: usize,
+        new_index: usize,
+        old_title: String,
+        new_title: String,
+    },
+}
+
+/// The differences between the entries of two versions of a plan.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PlanDiff {
+    changes: Vec<EntryChange>,
+    /// How the entries of the two versions line up, in order
+    steps: Vec<Step>,
+}
+
+/// A single step in lining up the entries of two versions of a plan, with
+/// the indexes of the entries involved.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Step {
+    Kept(usize, usize),
+    Renamed(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+/// Describes what happens to entries which are missing from the new version
+/// of a plan when it is applied (see `Plan::apply_update`).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum UpdateStrategy {
+    /// The plan ends up with exactly the entries of the new version, and the
+    /// history of removed entries is forgotten.
+    Replace,
+    /// Entries missing from the new version are kept where they were, so
+    /// that only new entries (and new titles) are taken from it. This is
+    /// useful for sources which only list recent entries, such as feeds.
+    Merge,
+}
+
+impl PlanDiff {
+    /// Lines up the given old and new entries by their titles.
+    fn new(old: &[Entry], new: &[Entry]) -> PlanDiff {
+        let steps = align(old, new);
+        let changes = steps.iter()
+            .filter_map(|step| match *step {
+                Step::Kept(_, _) => None,
+                Step::Renamed(o, n) => {
+                    Some(EntryChange::Renamed {
+                        old_index: o,
+                        new_index: n,
+                        old_title: old[o].title.clone(),
+                        new_title: new[n].title.clone(),
+                    })
+                }
+                Step::Removed(o) => {
+                    Some(EntryChange::Removed {
+                        index: o,
+                        title: old[o].title.clone(),
+                    })
+                }
+                Step::Added(n) => {
+                    Some(EntryChange::Added {
+                        index: n,
+                        title: new[n].title.clone(),
+                    })
+                }
+            })
+            .collect();
+        PlanDiff { changes, steps }
+    }
+
+    /// Returns the changes, in the order of the entries they affect.
+    pub fn changes(&self) -> &[EntryChange] {
+        &self.changes
+    }
+
+    /// Returns whether no entries were added, removed or renamed (although
+    /// the entries may still have different descriptions, etc.).
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Returns the number of entries added.
+    pub fn added(&self) -> usize {
+        self.changes.iter().filter(|c| matches!(c, EntryChange::Added { .. })).count()
+    }
+
+    /// Returns the number of entries removed.
+    pub fn removed(&self) -> usize {
+        self.changes.iter().filter(|c| matches!(c, EntryChange::Removed { .. })).count()
+    }
+
+    /// Returns the number of entries renamed.
+    pub fn renamed(&self) -> usize {
+        self.changes.iter().filter(|c| matches!(c, EntryChange::Renamed { .. })).count()
+    }
+
+    /// Returns the index (0-based) in the new version of the entry with the
+    /// given index in the old version, or `None` if it was removed.
+    pub fn new_index(&self, old_index: usize) -> Option<usize> {
+        self.steps.iter().filter_map(|step| match *step {
+            Step::Kept(o, n) | Step::Renamed(o, n) if o == old_index => Some(n),
+            _ => None,
+        }).next()
+    }
+}
+
+/// Lines up two lists of entries, keeping as many titles as possible.
+fn align(old: &[Entry], new: &[Entry]) -> Vec<Step> {
+    // Most updates only change a few entries, so the entries which are the
+    // same at the start and end are lined up first to keep the table below
+    // small
+    let prefix = old.iter().zip(new).take_while(|&(o, n)| o.title == n.title).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|&(o, n)| o.title == n.title)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    // The length of the longest common subsequence of the titles of
+    // `old_mid[i..]` and `new_mid[j..]`
+    let width = new_mid.len() + 1;
+    let mut lengths = vec![0; (old_mid.len() + 1) * width];
+    for i in (0..old_mid.len()).rev() {
+        for j in (0..new_mid.len()).rev() {
+            lengths[i * width + j] = if old_mid[i].title == new_mid[j].title {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                cmp::max(lengths[(i + 1) * width + j], lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut steps: Vec<_> = (0..prefix).map(|i| Step::Kept(i, i)).collect();
+    // Entries removed and added since the last entry which was kept
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old_mid.len() || j < new_mid.len() {
+        if i < old_mid.len() && j < new_mid.len() && old_mid[i].title == new_mid[j].title {
+            push_gap(&mut steps, &mut removed, &mut added);
+            steps.push(Step::Kept(prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if j == new_mid.len() ||
+                  (i < old_mid.len() && lengths[(i + 1) * width + j] >= lengths[i * width + j + 1]) {
+            removed.push(prefix + i);
+            i += 1;
+        } else {
+            added.push(prefix + j);
+            j += 1;
+        }
+    }
+    push_gap(&mut steps, &mut removed, &mut added);
+    steps.extend((0..suffix).map(|k| Step::Kept(old.len() - suffix + k, new.len() - suffix + k)));
+    steps
+}
+
+/// Adds steps for the given entries removed from and added to the same
+/// place, pairing them up as renamed entries if there are as many of each.
+fn push_gap(steps: &mut Vec<Step>, removed: &mut Vec<usize>, added: &mut Vec<usize>) {
+    if removed.len() == added.len() {
+        steps.extend(removed.iter().zip(added.iter()).map(|(&o, &n)| Step::Renamed(o, n)));
+    } else {
+        steps.extend(removed.iter().map(|&o| Step::Removed(o)));
+        steps.extend(added.iter().map(|&n| Step::Added(n)));
+    }
+    removed.clear();
+    added.clear();
+}
+
+impl Plan {
+    /// Returns the differences between the entries of this plan and those
+    /// of the given plan (treating this plan as the old version).
+    pub fn diff(&self, other: &Plan) -> PlanDiff {
+        PlanDiff::new(&self.entries, &other.entries)
+    }
+
+    /// Updates the entries and sections of this plan to those of the given
+    /// plan (such as a newer version of the file it was added from), keeping
+    /// its progress, and returns the changes that were made.
+    ///
+    /// The entries which are kept or renamed keep their notes (unless the
+    /// new version has its own) and their history, and the current entry
+    /// stays after the last entry which was read before, so that the
+    /// entries added after it are read next. Entries missing from the new
+    /// version are handled according to the given strategy (and are not
+    /// included in the changes if they are kept, in which case the new
+    /// indexes of the changes are those in the updated plan). The other
+    /// properties of the plan, such as its name and tags, are left alone.
+    /// The progress of each reader of the plan is kept in the same way.
+    pub fn apply_update(&mut self, other: &Plan, strategy: UpdateStrategy) -> PlanDiff {
+        self.map_readers(|plan| {
+            plan.apply_update(other, strategy);
+        });
+        let mut diff = self.diff(other);
+        let old_current = self.current_entry;
+        let mut old_entries: Vec<_> = self.entries.drain(..).map(Some).collect();
+        // The index of each old entry in the updated plan, if it was kept
+        let mut new_index = vec![None; old_entries.len()];
+        // The index of each entry of the new version in the updated plan
+        let mut other_index = vec![0; other.len()];
+
+        for step in &diff.steps {
+            match *step {
+                Step::Kept(o, n) | Step::Renamed(o, n) => {
+                    let old = old_entries[o].take().unwrap();
+                    let mut entry = other.entries[n].clone();
+                    if entry.notes.is_empty() {
+                        entry.notes = old.notes;
+                    }
+                    entry.skipped = old.skipped;
+                    new_index[o] = Some(self.entries.len());
+                    other_index[n] = self.entries.len();
+                    self.entries.push(entry);
+                }
+                Step::Removed(o) => {
+                    if strategy == UpdateStrategy::Merge {
+                        new_index[o] = Some(self.entries.len());
+                        self.entries.push(old_entries[o].take().unwrap());
+                    }
+                }
+                Step::Added(n) => {
+                    other_index[n] = self.entries.len();
+                    self.entries.push(other.entries[n].clone());
+                }
+            }
+        }
+
+        let last_read = new_index[..old_current.min(new_index.len())].iter().rev().filter_map(|&i| i).next();
+        self.current_entry = match last_read {
+            Some(i) => i + 1,
+            // If none of the entries which were read are left, the plan
+            // continues from the first one which wasn't read
+            None if old_current > 0 => new_index[old_current.min(new_index.len())..]
+                .iter()
+                .filter_map(|&i| i)
+                .next()
+                .unwrap_or(0),
+            None => 0,
+        };
+        if self.cyclic && self.current_entry == self.len() {
+            self.current_entry = 0;
+        }
+
+        self.history.retain(|e| new_index.get(e.entry).is_some_and(|i| i.is_some()));
+        for event in &mut self.history {
+            event.entry = new_index[event.entry].unwrap();
+        }
+        self.bookmarks.retain(|b| new_index.get(b.entry).is_some_and(|i| i.is_some()));
+        for bookmark in &mut self.bookmarks {
+            bookmark.entry = new_index[bookmark.entry].unwrap();
+        }
+        self.sections = other.sections
+            .iter()
+            .map(|s| Section::new(&s.title, other_index[s.start]))
+            .collect();
+        // Any entries kept from before the first track of the new version
+        // join it, so that every entry is still in a track
+        let starts: Vec<_> = other.tracks
+            .iter()
+            .enumerate()
+            .map(|(i, t)| if i == 0 { 0 } else { other_index[t.start] })
+            .chain(Some(self.len()))
+            .collect();
+        let tracks = other.tracks
+            .iter()
+            .zip(starts.windows(2))
+            .map(|(t, w)| Track::new(&t.name, w[0]..w[1]))
+            .collect();
+        // The tracks can't be invalid, since each of them has at least the
+        // entries it had in the new version
+        let _ = self.set_tracks(tracks);
+
+        if strategy == UpdateStrategy::Merge {
+            diff.changes.retain(|c| !matches!(c, EntryChange::Removed { .. }));
+            for change in &mut diff.changes {
+                match *change {
+                    EntryChange::Added { ref mut index, .. } |
+                    EntryChange::Renamed { new_index: ref mut index, .. } => *index = other_index[*index],
+                    EntryChange::Removed { .. } => {}
+                }
+            }
+        }
+        diff
+    }
+}
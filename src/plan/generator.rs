@@ -0,0 +1,112 @@
+//! This module provides `Generator`, which creates plans by splitting a
+//! numbered range (such as the pages or chapters of a book) into evenly
+//! sized entries, so that simple plans don't have to be written by hand.
+//!
+//! Each entry is given a title describing the part of the range it covers,
+//! such as `Pages 1–21` or `Chapter 7`.
+
+use super::{Entry, Plan};
+use super::super::errors::*;
+
+/// Describes how a range should be split into entries.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Split {
+    /// Split the range into the given number of entries, as evenly as
+    /// possible (earlier entries are made longer if necessary).
+    Entries(u32),
+    /// Split the range into entries of the given length (the last entry
+    /// may be shorter).
+    PerEntry(u32),
+}
+
+/// Generates plans covering a numbered range.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Generator {
+    unit: String,
+    start: u32,
+    end: u32,
+    split: Split,
+}
+
+impl Generator {
+    /// Returns a generator for the range `start` to `end` (inclusive) of
+    /// the given unit, e.g. "page" or "chapter".
+    ///
+    /// The unit is used (capitalized) in the titles of the entries, and is
+    /// made plural by adding an "s" for entries covering more than one
+    /// unit.
+    pub fn new(unit: &str, start: u32, end: u32, split: Split) -> Generator {
+        Generator {
+            unit: unit.to_owned(),
+            start,
+            end,
+            split,
+        }
+    }
+
+    /// Returns the entries covering the range, or an `InvalidGenerator`
+    /// error if the parameters don't make sense (e.g. the range is
+    /// backwards or there are more entries than units in the range).
+    pub fn entries(&self) -> Result<Vec<Entry>> {
+        if self.unit.trim().is_empty() {
+            return Err(ErrorKind::InvalidGenerator("the unit must not be empty".into()).into());
+        }
+        if self.end < self.start {
+            return Err(ErrorKind::InvalidGenerator(format!("the range {}-{} is backwards",
+                                                           self.start,
+                                                           self.end))
+                .into());
+        }
+        let total = self.end - self.start + 1;
+
+        let sizes = match self.split {
+            Split::Entries(0) | Split::PerEntry(0) => {
+                return Err(ErrorKind::InvalidGenerator("entries must not be empty".into()).into())
+            }
+            Split::Entries(n) if n > total => {
+                return Err(ErrorKind::InvalidGenerator(format!("cannot split {} {}s into {} entries",
+                                                               total,
+                                                               self.unit,
+                                                               n))
+                    .into())
+            }
+            Split::Entries(n) => {
+                (0..n).map(|i| total / n + if i < total % n { 1 } else { 0 }).collect::<Vec<_>>()
+            }
+            Split::PerEntry(k) => {
+                (0..total.div_ceil(k)).map(|i| k.min(total - i * k)).collect::<Vec<_>>()
+            }
+        };
+
+        let mut first = self.start;
+        Ok(sizes.into_iter()
+            .map(|size| {
+                let last = first + size - 1;
+                let entry = Entry::new(&self.title(first, last));
+                first = last + 1;
+                entry
+            })
+            .collect())
+    }
+
+    /// Generates a plan with the given name.
+    pub fn generate(&self, name: &str) -> Result<Plan> {
+        Ok(Plan::from_entries(name, self.entries()?))
+    }
+
+    /// Returns the title of an entry covering the given units.
+    fn title(&self, first: u32, last: u32) -> String {
+        let unit = self.unit.trim();
+        let mut chars = unit.chars();
+        let unit = match chars.next() {
+            Some(c) => c.to_uppercase().collect::<String>() + chars.as_str(),
+            None => String::new(),
+        };
+
+        if first == last {
+            format!("{} {}", unit, first)
+        } else {
+            format!("{}s {}–{}", unit, first, last)
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! This module provides `Plan::from_goodreads`, which reads the CSV file
+//! Goodreads exports a user's library as (from "Import and export" in the
+//! settings of "My Books").
+//!
+//! The export has a header record naming its columns, of which only
+//! `Title` is required; the others used are `Author`, `Exclusive Shelf`,
+//! `Bookshelves`, `Bookshelves with positions`, `Date Added` and
+//! `My Review`. Any other columns (such as ratings and ISBNs) are ignored.
+
+use std::io::Read;
+
+use csv;
+
+use super::{Entry, Plan};
+use super::super::errors::*;
+
+/// The shelf of books which have been read, which are left out of the plan.
+const READ_SHELF: &str = "read";
+
+/// A book from a Goodreads export, along with what it is sorted by.
+struct Book {
+    entry: Entry,
+    /// The position of the book on its shelf, if the export gives one
+    position: Option<u32>,
+    /// When the book was added, as `YYYY/MM/DD` (so that it sorts by date)
+    added: String,
+}
+
+impl Plan {
+    /// Attempts to construct a plan from a Goodreads library export.
+    ///
+    /// Each book which isn't on the `read` shelf is an entry, titled with
+    /// its title and author (e.g. "Dune by Frank Herbert"), and described by
+    /// the shelves it is on and the review written for it, if any. The books
+    /// are in the order of their positions on their shelves (the order they
+    /// were put in on the "to-read" shelf, for example), or else the order
+    /// in which they were added. Each entry of the plan is a book (see
+    /// `Plan::unit_label`).
+    ///
+    /// The resulting plan will be acyclic; this can be changed after creation
+    /// with the `set_cyclic` method.
+    pub fn from_goodreads<T: Read>(name: &str, input: T) -> Result<Plan> {
+        let mut r = csv::ReaderBuilder::new().flexible(true).from_reader(input);
+        let headers = r.headers().chain_err(|| "could not read header record")?.clone();
+        let column = |name: &str| headers.iter().position(|h| h.trim().eq_ignore_ascii_case(name));
+        let title_column = column("Title").ok_or_else(|| {
+                ErrorKind::TextFormat("the header has no 'Title' column (is this a Goodreads export?)".into())
+            })?;
+        let author_column = column("Author");
+        let shelf_column = column("Exclusive Shelf");
+        let shelves_column = column("Bookshelves");
+        let positions_column = column("Bookshelves with positions");
+        let added_column = column("Date Added");
+        let review_column = column("My Review");
+
+        let mut books = Vec::new();
+        for (n, record) in r.records().enumerate() {
+            // The header is record 1
+            let record = record.chain_err(|| format!("could not read record {}", n + 2))?;
+            let field = |column: Option<usize>| column.and_then(|i| record.get(i)).map_or("", str::trim);
+            let shelf = field(shelf_column);
+            if shelf == READ_SHELF {
+                continue;
+            }
+            let title = field(Some(title_column));
+            if title.is_empty() {
+                return Err(ErrorKind::TextFormat(format!("record {} has no title", n + 2)).into());
+            }
+
+            let title = match field(author_column) {
+                "" => title.to_owned(),
+                author => format!("{} by {}", title, author),
+            };
+            let mut shelves: Vec<_> = field(shelves_column)
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .collect();
+            if !shelf.is_empty() && !shelves.contains(&shelf) {
+                shelves.insert(0, shelf);
+            }
+            let mut description = Vec::new();
+            if !shelves.is_empty() {
+                description.push(format!("Shelves: {}", shelves.join(", ")));
+            }
+            let review = field(review_column);
+            if !review.is_empty() {
+                // Line breaks in reviews are exported as HTML
+                description.push(review.replace("<br/>", "\n").replace("<br />", "\n"));
+            }
+
+            books.push(Book {
+                entry: Entry::with_description(&title, &description.join("\n")),
+                position: shelf_position(field(positions_column), shelf),
+                added: field(added_column).to_owned(),
+            });
+        }
+
+        if books.is_empty() {
+            return Err(ErrorKind::TextFormat("there are no books which haven't been read".into()).into());
+        }
+        // Books without a position (which can only happen if the export
+        // doesn't give positions for every shelf) go last
+        books.sort_by(|a, b| {
+            (a.position.is_none(), a.position, &a.added).cmp(&(b.position.is_none(), b.position, &b.added))
+        });
+        let mut plan = Plan::from_entries(name, books.into_iter().map(|b| b.entry).collect());
+        plan.unit_label = "book".to_owned();
+        Ok(plan)
+    }
+}
+
+/// Returns the position of a book on the given shelf, from a list of its
+/// shelves with positions such as `to-read (#12), fantasy (#3)`.
+fn shelf_position(positions: &str, shelf: &str) -> Option<u32> {
+    positions.split(',').find_map(|s| {
+        let (name, position) = s.trim().split_once(" (#")?;
+        if name == shelf {
+            position.strip_suffix(')')?.parse().ok()
+        } else {
+            None
+        }
+    })
+}
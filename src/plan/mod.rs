@@ -0,0 +1,2943 @@
+//! This module provides the basic `Plan` type and a variety of ways
+//! to work with them, including reading and writing them from/to plain
+//! text files, via the `from_text` and `to_text` methods, respectively
+//! (`to_text_with_progress` also writes the progress through the plan,
+//! and `from_text_with` reads other dialects of the format, described by
+//! the `dialect` module).
+//! Plans can also be read from and written to CSV files, via the
+//! `from_csv` and `to_csv` methods, read from OPML outlines (such as
+//! podcast subscriptions or article queues) via `from_opml`, from RSS and
+//! Atom feeds (such as a blog's archive) via `from_feed` or from Goodreads
+//! library exports via `from_goodreads` (in the `goodreads` module), or
+//! generated from a range of pages or chapters using the `generator` module.
+//! Programs constructing plans entry by entry can use the `builder` module,
+//! and a plan can be brought up to date with a newer version of itself
+//! using the `diff` module.
+//! Text which is about to become a plan can be checked for suspicious
+//! formatting with `text_warnings`, and the entries of a plan for likely
+//! mistakes with `Plan::validate` (in the `validate` module).
+//! Entries may have prerequisites which should come before them, as
+//! described in the `dependency` module.
+//! A plan may also be read only on certain days of the week, as described in
+//! the `weekdays` module.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::iter;
+use std::ops;
+use std::io::{self, Read, BufRead, BufReader, Write, BufWriter};
+use std::slice;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, SecondsFormat, Utc, Weekday};
+use csv;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de;
+#[cfg(feature = "yaml-format")]
+use serde_yaml;
+#[cfg(feature = "toml-format")]
+use toml;
+use xml::reader::{EventReader, XmlEvent};
+
+#[cfg(feature = "bible")]
+pub mod bible;
+pub mod builder;
+pub mod dependency;
+pub mod dialect;
+pub mod diff;
+pub mod generator;
+pub mod goodreads;
+pub mod pace;
+pub mod reader;
+pub mod summary;
+pub mod track;
+pub mod validate;
+pub mod weekdays;
+
+use self::builder::PlanBuilder;
+use self::dialect::{BlankLines, Line, TextFormatOptions};
+use self::pace::{Pace, PaceTarget, Projection};
+use self::weekdays::ActiveDays;
+use self::reader::ReaderProgress;
+use self::track::Track;
+
+/// Represents a single entry in a reading plan, containing
+/// a title and description, the latter of which may be empty.
+/// An entry may also be scheduled to be read at a certain time, and may
+/// link to what should be read (e.g. a web article).
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Entry {
+    title: String,
+    description: String,
+    /// When the entry should be read, if it has been scheduled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    schedule: Option<Schedule>,
+    /// The URL of the entry's content, if it is online
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    /// How long the entry is expected to take to read, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    estimate: Option<Estimate>,
+    /// Notes written about the entry, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    notes: Vec<Note>,
+    /// Whether the entry was skipped rather than read the last time the
+    /// plan was advanced past it
+    #[serde(default, skip_serializing_if = "::std::ops::Not::not")]
+    skipped: bool,
+    /// The titles of the entries which should come before this one (see
+    /// the `dependency` module)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    prerequisites: Vec<String>,
+}
+
+/// The status of an entry in the current pass through a plan (see
+/// `Plan::entry_status`).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EntryStatus {
+    /// The entry is the current entry or comes after it.
+    Pending,
+    /// The plan was advanced past the entry normally.
+    Completed,
+    /// The entry was skipped (see `Plan::skip_current`).
+    Skipped,
+}
+
+/// A note written about an entry (e.g. thoughts after reading it).
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Note {
+    /// The text of the note
+    text: String,
+    /// When the note was written
+    created_at: DateTime<Utc>,
+}
+
+/// Describes when an entry is scheduled to be read: on a single date, over
+/// a range of dates or on a certain day of every week.
+///
+/// As text (including in the plain text plan format), a schedule is written
+/// either as a date in `YYYY-MM-DD` format, as a range of dates such as
+/// `2017-05-01 to 2017-05-03` or as `every {weekday}`, e.g. `every Monday`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Schedule {
+    /// A single date.
+    Date(NaiveDate),
+    /// A range of dates (including both the first and the last).
+    Range(NaiveDate, NaiveDate),
+    /// The given day of every week.
+    Weekly(Weekday),
+}
+
+/// An estimate of how long something (such as an entry) takes to read, in
+/// whole minutes.
+///
+/// As text (including in the plain text plan format), an estimate is
+/// written in hours and/or minutes, e.g. `25m`, `2h` or `1h 30m`.
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct Estimate {
+    minutes: u32,
+}
+
+/// Represents a single reading plan.
+///
+/// Each plan has a name and a list of `Entry`s, and keeps track of the
+/// current entry in the plan. Currently, you can construct a `Plan` by
+/// reading it from plain text input (using the `from_text`
+/// method), constructing it directly from a `Vec<Entry>` (using the
+/// `from_entries`
+/// method), building it up step by step (using `builder::PlanBuilder`),
+/// or by deserializing it from JSON. Likewise, it is possible
+/// to output the `Plan` to plain text using the `to_text` method
+/// or by serializing it using `serde`.
+///
+/// Besides just being a list of entries, a `Plan` also contains information
+/// determining the behavior of certain operations on it. Currently, the
+/// only data of this type is the `cyclic` property; if a plan is cyclic,
+/// it will "wrap around" when using the `next` or `previous` methods;
+/// if a plan is not cyclic, it will stop at the beginning of the plan
+/// or at a designated "end of plan" state.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Plan {
+    /// The version of the format the plan is stored in (see the
+    /// `migrations` module); a plan in memory is always in the current
+    /// version
+    #[serde(default = "current_version")]
+    version: u32,
+    name: String,
+    /// Who wrote the plan, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    author: Option<String>,
+    /// Where the plan came from (e.g. the web page it was published on), if
+    /// known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source_url: Option<String>,
+    /// The content hash (see `content_hash`) of the file the plan was last
+    /// added or updated from, if it came from one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source_hash: Option<String>,
+    /// When the plan was first stored (plans stored before this was
+    /// recorded don't have it)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created_at: Option<DateTime<Utc>>,
+    /// When the plan was last stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    modified_at: Option<DateTime<Utc>>,
+    /// The number of times the plan has been stored, which is used to
+    /// detect whether it was changed by someone else since it was read
+    #[serde(default)]
+    revision: u64,
+    /// Whether the plan is cyclic, i.e. whether it will wrap around
+    /// when the user tries to advance it past a certain entry
+    cyclic: bool,
+    /// Whether the entries of the plan are read in a random order (see
+    /// `set_random`)
+    #[serde(default)]
+    random: bool,
+    /// The current entry of the plan, as a 0-based index (i.e. the first
+    /// entry of the plan is 0). This can be equal to `entries.len()` to
+    /// represent "end of plan", for a plan which is not cyclic.
+    current_entry: usize,
+    entries: Vec<Entry>,
+    /// Tags used to group related plans together (e.g. for bulk operations)
+    #[serde(default)]
+    tags: Vec<String>,
+    /// What a single entry of the plan represents (e.g. "day" or "chapter")
+    #[serde(default = "default_unit_label")]
+    unit_label: String,
+    /// The priority of the plan relative to other plans (higher is more
+    /// important)
+    #[serde(default)]
+    priority: i32,
+    /// A record of every entry completed (by advancing past it), oldest
+    /// first
+    #[serde(default)]
+    history: Vec<HistoryEvent>,
+    /// The number of times a cyclic plan has wrapped around to its start
+    #[serde(default)]
+    cycles_completed: u32,
+    /// Whether the plan has been put aside (it is kept, but hidden from
+    /// most commands)
+    #[serde(default)]
+    archived: bool,
+    /// Whether the plan is one of the user's favorites, which come first
+    /// in lists and are used when no plan is named
+    #[serde(default)]
+    pinned: bool,
+    /// When the last entry of an acyclic plan was completed, ending the plan
+    /// (this is only meaningful while the plan is still at its end)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    completed_at: Option<DateTime<Utc>>,
+    /// The rate at which the plan should be read, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pace: Option<PaceTarget>,
+    /// The days of the week on which the plan is read (see the `weekdays`
+    /// module)
+    #[serde(default, skip_serializing_if = "ActiveDays::is_every_day")]
+    active_days: ActiveDays,
+    /// The sections grouping the entries of the plan, ordered by their
+    /// first entries (entries before the first section aren't in any
+    /// section)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    sections: Vec<Section>,
+    /// Named entries marking milestones in the plan, ordered by their
+    /// entries
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    bookmarks: Vec<Bookmark>,
+    /// The tracks the entries of the plan are divided into, each with its
+    /// own position (see the `track` module), in the order of their entries
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tracks: Vec<Track>,
+    /// The progress of the readers sharing the plan, by name (see the
+    /// `reader` module)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    readers: BTreeMap<String, ReaderProgress>,
+}
+
+/// A named group of consecutive entries in a plan, such as a part of a
+/// book (e.g. "Part I: Foundations").
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Section {
+    title: String,
+    /// The index of the first entry in the section (0-based); the section
+    /// continues until the start of the next section
+    start: usize,
+}
+
+/// A named entry of a plan, marking a milestone (e.g. "start of Part II")
+/// which can be gone to by name.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Bookmark {
+    name: String,
+    /// The index of the bookmarked entry (0-based)
+    entry: usize,
+}
+
+/// Something suspicious about the formatting of a plan in the plain text
+/// format, which doesn't stop it from being read but probably means that it
+/// won't be read as intended (see `text_warnings`).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Warning {
+    /// The line the warning is about (starting at 1)
+    line: usize,
+    message: String,
+}
+
+/// A record of an entry of a plan being completed.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct HistoryEvent {
+    /// The index of the completed entry (0-based)
+    entry: usize,
+    /// When the entry was completed
+    completed_at: DateTime<Utc>,
+}
+
+/// An entry of a plan which is due to be read on a certain date (see
+/// `Plan::due_entries`).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DueEntry<'a> {
+    index: usize,
+    entry: &'a Entry,
+    overdue: bool,
+}
+
+impl<'a> DueEntry<'a> {
+    /// Returns the index of the entry in the plan (0-based).
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the entry.
+    pub fn entry(&self) -> &'a Entry {
+        self.entry
+    }
+
+    /// Returns whether the entry was scheduled for an earlier date.
+    pub fn is_overdue(&self) -> bool {
+        self.overdue
+    }
+}
+
+/// Returns the current version of the plan format.
+fn current_version() -> u32 {
+    migrations::CURRENT_VERSION
+}
+
+/// Returns the unit label used for plans that don't specify one.
+fn default_unit_label() -> String {
+    "entry".into()
+}
+
+/// Parses a boolean property value, accepting the most common ways
+/// of writing one.
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+use super::errors::*;
+use super::migrations;
+
+impl Schedule {
+    /// Returns whether the given date is part of the schedule.
+    pub fn includes(&self, date: NaiveDate) -> bool {
+        match *self {
+            Schedule::Date(d) => d == date,
+            Schedule::Range(start, end) => start <= date && date <= end,
+            Schedule::Weekly(w) => date.weekday() == w,
+        }
+    }
+
+    /// Returns the first date of the schedule, or `None` for a weekly
+    /// schedule.
+    pub fn start_date(&self) -> Option<NaiveDate> {
+        match *self {
+            Schedule::Date(d) | Schedule::Range(d, _) => Some(d),
+            Schedule::Weekly(_) => None,
+        }
+    }
+
+    /// Returns the last date of the schedule, or `None` for a weekly
+    /// schedule.
+    pub fn end_date(&self) -> Option<NaiveDate> {
+        match *self {
+            Schedule::Date(d) | Schedule::Range(_, d) => Some(d),
+            Schedule::Weekly(_) => None,
+        }
+    }
+
+    /// Returns whether the whole schedule is before the given date (which
+    /// is never the case for a weekly schedule).
+    pub fn is_before(&self, date: NaiveDate) -> bool {
+        self.end_date().is_some_and(|d| d < date)
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Schedule::Date(d) => write!(f, "{}", d.format("%Y-%m-%d")),
+            Schedule::Range(start, end) => write!(f, "{} to {}", start.format("%Y-%m-%d"), end.format("%Y-%m-%d")),
+            Schedule::Weekly(w) => write!(f, "every {}", weekday_name(w)),
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Schedule> {
+        let s = s.trim();
+        let lower = s.to_lowercase();
+
+        let date = |d: &str| {
+            NaiveDate::parse_from_str(d.trim(), "%Y-%m-%d")
+                .map_err(|_| Error::from(ErrorKind::TextFormat(format!("invalid date in schedule '{}'", s))))
+        };
+
+        if let Some(weekday) = lower.strip_prefix("every ") {
+            weekday.trim()
+                .parse()
+                .map(Schedule::Weekly)
+                .map_err(|_| ErrorKind::TextFormat(format!("invalid weekday in schedule '{}'", s)).into())
+        } else if let Some((start, end)) = lower.split_once(" to ") {
+            let (start, end) = (date(start)?, date(end)?);
+            if end < start {
+                bail!(ErrorKind::TextFormat(format!("schedule '{}' ends before it starts", s)));
+            }
+            Ok(Schedule::Range(start, end))
+        } else {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .map(Schedule::Date)
+                .map_err(|_| ErrorKind::TextFormat(format!("invalid schedule '{}'", s)).into())
+        }
+    }
+}
+
+impl Serialize for Schedule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Schedule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> ::std::result::Result<Schedule, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+impl Estimate {
+    /// Returns an estimate of the given number of minutes.
+    pub fn from_minutes(minutes: u32) -> Estimate {
+        Estimate { minutes }
+    }
+
+    /// Returns the estimated number of minutes.
+    pub fn minutes(&self) -> u32 {
+        self.minutes
+    }
+}
+
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (hours, minutes) = (self.minutes / 60, self.minutes % 60);
+        match (hours, minutes) {
+            (0, m) => write!(f, "{}m", m),
+            (h, 0) => write!(f, "{}h", h),
+            (h, m) => write!(f, "{}h {}m", h, m),
+        }
+    }
+}
+
+impl FromStr for Estimate {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Estimate> {
+        let invalid = || -> Error { ErrorKind::TextFormat(format!("invalid time estimate '{}'", s.trim())).into() };
+        let mut minutes: u32 = 0;
+        let mut rest = s.trim();
+        if rest.is_empty() {
+            return Err(invalid());
+        }
+
+        // Each part is a number followed by a unit (`h` or `m`)
+        while !rest.is_empty() {
+            let digits = rest.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+            let n: u32 = rest[..digits].parse().map_err(|_| invalid())?;
+            let part = match rest[digits..].chars().next() {
+                Some('h') => n.checked_mul(60),
+                Some('m') => Some(n),
+                _ => None,
+            };
+            minutes = part.and_then(|p| minutes.checked_add(p)).ok_or_else(invalid)?;
+            rest = rest[digits + 1..].trim_start();
+        }
+
+        Ok(Estimate { minutes })
+    }
+}
+
+impl Serialize for Estimate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Estimate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> ::std::result::Result<Estimate, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+impl ops::Add for Estimate {
+    type Output = Estimate;
+
+    fn add(self, other: Estimate) -> Estimate {
+        Estimate { minutes: self.minutes.saturating_add(other.minutes) }
+    }
+}
+
+impl iter::Sum for Estimate {
+    fn sum<I: Iterator<Item = Estimate>>(iter: I) -> Estimate {
+        iter.fold(Estimate::default(), ops::Add::add)
+    }
+}
+
+/// Returns the full English name of the given weekday.
+fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday",
+    }
+}
+
+/// A small pseudo-random number generator (see
+/// <http://xoshiro.di.unimi.it/splitmix64.c>), used to shuffle plans and
+/// pick entries of random plans.
+///
+/// It is used instead of a general-purpose generator so that the same seed
+/// always gives the same order.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    /// Returns the next number in the sequence.
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Returns a seed for picking entries at random, based on the given time.
+fn time_seed(time: DateTime<Utc>) -> u64 {
+    time.timestamp_nanos_opt().unwrap_or_else(|| time.timestamp()) as u64
+}
+
+/// The stylesheet of the HTML pages written by `Plan::to_html`, kept simple so
+/// that they print well.
+const HTML_STYLE: &str = "body { font-family: sans-serif; max-width: 40em; margin: 2em auto; }
+ul { list-style: none; padding-left: 0; }
+li { margin: 0.5em 0; break-inside: avoid; }
+li p { margin: 0.2em 0 0 1.8em; color: #444; }
+.details { color: #777; }
+";
+
+/// Escapes text for use in HTML (in text or attribute values).
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Returns whether a line of the plain text format is a URL.
+fn is_url(line: &str) -> bool {
+    (line.starts_with("http://") || line.starts_with("https://")) && !line.contains(char::is_whitespace)
+}
+
+/// The line ending the header of a plan in the plain text format.
+const HEADER_END: &str = "---";
+
+/// Returns the number of lines in the header of a plan in the plain text
+/// format (including the line ending it), or 0 if it has no header.
+///
+/// If any line before the first `---` line isn't a header field (or blank),
+/// the plan has no header, and the `---` line is just an entry.
+fn header_len(lines: &[String]) -> usize {
+    match lines.iter().position(|l| l.trim_end() == HEADER_END) {
+        Some(end) if lines[..end].iter().all(|l| l.trim().is_empty() || header_field(l).is_some()) => end + 1,
+        _ => 0,
+    }
+}
+
+/// Splits a header field of a plan in the plain text format into its key
+/// and value (e.g. `author: Robert Murray M'Cheyne`).
+fn header_field(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    Some((key, value.trim()))
+}
+
+/// The keys of the header fields of a plan in the plain text format which
+/// describe its progress (see `Plan::to_text_with_progress`).
+const PROGRESS_FIELDS: &[&str] =
+    &["current", "track", "cycles", "completed", "pace_start", "skipped", "bookmark", "read", "note"];
+
+/// Formats a timestamp for a header field (in RFC 3339 format).
+fn format_timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+/// Parses a timestamp in a header field (in RFC 3339 format, as written by
+/// `Plan::to_text`).
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value).ok().map(|t| t.with_timezone(&Utc))
+}
+
+/// Escapes text (such as a note) for a header field, which must fit on a
+/// single line.
+fn escape_line(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+/// Reverses `escape_line`.
+fn unescape_line(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some(c) => result.push(c),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Splits a line of the plain text format into an entry title, its
+/// schedule, which is given in square brackets at the start of the line
+/// (e.g. `[2017-05-01] Genesis 1-3`), and its time estimate, which is given
+/// in parentheses at the end of the line (e.g. `Genesis 1-3 (25m)`).
+///
+/// If the line doesn't start with a valid schedule or end with a valid
+/// estimate, that part of the line is taken to be part of the title.
+fn parse_title(line: &str) -> (&str, Option<Schedule>, Option<Estimate>) {
+    let (mut title, mut schedule, mut estimate) = (line, None, None);
+    if title.starts_with('[') {
+        if let Some(end) = title.find(']') {
+            if let Ok(s) = title[1..end].parse() {
+                schedule = Some(s);
+                title = title[end + 1..].trim_start();
+            }
+        }
+    }
+    if title.ends_with(')') {
+        if let Some(start) = title.rfind('(') {
+            if let Ok(e) = title[start + 1..title.len() - 1].parse() {
+                estimate = Some(e);
+                title = title[..start].trim_end();
+            }
+        }
+    }
+    (title, schedule, estimate)
+}
+
+impl Section {
+    /// Returns a section with the given title, starting at the entry with
+    /// the given index (0-based).
+    pub fn new(title: &str, start: usize) -> Section {
+        Section {
+            title: title.to_owned(),
+            start,
+        }
+    }
+
+    /// Returns the title of the section.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Returns the index of the first entry in the section (0-based).
+    pub fn start(&self) -> usize {
+        self.start
+    }
+}
+
+impl Bookmark {
+    /// Returns the name of the bookmark.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the index of the bookmarked entry (0-based).
+    pub fn entry_index(&self) -> usize {
+        self.entry
+    }
+}
+
+impl Warning {
+    /// Returns the number of the line the warning is about (starting at 1).
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns a description of the problem.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Returns warnings about suspicious formatting in a plan in the plain text
+/// format (see `Plan::from_text`), in order of line number.
+///
+/// Problems which stop the plan from being read at all are reported by
+/// `Plan::from_text` instead; these warnings are about text which will be
+/// read, but probably not in the way the author intended, such as an
+/// invalid schedule (which becomes part of the title) or indentation mixing
+/// tabs and spaces.
+pub fn text_warnings<T: Read>(input: T) -> Result<Vec<Warning>> {
+    let mut warnings = Vec::new();
+    let mut warn = |line: usize, message: String| warnings.push(Warning { line: line + 1, message });
+    // The line of the first entry with each title
+    let mut titles = HashMap::new();
+    // The line of a URL in the description of the current entry, if it is
+    // the last line so far (other than blank lines, which don't end the
+    // description)
+    let mut url_line = None;
+
+    let lines = BufReader::new(input)
+        .lines()
+        .collect::<io::Result<Vec<_>>>()
+        .chain_err(|| "could not read line")?;
+    // The header is checked by `Plan::from_text`
+    for (n, l) in lines.iter().enumerate().skip(header_len(&lines)) {
+        let l = l.trim_end();
+        if l.is_empty() {
+            continue;
+        }
+
+        if l.starts_with(char::is_whitespace) {
+            let indent = &l[..l.len() - l.trim_start().len()];
+            if indent.contains(' ') && indent.contains('\t') {
+                warn(n, "indentation mixes tabs and spaces".into());
+            }
+            if let Some(line) = url_line.take() {
+                warn(line, "this URL is followed by more description, so it will be part of the description".into());
+            }
+            if is_url(l.trim_start()) {
+                url_line = Some(n);
+            }
+            continue;
+        }
+
+        url_line = None;
+        if let Some(header) = l.strip_prefix('#') {
+            if header.trim().is_empty() {
+                warn(n, "section header has no title".into());
+            }
+            continue;
+        }
+
+        let (title, schedule, _) = parse_title(l);
+        if schedule.is_none() && l.starts_with('[') {
+            if let Some(end) = l.find(']') {
+                warn(n,
+                     format!("'{}' is not a valid schedule, so it will be part of the title", &l[..=end]));
+            }
+        }
+        if title.is_empty() {
+            warn(n, "entry has an empty title".into());
+        } else if let Some(first) = titles.get(title) {
+            warn(n, format!("duplicate title '{}' (first used on line {})", title, first + 1));
+        } else {
+            titles.insert(title.to_owned(), n);
+        }
+    }
+
+    warnings.sort_by_key(|w| w.line);
+    Ok(warnings)
+}
+
+impl HistoryEvent {
+    /// Returns the index of the completed entry in the plan (0-based).
+    pub fn entry_index(&self) -> usize {
+        self.entry
+    }
+
+    /// Returns when the entry was completed.
+    pub fn completed_at(&self) -> DateTime<Utc> {
+        self.completed_at
+    }
+}
+
+impl fmt::Display for EntryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            EntryStatus::Pending => "pending",
+            EntryStatus::Completed => "completed",
+            EntryStatus::Skipped => "skipped",
+        })
+    }
+}
+
+impl Note {
+    /// Returns a note with the given text, written at the given time.
+    pub fn new(text: &str, created_at: DateTime<Utc>) -> Note {
+        Note {
+            text: text.into(),
+            created_at,
+        }
+    }
+
+    /// Returns the text of the note.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns when the note was written.
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+}
+
+impl Entry {
+    /// Returns an `Entry` with a title and no description.
+    pub fn new(title: &str) -> Entry {
+        Entry::with_description(title, "")
+    }
+
+    /// Returns an `Entry` with the given title and description.
+    pub fn with_description(title: &str, description: &str) -> Entry {
+        Entry {
+            title: title.into(),
+            description: description.into(),
+            schedule: None,
+            url: None,
+            estimate: None,
+            notes: Vec::new(),
+            skipped: false,
+            prerequisites: Vec::new(),
+        }
+    }
+
+    /// Returns the title of the entry.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Returns the description of the entry.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Returns when the entry is scheduled to be read, if it has been
+    /// scheduled.
+    pub fn schedule(&self) -> Option<Schedule> {
+        self.schedule
+    }
+
+    /// Sets (or removes) the schedule of the entry.
+    pub fn set_schedule(&mut self, schedule: Option<Schedule>) {
+        self.schedule = schedule;
+    }
+
+    /// Returns the first date the entry is scheduled for, if it is scheduled
+    /// for a date or a range of dates.
+    pub fn start_date(&self) -> Option<NaiveDate> {
+        self.schedule.and_then(|s| s.start_date())
+    }
+
+    /// Returns the last date the entry is scheduled for, if it is scheduled
+    /// for a date or a range of dates.
+    pub fn end_date(&self) -> Option<NaiveDate> {
+        self.schedule.and_then(|s| s.end_date())
+    }
+
+    /// Returns the URL of the entry, if it has one.
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    /// Sets (or removes) the URL of the entry.
+    pub fn set_url(&mut self, url: Option<&str>) {
+        self.url = url.map(str::to_owned);
+    }
+
+    /// Returns how long the entry is expected to take to read, if known.
+    pub fn estimate(&self) -> Option<Estimate> {
+        self.estimate
+    }
+
+    /// Sets (or removes) the time estimate of the entry.
+    pub fn set_estimate(&mut self, estimate: Option<Estimate>) {
+        self.estimate = estimate;
+    }
+
+    /// Returns the notes written about the entry, oldest first.
+    pub fn notes(&self) -> &[Note] {
+        &self.notes
+    }
+
+    /// Returns the paragraphs of the description of the entry, which are
+    /// separated by blank lines, without any empty ones. A paragraph may
+    /// contain several lines.
+    pub fn description_paragraphs(&self) -> impl Iterator<Item = &str> {
+        self.description.split("\n\n").map(str::trim).filter(|p| !p.is_empty())
+    }
+
+    /// Adds a line to the description of the entry, on a line of its own, or
+    /// at the start of a new paragraph.
+    fn push_description(&mut self, line: &str, new_paragraph: bool) {
+        if !self.description.is_empty() {
+            self.description += if new_paragraph { "\n\n" } else { "\n" };
+        }
+        self.description += line;
+    }
+
+    /// Adds a note to the entry.
+    pub fn add_note(&mut self, note: Note) {
+        self.notes.push(note);
+    }
+}
+
+impl Plan {
+    /// Constructs a plan from a list of entries, setting the current entry
+    /// to the first one. The resulting plan will be acyclic.
+    pub fn from_entries(name: &str, entries: Vec<Entry>) -> Plan {
+        Plan {
+            version: current_version(),
+            name: name.to_owned(),
+            author: None,
+            source_url: None,
+            source_hash: None,
+            created_at: None,
+            modified_at: None,
+            revision: 0,
+            cyclic: false,
+            random: false,
+            current_entry: 0,
+            entries,
+            tags: Vec::new(),
+            unit_label: default_unit_label(),
+            priority: 0,
+            history: Vec::new(),
+            cycles_completed: 0,
+            archived: false,
+            pinned: false,
+            completed_at: None,
+            pace: None,
+            active_days: ActiveDays::every_day(),
+            sections: Vec::new(),
+            bookmarks: Vec::new(),
+            tracks: Vec::new(),
+            readers: BTreeMap::new(),
+        }
+    }
+
+    /// Attempts to construct a plan from plain text input.
+    ///
+    /// The expected format of a plan in plain text is a series of
+    /// unindented lines, representing the titles of the entries in the
+    /// plan, each of which may be followed by indented lines containing
+    /// a more detailed description of that entry.
+    /// Note that any amount of indentation (tabs and/or spaces) will
+    /// be considered as a description. The line breaks of a description are
+    /// kept, and a blank line followed by more indented lines starts a new
+    /// paragraph of the description (see `Entry::description_paragraphs`).
+    ///
+    /// A title may start with a schedule in square brackets, which will
+    /// become the schedule of the entry (see `Schedule` for the format).
+    /// For example, `[2017-05-01] Genesis 1-3` or `[every Sunday] Psalm 1`.
+    ///
+    /// If the last indented line of an entry starts with `http://` or
+    /// `https://` and contains no spaces (e.g.
+    /// `https://example.com/article`), it is the URL of the entry rather
+    /// than part of its description.
+    ///
+    /// A title may also end with an estimate of how long the entry takes to
+    /// read in parentheses, which will become the estimate of the entry (see
+    /// `Estimate` for the format). For example, `Genesis 1-3 (25m)`.
+    ///
+    /// An unindented line starting with `#` is not an entry, but the header
+    /// of a section containing the entries after it (see `Section`), such
+    /// as `# Part I: Foundations`. Every section must contain at least one
+    /// entry.
+    ///
+    /// Similarly, an unindented line starting with `@` starts a track (see
+    /// the `track` module) containing the entries after it, such as
+    /// `@ Old Testament`. If a plan has any tracks, every entry must be in
+    /// one, so the first track has to come before the first entry.
+    ///
+    /// The entries may be preceded by a header describing the plan itself,
+    /// ended by a `---` line. Each line of the header is a field of the form
+    /// `key: value` (e.g. `cyclic: true` or `tags: bible, daily`), where the
+    /// key is either one of the properties supported by `set_property`
+    /// (e.g. `name`, `cyclic`, `tags` or `pace`, which starts when the plan
+    /// is read), or `created` or `modified` (times in RFC 3339 format, e.g.
+    /// `2017-05-01T12:00:00Z`). A name given in the header replaces the
+    /// given name. Any number of `after` fields give the prerequisites of
+    /// the entries, e.g. `after: Borrowing | Ownership` (see the
+    /// `dependency` module).
+    ///
+    /// The header may also describe the progress through the plan, as
+    /// written by `to_text_with_progress`: `current` (the current entry
+    /// number, or `end`), `track` (for each track that has been started, its
+    /// number and current entry number, or `end`, e.g. `2 15`), `cycles`
+    /// (the number of passes completed through a cyclic plan), `archived`,
+    /// `pinned`, `completed` (when the plan was finished),
+    /// `pace_start` (the day the pace was set and the number of entries
+    /// completed by then, e.g. `2017-05-01, 12`) and `skipped` (the numbers
+    /// of the skipped entries, e.g. `2, 5`), along with any number of
+    /// `bookmark` (e.g. `3 Part II`), `read` (an entry number and when it was
+    /// completed) and `note` (an entry number, when the note was written and
+    /// its text) fields. Without a `pace_start` field, the pace starts when
+    /// the plan is read.
+    ///
+    /// Unless the header says otherwise, the resulting plan will be acyclic;
+    /// this can be changed after creation with the `set_cyclic` method.
+    ///
+    /// Plans written in other dialects of the format (e.g. with bulleted
+    /// titles) can be read with `from_text_with`.
+    pub fn from_text<T: Read>(name: &str, input: T) -> Result<Plan> {
+        Plan::from_text_with(name, input, &TextFormatOptions::default())
+    }
+
+    /// Attempts to construct a plan from plain text input in the dialect of
+    /// the format described by the given options (see the `dialect`
+    /// module), which otherwise works just like `from_text`.
+    pub fn from_text_with<T: Read>(name: &str, input: T, options: &TextFormatOptions) -> Result<Plan> {
+        // The whole plan is read first, since whether it starts with a
+        // header depends on what comes after it
+        let lines = BufReader::new(input)
+            .lines()
+            .collect::<io::Result<Vec<_>>>()
+            .chain_err(|| "could not read line")?;
+        let header_len = header_len(&lines);
+        if header_len > 0 {
+            debug!("plan '{}' has a header, on lines 1 to {}", name, header_len);
+        } else if lines.iter().any(|l| l.trim_end() == HEADER_END) {
+            debug!("plan '{}' has no header, since not every line before '{}' is a header field",
+                   name,
+                   HEADER_END);
+        }
+        let mut builder = PlanBuilder::new(name);
+        // The current entry being processed
+        let mut current_entry: Option<Entry> = None;
+        // Whether the last line was blank, so that a description continuing
+        // after it starts a new paragraph, and whether the URL of the current
+        // entry (which may turn out to be part of its description) did
+        // (see `Entry::push_description`)
+        let mut after_blank = false;
+        let mut url_after_blank = false;
+
+        // Most header fields are properties, which are set once the plan is
+        // built
+        let mut properties = Vec::new();
+        // The progress is set after the properties, which it can depend on
+        // (e.g. only an acyclic plan can be at its end)
+        let mut progress = Vec::new();
+        // Prerequisites name entries, so they are added once the entries
+        // are known
+        let mut prerequisites = Vec::new();
+        for (n, l) in lines[..header_len].iter().enumerate() {
+            let (key, value) = match header_field(l) {
+                Some(field) => field,
+                None => continue,
+            };
+            let time = || {
+                parse_timestamp(value)
+                    .ok_or_else(|| ErrorKind::TextFormat(format!("invalid time '{}' on line {}", value, n + 1)))
+            };
+            match key {
+                "created" => {
+                    builder.created_at(time()?);
+                }
+                "modified" => {
+                    builder.modified_at(time()?);
+                }
+                "after" => prerequisites.push((n, value)),
+                key if PROGRESS_FIELDS.contains(&key) => progress.push((n, key, value)),
+                _ => properties.push((n, key, value)),
+            }
+        }
+
+        for (n, l) in lines.iter().enumerate().skip(header_len) {
+            // Trim any whitespace to the right of the line, since it
+            // doesn't matter
+            let line = options.classify(l.trim_end());
+            // In a paragraph, every line after the title is part of the
+            // description, indented or not
+            let paragraphs = options.blank_lines_mean() == BlankLines::Paragraphs;
+            let line = match line {
+                Line::Title(text) if paragraphs && current_entry.is_some() => Line::Description(text),
+                line => line,
+            };
+            trace!("line {}: {:?}", n + 1, line);
+
+            match line {
+                Line::Blank => {
+                    // Blank lines are skipped, but may be the end of an entry
+                    // or of a paragraph of its description
+                    match options.blank_lines_mean() {
+                        BlankLines::EndEntry => after_blank = current_entry.is_some(),
+                        BlankLines::Ignore => {}
+                        BlankLines::Paragraphs => {
+                            if let Some(e) = current_entry.take() {
+                                builder.push_entry(e);
+                            }
+                        }
+                    }
+                    continue;
+                }
+                Line::Comment => {}
+                Line::Track(name) => {
+                    // A track also ends any entry
+                    if let Some(e) = current_entry.take() {
+                        builder.push_entry(e);
+                    }
+                    builder.track(name);
+                }
+                Line::Description(text) => {
+                    // Add to the description of the current entry
+                    match current_entry {
+                        Some(ref mut e) => {
+                            // Only the last line can be a URL, so a URL
+                            // followed by more lines is part of the
+                            // description after all
+                            if let Some(url) = e.url.take() {
+                                e.push_description(&url, url_after_blank);
+                            }
+                            if is_url(text) {
+                                debug!("line {}: '{}' is a URL, so it is the link of the entry", n + 1, text);
+                                e.url = Some(text.to_owned());
+                                url_after_blank = after_blank;
+                            } else {
+                                e.push_description(text, after_blank);
+                            }
+                        }
+                        None => {
+                            // So that rustfmt will work :P
+                            return Err(ErrorKind::TextFormat(format!("description on line {} does \
+                                                                      not correspond to any entry",
+                                                                     n + 1))
+                                .into());
+                        }
+                    }
+                }
+                Line::Section(header) => {
+                    // This is a section header, which also ends any entry
+                    if let Some(e) = current_entry.take() {
+                        builder.push_entry(e);
+                    }
+                    builder.section(header);
+                }
+                Line::Title(text) => {
+                    // This is the title of a new entry, so add the previous
+                    // entry to the list and start a new one
+                    if let Some(e) = current_entry.take() {
+                        builder.push_entry(e);
+                    }
+
+                    let (title, schedule, estimate) = parse_title(text);
+                    if schedule.is_none() && title.starts_with('[') {
+                        debug!("line {}: the title starts with '[', but not with a valid schedule", n + 1);
+                    }
+                    let mut entry = Entry::new(title);
+                    entry.set_schedule(schedule);
+                    entry.set_estimate(estimate);
+                    current_entry = Some(entry);
+                }
+            }
+            after_blank = false;
+        }
+
+        // Add any entry that is left at the end
+        if let Some(e) = current_entry {
+            builder.push_entry(e);
+        }
+        debug!("read {} entries of plan '{}' from {} lines", builder.len(), name, lines.len());
+
+        // The plan can still be invalid, e.g. if it has no entries or a
+        // section header isn't followed by any
+        let mut plan = builder.build()?;
+        for (n, value) in prerequisites {
+            plan.set_prerequisite_field(value)
+                .chain_err(|| ErrorKind::TextFormat(format!("invalid header field on line {}", n + 1)))?;
+        }
+        for (n, key, value) in properties {
+            plan.set_property(key, value)
+                .chain_err(|| ErrorKind::TextFormat(format!("invalid header field on line {}", n + 1)))?;
+        }
+        let pace_start = progress.iter().any(|&(_, key, _)| key == "pace_start");
+        for (n, key, value) in progress {
+            plan.set_progress_field(key, value)
+                .chain_err(|| ErrorKind::TextFormat(format!("invalid header field on line {}", n + 1)))?;
+        }
+        if !pace_start && plan.pace.is_some() {
+            // The entries read before the pace was set don't count towards it
+            let pace = plan.pace();
+            plan.set_pace(pace, Local::now().date_naive());
+        }
+
+        Ok(plan)
+    }
+
+    /// Sets part of the progress through the plan from a header field of
+    /// the plain text format (see `from_text`).
+    fn set_progress_field(&mut self, key: &str, value: &str) -> Result<()> {
+        let invalid = || -> Error {
+            ErrorKind::InvalidPropertyValue(key.to_owned(), value.to_owned()).into()
+        };
+        // Most fields start with an entry number, which is returned as an
+        // index
+        let len = self.len();
+        let entry = |number: &str| match number.trim().parse::<usize>() {
+            Ok(n) if n >= 1 && n <= len => Ok(n - 1),
+            _ => Err(invalid()),
+        };
+        let (number, rest) = value.split_once(' ').unwrap_or((value, ""));
+
+        match key {
+            "current" if !self.tracks.is_empty() => {
+                match value {
+                    "end" if !self.cyclic => self.reset_tracks(true),
+                    _ => self.set_track_entry(entry(value)?),
+                }
+            }
+            "current" => {
+                self.current_entry = match value {
+                    "end" if !self.cyclic => len,
+                    _ => entry(value)?,
+                }
+            }
+            "track" => self.set_track_field(value)?,
+            "cycles" => self.cycles_completed = value.parse().map_err(|_| invalid())?,
+            "completed" => self.completed_at = Some(parse_timestamp(value).ok_or_else(&invalid)?),
+            "pace_start" => {
+                let (date, progress) = value.split_once(',').ok_or_else(&invalid)?;
+                let date = NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").map_err(|_| invalid())?;
+                let progress = progress.trim().parse().map_err(|_| invalid())?;
+                let pace = self.pace().ok_or_else(&invalid)?;
+                self.pace = Some(PaceTarget::new(pace, date, progress));
+            }
+            "skipped" => {
+                for number in value.split(',') {
+                    let index = entry(number)?;
+                    self.entries[index].skipped = true;
+                }
+            }
+            "bookmark" => self.set_bookmark(rest, entry(number)?)?,
+            "read" => {
+                self.history.push(HistoryEvent {
+                    entry: entry(number)?,
+                    completed_at: parse_timestamp(rest).ok_or_else(&invalid)?,
+                })
+            }
+            "note" => {
+                let index = entry(number)?;
+                let (time, text) = rest.split_once(' ').unwrap_or((rest, ""));
+                let time = parse_timestamp(time).ok_or_else(&invalid)?;
+                self.entries[index].notes.push(Note::new(&unescape_line(text), time));
+            }
+            _ => return Err(ErrorKind::UnknownProperty(key.to_owned()).into()),
+        }
+
+        Ok(())
+    }
+
+    /// Returns the header fields describing the progress through the plan
+    /// in the plain text format (see `from_text`), leaving out any which are
+    /// the same as for a new plan.
+    fn progress_fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = self.track_fields();
+        if self.current_entry > 0 && self.tracks.is_empty() {
+            let current = if self.is_ended() {
+                "end".to_owned()
+            } else {
+                self.current_entry_number().to_string()
+            };
+            fields.push(("current", current));
+        }
+        if self.cycles_completed > 0 {
+            fields.push(("cycles", self.cycles_completed.to_string()));
+        }
+        if self.archived {
+            fields.push(("archived", "true".to_owned()));
+        }
+        if self.pinned {
+            fields.push(("pinned", "true".to_owned()));
+        }
+        if let Some(completed_at) = self.completed_at() {
+            fields.push(("completed", format_timestamp(completed_at)));
+        }
+        if let Some(pace) = self.pace {
+            fields.push(("pace_start",
+                         format!("{}, {}", pace.start_date().format("%Y-%m-%d"), pace.start_progress())));
+        }
+        let skipped: Vec<_> = self.entries
+            .iter()
+            .enumerate()
+            .filter(|&(_, e)| e.skipped)
+            .map(|(i, _)| (i + 1).to_string())
+            .collect();
+        if !skipped.is_empty() {
+            fields.push(("skipped", skipped.join(", ")));
+        }
+        for bookmark in &self.bookmarks {
+            fields.push(("bookmark", format!("{} {}", bookmark.entry + 1, bookmark.name)));
+        }
+        for event in &self.history {
+            fields.push(("read", format!("{} {}", event.entry + 1, format_timestamp(event.completed_at))));
+        }
+        for (i, entry) in self.entries.iter().enumerate() {
+            for note in &entry.notes {
+                fields.push(("note",
+                             format!("{} {} {}", i + 1, format_timestamp(note.created_at), escape_line(&note.text))));
+            }
+        }
+        fields
+    }
+
+    /// Writes the plan using the standard plain text format to the specified
+    /// writer.
+    /// This format is documented in the documentation for `from_text`.
+    ///
+    /// Only the plan itself is written, not the progress through it, so
+    /// reading the text back gives a plan starting from its first entry (as
+    /// when editing a plan, for example). Use `to_text_with_progress` to
+    /// include the progress.
+    pub fn to_text<T: Write>(&self, output: T) -> Result<()> {
+        self.write_text(output, false)
+    }
+
+    /// Writes the plan using the standard plain text format to the specified
+    /// writer, including the progress through it in the header, so that
+    /// reading the text back with `from_text` gives the same plan (apart from
+    /// its revision and when it was last stored, which belong to the store).
+    pub fn to_text_with_progress<T: Write>(&self, output: T) -> Result<()> {
+        self.write_text(output, true)
+    }
+
+    /// Writes the plan in the plain text format, with or without its
+    /// progress.
+    fn write_text<T: Write>(&self, output: T, progress: bool) -> Result<()> {
+        // Buffer writes
+        let mut w = BufWriter::new(output);
+        let mut sections = self.sections.iter().peekable();
+        let mut tracks = self.tracks.iter().peekable();
+
+        // Only plans with something to put in the header are written with
+        // one, and the name is left out, since it is given when the plan is
+        // read (e.g. by the filename)
+        let mut header: Vec<_> = vec![("cyclic", Some("true".to_owned()).filter(|_| self.cyclic)),
+                                  ("random", Some("true".to_owned()).filter(|_| self.random)),
+                                  ("tags", Some(self.tags.join(", ")).filter(|t| !t.is_empty())),
+                                  ("unit", Some(self.unit_label.clone()).filter(|u| *u != default_unit_label())),
+                                  ("priority", Some(self.priority.to_string()).filter(|_| self.priority != 0)),
+                                  ("pace", self.pace().map(|p| p.to_string())),
+                                  ("days",
+                                   Some(self.active_days.to_string()).filter(|_| !self.active_days.is_every_day())),
+                                  ("author", self.author.clone()),
+                                  ("source", self.source_url.clone()),
+                                  ("created", self.created_at.map(format_timestamp)),
+                                  ("modified", self.modified_at.map(format_timestamp))]
+            .into_iter()
+            .filter_map(|(key, value)| value.map(|v| (key, v)))
+            .collect();
+        header.extend(self.prerequisite_fields());
+        if progress {
+            header.extend(self.progress_fields());
+        }
+        if !header.is_empty() {
+            for (key, value) in header {
+                writeln!(w, "{}: {}", key, value).chain_err(|| "could not write to text output")?;
+            }
+            writeln!(w, "{}", HEADER_END).chain_err(|| "could not write to text output")?;
+        }
+
+        for (i, e) in self.entries().enumerate() {
+            if let Some(track) = tracks.next_if(|t| t.start == i) {
+                writeln!(w, "@ {}", track.name).chain_err(|| "could not write to text output")?;
+            }
+            if let Some(section) = sections.next_if(|s| s.start == i) {
+                writeln!(w, "# {}", section.title).chain_err(|| "could not write to text output")?;
+            }
+            if let Some(schedule) = e.schedule() {
+                write!(w, "[{}] ", schedule).chain_err(|| "could not write to text output")?;
+            }
+            match e.estimate() {
+                Some(estimate) => writeln!(w, "{} ({})", e.title(), estimate),
+                None => writeln!(w, "{}", e.title()),
+            }.chain_err(|| "could not write to text output")?;
+            for line in e.description().lines() {
+                if line.is_empty() {
+                    writeln!(w)
+                } else {
+                    writeln!(w, "    {}", line)
+                }.chain_err(|| "could not write to text output")?;
+            }
+            if let Some(url) = e.url() {
+                writeln!(w, "    {}", url).chain_err(|| "could not write to text output")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Attempts to construct a plan from CSV input.
+    ///
+    /// Each record of the input is an entry, with up to three fields: the
+    /// title of the entry, its description and its schedule (see `Schedule`
+    /// for the format). Only the title is required. If the first record is
+    /// a header, i.e. its first field is `title` (in any case), it is
+    /// skipped.
+    ///
+    /// The resulting plan will be acyclic; this can be changed after creation
+    /// with the `set_cyclic` method.
+    pub fn from_csv<T: Read>(name: &str, input: T) -> Result<Plan> {
+        let mut r = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(input);
+        let mut entries = Vec::new();
+
+        for (n, record) in r.records().enumerate() {
+            let record = record.chain_err(|| format!("could not read record {}", n + 1))?;
+            let field = |i| record.get(i).map_or("", str::trim);
+            if n == 0 && field(0).eq_ignore_ascii_case("title") {
+                continue;
+            }
+            if n == 0 && field(0) == "Book Id" && field(1) == "Title" {
+                let problem = "this looks like a Goodreads export (use the goodreads format to read it)";
+                return Err(ErrorKind::TextFormat(problem.into()).into());
+            }
+            if field(0).is_empty() {
+                return Err(ErrorKind::TextFormat(format!("record {} has no title", n + 1)).into());
+            }
+
+            let mut entry = Entry::with_description(field(0), field(1));
+            if !field(2).is_empty() {
+                let schedule = field(2)
+                    .parse()
+                    .chain_err(|| ErrorKind::TextFormat(format!("invalid schedule in record {}", n + 1)))?;
+                entry.set_schedule(Some(schedule));
+            }
+            entries.push(entry);
+        }
+
+        if entries.is_empty() {
+            Err(ErrorKind::TextFormat("cannot construct an empty plan".into()).into())
+        } else {
+            Ok(Plan::from_entries(name, entries))
+        }
+    }
+
+    /// Writes the plan as CSV to the specified writer, starting with a
+    /// header record.
+    /// This format is documented in the documentation for `from_csv`.
+    pub fn to_csv<T: Write>(&self, output: T) -> Result<()> {
+        let mut w = csv::Writer::from_writer(output);
+
+        w.write_record(["title", "description", "date"])
+            .chain_err(|| "could not write to csv output")?;
+        for e in self.entries() {
+            let schedule = e.schedule().map(|s| s.to_string()).unwrap_or_default();
+            w.write_record([e.title(), e.description(), &schedule])
+                .chain_err(|| "could not write to csv output")?;
+        }
+
+        w.flush().chain_err(|| "could not write to csv output")
+    }
+
+    /// Writes the plan as a standalone HTML page to the specified writer,
+    /// for printing or sharing.
+    ///
+    /// The page shows the entries as a checklist, with the entries before
+    /// the current entry checked (skipped entries are marked as such), and
+    /// includes their descriptions, schedules, estimates and links. Plans
+    /// can't be read back from HTML.
+    pub fn to_html<T: Write>(&self, output: T) -> Result<()> {
+        let mut w = BufWriter::new(output);
+        let mut sections = self.sections.iter().peekable();
+
+        write!(w,
+               "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{name}</title>\n\
+                <style>\n{style}</style>\n</head>\n<body>\n<h1>{name}</h1>\n",
+               name = escape_html(&self.name),
+               style = HTML_STYLE)
+            .chain_err(|| "could not write to html output")?;
+        for (i, e) in self.entries().enumerate() {
+            // Each section has a list of its own
+            let section = sections.next_if(|s| s.start == i);
+            if i > 0 && section.is_some() {
+                writeln!(w, "</ul>").chain_err(|| "could not write to html output")?;
+            }
+            if let Some(section) = section {
+                writeln!(w, "<h2>{}</h2>", escape_html(&section.title)).chain_err(|| "could not write to html output")?;
+            }
+            if i == 0 || section.is_some() {
+                writeln!(w, "<ul>").chain_err(|| "could not write to html output")?;
+            }
+            let status = self.entry_status(i);
+            let mut details: Vec<_> = e.schedule().map(|s| s.to_string()).into_iter().collect();
+            details.extend(e.estimate().map(|e| format!("about {}", e)));
+            if status == Some(EntryStatus::Skipped) {
+                details.push("skipped".to_owned());
+            }
+
+            let mut item = format!("<li><label><input type=\"checkbox\"{}> <span class=\"title\">{}</span>",
+                                   if status == Some(EntryStatus::Pending) { "" } else { " checked" },
+                                   escape_html(e.title()));
+            if !details.is_empty() {
+                item += &format!(" <span class=\"details\">({})</span>", escape_html(&details.join(", ")));
+            }
+            item += "</label>";
+            for paragraph in e.description_paragraphs() {
+                item += &format!("\n<p>{}</p>", escape_html(paragraph).replace('\n', "<br>\n"));
+            }
+            if let Some(url) = e.url() {
+                item += &format!("\n<p><a href=\"{0}\">{0}</a></p>", escape_html(url));
+            }
+            writeln!(w, "{}</li>", item).chain_err(|| "could not write to html output")?;
+        }
+        write!(w, "</ul>\n</body>\n</html>\n").chain_err(|| "could not write to html output")
+    }
+
+    /// Attempts to construct a plan from an OPML outline.
+    ///
+    /// Each outline item without any items nested inside it is an entry;
+    /// items containing others (such as the categories of a subscription
+    /// list) are only used for grouping, and are skipped. The title of an
+    /// entry is given by the `text` attribute of its item (or `title`, if it
+    /// has no text), and its URL (the `url`, `htmlUrl` or `xmlUrl`
+    /// attribute, in that order of preference) becomes its description.
+    ///
+    /// The resulting plan will be acyclic; this can be changed after creation
+    /// with the `set_cyclic` method.
+    pub fn from_opml<T: Read>(name: &str, input: T) -> Result<Plan> {
+        // The outline items which have been started but not ended, along
+        // with whether any items have been found inside them
+        let mut open: Vec<(Entry, bool)> = Vec::new();
+        let mut entries = Vec::new();
+
+        for event in EventReader::new(input) {
+            match event.chain_err(|| ErrorKind::TextFormat("invalid OPML".into()))? {
+                XmlEvent::StartElement { ref name, ref attributes, .. } if name.local_name == "outline" => {
+                    let attribute = |key: &str| {
+                        attributes.iter()
+                            .find(|a| a.name.local_name == key && !a.value.trim().is_empty())
+                            .map(|a| a.value.trim())
+                    };
+                    let title = attribute("text").or_else(|| attribute("title")).ok_or_else(|| {
+                            ErrorKind::TextFormat(format!("outline item {} has no title",
+                                                          entries.len() + open.len() + 1))
+                        })?;
+                    let url = attribute("url").or_else(|| attribute("htmlUrl")).or_else(|| attribute("xmlUrl"));
+
+                    if let Some(parent) = open.last_mut() {
+                        parent.1 = true;
+                    }
+                    open.push((Entry::with_description(title, url.unwrap_or("")), false));
+                }
+                XmlEvent::EndElement { ref name } if name.local_name == "outline" => {
+                    if let Some((entry, false)) = open.pop() {
+                        entries.push(entry);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if entries.is_empty() {
+            Err(ErrorKind::TextFormat("cannot construct an empty plan".into()).into())
+        } else {
+            Ok(Plan::from_entries(name, entries))
+        }
+    }
+
+    /// Attempts to construct a plan from an RSS or Atom feed.
+    ///
+    /// Each item of the feed (an `item` element in RSS, or an `entry` in
+    /// Atom) is an entry, whose title is the title of the item and whose
+    /// description is its link, if it has one. An item without a title uses
+    /// its link as its title instead. Since feeds list their newest items
+    /// first, the entries are in the opposite order, so that the plan goes
+    /// through the feed from its oldest item.
+    ///
+    /// The resulting plan will be acyclic; this can be changed after creation
+    /// with the `set_cyclic` method.
+    #[cfg(feature = "feed")]
+    pub fn from_feed<T: Read>(name: &str, input: T) -> Result<Plan> {
+        // The title and link of the item being read, if any
+        let mut item: Option<(String, Option<String>)> = None;
+        // The text of the element being read
+        let mut text = String::new();
+        let mut entries = Vec::new();
+
+        for event in EventReader::new(input) {
+            match event.chain_err(|| ErrorKind::TextFormat("invalid feed".into()))? {
+                XmlEvent::StartElement { ref name, ref attributes, .. } => {
+                    match (name.local_name.as_str(), item.as_mut()) {
+                        ("item", _) | ("entry", _) => item = Some((String::new(), None)),
+                        // Atom links are attributes, and an item may have
+                        // several of them (the main one is `alternate`)
+                        ("link", Some(&mut (_, ref mut link))) if link.is_none() => {
+                            let attribute = |key: &str| {
+                                attributes.iter().find(|a| a.name.local_name == key).map(|a| a.value.trim())
+                            };
+                            if attribute("rel").is_none_or(|r| r == "alternate") {
+                                *link = attribute("href").filter(|h| !h.is_empty()).map(|h| h.to_owned());
+                            }
+                        }
+                        _ => {}
+                    }
+                    text.clear();
+                }
+                XmlEvent::Characters(ref s) | XmlEvent::CData(ref s) => text.push_str(s),
+                XmlEvent::EndElement { ref name } => {
+                    match (name.local_name.as_str(), item.as_mut()) {
+                        // Other elements may also have titles (e.g.
+                        // `media:title`), but the item's own comes first
+                        ("title", Some(&mut (ref mut title, _))) if title.is_empty() => {
+                            *title = text.trim().to_owned();
+                        }
+                        // RSS links are the text of the element
+                        ("link", Some(&mut (_, ref mut link))) if link.is_none() && !text.trim().is_empty() => {
+                            *link = Some(text.trim().to_owned());
+                        }
+                        ("item", Some(_)) | ("entry", Some(_)) => {
+                            let (title, link) = item.take().unwrap();
+                            let title = if title.is_empty() {
+                                link.clone().ok_or_else(|| {
+                                        ErrorKind::TextFormat(format!("feed item {} has no title", entries.len() + 1))
+                                    })?
+                            } else {
+                                title
+                            };
+                            entries.push(Entry::with_description(&title, link.as_ref().map_or("", |l| l)));
+                        }
+                        _ => {}
+                    }
+                    text.clear();
+                }
+                _ => {}
+            }
+        }
+
+        if entries.is_empty() {
+            Err(ErrorKind::TextFormat("cannot construct an empty plan".into()).into())
+        } else {
+            entries.reverse();
+            Ok(Plan::from_entries(name, entries))
+        }
+    }
+
+    /// Attempts to read a plan (including its progress) from TOML input.
+    ///
+    /// The plan has the same structure as when it is stored as JSON, and it
+    /// is migrated to the current version of the format in the same way
+    /// (see the `migrations` module).
+    #[cfg(feature = "toml-format")]
+    pub fn from_toml<T: Read>(mut input: T) -> Result<Plan> {
+        let mut text = String::new();
+        input.read_to_string(&mut text).chain_err(|| "could not read toml input")?;
+        let plan = toml::from_str(&text).chain_err(|| "invalid toml")?;
+        migrations::plan_from_value(plan)
+    }
+
+    /// Writes the plan (including its progress) as TOML to the specified
+    /// writer.
+    #[cfg(feature = "toml-format")]
+    pub fn to_toml<T: Write>(&self, mut output: T) -> Result<()> {
+        // Going through a `toml::Value` puts the fields in an order that
+        // TOML can represent (tables after simple values)
+        let value = toml::Value::try_from(self).chain_err(|| "could not convert plan to toml")?;
+        let text = toml::to_string_pretty(&value).chain_err(|| "could not convert plan to toml")?;
+        output.write_all(text.as_bytes()).chain_err(|| "could not write to toml output")
+    }
+
+    /// Attempts to read a plan (including its progress) from YAML input.
+    ///
+    /// As with `from_toml`, the plan has the same structure as when it is
+    /// stored as JSON.
+    #[cfg(feature = "yaml-format")]
+    pub fn from_yaml<T: Read>(input: T) -> Result<Plan> {
+        let plan = serde_yaml::from_reader(input).chain_err(|| "invalid yaml")?;
+        migrations::plan_from_value(plan)
+    }
+
+    /// Writes the plan (including its progress) as YAML to the specified
+    /// writer.
+    #[cfg(feature = "yaml-format")]
+    pub fn to_yaml<T: Write>(&self, output: T) -> Result<()> {
+        serde_yaml::to_writer(output, self).chain_err(|| "could not write to yaml output")
+    }
+
+    /// Advances the plan by the given number of entries.
+    ///
+    /// For a cyclic plan, this will wrap around; for an acyclic plan,
+    /// this will either go to the beginning of the plan or to
+    /// the "end of plan" position as appropriate.
+    ///
+    /// A negative increment can be specified.
+    ///
+    /// When advancing, every entry that is moved past is recorded in the
+    /// history of the plan as having been completed now (and is no longer
+    /// considered skipped, if it was skipped before). Going round a cyclic
+    /// plan more than once only records the last time round, since the
+    /// rest would just repeat it.
+    pub fn next(&mut self, inc: i32) {
+        self.next_at(inc, Utc::now())
+    }
+
+    /// Advances the plan by the given number of entries, recording any
+    /// completed entries in the history as having been completed at the
+    /// given time.
+    ///
+    /// Other than the time recorded in the history, this is the same as
+    /// `next`.
+    ///
+    /// A random plan (see `set_random`) is advanced one entry at a time,
+    /// with a new current entry picked at random after each one (for at
+    /// most as many entries as the plan has).
+    ///
+    /// A plan with tracks advances each of its tracks by the given number of
+    /// entries instead (see the `track` module).
+    pub fn next_at(&mut self, inc: i32, time: DateTime<Utc>) {
+        if self.random && self.tracks.is_empty() && inc > 0 {
+            let mut rng = SplitMix64(time_seed(time) ^ self.history.len() as u64);
+            for _ in 0..inc.min(self.len() as i32) {
+                self.advance_at(1, time);
+                self.pick_current(rng.next());
+            }
+        } else {
+            self.advance_at(inc, time);
+        }
+    }
+
+    /// Advances the plan by the given number of entries, as with `next_at`,
+    /// but always in order.
+    fn advance_at(&mut self, inc: i32, time: DateTime<Utc>) {
+        // An empty plan has nowhere to go
+        if self.entries.is_empty() {
+            return;
+        }
+
+        let was_ended = self.is_ended();
+        let completed_at = self.completed_at;
+        if self.tracks.is_empty() {
+            // Record the entries that will be completed, stopping at the end
+            // of an acyclic plan and only going round a cyclic one once
+            let inc_entries = inc.max(0) as usize;
+            let first = if self.cyclic { inc_entries.saturating_sub(self.len()) } else { 0 };
+            for i in first..inc_entries {
+                let entry = self.current_entry + i;
+                if !self.cyclic && entry >= self.len() {
+                    break;
+                }
+                let entry = entry % self.len();
+                self.entries[entry].skipped = false;
+                self.history.push(HistoryEvent {
+                    entry,
+                    completed_at: time,
+                });
+            }
+            self.move_by(inc);
+        } else {
+            self.advance_tracks_at(inc, time);
+        }
+        self.completed_at = match (was_ended, self.is_ended()) {
+            (false, true) => Some(time),
+            (true, true) => completed_at,
+            _ => None,
+        };
+    }
+
+    /// Skips the current entry, advancing the plan past it without reading
+    /// it: the entry is marked as skipped (see `entry_status`), and nothing
+    /// is recorded in the history.
+    ///
+    /// Returns whether an entry was skipped, which isn't the case if the
+    /// plan has ended. In a plan with tracks, only the track of the current
+    /// entry moves on.
+    pub fn skip_current(&mut self) -> bool {
+        match self.entries.get_mut(self.current_entry) {
+            Some(entry) => entry.skipped = true,
+            None => return false,
+        }
+        if !self.tracks.is_empty() {
+            let current = self.current_entry;
+            self.move_track_of(current, 1);
+            return true;
+        }
+        self.move_by(1);
+        if self.random {
+            self.pick_current(time_seed(Utc::now()));
+        }
+        true
+    }
+
+    /// Returns the index of an entry picked at random (determined by the
+    /// given seed) from the ones which haven't been read yet: the current
+    /// entry and the ones after it. Returns `None` if the plan has ended.
+    ///
+    /// This doesn't change the plan, so it can be used to pick something to
+    /// read from any plan.
+    pub fn random_entry(&self, seed: u64) -> Option<usize> {
+        if self.is_ended() {
+            return None;
+        }
+        let remaining = (self.len() - self.current_entry) as u64;
+        Some(self.current_entry + (SplitMix64(seed).next() % remaining) as usize)
+    }
+
+    /// Makes an entry picked at random (as with `random_entry`) the current
+    /// entry, by swapping it with the current one.
+    fn pick_current(&mut self, seed: u64) {
+        if let Some(index) = self.random_entry(seed) {
+            if index != self.current_entry {
+                let mut order: Vec<_> = (self.current_entry..self.len()).collect();
+                order.swap(0, index - self.current_entry);
+                self.reorder_remaining(order);
+            }
+        }
+    }
+
+    /// Moves the current entry of the plan by the given number of entries
+    /// (as described for `next`), without recording anything.
+    fn move_by(&mut self, inc: i32) {
+        // An empty plan has nowhere to go
+        if self.entries.is_empty() {
+            return;
+        }
+
+        let mut new_entry = self.current_entry as i32 + inc;
+        let n_entries = self.entries.len() as i32;
+
+        // Adjust out of range entries as appropriate for cyclic/acyclic
+        // plans, keeping count of how many times a cyclic plan wraps around
+        // (going backwards past the start undoes a wrap)
+        if new_entry < 0 {
+            if self.cyclic {
+                let wraps = new_entry.div_euclid(n_entries).unsigned_abs();
+                self.cycles_completed = self.cycles_completed.saturating_sub(wraps);
+                new_entry = new_entry.rem_euclid(n_entries);
+            } else {
+                new_entry = 0;
+            }
+        } else if new_entry >= n_entries {
+            if self.cyclic {
+                self.cycles_completed += (new_entry / n_entries) as u32;
+                new_entry %= n_entries;
+            } else {
+                new_entry = n_entries;
+            }
+        }
+
+        // Set the current entry; a plan only counts as completed if it was
+        // moved to its end by `next_at`
+        self.current_entry = new_entry as usize;
+        self.completed_at = None;
+    }
+
+    /// Returns a copy of the plan with the given name, with its progress
+    /// reset: the copy starts at the first entry, with no history, notes or
+    /// skipped entries, and isn't archived or pinned (and hasn't been stored
+    /// yet). Its other properties (such as its sections, tags and pace) are
+    /// kept, with the pace starting today.
+    pub fn fresh_copy(&self, name: &str) -> Plan {
+        let mut copy = self.clone();
+        copy.name = name.to_owned();
+        copy.created_at = None;
+        copy.modified_at = None;
+        copy.revision = 0;
+        copy.reset();
+        copy.history.clear();
+        copy.archived = false;
+        copy.pinned = false;
+        for entry in &mut copy.entries {
+            entry.notes.clear();
+            entry.skipped = false;
+        }
+        copy.set_pace(self.pace(), Local::now().date_naive());
+        copy
+    }
+
+    /// Splits the plan into two at the entry with the given index (0-based),
+    /// returning the plan with the entries before it and a new plan with
+    /// that entry and the ones after it, which has the same name (so it
+    /// needs to be renamed before it is stored).
+    ///
+    /// The first plan is moved to its end, counting as completed now (a
+    /// cyclic plan is made acyclic, and its pace is removed), even if some
+    /// of its entries hadn't been read. The second plan takes its entries'
+    /// history, notes, bookmarks and sections with it, and starts at the
+    /// same entry as the original did, or at its first entry if the
+    /// original was before it; it keeps the other properties of the
+    /// original (such as its tags and pace, starting today), but isn't
+    /// archived, pinned or stored yet, and didn't come from a source.
+    ///
+    /// Both plans must have entries, so the first entry can't be split off,
+    /// and plans with tracks or readers can't be split.
+    pub fn split_at(mut self, index: usize) -> Result<(Plan, Plan)> {
+        if index >= self.len() {
+            return Err(ErrorKind::EntryOutOfRange(index + 1, self.len()).into());
+        }
+        if index == 0 {
+            bail!("plan '{}' can't be split at its first entry, since it would be left without entries",
+                  self.name);
+        }
+        if !self.tracks.is_empty() {
+            bail!("the entries of plan '{}' belong to its tracks, so it can't be split", self.name);
+        }
+        if !self.readers.is_empty() {
+            bail!("plan '{}' is shared by several readers, so it can't be split", self.name);
+        }
+
+        let mut rest = self.clone();
+        rest.entries = self.entries.split_off(index);
+        rest.created_at = None;
+        rest.modified_at = None;
+        rest.revision = 0;
+        rest.archived = false;
+        rest.pinned = false;
+        rest.source_url = None;
+        rest.source_hash = None;
+        rest.current_entry = self.current_entry.saturating_sub(index);
+        if rest.current_entry < rest.len() {
+            rest.completed_at = None;
+        }
+        rest.history = self.history
+            .iter()
+            .filter(|e| e.entry >= index)
+            .map(|e| HistoryEvent { entry: e.entry - index, ..*e })
+            .collect();
+        rest.bookmarks = self.bookmarks
+            .iter()
+            .filter(|b| b.entry >= index)
+            .map(|b| Bookmark { name: b.name.clone(), entry: b.entry - index })
+            .collect();
+        // The section the new plan starts in continues in it
+        rest.sections = self.sections
+            .iter()
+            .rev()
+            .skip_while(|s| s.start > index)
+            .take(1)
+            .chain(self.sections.iter().filter(|s| s.start > index))
+            .map(|s| Section { title: s.title.clone(), start: s.start.saturating_sub(index) })
+            .collect();
+        let pace = self.pace();
+        rest.set_pace(pace, Local::now().date_naive());
+
+        self.history.retain(|e| e.entry < index);
+        self.bookmarks.retain(|b| b.entry < index);
+        self.sections.retain(|s| s.start < index);
+        self.cyclic = false;
+        self.pace = None;
+        self.current_entry = self.len();
+        self.completed_at = self.completed_at.or_else(|| Some(Utc::now()));
+        Ok((self, rest))
+    }
+
+    /// Resets the plan to its first entry, starting the count of completed
+    /// cycles over as well.
+    pub fn reset(&mut self) {
+        self.current_entry = 0;
+        self.cycles_completed = 0;
+        self.completed_at = None;
+        self.reset_tracks(false);
+    }
+
+    /// Moves the plan directly to the entry with the given number (starting
+    /// at 1, as with `current_entry_number`).
+    ///
+    /// For an acyclic plan, the number one past the last entry moves the
+    /// plan to its end. For a cyclic plan, numbers past the last entry wrap
+    /// around to the start of the plan, just as if the plan had been
+    /// advanced to them. Any other number (including 0) results in an
+    /// `EntryOutOfRange` error. Unlike `next`, nothing is recorded in the
+    /// history.
+    ///
+    /// In a plan with tracks, the entry becomes the current entry of its
+    /// track, and the other tracks stay where they are (except at the end of
+    /// the plan, where every track ends).
+    pub fn set_current_entry(&mut self, n: usize) -> Result<()> {
+        let len = self.len();
+        if n == 0 || len == 0 || (!self.cyclic && n > len + 1) {
+            return Err(ErrorKind::EntryOutOfRange(n, len).into());
+        }
+        if !self.tracks.is_empty() {
+            match (n - 1) % len {
+                _ if n == len + 1 && !self.cyclic => self.reset_tracks(true),
+                index => self.set_track_entry(index),
+            }
+            self.completed_at = None;
+            return Ok(());
+        }
+
+        self.current_entry = if self.cyclic { (n - 1) % len } else { n - 1 };
+        self.completed_at = None;
+        Ok(())
+    }
+
+    /// Reverts the plan by the given number of entries.
+    ///
+    /// This is simply a shortcut for using `next` with a negative
+    /// increment.
+    pub fn previous(&mut self, dec: i32) {
+        self.next(-dec)
+    }
+
+    /// Reverses the order of the entries which haven't been read yet (the
+    /// current entry and the ones after it), so that the last entry becomes
+    /// the current one.
+    ///
+    /// The entries before the current entry are left alone, so the current
+    /// position in the plan stays the same. Sections starting after the
+    /// current entry are removed, since their entries are no longer together.
+    ///
+    /// The entries of a plan with tracks can't be reordered, since they
+    /// belong to their tracks, and neither can those of a plan with readers,
+    /// since they may each be anywhere in it (so this does nothing).
+    pub fn reverse(&mut self) {
+        let order = (self.current_entry..self.len()).rev().collect();
+        self.reorder_remaining(order);
+    }
+
+    /// Shuffles the entries which haven't been read yet (the current entry
+    /// and the ones after it) into a random order, in the same way as
+    /// `reverse`.
+    ///
+    /// The order is determined by the given seed: shuffling the same plan
+    /// with the same seed always gives the same order. As with `reverse`, the
+    /// entries of a plan with tracks are left alone.
+    pub fn shuffle(&mut self, seed: u64) {
+        let mut order: Vec<_> = (self.current_entry..self.len()).collect();
+        let mut rng = SplitMix64(seed);
+        // Fisher-Yates shuffle
+        for i in (1..order.len()).rev() {
+            let j = (rng.next() % (i as u64 + 1)) as usize;
+            order.swap(i, j);
+        }
+        self.reorder_remaining(order);
+    }
+
+    /// Puts the entries from the current entry onwards in the given order,
+    /// given as the old indexes of the entries in their new order.
+    fn reorder_remaining(&mut self, order: Vec<usize>) {
+        if !self.tracks.is_empty() || !self.readers.is_empty() {
+            return;
+        }
+        let start = self.current_entry;
+        // The new index of each entry, by its old index
+        let mut new_index: Vec<_> = (0..self.len()).collect();
+        for (i, &old) in order.iter().enumerate() {
+            new_index[old] = start + i;
+        }
+
+        let mut old_entries: Vec<_> = self.entries.drain(start..).map(Some).collect();
+        self.entries.extend(order.iter().map(|&old| old_entries[old - start].take().unwrap()));
+        // Entries from earlier passes through a cyclic plan may be in the
+        // history (and any which are somehow out of range are left alone,
+        // rather than being moved onto other entries)
+        for event in &mut self.history {
+            event.entry = new_index.get(event.entry).cloned().unwrap_or(event.entry);
+        }
+        for bookmark in &mut self.bookmarks {
+            bookmark.entry = new_index.get(bookmark.entry).cloned().unwrap_or(bookmark.entry);
+        }
+        self.bookmarks.sort_by_key(|b| b.entry);
+        self.sections.retain(|s| s.start <= start);
+    }
+
+    /// Returns the name of the plan.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the history of completed entries, oldest first.
+    pub fn history(&self) -> &[HistoryEvent] {
+        &self.history
+    }
+
+    /// Sets the name of the plan.
+    ///
+    /// Since plans are stored under their names, the plan will need to be
+    /// stored again under its new name (and removed under its old one).
+    pub fn set_name(&mut self, name: &str) {
+        self.name = name.to_owned();
+    }
+
+    /// Returns the author of the plan, if known.
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    /// Sets the author of the plan.
+    pub fn set_author(&mut self, author: Option<&str>) {
+        self.author = author.map(str::to_owned);
+    }
+
+    /// Returns the URL the plan came from, if known.
+    pub fn source_url(&self) -> Option<&str> {
+        self.source_url.as_deref()
+    }
+
+    /// Sets the URL the plan came from.
+    pub fn set_source_url(&mut self, url: Option<&str>) {
+        self.source_url = url.map(str::to_owned);
+    }
+
+    /// Returns the content hash of the file the plan was last added or
+    /// updated from, if known.
+    pub fn source_hash(&self) -> Option<&str> {
+        self.source_hash.as_deref()
+    }
+
+    /// Sets the content hash of the file the plan was last added or updated
+    /// from (see `is_changed_from_source`).
+    pub fn set_source_hash(&mut self, hash: Option<&str>) {
+        self.source_hash = hash.map(str::to_owned);
+    }
+
+    /// Returns whether the entries of the plan have been changed (e.g. by
+    /// inserting an entry) since it was added or updated from a file, which
+    /// is never the case for a plan which didn't come from one.
+    pub fn is_changed_from_source(&self) -> bool {
+        self.source_hash.as_ref().is_some_and(|hash| *hash != self.content_hash())
+    }
+
+    /// Returns when the plan was first stored, if known.
+    pub fn created_at(&self) -> Option<DateTime<Utc>> {
+        self.created_at
+    }
+
+    /// Returns when the plan was last stored, if known.
+    pub fn modified_at(&self) -> Option<DateTime<Utc>> {
+        self.modified_at
+    }
+
+    /// Records that the plan was stored at the given time, which is also
+    /// when it was created if it wasn't stored before.
+    ///
+    /// This is done by `FsStore` whenever it writes the plan.
+    pub fn mark_modified(&mut self, time: DateTime<Utc>) {
+        self.created_at.get_or_insert(time);
+        self.modified_at = Some(time);
+    }
+
+    /// Returns the revision of the plan: the number of times it has been
+    /// stored (0 for a plan which has never been stored).
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Sets the revision of the plan.
+    ///
+    /// This is done by `FsStore` whenever it writes the plan, so that it
+    /// can tell whether a plan was changed since it was read (see
+    /// `FsStore::overwrite`).
+    pub fn set_revision(&mut self, revision: u64) {
+        self.revision = revision;
+    }
+
+    /// Sets a property of the plan from its textual representation.
+    ///
+    /// This provides a single interface for changing plan properties
+    /// (e.g. from the command line) without needing to know about each
+    /// of them individually. The supported properties are:
+    ///
+    /// * `cyclic`: whether the plan is cyclic (`true` or `false`)
+    /// * `random`: whether the entries are read in a random order (`true`
+    ///   or `false`)
+    /// * `name`: the name of the plan
+    /// * `tags`: a comma-separated list of tags, replacing the existing ones
+    /// * `unit`: what a single entry represents (e.g. `day`)
+    /// * `priority`: an integer priority (higher is more important)
+    /// * `pace`: the rate at which the plan should be read (see `Pace`),
+    ///   starting today, or `none` to remove it
+    /// * `days`: the days of the week on which the plan is read (see
+    ///   `ActiveDays`), e.g. `weekdays` or `mon, wed, fri`
+    /// * `author`: who wrote the plan, or `none` to remove it
+    /// * `source`: the URL the plan came from, or `none` to remove it
+    /// * `archived`: whether the plan is archived (`true` or `false`)
+    /// * `pinned`: whether the plan is pinned (`true` or `false`)
+    ///
+    /// An error is returned if the property doesn't exist or the value is
+    /// invalid for it, in which case the plan is left unchanged.
+    pub fn set_property(&mut self, key: &str, value: &str) -> Result<()> {
+        let invalid = || -> Error {
+            ErrorKind::InvalidPropertyValue(key.to_owned(), value.to_owned()).into()
+        };
+
+        match key {
+            "cyclic" => self.set_cyclic(parse_bool(value).ok_or_else(&invalid)?),
+            "random" => self.set_random(parse_bool(value).ok_or_else(&invalid)?),
+            "name" => {
+                // The name is used as a filename, so it can't contain
+                // path separators
+                if value.is_empty() || value.contains(['/', '\\']) {
+                    return Err(invalid());
+                }
+                self.set_name(value);
+            }
+            "tags" => {
+                self.tags.clear();
+                for tag in value.split(',').filter(|t| !t.trim().is_empty()) {
+                    self.add_tag(tag)?;
+                }
+            }
+            "unit" => {
+                if value.trim().is_empty() {
+                    return Err(invalid());
+                }
+                self.unit_label = value.trim().to_owned();
+            }
+            "priority" => self.priority = value.parse().map_err(|_| invalid())?,
+            "pace" => {
+                let pace = match value.trim() {
+                    "" | "none" => None,
+                    v => Some(v.parse().map_err(|_| invalid())?),
+                };
+                self.set_pace(pace, Local::now().date_naive());
+            }
+            "days" => self.active_days = value.parse().map_err(|_| invalid())?,
+            "author" => {
+                self.author = match value.trim() {
+                    "" | "none" => None,
+                    v => Some(v.to_owned()),
+                }
+            }
+            "source" => {
+                self.source_url = match value.trim() {
+                    "" | "none" => None,
+                    v if is_url(v) => Some(v.to_owned()),
+                    _ => return Err(invalid()),
+                }
+            }
+            "archived" => self.set_archived(parse_bool(value).ok_or_else(&invalid)?),
+            "pinned" => self.set_pinned(parse_bool(value).ok_or_else(&invalid)?),
+            _ => return Err(ErrorKind::UnknownProperty(key.to_owned()).into()),
+        }
+
+        Ok(())
+    }
+
+    /// Changes the properties of the plan which are written in the header of
+    /// the plain text format (see `to_text`) to those of the given plan, such
+    /// as one read back from text after being edited. The name and times of
+    /// the plan are left alone, and so is its pace unless it was changed (so
+    /// that it doesn't start over).
+    pub fn copy_properties(&mut self, other: &Plan) {
+        self.set_cyclic(other.cyclic);
+        self.random = other.random;
+        self.tags = other.tags.clone();
+        self.unit_label = other.unit_label.clone();
+        self.priority = other.priority;
+        self.author = other.author.clone();
+        self.source_url = other.source_url.clone();
+        if self.pace() != other.pace() {
+            self.set_pace(other.pace(), Local::now().date_naive());
+        }
+        self.active_days = other.active_days;
+    }
+
+    /// Returns the tags of the plan.
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Adds a tag to the plan, if it doesn't already have it.
+    ///
+    /// Tags can't be empty or contain commas (which are used to separate
+    /// tags in text), and leading and trailing whitespace is ignored.
+    pub fn add_tag(&mut self, tag: &str) -> Result<()> {
+        let tag = tag.trim();
+        if tag.is_empty() || tag.contains(',') {
+            return Err(ErrorKind::InvalidPropertyValue("tags".into(), tag.into()).into());
+        }
+        if !self.has_tag(tag) {
+            self.tags.push(tag.to_owned());
+        }
+        Ok(())
+    }
+
+    /// Removes a tag from the plan, returning whether the plan had the tag.
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        let old_len = self.tags.len();
+        self.tags.retain(|t| t != tag.trim());
+        self.tags.len() != old_len
+    }
+
+    /// Returns whether the plan has the given tag.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Returns the label describing what a single entry of the plan
+    /// represents (by default, "entry").
+    pub fn unit_label(&self) -> &str {
+        &self.unit_label
+    }
+
+    /// Returns the priority of the plan (higher is more important).
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// Returns whether the plan is cyclic.
+    pub fn is_cyclic(&self) -> bool {
+        self.cyclic
+    }
+
+    /// Returns the rate at which the plan should be read, if one has been
+    /// set.
+    pub fn pace(&self) -> Option<Pace> {
+        self.pace.map(|t| t.pace())
+    }
+
+    /// Sets (or removes) the rate at which the plan should be read, starting
+    /// on the given day from the current entry.
+    pub fn set_pace(&mut self, pace: Option<Pace>, start: NaiveDate) {
+        let progress = self.progress();
+        self.pace = pace.map(|p| PaceTarget::new(p, start, progress));
+    }
+
+    /// Returns the progress through the plan compared with its pace as of
+    /// the given day, or `None` if the plan has no pace (or would only be
+    /// finished too far in the future for the date to be represented).
+    ///
+    /// For a cyclic plan, the projection is for the end of the current pass
+    /// through the plan. Only the days on which the plan is read count (see
+    /// `active_days`).
+    pub fn projection(&self, today: NaiveDate) -> Option<Projection> {
+        let total = if self.cyclic {
+            (self.cycles_completed as usize + 1) * self.len()
+        } else {
+            self.len()
+        };
+        self.pace.and_then(|t| t.projection(self.progress(), total, today, self.active_days))
+    }
+
+    /// Returns the total number of entries completed, including those of
+    /// previous passes through a cyclic plan.
+    fn progress(&self) -> usize {
+        let current = if self.tracks.is_empty() { self.current_entry } else { self.tracks_progress() };
+        self.cycles_completed as usize * self.len() + current
+    }
+
+    /// Returns whether the plan has been archived.
+    ///
+    /// Archived plans are kept along with the others, but aren't meant to
+    /// be read for now; interfaces should generally hide them and refuse to
+    /// advance them.
+    pub fn is_archived(&self) -> bool {
+        self.archived
+    }
+
+    /// Sets whether the plan is archived.
+    pub fn set_archived(&mut self, archived: bool) {
+        self.archived = archived;
+    }
+
+    /// Returns whether the plan has been pinned as one of the user's
+    /// favorites.
+    ///
+    /// Interfaces should generally list pinned plans before the others, and
+    /// may use a pinned plan when the user doesn't name one.
+    pub fn is_pinned(&self) -> bool {
+        self.pinned
+    }
+
+    /// Sets whether the plan is pinned.
+    pub fn set_pinned(&mut self, pinned: bool) {
+        self.pinned = pinned;
+    }
+
+    /// Returns when the plan was finished by completing its last entry (see
+    /// `next_at`), or `None` if it isn't at its end (or was moved there
+    /// some other way, e.g. by skipping its last entry).
+    pub fn completed_at(&self) -> Option<DateTime<Utc>> {
+        if self.is_ended() {
+            self.completed_at
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of times the plan has been completed by wrapping
+    /// around to its start (this is always 0 for an acyclic plan).
+    ///
+    /// Only advancing the plan with `next` counts as completing a cycle;
+    /// moving it directly with `set_current_entry` does not.
+    pub fn cycles_completed(&self) -> u32 {
+        self.cycles_completed
+    }
+
+    /// Returns whether the entries of the plan are read in a random order.
+    pub fn is_random(&self) -> bool {
+        self.random
+    }
+
+    /// Sets whether the entries of the plan are read in a random order, like
+    /// a grab bag.
+    ///
+    /// Whenever a random plan is advanced (or an entry is skipped), the new
+    /// current entry is picked at random from the ones which haven't been
+    /// read yet, and moved into place (as with `shuffle`, this removes the
+    /// sections after it). The entries before the current entry are still
+    /// the ones which have been read, so a random plan ends (or, if it is
+    /// also cyclic, starts over) once every entry has been read. The current
+    /// entry isn't changed when this is set.
+    pub fn set_random(&mut self, random: bool) {
+        self.random = random;
+    }
+
+    /// Sets whether the plan is cyclic.
+    ///
+    /// If the plan is at its end when this is set, the current entry
+    /// will be set to the first entry in the plan.
+    pub fn set_cyclic(&mut self, cyclic: bool) {
+        self.map_readers(|plan| plan.set_cyclic(cyclic));
+        self.cyclic = cyclic;
+        if self.cyclic && self.current_entry == self.len() {
+            self.current_entry = 0;
+        }
+        if self.cyclic {
+            self.restart_ended_tracks();
+        }
+    }
+
+    /// Returns the current entry number of the plan (as a 1-based index).
+    /// If the plan is at its end, this will be 1 more than the length of
+    /// the plan.
+    pub fn current_entry_number(&self) -> usize {
+        self.current_entry + 1
+    }
+
+    /// Returns the status of the entry with the given index (0-based) in
+    /// the current pass through the plan, or `None` if there is no such
+    /// entry.
+    ///
+    /// The entries before the current entry (or, in a plan with tracks, the
+    /// current entry of their track) have been either completed or skipped,
+    /// and the others are pending.
+    pub fn entry_status(&self, index: usize) -> Option<EntryStatus> {
+        let entry = self.entries.get(index)?;
+        let current = self.track_of(index).map_or(self.current_entry, |t| t.current_entry);
+        Some(if index >= current {
+            EntryStatus::Pending
+        } else if entry.skipped {
+            EntryStatus::Skipped
+        } else {
+            EntryStatus::Completed
+        })
+    }
+
+    /// Returns the number of entries which were skipped in the current pass
+    /// through the plan.
+    pub fn skipped_count(&self) -> usize {
+        (0..self.len()).filter(|&i| self.entry_status(i) == Some(EntryStatus::Skipped)).count()
+    }
+
+    /// Returns the total estimated time to read the rest of the plan (the
+    /// current entry and those after it), counting only the entries with
+    /// estimates. Returns `None` if none of them has one.
+    pub fn total_remaining_time(&self) -> Option<Estimate> {
+        let estimates: Vec<_> = self.entries
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| self.entry_status(i) == Some(EntryStatus::Pending))
+            .filter_map(|(_, e)| e.estimate)
+            .collect();
+        if estimates.is_empty() {
+            return None;
+        }
+        Some(estimates.into_iter().sum())
+    }
+
+    /// Returns the number of entries in the plan.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the plan has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns a hash of the content of the entries of the plan (their
+    /// titles, descriptions, schedules, URLs and estimates), as 16
+    /// hexadecimal digits.
+    ///
+    /// Nothing else goes into the hash, not even the progress through the
+    /// plan, so a plan has the same hash as the file it was read from until
+    /// its entries are changed. The hash is the same on every computer, so
+    /// it can be stored and compared later.
+    pub fn content_hash(&self) -> String {
+        // 64-bit FNV-1a, with the fields of each entry separated by bytes
+        // which can't appear in UTF-8 text
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut feed = |bytes: &[u8], end: u8| {
+            for &byte in bytes.iter().chain(&[end]) {
+                hash = (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3);
+            }
+        };
+        for entry in &self.entries {
+            let schedule = entry.schedule.map(|s| s.to_string()).unwrap_or_default();
+            let estimate = entry.estimate.map(|e| e.to_string()).unwrap_or_default();
+            let url = entry.url.as_deref().unwrap_or("");
+            for field in &[&entry.title[..], &entry.description, &schedule, url, &estimate] {
+                feed(field.as_bytes(), 0xff);
+            }
+            feed(&[], 0xfe);
+        }
+        format!("{:016x}", hash)
+    }
+
+    /// Checks that the plan is internally consistent, returning an
+    /// `InvalidPlan` error describing the problem if it is not.
+    ///
+    /// Plans built using the methods of this type are always consistent,
+    /// but plans read from elsewhere (e.g. a file edited by hand) may not
+    /// be.
+    pub fn check(&self) -> Result<()> {
+        let problem = if self.entries.is_empty() {
+            "it has no entries".to_owned()
+        } else if self.current_entry > self.len() || (self.cyclic && self.current_entry == self.len()) {
+            format!("its current entry ({}) is past its end", self.current_entry_number())
+        } else if let Some(section) = self.sections.iter().find(|s| s.start >= self.len()) {
+            format!("its section '{}' has no entries", section.title)
+        } else if self.sections.windows(2).any(|w| w[0].start >= w[1].start) {
+            "its sections are out of order".to_owned()
+        } else if let Some(bookmark) = self.bookmarks.iter().find(|b| b.entry >= self.len()) {
+            format!("its bookmark '{}' is past its end", bookmark.name)
+        } else if let Some(event) = self.history.iter().find(|e| e.entry >= self.len()) {
+            format!("its history has an entry ({}) past its end", event.entry + 1)
+        } else if let Some(problem) = track::tracks_problem(&self.tracks, self.len()) {
+            problem
+        } else if let Some(reader) = self.reader_out_of_range() {
+            format!("the progress of its reader '{}' is past its end", reader)
+        } else {
+            return Ok(());
+        };
+        Err(ErrorKind::InvalidPlan(self.name.clone(), problem).into())
+    }
+
+    /// Returns whether this plan is at its end (for an acyclic plan).
+    pub fn is_ended(&self) -> bool {
+        self.current_entry_number() > self.len()
+    }
+
+    /// Returns the current `Entry` of the plan, or `None` if we are
+    /// at the end of the plan.
+    pub fn current_entry(&self) -> Option<&Entry> {
+        self.entries.get(self.current_entry)
+    }
+
+    /// Replaces the entries of the plan, keeping its other properties.
+    ///
+    /// Since notes can't be written in most formats (such as the plain text
+    /// format), any new entry without notes is given the notes of an old
+    /// entry with the same title, if there is one.
+    ///
+    /// The current position is preserved as well as possible: if one of
+    /// the new entries has the same title as the current entry, it becomes
+    /// the current entry (the one closest to the old position is chosen if
+    /// there are several), and otherwise the current entry number stays the
+    /// same, as long as it is still in range. A plan at its end will stay
+    /// at its end. The history follows the completed entries to the new
+    /// entries with the same titles in the same way, and forgets any which
+    /// no longer exist.
+    ///
+    /// The tracks of the plan are left alone, so they must be replaced as
+    /// well (see `set_tracks`) if the plan has any. The positions of the
+    /// readers of the plan are preserved in the same way as its own.
+    pub fn replace_entries(&mut self, mut entries: Vec<Entry>) {
+        self.map_readers(|plan| plan.replace_entries(entries.clone()));
+        let old_position = self.current_entry;
+        let old_titles: Vec<_> = self.entries.iter().map(|e| e.title.clone()).collect();
+
+        for entry in entries.iter_mut().filter(|e| e.notes.is_empty()) {
+            if let Some(old) = self.entries.iter_mut().find(|e| e.title == entry.title && !e.notes.is_empty()) {
+                entry.notes = old.notes.split_off(0);
+            }
+        }
+        self.entries = entries;
+
+        // The index of the new entry with the same title as the old entry
+        // at the given index, closest to its old position
+        let new_index = |entries: &[Entry], old: usize| {
+            let title = old_titles.get(old)?;
+            entries.iter()
+                .enumerate()
+                .filter(|&(_, e)| e.title == *title)
+                .map(|(i, _)| i)
+                .min_by_key(|&i| (i as isize - old as isize).abs())
+        };
+        let entries = &self.entries;
+        self.history = self.history
+            .iter()
+            .filter_map(|e| new_index(entries, e.entry).map(|entry| HistoryEvent { entry, ..e.clone() }))
+            .collect();
+        self.current_entry = match new_index(entries, old_position) {
+            Some(i) => i,
+            None if self.cyclic => old_position.min(self.len().saturating_sub(1)),
+            None => old_position.min(self.len()),
+        };
+        let len = self.len();
+        self.sections.retain(|s| s.start < len);
+        self.bookmarks.retain(|b| b.entry < len);
+    }
+
+    /// Returns the sections of the plan, ordered by their first entries.
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    /// Replaces the sections of the plan, returning an `InvalidPlan` error
+    /// (and leaving the plan unchanged) if any section would have no
+    /// entries.
+    pub fn set_sections(&mut self, mut sections: Vec<Section>) -> Result<()> {
+        sections.sort_by_key(|s| s.start);
+        let empty = sections.iter()
+            .enumerate()
+            .find(|&(i, s)| s.start >= self.len() || sections.get(i + 1).is_some_and(|n| n.start == s.start));
+        if let Some((_, section)) = empty {
+            return Err(ErrorKind::InvalidPlan(self.name.clone(),
+                                              format!("its section '{}' has no entries", section.title))
+                .into());
+        }
+        self.sections = sections;
+        Ok(())
+    }
+
+    /// Returns the section containing the entry with the given index
+    /// (0-based), if there is one.
+    pub fn section_of(&self, index: usize) -> Option<&Section> {
+        if index >= self.len() {
+            return None;
+        }
+        self.sections.iter().rev().find(|s| s.start <= index)
+    }
+
+    /// Returns the section containing the current entry, if there is one.
+    pub fn current_section(&self) -> Option<&Section> {
+        self.section_of(self.current_entry)
+    }
+
+    /// Returns the bookmarks of the plan, ordered by their entries.
+    pub fn bookmarks(&self) -> &[Bookmark] {
+        &self.bookmarks
+    }
+
+    /// Returns the bookmark with the given name, if there is one.
+    pub fn bookmark(&self, name: &str) -> Option<&Bookmark> {
+        self.bookmarks.iter().find(|b| b.name == name)
+    }
+
+    /// Bookmarks the entry with the given index (0-based) under the given
+    /// name, replacing any bookmark with the same name, returning an
+    /// `EntryOutOfRange` error if there is no such entry.
+    ///
+    /// Bookmark names can't be empty or consist only of digits (so that
+    /// they can't be mistaken for entry numbers), and leading and trailing
+    /// whitespace is ignored.
+    pub fn set_bookmark(&mut self, name: &str, index: usize) -> Result<()> {
+        let name = name.trim();
+        if name.chars().all(|c| c.is_ascii_digit()) {
+            return Err(ErrorKind::InvalidPropertyValue("bookmark".into(), name.into()).into());
+        }
+        if index >= self.len() {
+            return Err(ErrorKind::EntryOutOfRange(index + 1, self.len()).into());
+        }
+
+        self.bookmarks.retain(|b| b.name != name);
+        let position = self.bookmarks.iter().position(|b| b.entry > index).unwrap_or(self.bookmarks.len());
+        self.bookmarks.insert(position,
+                              Bookmark {
+                                  name: name.to_owned(),
+                                  entry: index,
+                              });
+        Ok(())
+    }
+
+    /// Removes the bookmark with the given name and returns it, returning a
+    /// `BookmarkDoesNotExist` error if there is no such bookmark.
+    pub fn remove_bookmark(&mut self, name: &str) -> Result<Bookmark> {
+        match self.bookmarks.iter().position(|b| b.name == name) {
+            Some(i) => Ok(self.bookmarks.remove(i)),
+            None => Err(ErrorKind::BookmarkDoesNotExist(self.name.clone(), name.to_owned()).into()),
+        }
+    }
+
+    /// Returns the first bookmark at or after the current entry (i.e. the
+    /// next milestone to be reached), if there is one.
+    pub fn next_bookmark(&self) -> Option<&Bookmark> {
+        self.bookmarks.iter().find(|b| b.entry >= self.current_entry)
+    }
+
+    /// Inserts an entry at the given index (0-based), so that it comes
+    /// before the entry currently at that index, returning an
+    /// `EntryOutOfRange` error if the index is past the end of the plan.
+    ///
+    /// An entry inserted before the current entry counts as already read,
+    /// so the current entry stays the same; an entry inserted at the current
+    /// position (including the end of a plan which has ended) becomes the
+    /// current entry. The history and bookmarks are adjusted to match. In a
+    /// plan with tracks, the entry joins the track of the entry it comes
+    /// before (or the last track, at the end of the plan), and the same goes
+    /// for the current entry of that track. The progress of each reader of
+    /// the plan is adjusted in the same way.
+    pub fn insert_entry(&mut self, index: usize, entry: Entry) -> Result<()> {
+        if index > self.len() {
+            return Err(ErrorKind::EntryOutOfRange(index + 1, self.len()).into());
+        }
+
+        self.map_readers(|plan| {
+            let _ = plan.insert_entry(index, entry.clone());
+        });
+        self.entries.insert(index, entry);
+        if index < self.current_entry {
+            self.current_entry += 1;
+        }
+        // The new entry joins the section of the entry it comes before
+        for section in self.sections.iter_mut().filter(|s| s.start > index) {
+            section.start += 1;
+        }
+        for event in self.history.iter_mut().filter(|e| e.entry >= index) {
+            event.entry += 1;
+        }
+        for bookmark in self.bookmarks.iter_mut().filter(|b| b.entry >= index) {
+            bookmark.entry += 1;
+        }
+        self.insert_track_entry(index);
+        Ok(())
+    }
+
+    /// Adds an entry to the end of the plan.
+    ///
+    /// If the plan has ended, the new entry becomes the current entry.
+    pub fn push_entry(&mut self, entry: Entry) {
+        let len = self.len();
+        // Inserting at the end of the plan is always in range
+        let _ = self.insert_entry(len, entry);
+    }
+
+    /// Removes the entry with the given index (0-based) and returns it,
+    /// returning an `EntryOutOfRange` error if there is no such entry.
+    ///
+    /// If the current entry is removed, the entry after it becomes the
+    /// current entry. The history of the removed entry is removed as well,
+    /// and so is its section if it was the only entry in it; a bookmark of
+    /// the removed entry moves to the entry after it. The same goes for the
+    /// track of the removed entry, in a plan with tracks, and for the
+    /// progress of each reader of the plan.
+    /// The only entry of a plan can't be removed, since a plan must have at
+    /// least one entry.
+    pub fn remove_entry(&mut self, index: usize) -> Result<Entry> {
+        if index >= self.len() {
+            return Err(ErrorKind::EntryOutOfRange(index + 1, self.len()).into());
+        }
+        if self.len() == 1 {
+            bail!("cannot remove the only entry of plan '{}'", self.name);
+        }
+
+        self.map_readers(|plan| {
+            let _ = plan.remove_entry(index);
+        });
+        let entry = self.entries.remove(index);
+        if index < self.current_entry {
+            self.current_entry -= 1;
+        }
+        if self.cyclic && self.current_entry == self.len() {
+            self.current_entry = 0;
+        }
+        self.history.retain(|e| e.entry != index);
+        for event in self.history.iter_mut().filter(|e| e.entry > index) {
+            event.entry -= 1;
+        }
+        for section in self.sections.iter_mut().filter(|s| s.start > index) {
+            section.start -= 1;
+        }
+        // A section left without entries starts at the same place as the
+        // next one (or the end of the plan)
+        let len = self.len();
+        let starts: Vec<_> = self.sections.iter().map(|s| s.start).skip(1).chain(Some(len)).collect();
+        let mut starts = starts.into_iter();
+        self.sections.retain(|s| starts.next().is_some_and(|next| s.start < next));
+        // A bookmark of the removed entry moves to the entry after it, if
+        // there is one
+        for bookmark in self.bookmarks.iter_mut().filter(|b| b.entry > index) {
+            bookmark.entry -= 1;
+        }
+        self.bookmarks.retain(|b| b.entry < len);
+        self.remove_track_entry(index);
+        Ok(entry)
+    }
+
+    /// Adds a note with the given text to the entry with the given index
+    /// (0-based), returning an `EntryOutOfRange` error if there is no such
+    /// entry.
+    pub fn add_note(&mut self, entry_index: usize, text: &str) -> Result<()> {
+        self.add_note_at(entry_index, text, Utc::now())
+    }
+
+    /// Adds a note with the given text to the entry with the given index,
+    /// recording it as having been written at the given time.
+    ///
+    /// Other than the time recorded, this is the same as `add_note`.
+    pub fn add_note_at(&mut self, entry_index: usize, text: &str, time: DateTime<Utc>) -> Result<()> {
+        let len = self.len();
+        match self.entries.get_mut(entry_index) {
+            Some(entry) => {
+                entry.add_note(Note::new(text, time));
+                Ok(())
+            }
+            None => Err(ErrorKind::EntryOutOfRange(entry_index + 1, len).into()),
+        }
+    }
+
+    /// Returns the entry scheduled to be read on the given date, if there
+    /// is one.
+    ///
+    /// An entry scheduled for that specific date is preferred over one
+    /// scheduled for a range of dates or for that day of every week. If there are several possible
+    /// entries, the first one in the plan is returned.
+    pub fn entry_for_date(&self, date: NaiveDate) -> Option<&Entry> {
+        self.entries
+            .iter()
+            .find(|e| e.schedule() == Some(Schedule::Date(date)))
+            .or_else(|| {
+                self.entries.iter().find(|e| e.schedule().is_some_and(|s| s.includes(date)))
+            })
+    }
+
+    /// Returns the entries which should be read on the given date, in
+    /// order.
+    ///
+    /// Only entries which haven't been completed yet (i.e. the current entry
+    /// and those after it) are considered. An entry is due if it is
+    /// scheduled for the given date (or a range including it), or overdue if
+    /// all the dates it was scheduled for are earlier; weekly entries are
+    /// never overdue. A plan without any scheduled entries is simply read in
+    /// order, so its current entry is always due. Nothing is due on a day the
+    /// plan isn't read (see `is_active_on`).
+    pub fn due_entries(&self, date: NaiveDate) -> Vec<DueEntry<'_>> {
+        if !self.is_active_on(date) {
+            return Vec::new();
+        }
+        let remaining = self.entries.iter().enumerate().skip(self.current_entry);
+
+        if self.entries.iter().all(|e| e.schedule().is_none()) {
+            return remaining.take(1)
+                .map(|(index, entry)| DueEntry { index, entry, overdue: false })
+                .collect();
+        }
+        remaining.filter_map(|(index, entry)| {
+                let overdue = match entry.schedule()? {
+                    s if s.is_before(date) => true,
+                    s if s.includes(date) => false,
+                    _ => return None,
+                };
+                Some(DueEntry { index, entry, overdue })
+            })
+            .collect()
+    }
+
+    /// Returns the number of entries which haven't been completed yet (i.e.
+    /// the current entry and those after it) but were scheduled to be read
+    /// before the given date, i.e. how far behind schedule the plan is.
+    pub fn behind_by(&self, date: NaiveDate) -> usize {
+        self.remaining()
+            .filter(|e| e.schedule().is_some_and(|s| s.is_before(date)))
+            .count()
+    }
+
+    /// Returns the index (0-based) of the entry which the plan should be at
+    /// to be back on schedule on the given date, i.e. the entry after the
+    /// last overdue one (which may be the end of the plan), or `None` if the
+    /// plan isn't behind schedule.
+    pub fn catch_up_index(&self, date: NaiveDate) -> Option<usize> {
+        self.entries
+            .iter()
+            .enumerate()
+            .skip(self.current_entry)
+            .rev()
+            .find(|&(_, e)| e.schedule().is_some_and(|s| s.is_before(date)))
+            .map(|(i, _)| i + 1)
+    }
+
+    /// Returns an iterator over entries in the plan, of type `&Entry`
+    pub fn entries(&self) -> slice::Iter<'_, Entry> {
+        self.entries.iter()
+    }
+
+    /// Returns an iterator over the entries in the plan starting at the
+    /// given index (0-based), which is empty if the index is past the end.
+    pub fn entries_from(&self, index: usize) -> slice::Iter<'_, Entry> {
+        self.entries.get(index..).unwrap_or(&[]).iter()
+    }
+
+    /// Returns an iterator over the entries which haven't been completed yet
+    /// in the current pass through the plan, i.e. the current entry and
+    /// those after it (which is empty if the plan has ended).
+    pub fn remaining(&self) -> slice::Iter<'_, Entry> {
+        self.entries_from(self.current_entry)
+    }
+
+    /// Returns an iterator over the entries which have been completed in the
+    /// current pass through the plan, i.e. those before the current entry
+    /// (including any which were skipped; see `entry_status`).
+    pub fn completed(&self) -> slice::Iter<'_, Entry> {
+        self.entries[..self.current_entry.min(self.len())].iter()
+    }
+
+    /// Returns the entry with the given index (0-based), or `None` if there
+    /// is no such entry.
+    pub fn entry(&self, index: usize) -> Option<&Entry> {
+        self.entries.get(index)
+    }
+
+    /// Returns the entries whose titles contain the given text (ignoring
+    /// case), in order, along with their indexes (0-based).
+    pub fn find_entry(&self, title: &str) -> Vec<(usize, &Entry)> {
+        let title = title.to_lowercase();
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|&(_, e)| e.title.to_lowercase().contains(&title))
+            .collect()
+    }
+}
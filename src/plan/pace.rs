@@ -0,0 +1,249 @@
+//! This module provides `Pace`, a target rate of reading for a plan (such
+//! as five entries per week), and `Projection`, which compares the progress
+//! through a plan with its pace.
+//!
+//! A pace applies from the day it is set: the entries before the current
+//! entry at that time don't count towards it. The schedule given by a pace
+//! is spread evenly over the days, so that (for example) a plan with a pace
+//! of two entries per week is expected to have one entry read by the end of
+//! its fourth day. For a plan which isn't read every day (see the
+//! `weekdays` module), the entries of each week are spread over the days it
+//! is read instead.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de;
+
+use super::weekdays::ActiveDays;
+use super::super::errors::*;
+
+/// A target rate of reading, as a number of entries per number of days.
+///
+/// In text, a pace is written like `5 per week`, `1 per day` or
+/// `3 per 10 days` (`month` is taken to mean 30 days).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Pace {
+    entries: u32,
+    days: u32,
+}
+
+/// A pace set for a plan, along with where it starts.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct PaceTarget {
+    pace: Pace,
+    /// The day the pace was set
+    start_date: NaiveDate,
+    /// The number of entries which had been completed when the pace was set
+    /// (including those of previous passes through a cyclic plan)
+    start_progress: usize,
+}
+
+/// The progress through a plan compared with its pace, as of a certain
+/// day (see `Plan::projection`).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Projection {
+    pace: Pace,
+    scheduled_finish: NaiveDate,
+    projected_finish: Option<NaiveDate>,
+    days_ahead: i64,
+    to_read_today: usize,
+}
+
+impl Pace {
+    /// Returns a pace of the given number of entries every given number of
+    /// days, or an error if either is 0.
+    pub fn new(entries: u32, days: u32) -> Result<Pace> {
+        if entries == 0 || days == 0 {
+            bail!("a pace must have at least one entry and one day");
+        }
+        Ok(Pace { entries, days })
+    }
+
+    /// Returns the number of entries to read in each period.
+    pub fn entries(&self) -> u32 {
+        self.entries
+    }
+
+    /// Returns the length of each period in days.
+    pub fn days(&self) -> u32 {
+        self.days
+    }
+
+    /// Returns the number of entries which should be read in the given
+    /// number of days (rounded up, so that partial entries are read early).
+    fn entries_in(&self, days: u64) -> u64 {
+        (days * u64::from(self.entries)).div_ceil(u64::from(self.days))
+    }
+
+    /// Returns the number of days it takes to read the given number of
+    /// entries.
+    fn days_for(&self, entries: u64) -> u64 {
+        (entries * u64::from(self.days)).div_ceil(u64::from(self.entries))
+    }
+}
+
+impl fmt::Display for Pace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.days {
+            1 => write!(f, "{} per day", self.entries),
+            7 => write!(f, "{} per week", self.entries),
+            d if d % 7 == 0 => write!(f, "{} per {} weeks", self.entries, d / 7),
+            d => write!(f, "{} per {} days", self.entries, d),
+        }
+    }
+}
+
+impl FromStr for Pace {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Pace> {
+        let invalid = || -> Error { ErrorKind::TextFormat(format!("invalid pace '{}'", s)).into() };
+        let lower = s.trim().to_lowercase();
+        let mut parts = lower.splitn(2, " per ");
+        let (amount, period) = match (parts.next(), parts.next()) {
+            (Some(a), Some(p)) => (a, p.trim()),
+            _ => return Err(invalid()),
+        };
+
+        // The amount may be followed by a unit (e.g. "5 chapters"), which
+        // doesn't matter
+        let entries = amount.split_whitespace()
+            .next()
+            .and_then(|n| n.parse().ok())
+            .ok_or_else(&invalid)?;
+        let (count, unit) = match period.split_once(' ') {
+            Some((n, unit)) => (n.parse().map_err(|_| invalid())?, unit.trim()),
+            None => (1, period),
+        };
+        let unit_days = match unit.trim_end_matches('s') {
+            "day" => 1,
+            "week" => 7,
+            "fortnight" => 14,
+            "month" => 30,
+            _ => return Err(invalid()),
+        };
+
+        Pace::new(entries, count * unit_days).map_err(|_| invalid())
+    }
+}
+
+impl Serialize for Pace {
+    fn serialize<S: Serializer>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Pace {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> ::std::result::Result<Pace, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+impl PaceTarget {
+    /// Returns a target of the given pace, starting on the given day with
+    /// the given number of entries already completed.
+    pub fn new(pace: Pace, start_date: NaiveDate, start_progress: usize) -> PaceTarget {
+        PaceTarget {
+            pace,
+            start_date,
+            start_progress,
+        }
+    }
+
+    /// Returns the pace.
+    pub fn pace(&self) -> Pace {
+        self.pace
+    }
+
+    /// Returns the day the pace was set.
+    pub fn start_date(&self) -> NaiveDate {
+        self.start_date
+    }
+
+    /// Returns the number of entries which had been completed when the pace
+    /// was set.
+    pub fn start_progress(&self) -> usize {
+        self.start_progress
+    }
+
+    /// Returns the projection for a plan with the given number of entries
+    /// completed out of the given total, as of the given day, for a plan read
+    /// on the given days, or `None` if the plan would be finished too far in
+    /// the future for the date to be represented.
+    pub fn projection(&self,
+                      progress: usize,
+                      total: usize,
+                      today: NaiveDate,
+                      active_days: ActiveDays)
+                      -> Option<Projection> {
+        let pace = self.pace;
+        // The pace over the active days alone, which keeps the same number of
+        // entries in each week, and the number of them so far
+        let active = Pace::new(pace.entries * 7, pace.days * active_days.count()).unwrap_or(pace);
+        let elapsed = active_days.count_between(self.start_date, today);
+        let today_active = if active_days.contains(today.weekday()) { 1 } else { 0 };
+        let scheduled = total.saturating_sub(self.start_progress) as u64;
+        let done = progress.saturating_sub(self.start_progress) as u64;
+        let remaining = total.saturating_sub(progress) as u64;
+
+        // The schedule is spread evenly over the days, so the number of
+        // entries that should have been read before today is fractional
+        let expected = (elapsed as f64 * f64::from(active.entries) / f64::from(active.days))
+            .min(scheduled as f64);
+        let days_ahead = ((done as f64 - expected) * f64::from(active.days) /
+                          f64::from(active.entries))
+            .trunc() as i64;
+        let due_today = active.entries_in(elapsed + today_active).min(scheduled);
+
+        let scheduled_finish =
+            active_days.nth_from(self.start_date, active.days_for(scheduled).max(1))?;
+        let projected_finish = if remaining == 0 {
+            None
+        } else {
+            Some(active_days.nth_from(today, active.days_for(remaining))?)
+        };
+        Some(Projection {
+            pace,
+            scheduled_finish,
+            projected_finish,
+            days_ahead,
+            to_read_today: due_today.saturating_sub(done) as usize,
+        })
+    }
+}
+
+impl Projection {
+    /// Returns the pace the projection is based on.
+    pub fn pace(&self) -> Pace {
+        self.pace
+    }
+
+    /// Returns the day on which the plan will be finished if it is read at
+    /// its pace from the day the pace was set.
+    pub fn scheduled_finish(&self) -> NaiveDate {
+        self.scheduled_finish
+    }
+
+    /// Returns the day on which the plan will be finished if it is read at
+    /// its pace from now on, or `None` if it is already finished.
+    pub fn projected_finish(&self) -> Option<NaiveDate> {
+        self.projected_finish
+    }
+
+    /// Returns how many days ahead of schedule the plan is (a negative
+    /// number means it is behind schedule), counting only the days it is
+    /// read.
+    pub fn days_ahead(&self) -> i64 {
+        self.days_ahead
+    }
+
+    /// Returns the number of entries which need to be read today to be on
+    /// schedule by the end of the day.
+    pub fn to_read_today(&self) -> usize {
+        self.to_read_today
+    }
+}
@@ -0,0 +1,144 @@
+//! This module provides the progress of the readers sharing a plan, so
+//! that several people (e.g. the members of a family) can read the same
+//! plan, each at their own pace.
+//!
+//! A plan always has its own progress, which is what the other methods of
+//! `Plan` work with, and it may also keep the progress of any number of
+//! readers, by name. To read or change the progress of a reader, use
+//! `Plan::for_reader` to get the plan as that reader sees it, and then
+//! `Plan::set_reader_progress` to store the reader's progress again. The
+//! entries of the plan and its other properties are shared by all of its
+//! readers, and changes to them (such as inserting an entry) are applied to
+//! the progress of every reader as well.
+//!
+//! The progress of a reader consists of their current entry (and that of
+//! each track), their history, the entries they skipped, the number of
+//! times they have read through a cyclic plan and when they finished it.
+
+use std::mem;
+
+use chrono::{DateTime, Utc};
+
+use super::{HistoryEvent, Plan};
+
+/// The progress of one of the readers of a plan.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone)]
+pub(super) struct ReaderProgress {
+    current_entry: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    history: Vec<HistoryEvent>,
+    #[serde(default)]
+    cycles_completed: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    completed_at: Option<DateTime<Utc>>,
+    /// The current entry of each track of the plan, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tracks: Vec<usize>,
+    /// The indexes of the entries the reader skipped, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    skipped: Vec<usize>,
+}
+
+impl Plan {
+    /// Returns the names of the readers whose progress the plan keeps, in
+    /// alphabetical order.
+    pub fn readers(&self) -> Vec<&str> {
+        self.readers.keys().map(String::as_str).collect()
+    }
+
+    /// Returns the plan as the reader with the given name sees it: a copy of
+    /// the plan with the progress of the reader in place of its own (and
+    /// without the progress of any other readers).
+    ///
+    /// A reader whose progress the plan doesn't keep yet starts at the first
+    /// entry.
+    pub fn for_reader(&self, reader: &str) -> Plan {
+        let mut plan = self.clone();
+        plan.readers.clear();
+        plan.restore_progress(self.readers.get(reader).cloned().unwrap_or_default());
+        plan
+    }
+
+    /// Stores the progress of the given plan, as returned by `for_reader`
+    /// (and then changed), as the progress of the reader with the given
+    /// name.
+    pub fn set_reader_progress(&mut self, reader: &str, plan: &Plan) {
+        self.readers.insert(reader.to_owned(), plan.current_progress());
+    }
+
+    /// Makes the same change to the progress of every reader as the given
+    /// function makes to the plan as they see it (see `for_reader`).
+    ///
+    /// This is used by the methods which change the entries of the plan,
+    /// which call it before changing the plan itself.
+    pub(super) fn map_readers<F: FnMut(&mut Plan)>(&mut self, mut f: F) {
+        if self.readers.is_empty() {
+            return;
+        }
+        // The plans the readers see mustn't have readers of their own
+        let readers = mem::take(&mut self.readers);
+        let updated = readers.into_iter()
+            .map(|(name, progress)| {
+                let mut plan = self.clone();
+                plan.restore_progress(progress);
+                f(&mut plan);
+                (name, plan.current_progress())
+            })
+            .collect();
+        self.readers = updated;
+    }
+
+    /// Returns the name of a reader whose progress doesn't fit the plan, if
+    /// there is one (see `Plan::check`).
+    pub(super) fn reader_out_of_range(&self) -> Option<&str> {
+        let len = self.len();
+        self.readers
+            .iter()
+            .find(|&(_, p)| {
+                p.current_entry > len || p.history.iter().any(|e| e.entry >= len) ||
+                p.skipped.iter().any(|&i| i >= len)
+            })
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Returns the progress of the plan itself.
+    fn current_progress(&self) -> ReaderProgress {
+        ReaderProgress {
+            current_entry: self.current_entry,
+            history: self.history.clone(),
+            cycles_completed: self.cycles_completed,
+            completed_at: self.completed_at,
+            tracks: self.tracks.iter().map(|t| t.current_entry).collect(),
+            skipped: self.entries
+                .iter()
+                .enumerate()
+                .filter(|&(_, e)| e.skipped)
+                .map(|(i, _)| i)
+                .collect(),
+        }
+    }
+
+    /// Replaces the progress of the plan itself with the given progress.
+    fn restore_progress(&mut self, progress: ReaderProgress) {
+        let end = if self.cyclic { self.len().saturating_sub(1) } else { self.len() };
+        self.current_entry = progress.current_entry.min(end);
+        self.history = progress.history;
+        self.cycles_completed = progress.cycles_completed;
+        self.completed_at = progress.completed_at;
+        for (i, entry) in self.entries.iter_mut().enumerate() {
+            entry.skipped = progress.skipped.binary_search(&i).is_ok();
+        }
+        if self.tracks.is_empty() {
+            return;
+        }
+        if progress.tracks.len() == self.tracks.len() {
+            for (track, &current) in self.tracks.iter_mut().zip(&progress.tracks) {
+                track.current_entry = current.clamp(track.start, track.end);
+            }
+            self.sync_tracks();
+        } else {
+            // A reader who hasn't started the plan yet has no tracks
+            self.reset_tracks(false);
+        }
+    }
+}
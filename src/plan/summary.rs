@@ -0,0 +1,320 @@
+//! This module provides `PlanSummary`, which describes a plan without its
+//! entries, for interfaces which only need to show an overview of many
+//! plans (such as `reading list`).
+//!
+//! A plan file in the current version of the JSON format can be summarized
+//! without reading its entries into memory: they are only counted as the
+//! file is parsed. Other plan files are read in full and then summarized.
+//!
+//! Summaries can be sorted in any of the orders given by `SortOrder` (see
+//! `FsStore::sorted_summaries`).
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::io::Read;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer};
+use serde::de::{IgnoredAny, SeqAccess, Visitor};
+use serde_json;
+
+use super::{HistoryEvent, Plan, Section};
+use super::pace::{Pace, PaceTarget};
+use super::track::Track;
+use super::weekdays::ActiveDays;
+use super::super::errors::*;
+use super::super::migrations;
+
+/// The properties of a plan and its progress, without its entries.
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct PlanSummary {
+    /// The version of the format the plan is stored in; only plans in the
+    /// current version can be summarized directly
+    #[serde(default)]
+    version: u32,
+    name: String,
+    #[serde(default)]
+    revision: u64,
+    #[serde(default)]
+    modified_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    source_hash: Option<String>,
+    cyclic: bool,
+    #[serde(default)]
+    random: bool,
+    current_entry: usize,
+    #[serde(rename = "entries")]
+    len: EntryCount,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default = "super::default_unit_label")]
+    unit_label: String,
+    #[serde(default)]
+    cycles_completed: u32,
+    #[serde(default)]
+    archived: bool,
+    #[serde(default)]
+    pinned: bool,
+    #[serde(default)]
+    pace: Option<PaceTarget>,
+    #[serde(default)]
+    active_days: ActiveDays,
+    #[serde(default)]
+    sections: Vec<Section>,
+    #[serde(default)]
+    history: Vec<HistoryEvent>,
+    #[serde(default)]
+    tracks: Vec<Track>,
+}
+
+/// An order in which plan summaries can be sorted.
+///
+/// Plans are sorted by name in alphabetical order, and by anything else
+/// starting with the largest (e.g. the most recently modified), with ties
+/// broken by name.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SortOrder {
+    Name,
+    /// By how much of the current pass through the plan has been completed
+    /// (see `PlanSummary::percent_complete`)
+    Progress,
+    /// By when the plan was last modified
+    Recent,
+    /// By the number of entries in the plan
+    Length,
+}
+
+/// The number of entries in a plan, which is deserialized from the list of
+/// entries without keeping them.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct EntryCount(usize);
+
+impl PlanSummary {
+    /// Summarizes a plan stored as JSON, without reading its entries into
+    /// memory.
+    ///
+    /// Returns `None` if the plan is stored in an older version of the
+    /// format (see the `migrations` module), in which case it needs to be
+    /// read in full (and migrated) to be summarized.
+    pub fn from_json<R: Read>(input: R) -> Result<Option<PlanSummary>> {
+        let summary: PlanSummary = serde_json::from_reader(input).chain_err(|| "json error in plan file")?;
+        if summary.version == migrations::CURRENT_VERSION {
+            Ok(Some(summary))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns the name of the plan.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the revision of the plan (see `Plan::revision`).
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Returns when the plan was last stored, if known (see
+    /// `Plan::modified_at`).
+    pub fn modified_at(&self) -> Option<DateTime<Utc>> {
+        self.modified_at
+    }
+
+    /// Returns whether the plan came from a file (see `Plan::source_hash`),
+    /// in which case `Plan::is_changed_from_source` may be true.
+    pub fn has_source(&self) -> bool {
+        self.source_hash.is_some()
+    }
+
+    /// Returns whether the plan is cyclic.
+    pub fn is_cyclic(&self) -> bool {
+        self.cyclic
+    }
+
+    /// Returns whether the entries of the plan are read in a random order.
+    pub fn is_random(&self) -> bool {
+        self.random
+    }
+
+    /// Returns the number of entries in the plan.
+    pub fn len(&self) -> usize {
+        self.len.0
+    }
+
+    /// Returns whether the plan has no entries (which is only possible for
+    /// an invalid plan).
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the current entry number (starting at 1), as with
+    /// `Plan::current_entry_number`.
+    pub fn current_entry_number(&self) -> usize {
+        self.current_entry + 1
+    }
+
+    /// Returns whether the plan is at its end (for an acyclic plan).
+    pub fn is_ended(&self) -> bool {
+        self.current_entry_number() > self.len()
+    }
+
+    /// Returns the percentage of the entries of the plan which have been
+    /// completed, in the current pass through a cyclic plan (or through
+    /// each of its tracks).
+    pub fn percent_complete(&self) -> f64 {
+        let completed = if self.tracks.is_empty() {
+            self.current_entry
+        } else {
+            self.tracks.iter().map(|t| t.position() - 1).sum()
+        };
+        completed as f64 / self.len().max(1) as f64 * 100.0
+    }
+
+    /// Returns the tracks of the plan (see `Plan::tracks`).
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    /// Returns the tags of the plan.
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Returns whether the plan has the given tag.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Returns the label describing what a single entry of the plan
+    /// represents (see `Plan::unit_label`).
+    pub fn unit_label(&self) -> &str {
+        &self.unit_label
+    }
+
+    /// Returns the number of times a cyclic plan has wrapped around to its
+    /// start.
+    pub fn cycles_completed(&self) -> u32 {
+        self.cycles_completed
+    }
+
+    /// Returns whether the plan has been archived.
+    pub fn is_archived(&self) -> bool {
+        self.archived
+    }
+
+    /// Returns whether the plan has been pinned.
+    pub fn is_pinned(&self) -> bool {
+        self.pinned
+    }
+
+    /// Returns the rate at which the plan should be read, if it has one.
+    pub fn pace(&self) -> Option<Pace> {
+        self.pace.map(|t| t.pace())
+    }
+
+    /// Returns the days of the week on which the plan is read.
+    pub fn active_days(&self) -> ActiveDays {
+        self.active_days
+    }
+
+    /// Returns the section containing the current entry, if there is one.
+    pub fn current_section(&self) -> Option<&Section> {
+        if self.is_ended() {
+            return None;
+        }
+        self.sections.iter().rev().find(|s| s.start() <= self.current_entry)
+    }
+
+    /// Returns the history of the plan (see `Plan::history`).
+    pub fn history(&self) -> &[HistoryEvent] {
+        &self.history
+    }
+}
+
+impl<'a> From<&'a Plan> for PlanSummary {
+    fn from(plan: &'a Plan) -> PlanSummary {
+        PlanSummary {
+            version: plan.version,
+            name: plan.name.clone(),
+            revision: plan.revision,
+            modified_at: plan.modified_at,
+            source_hash: plan.source_hash.clone(),
+            cyclic: plan.cyclic,
+            random: plan.random,
+            current_entry: plan.current_entry,
+            len: EntryCount(plan.len()),
+            tags: plan.tags.clone(),
+            unit_label: plan.unit_label.clone(),
+            cycles_completed: plan.cycles_completed,
+            archived: plan.archived,
+            pinned: plan.pinned,
+            pace: plan.pace,
+            active_days: plan.active_days,
+            sections: plan.sections.clone(),
+            history: plan.history.clone(),
+            tracks: plan.tracks.clone(),
+        }
+    }
+}
+
+impl SortOrder {
+    /// The names of the orders, as accepted by `from_name`.
+    pub const NAMES: &'static [&'static str] = &["name", "progress", "recent", "length"];
+
+    /// Returns the order with the given name (one of `NAMES`), if there is
+    /// one.
+    pub fn from_name(name: &str) -> Option<SortOrder> {
+        match name {
+            "name" => Some(SortOrder::Name),
+            "progress" => Some(SortOrder::Progress),
+            "recent" => Some(SortOrder::Recent),
+            "length" => Some(SortOrder::Length),
+            _ => None,
+        }
+    }
+
+    /// Compares two plan summaries in this order, using the given times at
+    /// which they were last modified (which may come from somewhere other
+    /// than the summaries themselves, such as the plan files).
+    ///
+    /// Plans with no known modification time come after all the others.
+    pub fn compare(self,
+                   a: &PlanSummary,
+                   a_modified: Option<DateTime<Utc>>,
+                   b: &PlanSummary,
+                   b_modified: Option<DateTime<Utc>>)
+                   -> Ordering {
+        let ordering = match self {
+            SortOrder::Name => Ordering::Equal,
+            SortOrder::Progress => b.percent_complete().total_cmp(&a.percent_complete()),
+            SortOrder::Recent => b_modified.cmp(&a_modified),
+            SortOrder::Length => b.len().cmp(&a.len()),
+        };
+        ordering.then_with(|| a.name().cmp(b.name()))
+    }
+}
+
+impl<'de> Deserialize<'de> for EntryCount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> ::std::result::Result<EntryCount, D::Error> {
+        struct CountVisitor;
+
+        impl<'de> Visitor<'de> for CountVisitor {
+            type Value = EntryCount;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a list of entries")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> ::std::result::Result<EntryCount, A::Error> {
+                let mut count = 0;
+                while seq.next_element::<IgnoredAny>()?.is_some() {
+                    count += 1;
+                }
+                Ok(EntryCount(count))
+            }
+        }
+
+        deserializer.deserialize_seq(CountVisitor)
+    }
+}
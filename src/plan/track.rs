@@ -0,0 +1,336 @@
+//! This module provides `Track`, a named series of entries within a plan
+//! which is read alongside the plan's other tracks, each at its own
+//! position (e.g. a Bible reading plan with a chapter a day from each of
+//! the Old Testament, the New Testament and the Psalms).
+//!
+//! The tracks of a plan divide all of its entries into consecutive groups,
+//! much like sections, except that every entry belongs to a track. Advancing
+//! a plan with tracks (see `Plan::next`) advances every track by the same
+//! number of entries: a track of an acyclic plan stops at its end, while a
+//! track of a cyclic plan starts over on its own. The plan as a whole ends
+//! once all of its tracks have ended, and its current entry is the current
+//! entry of its first track which hasn't.
+//!
+//! Plans with tracks are always read in order, even if they are random.
+
+use std::mem;
+use std::ops::Range;
+
+use chrono::{DateTime, Utc};
+
+use super::{HistoryEvent, Plan};
+use super::super::errors::*;
+
+/// A named series of consecutive entries in a plan, with its own position.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Track {
+    pub(super) name: String,
+    /// The index of the first entry of the track
+    pub(super) start: usize,
+    /// The index after the last entry of the track
+    pub(super) end: usize,
+    /// The index of the current entry of the track, which is `end` once the
+    /// track has ended
+    pub(super) current_entry: usize,
+}
+
+impl Track {
+    /// Returns a track with the given name, containing the entries with the
+    /// given indexes (0-based), which starts at its first entry.
+    pub fn new(name: &str, entries: Range<usize>) -> Track {
+        Track {
+            name: name.to_owned(),
+            start: entries.start,
+            end: entries.end,
+            current_entry: entries.start,
+        }
+    }
+
+    /// Returns the name of the track.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the indexes (0-based) of the entries in the track.
+    pub fn entries(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// Returns the number of entries in the track.
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Returns whether the track has no entries (which is only possible for
+    /// an invalid plan).
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the index (0-based, in the whole plan) of the current entry
+    /// of the track, or `None` if the track has ended.
+    pub fn current_index(&self) -> Option<usize> {
+        Some(self.current_entry).filter(|&i| i < self.end)
+    }
+
+    /// Returns the number of the current entry within the track (starting
+    /// at 1), which is 1 more than the length of the track once it has
+    /// ended.
+    pub fn position(&self) -> usize {
+        self.current_entry - self.start + 1
+    }
+
+    /// Returns whether the track has ended.
+    pub fn is_ended(&self) -> bool {
+        self.current_entry >= self.end
+    }
+
+    /// Moves the current entry of the track by the given number of entries,
+    /// wrapping around within the track if the plan is cyclic.
+    fn move_by(&mut self, inc: i32, cyclic: bool) {
+        let len = self.len() as i32;
+        let position = (self.current_entry - self.start) as i32 + inc;
+        let position = if cyclic { position.rem_euclid(len) } else { position.clamp(0, len) };
+        self.current_entry = self.start + position as usize;
+    }
+}
+
+impl Plan {
+    /// Returns the tracks of the plan, in the order of their entries, which
+    /// is empty if the plan doesn't have any.
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    /// Returns the track containing the entry with the given index
+    /// (0-based), if the plan has tracks.
+    pub fn track_of(&self, index: usize) -> Option<&Track> {
+        self.tracks.iter().find(|t| t.entries().contains(&index))
+    }
+
+    /// Returns the indices (0-based) of the current entries of the plan,
+    /// which are the entries completed by moving on by one entry: the current
+    /// entry of each track which hasn't ended, or just the current entry of a
+    /// plan without tracks (unless it is at its end).
+    pub fn current_indices(&self) -> Vec<usize> {
+        if self.tracks.is_empty() {
+            Some(self.current_entry).filter(|&i| i < self.len()).into_iter().collect()
+        } else {
+            self.tracks.iter().filter_map(Track::current_index).collect()
+        }
+    }
+
+    /// Replaces the tracks of the plan, which must contain every entry of
+    /// the plan in order, without any empty tracks; otherwise, an
+    /// `InvalidPlan` error is returned, and the plan is left unchanged. An
+    /// empty list of tracks removes them.
+    ///
+    /// A track with the same name as one of the old tracks keeps as much of
+    /// its progress as it can. Otherwise, the entries of the new track
+    /// before the old current entry of the plan count as read. The same goes
+    /// for the tracks of each reader of the plan.
+    pub fn set_tracks(&mut self, tracks: Vec<Track>) -> Result<()> {
+        if let Some(problem) = tracks_problem(&tracks, self.len()) {
+            return Err(ErrorKind::InvalidPlan(self.name.clone(), problem).into());
+        }
+        self.map_readers(|plan| {
+            let _ = plan.set_tracks(tracks.clone());
+        });
+        let old_tracks = mem::replace(&mut self.tracks, tracks);
+        let old_current = self.current_entry;
+        for track in &mut self.tracks {
+            let progress = match old_tracks.iter().find(|t| t.name == track.name) {
+                Some(old) => old.current_entry - old.start,
+                None => old_current.saturating_sub(track.start),
+            };
+            track.current_entry = track.start + progress.min(track.len());
+            if self.cyclic && track.is_ended() {
+                track.current_entry = track.start;
+            }
+        }
+        self.sync_tracks();
+        Ok(())
+    }
+
+    /// Advances every track of the plan by the given number of entries, as
+    /// described for `next_at`.
+    pub(super) fn advance_tracks_at(&mut self, inc: i32, time: DateTime<Utc>) {
+        let inc_entries = inc.max(0) as usize;
+        for track in &mut self.tracks {
+            // As for a plan without tracks, going round a track more than
+            // once only records the last time round
+            let first = if self.cyclic { inc_entries.saturating_sub(track.len()) } else { 0 };
+            for i in first..inc_entries {
+                let position = track.current_entry - track.start + i;
+                if !self.cyclic && position >= track.len() {
+                    break;
+                }
+                let entry = track.start + position % track.len();
+                self.entries[entry].skipped = false;
+                self.history.push(HistoryEvent {
+                    entry,
+                    completed_at: time,
+                });
+            }
+            track.move_by(inc, self.cyclic);
+        }
+        self.sync_tracks();
+    }
+
+    /// Moves the track containing the entry with the given index by the given
+    /// number of entries, without recording anything.
+    pub(super) fn move_track_of(&mut self, index: usize, inc: i32) {
+        let cyclic = self.cyclic;
+        if let Some(track) = self.tracks.iter_mut().find(|t| t.entries().contains(&index)) {
+            track.move_by(inc, cyclic);
+        }
+        self.sync_tracks();
+    }
+
+    /// Moves every track of a cyclic plan which has ended back to its start.
+    pub(super) fn restart_ended_tracks(&mut self) {
+        for track in self.tracks.iter_mut().filter(|t| t.is_ended()) {
+            track.current_entry = track.start;
+        }
+        self.sync_tracks();
+    }
+
+    /// Moves every track of the plan to its start (or its end).
+    pub(super) fn reset_tracks(&mut self, end: bool) {
+        for track in &mut self.tracks {
+            track.current_entry = if end { track.end } else { track.start };
+        }
+        self.sync_tracks();
+    }
+
+    /// Makes the entry with the given index (0-based) the current entry of
+    /// its track.
+    pub(super) fn set_track_entry(&mut self, index: usize) {
+        if let Some(track) = self.tracks.iter_mut().find(|t| t.entries().contains(&index)) {
+            track.current_entry = index;
+        }
+        self.sync_tracks();
+    }
+
+    /// Sets the current entry of the track with the given number (starting
+    /// at 1) from a field in the header of the plain text format, which
+    /// gives the track number and the entry number (or `end`), e.g. `2 15`.
+    pub(super) fn set_track_field(&mut self, value: &str) -> Result<()> {
+        let invalid = || -> Error { ErrorKind::InvalidPropertyValue("track".into(), value.into()).into() };
+        let (number, entry) = value.split_once(' ').ok_or_else(&invalid)?;
+        let cyclic = self.cyclic;
+        let track = number.parse::<usize>()
+            .ok()
+            .and_then(|n| n.checked_sub(1))
+            .and_then(|i| self.tracks.get_mut(i))
+            .ok_or_else(&invalid)?;
+        track.current_entry = match entry.trim() {
+            "end" if !cyclic => track.end,
+            entry => {
+                match entry.parse::<usize>() {
+                    Ok(n) if n > track.start && n <= track.end => n - 1,
+                    _ => return Err(invalid()),
+                }
+            }
+        };
+        self.sync_tracks();
+        Ok(())
+    }
+
+    /// Returns the header fields for the positions of the tracks in the
+    /// plain text format (see `set_track_field`), leaving out the tracks at
+    /// their starts.
+    pub(super) fn track_fields(&self) -> Vec<(&'static str, String)> {
+        self.tracks
+            .iter()
+            .enumerate()
+            .filter(|&(_, t)| t.current_entry > t.start)
+            .map(|(i, t)| {
+                let entry = match t.current_index() {
+                    Some(index) => (index + 1).to_string(),
+                    None => "end".to_owned(),
+                };
+                ("track", format!("{} {}", i + 1, entry))
+            })
+            .collect()
+    }
+
+    /// Adjusts the tracks for an entry inserted at the given index, which
+    /// joins the track of the entry it comes before (or the last track, at
+    /// the end of the plan).
+    pub(super) fn insert_track_entry(&mut self, index: usize) {
+        // An entry added to the end of the plan is after every track
+        let at_end = index + 1 == self.len();
+        for track in &mut self.tracks {
+            if track.start > index {
+                track.start += 1;
+            }
+            if track.end > index || (at_end && track.end == index) {
+                track.end += 1;
+            }
+            if track.current_entry > index {
+                track.current_entry += 1;
+            }
+        }
+        self.sync_tracks();
+    }
+
+    /// Adjusts the tracks for the entry which was removed from the given
+    /// index, removing its track if it was the only entry in it.
+    pub(super) fn remove_track_entry(&mut self, index: usize) {
+        let cyclic = self.cyclic;
+        for track in &mut self.tracks {
+            if track.start > index {
+                track.start -= 1;
+            }
+            if track.end > index {
+                track.end -= 1;
+            }
+            if track.current_entry > index {
+                track.current_entry -= 1;
+            }
+            if cyclic && track.is_ended() {
+                track.current_entry = track.start;
+            }
+        }
+        self.tracks.retain(|t| !t.is_empty());
+        self.sync_tracks();
+    }
+
+    /// Returns the number of entries completed in the current pass through
+    /// every track of the plan.
+    pub(super) fn tracks_progress(&self) -> usize {
+        self.tracks.iter().map(|t| t.current_entry - t.start).sum()
+    }
+
+    /// Makes the current entry of the plan that of its first track which
+    /// hasn't ended, or the end of the plan if they all have.
+    pub(super) fn sync_tracks(&mut self) {
+        if !self.tracks.is_empty() {
+            self.current_entry = self.tracks.iter().find_map(Track::current_index).unwrap_or(self.entries.len());
+        }
+    }
+}
+
+/// Returns what is wrong with the given tracks for a plan with the given
+/// number of entries, if anything (see `Plan::set_tracks`).
+pub(super) fn tracks_problem(tracks: &[Track], len: usize) -> Option<String> {
+    if tracks.is_empty() {
+        return None;
+    }
+    if let Some(track) = tracks.iter().find(|t| t.is_empty()) {
+        return Some(format!("its track '{}' has no entries", track.name));
+    }
+    if tracks[0].start > 0 {
+        return Some("it has entries before its first track".to_owned());
+    }
+    let contiguous = tracks.windows(2).all(|w| w[0].end == w[1].start) &&
+                     tracks.last().is_some_and(|t| t.end == len);
+    if !contiguous {
+        return Some("its tracks don't contain all of its entries in order".to_owned());
+    }
+    if let Some(track) = tracks.iter().find(|t| t.current_entry < t.start || t.current_entry > t.end) {
+        return Some(format!("the current entry of its track '{}' is out of range", track.name));
+    }
+    None
+}
@@ -0,0 +1,208 @@
+//! This module provides `Plan::validate`, which looks for things in a plan
+//! that are probably mistakes, even though the plan is valid (in the sense
+//! of `Plan::check`), such as two entries with the same title or a date
+//! far from those of the entries around it.
+//!
+//! Unlike `text_warnings`, which is about the formatting of a plan in the
+//! plain text format, these warnings are about the entries themselves, so
+//! they apply to plans read from any format.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use chrono::NaiveDate;
+
+use super::Plan;
+
+/// Titles longer than this (in characters) are probably a title and
+/// description which were run together.
+const MAX_TITLE_LENGTH: usize = 120;
+
+/// The kind of problem a `PlanWarning` describes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WarningKind {
+    /// The entry has the same title as an earlier entry.
+    DuplicateTitle,
+    /// The entry has no description, although most entries of the plan do.
+    MissingDescription,
+    /// The title of the entry is suspiciously long.
+    LongTitle,
+    /// The entry is dated much later than the previous dated entry,
+    /// compared to the other dated entries of the plan.
+    DateGap,
+    /// The entry is dated on or before the previous dated entry (or the end
+    /// of its range of dates).
+    DateOverlap,
+    /// The entry comes before one of its prerequisites.
+    PrerequisiteOrder,
+    /// One of the prerequisites of the entry isn't the title of any entry.
+    UnknownPrerequisite,
+}
+
+/// Something about an entry of a plan which is probably a mistake (see
+/// `Plan::validate`).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PlanWarning {
+    kind: WarningKind,
+    /// The index of the entry the warning is about (0-based)
+    entry: usize,
+    message: String,
+}
+
+impl WarningKind {
+    /// Returns the name of the kind of warning, as used in JSON output.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            WarningKind::DuplicateTitle => "duplicate-title",
+            WarningKind::MissingDescription => "missing-description",
+            WarningKind::LongTitle => "long-title",
+            WarningKind::DateGap => "date-gap",
+            WarningKind::DateOverlap => "date-overlap",
+            WarningKind::PrerequisiteOrder => "prerequisite-order",
+            WarningKind::UnknownPrerequisite => "unknown-prerequisite",
+        }
+    }
+}
+
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl PlanWarning {
+    /// Returns the kind of problem.
+    pub fn kind(&self) -> WarningKind {
+        self.kind
+    }
+
+    /// Returns the index of the entry the warning is about (0-based).
+    pub fn entry_index(&self) -> usize {
+        self.entry
+    }
+
+    /// Returns a description of the problem.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for PlanWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "entry {}: {}", self.entry + 1, self.message)
+    }
+}
+
+impl Plan {
+    /// Returns warnings about anything in the entries of the plan which is
+    /// probably a mistake, in order of entry.
+    ///
+    /// The plan is checked for duplicate titles, suspiciously long titles,
+    /// entries without a description when at least three quarters of the
+    /// entries have one, and dated entries which are out of order, share a
+    /// date or come long after the previous dated entry (more than three
+    /// times the usual gap between dates). An entry scheduled for a range of
+    /// dates is compared to the previous entry by its first date and to the
+    /// next entry by its last date. Entries which come before one of their
+    /// prerequisites, or have a prerequisite which isn't the title of any
+    /// entry, are reported as well.
+    pub fn validate(&self) -> Vec<PlanWarning> {
+        let mut warnings = Vec::new();
+        {
+            let mut warn = |kind, entry, message| warnings.push(PlanWarning { kind, entry, message });
+
+            let described = self.entries.iter().filter(|e| !e.description.trim().is_empty()).count();
+            let descriptions_expected = described < self.len() && described * 4 >= self.len() * 3;
+            let first_indexes = self.first_indexes();
+            // The index of the first entry with each title seen so far
+            let mut titles = HashMap::new();
+            for (i, entry) in self.entries.iter().enumerate() {
+                if let Some(first) = titles.get(entry.title.as_str()) {
+                    warn(WarningKind::DuplicateTitle,
+                         i,
+                         format!("duplicate title '{}' (also used by entry {})", entry.title, first + 1));
+                } else {
+                    titles.insert(entry.title.as_str(), i);
+                }
+                let length = entry.title.chars().count();
+                if length > MAX_TITLE_LENGTH {
+                    warn(WarningKind::LongTitle,
+                         i,
+                         format!("title is {} characters long; is part of it meant to be the description?", length));
+                }
+                if descriptions_expected && entry.description.trim().is_empty() {
+                    warn(WarningKind::MissingDescription,
+                         i,
+                         format!("no description, unlike {} of the {} entries", described, self.len()));
+                }
+                for prerequisite in &entry.prerequisites {
+                    match first_indexes.get(prerequisite.as_str()) {
+                        Some(&p) if p > i => {
+                            warn(WarningKind::PrerequisiteOrder,
+                                 i,
+                                 format!("comes before its prerequisite, entry {} ('{}')", p + 1, prerequisite));
+                        }
+                        Some(_) => {}
+                        None => {
+                            warn(WarningKind::UnknownPrerequisite,
+                                 i,
+                                 format!("no entry has the title of its prerequisite '{}'", prerequisite));
+                        }
+                    }
+                }
+            }
+
+            // The first and last dates of each dated entry
+            let dates: Vec<(usize, NaiveDate, NaiveDate)> = self.entries
+                .iter()
+                .enumerate()
+                .filter_map(|(i, e)| Some((i, e.start_date()?, e.end_date()?)))
+                .collect();
+            let days_between: Vec<_> = dates.windows(2).map(|w| (w[1].1 - w[0].2).num_days()).collect();
+            // Plans which often have several entries on the same day aren't
+            // checked for shared dates or gaps, and neither are plans with
+            // too few dates to tell what is usual
+            let shared_dates_expected = days_between.iter().filter(|&&d| d == 0).count() * 4 > days_between.len();
+            let mut gaps: Vec<_> = days_between.iter().cloned().filter(|&d| d > 0).collect();
+            gaps.sort();
+            let usual_gap = if gaps.len() >= 3 && !shared_dates_expected {
+                Some(gaps[gaps.len() / 2])
+            } else {
+                None
+            };
+            for w in dates.windows(2) {
+                let ((previous, _, previous_date), (i, date, _)) = (w[0], w[1]);
+                let days = (date - previous_date).num_days();
+                if days < 0 {
+                    warn(WarningKind::DateOverlap,
+                         i,
+                         format!("dated {}, before entry {} ({})", date, previous + 1, previous_date));
+                } else if days == 0 && !shared_dates_expected {
+                    warn(WarningKind::DateOverlap,
+                         i,
+                         format!("dated {}, the same day as entry {}", date, previous + 1));
+                } else if usual_gap.is_some_and(|usual| days > usual * 3) {
+                    warn(WarningKind::DateGap,
+                         i,
+                         format!("dated {} days after entry {} ({}), although entries are usually {} apart",
+                                 days,
+                                 previous + 1,
+                                 previous_date,
+                                 plural_days(usual_gap.unwrap())));
+                }
+            }
+        }
+
+        warnings.sort_by_key(|w| w.entry);
+        warnings
+    }
+}
+
+/// Returns a number of days as text, e.g. "1 day" or "7 days".
+fn plural_days(days: i64) -> String {
+    if days == 1 {
+        "1 day".to_owned()
+    } else {
+        format!("{} days", days)
+    }
+}
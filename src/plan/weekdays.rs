@@ -0,0 +1,198 @@
+//! This module provides `ActiveDays`, the days of the week on which a plan
+//! is read, for plans which take days off (e.g. a plan read only on
+//! weekdays).
+//!
+//! A plan is active on every day unless its active days are set. On its
+//! other days, nothing in the plan is due (see `Plan::due_entries`), and its
+//! pace (see the `pace` module) is kept up on its active days alone, so that
+//! a plan read at 5 entries per week on weekdays is expected to have one
+//! entry read each weekday and none at the weekend.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de;
+
+use super::Plan;
+use super::super::errors::*;
+
+/// The days of the week in order, starting on Monday.
+const WEEK: [Weekday; 7] = [Weekday::Mon,
+                            Weekday::Tue,
+                            Weekday::Wed,
+                            Weekday::Thu,
+                            Weekday::Fri,
+                            Weekday::Sat,
+                            Weekday::Sun];
+
+/// A non-empty set of days of the week on which a plan is read.
+///
+/// In text, the days are written as `every day`, `weekdays`, `weekends`, or
+/// a comma-separated list of days and ranges of days, such as `mon-fri` or
+/// `mon, wed, fri` (the days may also be written in full).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ActiveDays {
+    /// The days, as a bit for each day, starting with Monday as the lowest
+    /// bit
+    mask: u8,
+}
+
+impl ActiveDays {
+    /// Returns the set of every day of the week.
+    pub fn every_day() -> ActiveDays {
+        ActiveDays { mask: 0x7f }
+    }
+
+    /// Returns the set of the given days, or an error if there are none.
+    pub fn new(days: &[Weekday]) -> Result<ActiveDays> {
+        if days.is_empty() {
+            bail!("a plan must be active on at least one day");
+        }
+        Ok(ActiveDays { mask: days.iter().fold(0, |mask, &d| mask | bit(d)) })
+    }
+
+    /// Returns whether the set contains the given day.
+    pub fn contains(&self, day: Weekday) -> bool {
+        self.mask & bit(day) != 0
+    }
+
+    /// Returns whether the set contains every day of the week.
+    pub fn is_every_day(&self) -> bool {
+        *self == ActiveDays::every_day()
+    }
+
+    /// Returns the number of days of the week in the set.
+    pub fn count(&self) -> u32 {
+        self.mask.count_ones()
+    }
+
+    /// Returns the number of days from `start` up to (but not including)
+    /// `end` which are in the set.
+    pub fn count_between(&self, start: NaiveDate, end: NaiveDate) -> u64 {
+        let total = (end - start).num_days().max(0) as u64;
+        let weeks = total / 7;
+        // The days left over after the whole weeks start on the same day of
+        // the week as `start`
+        let first = start.weekday().num_days_from_monday() as usize;
+        let rest = (0..total % 7)
+            .filter(|&i| self.contains(WEEK[(first + i as usize) % 7]))
+            .count();
+        weeks * u64::from(self.count()) + rest as u64
+    }
+
+    /// Returns the date of the `n`th day (starting at 1) in the set from
+    /// `start` onwards, counting `start` itself if it is in the set, or
+    /// `None` if it is too far away to be represented.
+    pub fn nth_from(&self, start: NaiveDate, n: u64) -> Option<NaiveDate> {
+        let count = u64::from(self.count());
+        // Whole weeks are skipped at once
+        let weeks = n.saturating_sub(1) / count;
+        let mut date = start.checked_add_days(Days::new(weeks.checked_mul(7)?))?;
+        let mut left = (n - weeks * count).max(1);
+        loop {
+            if self.contains(date.weekday()) {
+                left -= 1;
+                if left == 0 {
+                    return Some(date);
+                }
+            }
+            date = date.succ_opt()?;
+        }
+    }
+}
+
+impl Default for ActiveDays {
+    fn default() -> ActiveDays {
+        ActiveDays::every_day()
+    }
+}
+
+impl fmt::Display for ActiveDays {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mask {
+            0x7f => f.write_str("every day"),
+            0x1f => f.write_str("weekdays"),
+            0x60 => f.write_str("weekends"),
+            _ => {
+                let days: Vec<_> = WEEK.iter()
+                    .filter(|&&d| self.contains(d))
+                    .map(|d| d.to_string())
+                    .collect();
+                f.write_str(&days.join(", "))
+            }
+        }
+    }
+}
+
+impl FromStr for ActiveDays {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ActiveDays> {
+        let invalid = || -> Error { ErrorKind::TextFormat(format!("invalid days '{}'", s)).into() };
+        let lower = s.trim().to_lowercase();
+        match lower.as_str() {
+            "every day" | "daily" => return Ok(ActiveDays::every_day()),
+            "weekdays" => return ActiveDays::new(&WEEK[..5]),
+            "weekends" => return ActiveDays::new(&WEEK[5..]),
+            _ => {}
+        }
+
+        let mut days = Vec::new();
+        for part in lower.split(',') {
+            let (first, last) = match part.split_once('-') {
+                Some((first, last)) => (first, last),
+                None => (part, part),
+            };
+            let first = first.trim().parse::<Weekday>().map_err(|_| invalid())?;
+            let last = last.trim().parse::<Weekday>().map_err(|_| invalid())?;
+            // A range may wrap around the end of the week (e.g. `fri-mon`)
+            let mut day = first;
+            days.push(day);
+            while day != last {
+                day = day.succ();
+                days.push(day);
+            }
+        }
+        ActiveDays::new(&days).map_err(|_| invalid())
+    }
+}
+
+impl Serialize for ActiveDays {
+    fn serialize<S: Serializer>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ActiveDays {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<ActiveDays, D::Error>
+        where D: Deserializer<'de>
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+impl Plan {
+    /// Returns the days of the week on which the plan is read.
+    pub fn active_days(&self) -> ActiveDays {
+        self.active_days
+    }
+
+    /// Sets the days of the week on which the plan is read.
+    pub fn set_active_days(&mut self, days: ActiveDays) {
+        self.active_days = days;
+    }
+
+    /// Returns whether the plan is read on the given date, i.e. whether it
+    /// falls on one of its active days.
+    pub fn is_active_on(&self, date: NaiveDate) -> bool {
+        self.active_days.contains(date.weekday())
+    }
+}
+
+/// Returns the bit for the given day in the mask of an `ActiveDays`.
+fn bit(day: Weekday) -> u8 {
+    1 << day.num_days_from_monday()
+}
@@ -0,0 +1,171 @@
+//! This module provides `Reminder`, a rule saying when to remind the user to
+//! read, as given by the `reminders` setting (see the `config` module) and
+//! checked by `reading remind`, which is meant to be run regularly (e.g. by
+//! cron or a systemd timer).
+//!
+//! A rule has the form `{days} at {time}`, optionally followed by
+//! `if {condition}`, for example `daily at 21:00 if no entry completed today`
+//! or `weekdays at 07:30`. The days are `daily`, `weekdays`, `weekends` or a
+//! single weekday (e.g. `Sunday`), and the time is given as `HH:MM` in local
+//! time. A reminder is due from its time until the end of the day, as long
+//! as its condition holds; the conditions are:
+//!
+//! * `no entry completed today`: no entry of any plan has been completed
+//!   today (and there is a plan which isn't finished)
+//! * `entries due`: a scheduled entry of a plan is due today, or overdue
+//! * `behind schedule`: a plan has fallen behind its schedule or its pace
+//!
+//! Archived plans are never taken into account.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, NaiveTime, TimeZone, Weekday};
+
+use super::Plan;
+use super::errors::*;
+
+/// A rule saying when to remind the user to read.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Reminder {
+    days: Days,
+    time: NaiveTime,
+    condition: Option<Condition>,
+}
+
+/// The days on which a reminder can be due.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Days {
+    Daily,
+    Weekdays,
+    Weekends,
+    On(Weekday),
+}
+
+/// What has to be true of the plans for a reminder to be due.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Condition {
+    /// No entry of any plan has been completed today
+    NothingCompletedToday,
+    /// A scheduled entry is due today or overdue
+    EntriesDue,
+    /// A plan has fallen behind its schedule or its pace
+    BehindSchedule,
+}
+
+impl Reminder {
+    /// Returns the time of day from which the reminder is due.
+    pub fn time(&self) -> NaiveTime {
+        self.time
+    }
+
+    /// Returns the condition under which the reminder is due, if it has one.
+    pub fn condition(&self) -> Option<Condition> {
+        self.condition
+    }
+
+    /// Returns whether the reminder is due at the given time, given all the
+    /// plans.
+    pub fn is_due<Tz: TimeZone>(&self, plans: &[Plan], now: &DateTime<Tz>) -> bool {
+        let today = now.date_naive();
+        let on_day = match self.days {
+            Days::Daily => true,
+            Days::Weekdays => !is_weekend(today.weekday()),
+            Days::Weekends => is_weekend(today.weekday()),
+            Days::On(weekday) => today.weekday() == weekday,
+        };
+        if !on_day || now.time() < self.time {
+            return false;
+        }
+
+        let mut active = plans.iter().filter(|p| !p.is_archived());
+        match self.condition {
+            None => true,
+            Some(Condition::NothingCompletedToday) => {
+                let completed_today = plans.iter()
+                    .flat_map(|p| p.history())
+                    .any(|e| e.completed_at().with_timezone(&now.timezone()).date_naive() == today);
+                !completed_today && active.any(|p| !p.is_ended())
+            }
+            Some(Condition::EntriesDue) => {
+                active.any(|p| p.due_entries(today).iter().any(|d| d.entry().schedule().is_some()))
+            }
+            Some(Condition::BehindSchedule) => {
+                active.any(|p| p.behind_by(today) > 0 || p.projection(today).is_some_and(|pr| pr.days_ahead() < 0))
+            }
+        }
+    }
+
+    /// Returns a message reminding the user what to do.
+    pub fn message(&self) -> &'static str {
+        match self.condition {
+            None => "Time to read",
+            Some(Condition::NothingCompletedToday) => "Time to read: you haven't completed any entries today",
+            Some(Condition::EntriesDue) => "Time to read: some scheduled entries are due",
+            Some(Condition::BehindSchedule) => "Time to read: some plans are behind schedule",
+        }
+    }
+}
+
+/// Returns the reminders which are due at the given time, given all the
+/// plans.
+pub fn due_reminders<'a, Tz: TimeZone>(reminders: &'a [Reminder],
+                                       plans: &[Plan],
+                                       now: &DateTime<Tz>)
+                                       -> Vec<&'a Reminder> {
+    reminders.iter().filter(|r| r.is_due(plans, now)).collect()
+}
+
+/// Returns whether the given day is part of the weekend.
+fn is_weekend(weekday: Weekday) -> bool {
+    weekday == Weekday::Sat || weekday == Weekday::Sun
+}
+
+impl FromStr for Reminder {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Reminder> {
+        let invalid = |problem: &str| -> Error { ErrorKind::InvalidReminder(s.to_owned(), problem.to_owned()).into() };
+        let lower = s.trim().to_lowercase();
+        let (rule, condition) = match lower.split_once(" if ") {
+            Some((rule, condition)) => (rule, Some(condition.trim())),
+            None => (&lower[..], None),
+        };
+        let (days, time) = rule.split_once(" at ").ok_or_else(|| invalid("there is no time (e.g. 'at 21:00')"))?;
+
+        let days = match days.trim() {
+            "daily" => Days::Daily,
+            "weekdays" => Days::Weekdays,
+            "weekends" => Days::Weekends,
+            day => Days::On(day.parse().map_err(|_| invalid("the days must be daily, weekdays, weekends or a day"))?),
+        };
+        let time = NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|_| invalid("the time must be HH:MM"))?;
+        let condition = match condition {
+            None => None,
+            Some("no entry completed today") => Some(Condition::NothingCompletedToday),
+            Some("entries due") => Some(Condition::EntriesDue),
+            Some("behind schedule") => Some(Condition::BehindSchedule),
+            Some(_) => return Err(invalid("unknown condition")),
+        };
+
+        Ok(Reminder { days, time, condition })
+    }
+}
+
+impl fmt::Display for Reminder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.days {
+            Days::Daily => f.write_str("daily")?,
+            Days::Weekdays => f.write_str("weekdays")?,
+            Days::Weekends => f.write_str("weekends")?,
+            Days::On(weekday) => write!(f, "{:?}", weekday)?,
+        }
+        write!(f, " at {}", self.time.format("%H:%M"))?;
+        match self.condition {
+            None => Ok(()),
+            Some(Condition::NothingCompletedToday) => f.write_str(" if no entry completed today"),
+            Some(Condition::EntriesDue) => f.write_str(" if entries due"),
+            Some(Condition::BehindSchedule) => f.write_str(" if behind schedule"),
+        }
+    }
+}
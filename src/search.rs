@@ -0,0 +1,119 @@
+//! This module provides functions for searching the entries of plans, e.g.
+//! to find out which plan contains a certain passage.
+//!
+//! A search is described by a `Query`, which matches either a plain
+//! substring (ignoring case) or a regular expression. The title and
+//! description of each entry are searched separately, and every field that
+//! matches gives a `SearchHit` recording where the match was found, so that
+//! it can be highlighted when displayed.
+
+use regex::{Regex, RegexBuilder};
+
+use super::Plan;
+use super::errors::*;
+
+/// A query to search for in the entries of plans.
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// The compiled form of the query
+    regex: Regex,
+}
+
+/// The field of an entry in which a match was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// The title of the entry.
+    Title,
+    /// The description of the entry.
+    Description,
+}
+
+/// A match of a query in one of the fields of an entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchHit {
+    plan: String,
+    entry: usize,
+    field: Field,
+    text: String,
+    start: usize,
+    end: usize,
+}
+
+impl Query {
+    /// Returns a query matching the given text anywhere in a field,
+    /// ignoring case, or an `InvalidQuery` error if the text is too long
+    /// to search for.
+    pub fn substring(text: &str) -> Result<Query> {
+        let regex = RegexBuilder::new(&::regex::escape(text))
+            .case_insensitive(true)
+            .build()
+            .chain_err(|| ErrorKind::InvalidQuery(text.into()))?;
+        Ok(Query { regex })
+    }
+
+    /// Returns a query matching the given regular expression, or an
+    /// `InvalidQuery` error if the expression is not valid.
+    pub fn regex(pattern: &str) -> Result<Query> {
+        let regex = Regex::new(pattern).chain_err(|| ErrorKind::InvalidQuery(pattern.into()))?;
+        Ok(Query { regex })
+    }
+
+    /// Returns the byte range of the first match of the query in the given
+    /// text, if there is one.
+    pub fn find(&self, text: &str) -> Option<(usize, usize)> {
+        self.regex.find(text).map(|m| (m.start(), m.end()))
+    }
+}
+
+impl SearchHit {
+    /// Returns the name of the plan containing the match.
+    pub fn plan(&self) -> &str {
+        &self.plan
+    }
+
+    /// Returns the number of the entry containing the match (starting at 1,
+    /// as with `Plan::current_entry_number`).
+    pub fn entry_number(&self) -> usize {
+        self.entry
+    }
+
+    /// Returns the field in which the match was found.
+    pub fn field(&self) -> Field {
+        self.field
+    }
+
+    /// Returns the full text of the field in which the match was found.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the text of the field split into the parts before, inside
+    /// and after the match, for highlighting.
+    pub fn parts(&self) -> (&str, &str, &str) {
+        (&self.text[..self.start], &self.text[self.start..self.end], &self.text[self.end..])
+    }
+}
+
+/// Returns all the matches of the query in the entries of the given plan,
+/// in order.
+pub fn search_plan(plan: &Plan, query: &Query) -> Vec<SearchHit> {
+    let mut hits = Vec::new();
+
+    for (i, entry) in plan.entries().enumerate() {
+        let fields = [(Field::Title, entry.title()), (Field::Description, entry.description())];
+        for &(field, text) in &fields {
+            if let Some((start, end)) = query.find(text) {
+                hits.push(SearchHit {
+                    plan: plan.name().to_owned(),
+                    entry: i + 1,
+                    field,
+                    text: text.to_owned(),
+                    start,
+                    end,
+                });
+            }
+        }
+    }
+
+    hits
+}
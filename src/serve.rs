@@ -0,0 +1,171 @@
+//! The `serve` subcommand, which is only available when the `serve` feature
+//! is enabled.
+//!
+//! Serving makes the plans available over a small HTTP API, so that other
+//! programs (such as a web page on a phone or a button on a control panel)
+//! can show and advance them. Every response is JSON, in the same form as
+//! the output of the corresponding subcommand with `--json`:
+//!
+//! * `GET /plans` lists the unarchived plans (add `?all=true` to include
+//!   archived plans, or `?tag={tag}` to list only plans with a tag).
+//! * `GET /plans/{plan}` returns a plan along with all its entries.
+//! * `GET /plans/{plan}/current` returns the current entry of a plan.
+//! * `POST /plans/{plan}/next` advances a plan (by `?count={count}` entries,
+//!   from 1 to 1000, or one by default) and returns the new current entry.
+//!
+//! A failed request gets a response with an error status (e.g. 404 if the
+//! plan doesn't exist, or 400 for a plan name containing `/`, `\` or `..`)
+//! and a body like `{ "error": "..." }`. There is no authentication, so the
+//! server only listens on the local computer unless told otherwise.
+
+use serde_json::Value;
+use tiny_http::{Header, Method, Response, Server};
+
+use reading::PlanStore;
+use reading::errors::*;
+use reading::files;
+
+use output::{change_json, entry_json, plan_json, position_json, summary_json};
+
+/// The most entries a plan can be advanced by in one request.
+const MAX_COUNT: i32 = 1000;
+
+/// An unsuccessful response to a request: its HTTP status code and a
+/// description of the problem.
+struct Failure(u16, String);
+
+impl From<Error> for Failure {
+    fn from(e: Error) -> Failure {
+        let status = match *e.kind() {
+            ErrorKind::InvalidPlanName(_) => 400,
+            ErrorKind::PlanDoesNotExist(_) => 404,
+            ErrorKind::ConcurrentModification(_) => 409,
+            _ => 500,
+        };
+        let message = e.iter().map(|e| e.to_string()).collect::<Vec<_>>().join(": ");
+        Failure(status, message)
+    }
+}
+
+/// Serves the plans in the given store at the given address (e.g.
+/// `127.0.0.1:8080`) until the program is stopped, handling one request at
+/// a time.
+pub fn run<S: PlanStore>(store: &mut S, address: &str) -> Result<()> {
+    let server = Server::http(address).map_err(|e| format!("could not listen on {}: {}", address, e))?;
+    println!("Serving plans at http://{}/plans (press Ctrl-C to stop)", address);
+    let content_type = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap();
+
+    for request in server.incoming_requests() {
+        let (status, body) = match handle(store, request.method(), request.url()) {
+            Ok(body) => (200, body),
+            Err(Failure(status, message)) => (status, json!({ "error": message })),
+        };
+        let response = Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(content_type.clone());
+        // A client which went away doesn't stop the others from being served
+        if let Err(e) = request.respond(response) {
+            eprintln!("Error: could not send response: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Handles a request with the given method and URL (the path and query
+/// string), returning the body of the response.
+fn handle<S: PlanStore>(store: &mut S, method: &Method, url: &str) -> ::std::result::Result<Value, Failure> {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let param = |key: &str| {
+        query.split('&')
+            .filter_map(|p| p.split_once('='))
+            .find(|&(k, _)| k == key)
+            .map(|(_, v)| percent_decode(&v.replace('+', " ")))
+    };
+    let segments: Vec<_> = path.split('/').filter(|s| !s.is_empty()).map(percent_decode).collect();
+    let segments: Vec<_> = segments.iter().map(String::as_str).collect();
+    // A decoded name could lead outside the plans directory (e.g. `..%2Fx`)
+    if let ["plans", name, ..] = segments[..] {
+        if !files::is_valid_plan_name(name) {
+            return Err(Failure(400, format!("invalid plan name '{}'", name)));
+        }
+    }
+
+    match (method, &segments[..]) {
+        (&Method::Get, ["plans"]) => {
+            let all = param("all").is_some_and(|a| a == "true");
+            let tag = param("tag");
+            let mut failures = 0;
+            let mut plans = Vec::new();
+            for summary in store.summaries()? {
+                match summary {
+                    Ok(s) if (all || !s.is_archived()) && tag.as_ref().is_none_or(|t| s.has_tag(t)) => {
+                        plans.push(summary_json(&s))
+                    }
+                    Ok(_) => {}
+                    Err(_) => failures += 1,
+                }
+            }
+            Ok(json!({ "plans": plans, "failures": failures }))
+        }
+        (&Method::Get, ["plans", name]) => {
+            let plan = store.read(name)?;
+            let entries: Vec<_> = plan.entries().enumerate().map(|(i, e)| entry_json(i + 1, e)).collect();
+            Ok(json!({ "plan": plan_json(&plan), "entries": entries }))
+        }
+        (&Method::Get, ["plans", name, "current"]) => {
+            let plan = store.read(name)?;
+            let entry = plan.current_entry().map(|e| entry_json(plan.current_entry_number(), e));
+            Ok(json!({ "plan": plan_json(&plan), "entry": entry }))
+        }
+        (&Method::Post, ["plans", name, "next"]) => {
+            let count = match param("count") {
+                Some(c) => match c.parse() {
+                    Ok(count) if (1..=MAX_COUNT).contains(&count) => count,
+                    _ => return Err(Failure(400, format!("invalid count '{}' (must be from 1 to {})", c, MAX_COUNT))),
+                },
+                None => 1,
+            };
+            if store.read(name)?.is_archived() {
+                return Err(Failure(409, format!("plan '{}' is archived", name)));
+            }
+            let (from, plan) = super::update(store, name, |plan| {
+                let from = position_json(plan);
+                plan.next(count);
+                Ok((from, plan.clone()))
+            })?;
+            let entry = plan.current_entry().map(|e| entry_json(plan.current_entry_number(), e));
+            let mut result = change_json(&plan, from);
+            result["entry"] = entry.into();
+            Ok(result)
+        }
+        (_, ["plans"]) |
+        (_, ["plans", _]) |
+        (_, ["plans", _, "current"]) |
+        (_, ["plans", _, "next"]) => Err(Failure(405, format!("{} is not allowed here", method))),
+        _ => Err(Failure(404, format!("there is nothing at {}", path))),
+    }
+}
+
+/// Decodes the percent-encoded characters (e.g. `%20` for a space) in part
+/// of a URL, leaving any invalid encodings alone.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3)
+            .filter(|h| h.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|h| u8::from_str_radix(&String::from_utf8_lossy(h), 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,565 @@
+//! This module provides functions for computing statistics about plans,
+//! such as how much of a plan has been completed and how quickly it is
+//! being read, so that they can be shown by any interface.
+//!
+//! Reading pace is based on the history of each plan (see
+//! `Plan::history`), looking back over at most the last four weeks, so
+//! plans that were completed before history was recorded will not have a
+//! pace.
+//!
+//! The history is also used to compute reading streaks: the number of
+//! consecutive days on which at least one entry of any plan was completed,
+//! and reports of the entries completed each day over a period (see
+//! `Report`). A `Calendar` shows the entries scheduled for and completed on
+//! each day of a month.
+
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, TimeZone, Utc};
+
+use super::{Estimate, HistoryEvent, Plan};
+use super::errors::*;
+
+/// The number of days of history used to compute the reading pace.
+const PACE_WINDOW_DAYS: i64 = 28;
+
+/// Statistics about the progress through a single plan.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanStats {
+    name: String,
+    total: usize,
+    completed: usize,
+    skipped: usize,
+    remaining_time: Option<Estimate>,
+    entries_per_week: Option<f64>,
+    estimated_completion: Option<NaiveDate>,
+}
+
+/// Statistics about the progress through a collection of plans.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    plans: usize,
+    total: usize,
+    completed: usize,
+    skipped: usize,
+    remaining_time: Option<Estimate>,
+    entries_per_week: Option<f64>,
+}
+
+/// The entries completed in a collection of plans on each day of a period
+/// (e.g. the last two weeks).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    start: NaiveDate,
+    end: NaiveDate,
+    plans: Vec<PlanReport>,
+    /// The number of entries completed in all the plans on each day
+    daily: Vec<usize>,
+}
+
+/// The entries scheduled for and completed on each day of a month, in a
+/// collection of plans.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Calendar {
+    /// The first day of the month
+    start: NaiveDate,
+    /// The number of entries scheduled for each day of the month
+    scheduled: Vec<usize>,
+    /// The number of entries completed on each day of the month
+    completed: Vec<usize>,
+}
+
+/// The entries of a single plan completed on each day of the period of a
+/// `Report`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanReport {
+    name: String,
+    daily: Vec<usize>,
+}
+
+impl PlanStats {
+    /// Computes the statistics for the given plan as of the given time.
+    ///
+    /// Dates (e.g. of completed entries) are determined in the time zone
+    /// of `now`.
+    pub fn for_plan<Tz: TimeZone>(plan: &Plan, now: &DateTime<Tz>) -> PlanStats {
+        let today = now.date_naive();
+        let completed = plan.current_entry_number() - 1;
+        let remaining = plan.len() - completed;
+        let entries_per_week = pace(plan.history().iter().map(|e| e.completed_at()), now);
+        let estimated_completion = match entries_per_week {
+            Some(pace) if remaining > 0 => {
+                let days = (remaining as f64 * 7.0 / pace).ceil() as i64;
+                Some(today + Duration::days(days))
+            }
+            _ => None,
+        };
+
+        PlanStats {
+            name: plan.name().to_owned(),
+            total: plan.len(),
+            completed,
+            skipped: plan.skipped_count(),
+            remaining_time: plan.total_remaining_time(),
+            entries_per_week,
+            estimated_completion,
+        }
+    }
+
+    /// Returns the name of the plan.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the total number of entries in the plan.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Returns the number of entries before the current entry (for a
+    /// cyclic plan, this is the number completed in the current pass).
+    pub fn completed(&self) -> usize {
+        self.completed
+    }
+
+    /// Returns the number of the completed entries which were skipped
+    /// rather than read (see `Plan::skip_current`).
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Returns the number of entries left to read, including the current
+    /// entry.
+    pub fn remaining(&self) -> usize {
+        self.total - self.completed
+    }
+
+    /// Returns the estimated time needed to read the rest of the plan (see
+    /// `Plan::total_remaining_time`).
+    pub fn remaining_time(&self) -> Option<Estimate> {
+        self.remaining_time
+    }
+
+    /// Returns the percentage of the plan which has been completed.
+    pub fn percent_complete(&self) -> f64 {
+        percent(self.completed, self.total)
+    }
+
+    /// Returns the average number of entries completed per week recently,
+    /// or `None` if no entries have been completed recently.
+    pub fn entries_per_week(&self) -> Option<f64> {
+        self.entries_per_week
+    }
+
+    /// Returns the date on which the plan will be finished if the current
+    /// pace is kept up, or `None` if there is no pace or the plan is
+    /// already finished.
+    pub fn estimated_completion(&self) -> Option<NaiveDate> {
+        self.estimated_completion
+    }
+}
+
+impl Summary {
+    /// Computes the statistics for all the given plans as of the given
+    /// time.
+    pub fn for_plans<'a, I, Tz>(plans: I, now: &DateTime<Tz>) -> Summary
+        where I: IntoIterator<Item = &'a Plan>,
+              Tz: TimeZone
+    {
+        let mut summary = Summary {
+            plans: 0,
+            total: 0,
+            completed: 0,
+            skipped: 0,
+            remaining_time: None,
+            entries_per_week: None,
+        };
+        let mut history = Vec::new();
+
+        for plan in plans {
+            summary.plans += 1;
+            summary.total += plan.len();
+            summary.completed += plan.current_entry_number() - 1;
+            summary.skipped += plan.skipped_count();
+            if let Some(time) = plan.total_remaining_time() {
+                summary.remaining_time = Some(summary.remaining_time.unwrap_or_default() + time);
+            }
+            history.extend(plan.history().iter().map(|e| e.completed_at()));
+        }
+        summary.entries_per_week = pace(history, now);
+
+        summary
+    }
+
+    /// Returns the number of plans.
+    pub fn plans(&self) -> usize {
+        self.plans
+    }
+
+    /// Returns the total number of entries in all the plans.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Returns the number of entries completed in all the plans.
+    pub fn completed(&self) -> usize {
+        self.completed
+    }
+
+    /// Returns the number of completed entries in all the plans which were
+    /// skipped rather than read.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Returns the number of entries left to read in all the plans.
+    pub fn remaining(&self) -> usize {
+        self.total - self.completed
+    }
+
+    /// Returns the estimated time needed to read the rest of all the plans,
+    /// or `None` if none of the remaining entries have estimates.
+    pub fn remaining_time(&self) -> Option<Estimate> {
+        self.remaining_time
+    }
+
+    /// Returns the percentage of all entries which have been completed.
+    pub fn percent_complete(&self) -> f64 {
+        percent(self.completed, self.total)
+    }
+
+    /// Returns the average number of entries completed per week recently
+    /// across all the plans, or `None` if no entries have been completed
+    /// recently.
+    pub fn entries_per_week(&self) -> Option<f64> {
+        self.entries_per_week
+    }
+}
+
+impl Report {
+    /// Computes the report for the given plans over the period from `start`
+    /// to the day of `now` (inclusive), based on their histories.
+    ///
+    /// Dates are determined in the time zone of `now`, and only plans with
+    /// entries completed during the period are included in the report.
+    pub fn for_plans<'a, I, Tz>(plans: I, start: NaiveDate, now: &DateTime<Tz>) -> Report
+        where I: IntoIterator<Item = &'a Plan>,
+              Tz: TimeZone
+    {
+        let end = now.date_naive();
+        let start = start.min(end);
+        let days = (end - start).num_days() as usize + 1;
+        let mut report = Report {
+            start,
+            end,
+            plans: Vec::new(),
+            daily: vec![0; days],
+        };
+
+        for plan in plans {
+            let mut daily = vec![0; days];
+            for event in plan.history() {
+                let date = event.completed_at().with_timezone(&now.timezone()).date_naive();
+                if date >= start && date <= end {
+                    daily[(date - start).num_days() as usize] += 1;
+                }
+            }
+            if daily.iter().any(|&n| n > 0) {
+                for (total, n) in report.daily.iter_mut().zip(&daily) {
+                    *total += n;
+                }
+                report.plans.push(PlanReport {
+                    name: plan.name().to_owned(),
+                    daily,
+                });
+            }
+        }
+
+        report
+    }
+
+    /// Returns the first day of the period.
+    pub fn start(&self) -> NaiveDate {
+        self.start
+    }
+
+    /// Returns the last day of the period.
+    pub fn end(&self) -> NaiveDate {
+        self.end
+    }
+
+    /// Returns the reports for the plans with entries completed during the
+    /// period.
+    pub fn plans(&self) -> &[PlanReport] {
+        &self.plans
+    }
+
+    /// Returns the number of entries completed in all the plans on each day
+    /// of the period, starting with the first.
+    pub fn daily(&self) -> &[usize] {
+        &self.daily
+    }
+
+    /// Returns the number of entries completed in all the plans during the
+    /// period.
+    pub fn completed(&self) -> usize {
+        self.daily.iter().sum()
+    }
+
+    /// Returns the number of days in the period on which any entry was
+    /// completed.
+    pub fn days_read(&self) -> usize {
+        self.daily.iter().filter(|&&n| n > 0).count()
+    }
+}
+
+impl PlanReport {
+    /// Returns the name of the plan.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the number of entries of the plan completed on each day of
+    /// the period, starting with the first.
+    pub fn daily(&self) -> &[usize] {
+        &self.daily
+    }
+
+    /// Returns the number of entries of the plan completed during the
+    /// period.
+    pub fn completed(&self) -> usize {
+        self.daily.iter().sum()
+    }
+}
+
+impl Calendar {
+    /// Computes the calendar for the given plans for the month starting on
+    /// `start` (see `month_start`), based on the schedules of their entries
+    /// and their histories.
+    ///
+    /// Every entry scheduled for a day counts, whether or not it has been
+    /// read (an entry scheduled for a range of dates counts on each of them,
+    /// and one scheduled for a day of every week on each such day), except
+    /// for the entries of archived plans. The days entries were completed
+    /// are determined in the time zone of `now`.
+    pub fn for_plans<'a, I, Tz>(plans: I, start: NaiveDate, now: &DateTime<Tz>) -> Calendar
+        where I: IntoIterator<Item = &'a Plan>,
+              Tz: TimeZone
+    {
+        let end = start.checked_add_months(Months::new(1)).unwrap_or(start);
+        let days = (end - start).num_days() as usize;
+        let mut calendar = Calendar {
+            start,
+            scheduled: vec![0; days],
+            completed: vec![0; days],
+        };
+
+        for plan in plans {
+            for event in plan.history() {
+                let date = event.completed_at().with_timezone(&now.timezone()).date_naive();
+                if date >= start && date < end {
+                    calendar.completed[(date - start).num_days() as usize] += 1;
+                }
+            }
+            if plan.is_archived() {
+                continue;
+            }
+            for schedule in plan.entries().filter_map(|e| e.schedule()) {
+                for (date, scheduled) in start.iter_days().zip(&mut calendar.scheduled) {
+                    if schedule.includes(date) {
+                        *scheduled += 1;
+                    }
+                }
+            }
+        }
+
+        calendar
+    }
+
+    /// Returns the first day of the month.
+    pub fn start(&self) -> NaiveDate {
+        self.start
+    }
+
+    /// Returns the number of entries scheduled for each day of the month,
+    /// starting with the first.
+    pub fn scheduled(&self) -> &[usize] {
+        &self.scheduled
+    }
+
+    /// Returns the number of entries completed on each day of the month,
+    /// starting with the first.
+    pub fn completed(&self) -> &[usize] {
+        &self.completed
+    }
+}
+
+/// Returns the first day of the month described by `month`, as of the given
+/// day, for use with `Calendar::for_plans`.
+///
+/// The month is either given by its year and number (e.g. `2017-05`), or
+/// relative to the month of the given day as a number of months before or
+/// after it (e.g. `-1` for last month or `+1` for next month).
+pub fn month_start(month: &str, today: NaiveDate) -> Result<NaiveDate> {
+    let invalid = || -> Error { ErrorKind::TextFormat(format!("invalid month '{}'", month)).into() };
+    let month = month.trim();
+    let this_month = today.with_day(1).ok_or_else(&invalid)?;
+    if let Some(n) = month.strip_prefix('+') {
+        let n = n.parse().map_err(|_| invalid())?;
+        return this_month.checked_add_months(Months::new(n)).ok_or_else(invalid);
+    }
+    if let Some(n) = month.strip_prefix('-') {
+        let n = n.parse().map_err(|_| invalid())?;
+        return this_month.checked_sub_months(Months::new(n)).ok_or_else(invalid);
+    }
+    NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d").map_err(|_| invalid())
+}
+
+/// Returns the first day of the period described by `since`, as of the given
+/// day, for use with `Report::for_plans`.
+///
+/// The period is either a length of time ending today, which is a number
+/// followed by `d`, `w` or `m` (days, weeks or months; e.g. `2w` is the last
+/// 14 days, including today), or the date it starts on (e.g. `2017-05-01`).
+pub fn period_start(since: &str, today: NaiveDate) -> Result<NaiveDate> {
+    let invalid = || -> Error { ErrorKind::TextFormat(format!("invalid period '{}'", since)).into() };
+    let since = since.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(since, "%Y-%m-%d") {
+        return Ok(date);
+    }
+
+    let unit = since.chars().last().ok_or_else(&invalid)?;
+    let n: u32 = since[..since.len() - unit.len_utf8()].parse().map_err(|_| invalid())?;
+    if n == 0 {
+        return Err(invalid());
+    }
+    let start = match unit.to_ascii_lowercase() {
+        'd' => today.checked_sub_signed(Duration::days(i64::from(n))),
+        'w' => today.checked_sub_signed(Duration::weeks(i64::from(n))),
+        'm' => today.checked_sub_months(Months::new(n)),
+        _ => None,
+    };
+    // The period includes today, so it starts the day after the same day
+    // in the past
+    start.and_then(|d| d.succ_opt()).ok_or_else(invalid)
+}
+
+/// Returns a sparkline for the given counts: a line of block characters,
+/// one for each count, whose heights are proportional to the counts.
+///
+/// If there are more counts than `width`, consecutive counts are added
+/// together (e.g. the days of each week), so that the line is no longer
+/// than `width` characters.
+pub fn sparkline(counts: &[usize], width: usize) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let group = (counts.len() + width - 1) / width.max(1);
+    let counts: Vec<usize> = counts.chunks(group.max(1)).map(|c| c.iter().sum()).collect();
+    let max = counts.iter().cloned().max().unwrap_or(0);
+    counts.iter()
+        .map(|&n| BARS[(n * (BARS.len() - 1)).checked_div(max).unwrap_or(0)])
+        .collect()
+}
+
+/// Returns the length in days of the current reading streak across all the
+/// given plans, as of the given time.
+///
+/// A streak that ended yesterday is still current, since there is still
+/// time to read something today; if nothing was read yesterday or today,
+/// the current streak is 0.
+pub fn current_streak<'a, I, Tz>(plans: I, now: &DateTime<Tz>) -> u32
+    where I: IntoIterator<Item = &'a Plan>,
+          Tz: TimeZone
+{
+    current_streak_in(plans.into_iter().map(Plan::history), now)
+}
+
+/// Returns the length in days of the current reading streak given the
+/// histories of some plans, as with `current_streak` (this is useful when
+/// only the histories are available, e.g. from `PlanSummary::history`).
+pub fn current_streak_in<'a, I, Tz>(histories: I, now: &DateTime<Tz>) -> u32
+    where I: IntoIterator<Item = &'a [HistoryEvent]>,
+          Tz: TimeZone
+{
+    let dates = reading_dates(histories, now);
+    let today = now.date_naive();
+    let mut day = if dates.contains(&today) {
+        today
+    } else {
+        today - Duration::days(1)
+    };
+
+    let mut streak = 0;
+    while dates.contains(&day) {
+        streak += 1;
+        day -= Duration::days(1);
+    }
+    streak
+}
+
+/// Returns the length in days of the longest reading streak ever recorded
+/// across all the given plans.
+///
+/// Days are determined in the time zone of `now`.
+pub fn longest_streak<'a, I, Tz>(plans: I, now: &DateTime<Tz>) -> u32
+    where I: IntoIterator<Item = &'a Plan>,
+          Tz: TimeZone
+{
+    let mut longest = 0;
+    let mut streak = 0;
+    let mut previous: Option<NaiveDate> = None;
+
+    for date in reading_dates(plans.into_iter().map(Plan::history), now) {
+        streak = match previous {
+            Some(p) if date - p == Duration::days(1) => streak + 1,
+            _ => 1,
+        };
+        longest = longest.max(streak);
+        previous = Some(date);
+    }
+    longest
+}
+
+/// Returns the dates (in the time zone of `now`) on which any entry was
+/// completed, given the histories of some plans.
+fn reading_dates<'a, I, Tz>(histories: I, now: &DateTime<Tz>) -> BTreeSet<NaiveDate>
+    where I: IntoIterator<Item = &'a [HistoryEvent]>,
+          Tz: TimeZone
+{
+    histories.into_iter()
+        .flatten()
+        .map(|e| e.completed_at().with_timezone(&now.timezone()).date_naive())
+        .collect()
+}
+
+/// Returns the given fraction as a percentage, treating an empty total as
+/// being complete.
+fn percent(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        100.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+/// Computes the average number of entries completed per week, given the
+/// completion times of the entries.
+///
+/// Only the last `PACE_WINDOW_DAYS` days are considered; if reading
+/// started more recently than that, the pace is computed over the time
+/// since reading started (but at least a week, so that a good first day
+/// doesn't give an unrealistic pace).
+fn pace<I, Tz>(completions: I, now: &DateTime<Tz>) -> Option<f64>
+    where I: IntoIterator<Item = DateTime<Utc>>,
+          Tz: TimeZone
+{
+    let today = now.date_naive();
+    let start = today - Duration::days(PACE_WINDOW_DAYS - 1);
+    let dates: Vec<_> = completions.into_iter()
+        .map(|t| t.with_timezone(&now.timezone()).date_naive())
+        .filter(|&d| d >= start && d <= today)
+        .collect();
+
+    let first = dates.iter().min()?;
+    let days = ((today - *first).num_days() + 1).max(7);
+    Some(dates.len() as f64 * 7.0 / days as f64)
+}
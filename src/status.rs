@@ -0,0 +1,110 @@
+//! This module provides the exit statuses of `reading`, so that scripts
+//! can tell what happened without reading its output (e.g. with `--quiet`):
+//!
+//! * 0: the subcommand succeeded
+//! * 1: something else went wrong (including invalid arguments)
+//! * 2: a plan (or a profile, bookmark or plan in the trash, or the plans
+//!   directory) doesn't exist
+//! * 3: a plan has already ended, so it can't be advanced
+//! * 4: some input is invalid, such as a plan file (in any format), the
+//!   value of a property or setting, or an entry number past the end of a
+//!   plan
+//! * 5: a plan (or profile) already exists
+//! * 6: a plan is being changed by another program, so trying again later
+//!   may work
+//! * 7: `reading remind` found a reminder which is due
+//!
+//! Every error is given its status by `exit_status`, from the first error
+//! in its chain of causes which has a status of its own. When some of
+//! several plans can't be changed, the error for the first of them is the
+//! cause (see `ErrorKind::PlansNotChanged`), so it gives the status.
+
+use std::error::Error as StdError;
+
+use serde_json;
+#[cfg(feature = "yaml-format")]
+use serde_yaml;
+use toml;
+
+use super::errors::*;
+
+/// The exit status when something goes wrong which has no status of its own.
+pub const ERROR: i32 = 1;
+/// The exit status when something doesn't exist.
+pub const NOT_FOUND: i32 = 2;
+/// The exit status when a plan has already ended.
+pub const PLAN_ENDED: i32 = 3;
+/// The exit status when some input is invalid.
+pub const INVALID_INPUT: i32 = 4;
+/// The exit status when something already exists.
+pub const ALREADY_EXISTS: i32 = 5;
+/// The exit status when a plan is being changed by another program.
+pub const BUSY: i32 = 6;
+/// The exit status of `reading remind` when a reminder is due, which is
+/// different from that of any error.
+pub const REMINDER_DUE: i32 = 7;
+
+/// Returns the exit status for the given error (see the module
+/// documentation).
+pub fn exit_status(e: &Error) -> i32 {
+    let mut error = e;
+    loop {
+        if let Some(status) = kind_status(error.kind()) {
+            return status;
+        }
+        let next = match error.1.next_error {
+            Some(ref next) => next,
+            None => return ERROR,
+        };
+        if let Some(next) = next.downcast_ref::<Error>() {
+            error = next;
+        } else if let Some(json) = next.downcast_ref::<serde_json::Error>() {
+            return if json.is_io() { ERROR } else { INVALID_INPUT };
+        } else {
+            return foreign_status(&**next);
+        }
+    }
+}
+
+/// Returns the exit status for an error from another crate which isn't a
+/// JSON error.
+fn foreign_status(error: &(dyn StdError + Send + 'static)) -> i32 {
+    #[cfg(feature = "yaml-format")]
+    {
+        if error.is::<serde_yaml::Error>() {
+            return INVALID_INPUT;
+        }
+    }
+    if error.is::<toml::de::Error>() { INVALID_INPUT } else { ERROR }
+}
+
+/// Returns the exit status for the given kind of error, if it has one.
+fn kind_status(kind: &ErrorKind) -> Option<i32> {
+    match *kind {
+        ErrorKind::PlanDoesNotExist(_) |
+        ErrorKind::MisspelledPlan(..) |
+        ErrorKind::ProfileDoesNotExist(_) |
+        ErrorKind::BookmarkDoesNotExist(..) |
+        ErrorKind::NotInTrash(_) |
+        ErrorKind::NoConfigDirectory => Some(NOT_FOUND),
+        ErrorKind::PlanEnded(_) => Some(PLAN_ENDED),
+        ErrorKind::TextFormat(_) |
+        ErrorKind::InvalidPropertyValue(..) |
+        ErrorKind::InvalidSettingValue(..) |
+        ErrorKind::InvalidQuery(_) |
+        ErrorKind::InvalidPlanName(_) |
+        ErrorKind::InvalidReminder(..) |
+        ErrorKind::InvalidPlan(..) |
+        ErrorKind::UnsupportedVersion(..) |
+        ErrorKind::UnknownProperty(_) |
+        ErrorKind::InvalidGenerator(_) |
+        ErrorKind::EntryOutOfRange(..) |
+        ErrorKind::Csv(_) => Some(INVALID_INPUT),
+        ErrorKind::Json(ref e) if !e.is_io() => Some(INVALID_INPUT),
+        ErrorKind::PlanAlreadyExists(_) |
+        ErrorKind::ProfileAlreadyExists(_) => Some(ALREADY_EXISTS),
+        ErrorKind::PlanLocked(_) |
+        ErrorKind::ConcurrentModification(_) => Some(BUSY),
+        _ => None,
+    }
+}
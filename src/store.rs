@@ -0,0 +1,500 @@
+//! This module provides the `PlanStore` trait, which abstracts over the
+//! place where plans are stored. The standard implementation is `FsStore`
+//! in the `files` module, which stores plans as files in a directory, but
+//! anything that can read and write plans by name (e.g. a database) can
+//! implement the trait and be used in the same way.
+//!
+//! Every plan in a store is identified by its name, so a store can contain
+//! at most one plan with any given name.
+//!
+//! For programs that don't need to keep plans around (and for tests), the
+//! `MemoryStore` in this module keeps plans in memory only.
+//!
+//! Programs which show plans as they change (e.g. a GUI) can wrap any store
+//! in an `ObservedStore`, which tells its subscribers about every plan that
+//! is added, removed, advanced or otherwise changed through it, so that
+//! they don't have to keep reading the plans to find out.
+
+use std::collections::HashMap;
+
+use super::Plan;
+use super::plan::summary::PlanSummary;
+use super::search::{self, Query, SearchHit};
+use super::errors::*;
+
+/// Describes the set of plans that a command should operate on.
+///
+/// Commands that can act on more than one plan at a time (e.g. advancing
+/// every plan with a certain tag) should use this type along with the
+/// `PlanStore::select` method, so that all of them select plans in the
+/// same way.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Selection {
+    /// The single plan with the given name.
+    Name(String),
+    /// Every plan having the given tag.
+    Tag(String),
+    /// Every plan in the store.
+    All,
+}
+
+impl Selection {
+    /// Returns whether the given plan is part of this selection.
+    pub fn matches(&self, plan: &Plan) -> bool {
+        match *self {
+            Selection::Name(ref name) => plan.name() == name,
+            Selection::Tag(ref tag) => plan.has_tag(tag),
+            Selection::All => true,
+        }
+    }
+}
+
+/// A `PlanStore` which keeps plans in memory, so that nothing is kept once
+/// it is dropped.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryStore {
+    /// The plans in the store, by name
+    plans: HashMap<String, Plan>,
+}
+
+impl MemoryStore {
+    /// Returns an empty store.
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+}
+
+impl PlanStore for MemoryStore {
+    fn read(&self, name: &str) -> Result<Plan> {
+        self.plans
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ErrorKind::PlanDoesNotExist(name.into()).into())
+    }
+
+    fn write(&mut self, plan: &Plan) -> Result<()> {
+        self.plans.insert(plan.name().to_owned(), plan.clone());
+        Ok(())
+    }
+
+    fn remove(&mut self, name: &str) -> Result<()> {
+        match self.plans.remove(name) {
+            Some(_) => Ok(()),
+            None => Err(ErrorKind::PlanDoesNotExist(name.into()).into()),
+        }
+    }
+
+    fn list(&self) -> Result<Vec<String>> {
+        let mut names: Vec<_> = self.plans.keys().cloned().collect();
+        names.sort();
+        Ok(names)
+    }
+
+    fn exists(&self, name: &str) -> Result<bool> {
+        Ok(self.plans.contains_key(name))
+    }
+}
+
+/// A place where plans can be stored and retrieved by name.
+///
+/// Only the basic operations need to be implemented; the other methods
+/// (such as `add` and `select`) are provided in terms of them.
+pub trait PlanStore {
+    /// Reads the plan with the given name, returning a `PlanDoesNotExist`
+    /// error if there is no such plan.
+    fn read(&self, name: &str) -> Result<Plan>;
+
+    /// Writes the given plan to the store, overwriting any existing plan
+    /// with the same name.
+    fn write(&mut self, plan: &Plan) -> Result<()>;
+
+    /// Removes the plan with the given name, returning a `PlanDoesNotExist`
+    /// error if there is no such plan.
+    fn remove(&mut self, name: &str) -> Result<()>;
+
+    /// Returns the names of all the plans in the store.
+    fn list(&self) -> Result<Vec<String>>;
+
+    /// Returns whether a plan with the given name exists in the store.
+    fn exists(&self, name: &str) -> Result<bool>;
+
+    /// Reads the plan with the given name, changes it using the given
+    /// function and writes it back, returning the result of the function.
+    ///
+    /// If the function returns an error, the plan is not written. Stores
+    /// which can be used by several programs at once should override this
+    /// method to make sure that the plan isn't changed by anyone else in
+    /// the meantime. The function must not rename the plan.
+    fn update<F, T>(&mut self, name: &str, f: F) -> Result<T>
+        where F: FnOnce(&mut Plan) -> Result<T>
+    {
+        let mut plan = self.read(name)?;
+        let result = f(&mut plan)?;
+
+        if plan.name() != name {
+            bail!("cannot rename plan '{}' while updating it", name);
+        }
+        self.write(&plan)?;
+
+        Ok(result)
+    }
+
+    /// Writes the given plan to the store, returning a `PlanAlreadyExists`
+    /// error if there is already a plan with the same name.
+    fn add(&mut self, plan: &Plan) -> Result<()> {
+        if self.exists(plan.name())? {
+            Err(ErrorKind::PlanAlreadyExists(plan.name().into()).into())
+        } else {
+            self.write(plan)
+        }
+    }
+
+    /// Renames the plan with the given name to `new_name`, returning the
+    /// renamed plan. A `PlanAlreadyExists` error is returned if there is
+    /// already a plan named `new_name`.
+    ///
+    /// The plan is added under its new name and then removed under its old
+    /// one. Stores which keep more than the plans themselves (such as their
+    /// history of changes) should override this method to move that as well.
+    fn rename(&mut self, name: &str, new_name: &str) -> Result<Plan> {
+        let mut plan = self.read(name)?;
+        plan.set_property("name", new_name)?;
+        if new_name != name {
+            self.add(&plan)?;
+            self.remove(name)?;
+        }
+        Ok(plan)
+    }
+
+    /// Adds a plan to the store for each of the given sources (e.g. the
+    /// names of files), read from it by the given function, and returns
+    /// each source along with the plan added from it or what went wrong, in
+    /// order.
+    ///
+    /// A source which can't be read, or whose plan can't be added (e.g.
+    /// because there is already a plan with the same name), doesn't stop
+    /// the others from being added.
+    fn add_all<I, T, F>(&mut self, sources: I, mut read: F) -> Vec<(T, Result<Plan>)>
+        where I: IntoIterator<Item = T>,
+              F: FnMut(&T) -> Result<Plan>
+    {
+        sources.into_iter()
+            .map(|source| {
+                let result = read(&source).and_then(|plan| self.add(&plan).map(|_| plan));
+                (source, result)
+            })
+            .collect()
+    }
+
+    /// Adds a copy of the plan named `src` to the store under the name
+    /// `dest`, with its progress reset (see `Plan::fresh_copy`), returning
+    /// the copy. A `PlanAlreadyExists` error is returned if there is already
+    /// a plan named `dest`.
+    fn copy(&mut self, src: &str, dest: &str) -> Result<Plan> {
+        let copy = self.read(src)?.fresh_copy(dest);
+        self.add(&copy)?;
+        Ok(copy)
+    }
+
+    /// Reads every plan in the store.
+    ///
+    /// Each plan is read separately, so that a single unreadable plan
+    /// doesn't prevent the others from being read.
+    fn plans(&self) -> Result<Vec<Result<Plan>>> {
+        Ok(self.list()?.iter().map(|name| self.read(name)).collect())
+    }
+
+    /// Summarizes every plan in the store (see `PlanSummary`).
+    ///
+    /// As with `plans`, each plan is summarized separately. By default,
+    /// every plan is read in full; stores which can summarize plans more
+    /// cheaply should override this method.
+    fn summaries(&self) -> Result<Vec<Result<PlanSummary>>> {
+        Ok(self.plans()?.into_iter().map(|plan| plan.map(|p| PlanSummary::from(&p))).collect())
+    }
+
+    /// Returns the names of the pinned plans in the store (see
+    /// `Plan::is_pinned`) which aren't archived, in the order of `list`.
+    ///
+    /// Plans which can't be summarized are left out.
+    fn pinned(&self) -> Result<Vec<String>> {
+        Ok(self.summaries()?
+            .into_iter()
+            .filter_map(|s| s.ok())
+            .filter(|s| s.is_pinned() && !s.is_archived())
+            .map(|s| s.name().to_owned())
+            .collect())
+    }
+
+    /// Returns all the plans in the store that are part of the given
+    /// selection.
+    ///
+    /// Selecting a single plan by name will return an error if that plan
+    /// does not exist; selecting plans by tag will simply return an empty
+    /// list if no plans have the tag.
+    fn select(&self, selection: &Selection) -> Result<Vec<Plan>> {
+        match *selection {
+            Selection::Name(ref name) => Ok(vec![self.read(name)?]),
+            Selection::Tag(_) | Selection::All => {
+                let mut selected = Vec::new();
+                for plan in self.plans()? {
+                    let plan = plan?;
+                    if selection.matches(&plan) {
+                        selected.push(plan);
+                    }
+                }
+                Ok(selected)
+            }
+        }
+    }
+
+    /// Changes every plan in the given selection using the given function
+    /// (as with `update`), returning the name of each plan along with the
+    /// result of changing it, ordered by name.
+    ///
+    /// Archived plans are left out, unless a single plan is selected by
+    /// name. Each plan is changed separately, so that a plan which can't be
+    /// read or changed (or for which the function returns an error) doesn't
+    /// prevent the others from being changed: its error is returned in
+    /// place of its result. An error is only returned if the plans can't be
+    /// listed.
+    fn update_each<F, T>(&mut self, selection: &Selection, mut f: F) -> Result<Vec<(String, Result<T>)>>
+        where F: FnMut(&mut Plan) -> Result<T>
+    {
+        let names = match *selection {
+            Selection::Name(ref name) => vec![name.clone()],
+            _ => self.list()?,
+        };
+        let mut results = Vec::new();
+        for name in names {
+            let selected = match self.read(&name) {
+                Ok(plan) => {
+                    selection.matches(&plan) && (!plan.is_archived() || matches!(*selection, Selection::Name(_)))
+                }
+                Err(e) => {
+                    results.push((name, Err(e)));
+                    continue;
+                }
+            };
+            if selected {
+                let result = self.update(&name, &mut f);
+                results.push((name, result));
+            }
+        }
+        Ok(results)
+    }
+
+    /// Searches the entries of every plan in the store (see the `search`
+    /// module), returning the matches ordered by plan name.
+    fn search(&self, query: &Query) -> Result<Vec<SearchHit>> {
+        let mut hits = Vec::new();
+        for plan in self.plans()? {
+            hits.extend(search::search_plan(&plan?, query));
+        }
+        Ok(hits)
+    }
+
+    /// Returns the name of the plan whose name is most similar to the given
+    /// one (ignoring case), for suggesting a plan when a name is misspelled.
+    ///
+    /// Only names which are close enough to be a likely misspelling are
+    /// considered, and `None` is returned if there are none, or if several
+    /// are equally close.
+    fn closest_plan(&self, name: &str) -> Result<Option<String>> {
+        let name = name.to_lowercase();
+        // Allow about one mistake for every three characters
+        let max_distance = (name.chars().count() / 3).max(1);
+        let mut closest = None;
+        let mut ambiguous = false;
+
+        for candidate in self.list()? {
+            let distance = edit_distance(&name, &candidate.to_lowercase());
+            match closest {
+                _ if distance > max_distance => {}
+                Some((_, d)) if distance > d => {}
+                Some((_, d)) if distance == d => ambiguous = true,
+                _ => {
+                    closest = Some((candidate, distance));
+                    ambiguous = false;
+                }
+            }
+        }
+
+        Ok(if ambiguous { None } else { closest.map(|(c, _)| c) })
+    }
+}
+
+/// A change to the plans in a store, as reported to the subscribers of an
+/// `ObservedStore`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum StoreEvent {
+    /// A plan with the given name was added.
+    PlanAdded(String),
+    /// The plan with the given name was removed.
+    PlanRemoved(String),
+    /// The plan with the given name was moved from one entry to another
+    /// (given by their numbers, or `None` for the end of the plan), or read
+    /// through again from the same entry.
+    PlanAdvanced {
+        plan: String,
+        from: Option<usize>,
+        to: Option<usize>,
+    },
+    /// The plan with the given name was changed without moving it (e.g. its
+    /// entries or properties were changed).
+    PlanChanged(String),
+}
+
+/// A function subscribed to the changes made through an `ObservedStore`.
+type Subscriber = Box<dyn FnMut(&StoreEvent)>;
+
+/// A `PlanStore` which wraps another store, calling the functions
+/// subscribed to it (see `subscribe`) with a `StoreEvent` after each change
+/// made through it.
+///
+/// Only changes which are successfully made are reported, and writing or
+/// updating a plan without changing it isn't reported at all. Changes made
+/// directly to the wrapped store (or by other programs) can't be seen.
+pub struct ObservedStore<S> {
+    store: S,
+    subscribers: Vec<Subscriber>,
+}
+
+impl<S: PlanStore> ObservedStore<S> {
+    /// Returns the given store, without any subscribers yet.
+    pub fn new(store: S) -> ObservedStore<S> {
+        ObservedStore {
+            store,
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// Subscribes the given function to the changes made through the store,
+    /// so that it is called with each of them, after any functions
+    /// subscribed before it.
+    pub fn subscribe<F: FnMut(&StoreEvent) + 'static>(&mut self, callback: F) {
+        self.subscribers.push(Box::new(callback));
+    }
+
+    /// Returns the wrapped store.
+    pub fn get_ref(&self) -> &S {
+        &self.store
+    }
+
+    /// Returns the wrapped store, dropping the subscribers.
+    pub fn into_inner(self) -> S {
+        self.store
+    }
+
+    /// Calls every subscriber with the given event.
+    fn notify(&mut self, event: StoreEvent) {
+        for subscriber in &mut self.subscribers {
+            subscriber(&event);
+        }
+    }
+}
+
+impl<S: PlanStore> PlanStore for ObservedStore<S> {
+    fn read(&self, name: &str) -> Result<Plan> {
+        self.store.read(name)
+    }
+
+    fn write(&mut self, plan: &Plan) -> Result<()> {
+        // A plan which exists but can't be read is being replaced
+        let old = if self.store.exists(plan.name())? { Some(self.store.read(plan.name()).ok()) } else { None };
+        self.store.write(plan)?;
+        let event = match old {
+            None => Some(StoreEvent::PlanAdded(plan.name().to_owned())),
+            Some(None) => Some(StoreEvent::PlanChanged(plan.name().to_owned())),
+            Some(Some(old)) => change_event(&old, plan),
+        };
+        if let Some(event) = event {
+            self.notify(event);
+        }
+        Ok(())
+    }
+
+    fn update<F, T>(&mut self, name: &str, f: F) -> Result<T>
+        where F: FnOnce(&mut Plan) -> Result<T>
+    {
+        // Updates are left to the wrapped store, which may lock the plan
+        let mut event = None;
+        let result = self.store.update(name, |plan| {
+            let old = plan.clone();
+            let result = f(plan)?;
+            event = change_event(&old, plan);
+            Ok(result)
+        })?;
+        if let Some(event) = event {
+            self.notify(event);
+        }
+        Ok(result)
+    }
+
+    fn remove(&mut self, name: &str) -> Result<()> {
+        self.store.remove(name)?;
+        self.notify(StoreEvent::PlanRemoved(name.to_owned()));
+        Ok(())
+    }
+
+    fn rename(&mut self, name: &str, new_name: &str) -> Result<Plan> {
+        let plan = self.store.rename(name, new_name)?;
+        if new_name != name {
+            self.notify(StoreEvent::PlanAdded(new_name.to_owned()));
+            self.notify(StoreEvent::PlanRemoved(name.to_owned()));
+        }
+        Ok(plan)
+    }
+
+    fn list(&self) -> Result<Vec<String>> {
+        self.store.list()
+    }
+
+    fn exists(&self, name: &str) -> Result<bool> {
+        self.store.exists(name)
+    }
+
+    fn summaries(&self) -> Result<Vec<Result<PlanSummary>>> {
+        self.store.summaries()
+    }
+}
+
+/// Returns the event for a change from the old version of a plan to the
+/// new one, or `None` if they are the same.
+fn change_event(old: &Plan, new: &Plan) -> Option<StoreEvent> {
+    // The entry number of a plan, or `None` at its end
+    let position = |plan: &Plan| if plan.is_ended() { None } else { Some(plan.current_entry_number()) };
+    let moved = position(old) != position(new) || old.history().len() != new.history().len() ||
+                old.cycles_completed() != new.cycles_completed();
+    if moved {
+        Some(StoreEvent::PlanAdvanced {
+            plan: new.name().to_owned(),
+            from: position(old),
+            to: position(new),
+        })
+    } else if old != new {
+        Some(StoreEvent::PlanChanged(new.name().to_owned()))
+    } else {
+        None
+    }
+}
+
+/// Returns the Levenshtein distance between two strings: the number of
+/// characters which must be inserted, removed or replaced to change one
+/// into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<_> = b.chars().collect();
+    // The distances between the part of `a` seen so far and each prefix of `b`
+    let mut row: Vec<_> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let replaced = diagonal + if ca == cb { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = replaced.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
@@ -0,0 +1,205 @@
+//! The styles used for text output, which can be changed by choosing a
+//! color theme with `--theme` (or the `theme` setting).
+//!
+//! There are two built-in themes: `default`, with a few colors, and `plain`,
+//! which doesn't style anything. Other themes can be defined in the
+//! configuration file (see the `config` module of the library), describing
+//! the style of each kind of text in words, such as `bold red`, `italic`,
+//! `dimmed`, `underline yellow on blue`, `214` (a color from the 256-color
+//! palette) or `#ff8800`. Kinds of text that a theme doesn't mention keep
+//! the style of the default theme. A custom theme can also replace one of
+//! the built-in ones by using the same name.
+
+use std::env;
+
+use ansi_term::{Colour, Style};
+use atty;
+
+use reading::config::{Config, Theme};
+use reading::errors::*;
+
+/// The names of the built-in themes.
+pub const BUILT_IN_THEMES: &[&str] = &["default", "plain"];
+
+/// Describes all the styles that can be used in printing text.
+#[derive(Debug, Clone)]
+pub struct StyleSet {
+    /// Normal text
+    pub normal: Style,
+    /// Title text
+    pub title: Style,
+    /// Description (or label) text
+    pub description: Style,
+    /// Error text
+    pub error: Style,
+    /// Highlighted text (e.g. search matches)
+    pub highlight: Style,
+    /// Muted text (less important details, e.g. schedules)
+    pub muted: Style,
+    /// The filled part of progress bars
+    pub progress: Style,
+}
+
+impl StyleSet {
+    /// Preset for the --no-ansi option (no style)
+    pub fn no_ansi() -> StyleSet {
+        StyleSet {
+            normal: Style::new(),
+            title: Style::new(),
+            description: Style::new(),
+            error: Style::new(),
+            highlight: Style::new(),
+            muted: Style::new(),
+            progress: Style::new(),
+        }
+    }
+
+    /// Preset for the normal "fancy" style
+    fn fancy() -> StyleSet {
+        StyleSet {
+            normal: Style::new(),
+            title: Colour::White.bold(),
+            description: Style::new().italic(),
+            error: Colour::Red.normal(),
+            highlight: Colour::Yellow.bold(),
+            muted: Style::new().dimmed(),
+            progress: Colour::Green.normal(),
+        }
+    }
+
+    /// Returns the style set of the theme with the given name, which is
+    /// either defined in the settings or built in.
+    pub fn named(name: &str, config: &Config) -> Result<StyleSet> {
+        if let Some(theme) = config.themes().get(name) {
+            return StyleSet::from_theme(theme).chain_err(|| format!("invalid theme '{}'", name));
+        }
+
+        match name {
+            "default" => Ok(StyleSet::fancy()),
+            "plain" => Ok(StyleSet::no_ansi()),
+            _ => {
+                let mut names: Vec<_> = config.themes().keys().map(|n| n.as_str()).collect();
+                names.extend(BUILT_IN_THEMES.iter().filter(|n| !config.themes().contains_key(**n)));
+                bail!("unknown theme '{}' (the available themes are {})", name, names.join(", "))
+            }
+        }
+    }
+
+    /// Returns the style set for a theme defined in the settings, using the
+    /// default style for any kind of text the theme doesn't mention.
+    fn from_theme(theme: &Theme) -> Result<StyleSet> {
+        let mut style_set = StyleSet::fancy();
+        for &role in Theme::ROLES {
+            if let Some(description) = theme.style(role) {
+                let style = parse_style(description).chain_err(|| format!("invalid style for '{}'", role))?;
+                *style_set.role_mut(role) = style;
+            }
+        }
+        Ok(style_set)
+    }
+
+    /// Returns the style for the given kind of text (one of `Theme::ROLES`).
+    fn role_mut(&mut self, role: &str) -> &mut Style {
+        match role {
+            "normal" => &mut self.normal,
+            "title" => &mut self.title,
+            "description" => &mut self.description,
+            "error" => &mut self.error,
+            "highlight" => &mut self.highlight,
+            "muted" => &mut self.muted,
+            "progress" => &mut self.progress,
+            _ => unreachable!("unknown style role '{}'", role),
+        }
+    }
+
+    /// Returns the style set to use for output with the given theme,
+    /// falling back to the `no_ansi` preset if fancy output was disabled
+    /// or the terminal doesn't support ANSI escape codes.
+    ///
+    /// The theme is checked even if it won't be used, so that mistakes in
+    /// it don't go unnoticed.
+    pub fn for_terminal(no_ansi: bool, theme: &str, config: &Config) -> Result<StyleSet> {
+        let style_set = StyleSet::named(theme, config)?;
+        if no_ansi || !ansi_supported() {
+            Ok(StyleSet::no_ansi())
+        } else {
+            Ok(style_set)
+        }
+    }
+}
+
+/// Parses a description of a style, such as `bold red on white`.
+fn parse_style(description: &str) -> Result<Style> {
+    let mut style = Style::new();
+    let mut words = description.split_whitespace();
+
+    while let Some(word) = words.next() {
+        let word = word.to_lowercase();
+        style = match word.as_str() {
+            "plain" | "normal" => style,
+            "bold" => style.bold(),
+            "dimmed" | "dim" => style.dimmed(),
+            "italic" => style.italic(),
+            "underline" => style.underline(),
+            "blink" => style.blink(),
+            "reverse" => style.reverse(),
+            "hidden" => style.hidden(),
+            "strikethrough" => style.strikethrough(),
+            "on" => {
+                let colour = words.next().and_then(parse_colour);
+                style.on(colour.ok_or_else(|| format!("expected a color after 'on' in '{}'", description))?)
+            }
+            _ => style.fg(parse_colour(&word).ok_or_else(|| format!("unknown style '{}'", word))?),
+        };
+    }
+    Ok(style)
+}
+
+/// Parses a color, given as a name (e.g. `red`), a number from the
+/// 256-color palette or an RGB value like `#ff8800`.
+fn parse_colour(name: &str) -> Option<Colour> {
+    let name = name.to_lowercase();
+    let colour = match name.as_str() {
+        "black" => Colour::Black,
+        "red" => Colour::Red,
+        "green" => Colour::Green,
+        "yellow" => Colour::Yellow,
+        "blue" => Colour::Blue,
+        "purple" | "magenta" => Colour::Purple,
+        "cyan" => Colour::Cyan,
+        "white" => Colour::White,
+        _ if name.starts_with('#') && name.len() == 7 => {
+            let rgb = u32::from_str_radix(&name[1..], 16).ok()?;
+            Colour::RGB((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+        }
+        _ => Colour::Fixed(name.parse().ok()?),
+    };
+    Some(colour)
+}
+
+/// Returns whether standard output can display ANSI escape codes.
+///
+/// Output that isn't going to a terminal (e.g. a pipe or file) and terminals
+/// with `TERM=dumb` are assumed not to support them.
+fn ansi_supported() -> bool {
+    if !atty::is(atty::Stream::Stdout) {
+        return false;
+    }
+    if env::var("TERM").map(|t| t == "dumb").unwrap_or(false) {
+        return false;
+    }
+    enable_ansi()
+}
+
+/// Tries to enable ANSI escape processing in the Windows console, returning
+/// whether it was successful (it is only possible on Windows 10 and later).
+#[cfg(windows)]
+fn enable_ansi() -> bool {
+    ::ansi_term::enable_ansi_support().is_ok()
+}
+
+/// ANSI escape codes don't need to be enabled anywhere but Windows.
+#[cfg(not(windows))]
+fn enable_ansi() -> bool {
+    true
+}
@@ -0,0 +1,153 @@
+//! The interactive terminal interface used by the `tui` subcommand, which is
+//! only available when the `tui` feature is enabled.
+//!
+//! The interface shows a list of all plans, along with the entries of the
+//! selected plan (with the current entry highlighted), and allows plans to
+//! be advanced or reverted using the keyboard. All changes are saved to the
+//! store immediately.
+
+use std::io::{self, Write};
+
+use termion::{clear, cursor, style, terminal_size};
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::screen::AlternateScreen;
+
+use reading::{Plan, PlanStore};
+use reading::errors::*;
+
+/// The help text shown at the top of the screen.
+const HELP: &str = "up/down: select plan   left/right: previous/next entry   q: quit";
+
+/// The state of the interface.
+struct State {
+    /// All the plans that could be read, sorted by name
+    plans: Vec<Plan>,
+    /// The index of the selected plan in `plans`
+    selected: usize,
+    /// A message to show at the bottom of the screen (e.g. an error)
+    message: String,
+}
+
+impl State {
+    /// Moves the selected plan by the given number of entries (backwards
+    /// if negative) and saves it to the store.
+    ///
+    /// The latest version of the plan in the store is used, in case it was
+    /// changed by someone else in the meantime.
+    fn advance<S: PlanStore>(&mut self, store: &mut S, inc: i32) {
+        let plan = &mut self.plans[self.selected];
+        let result = store.update(plan.name(), |p| {
+            p.next(inc);
+            Ok(p.clone())
+        });
+        self.message = match result {
+            Ok(p) => {
+                *plan = p;
+                String::new()
+            }
+            Err(e) => format!("could not save plan: {}", e),
+        };
+    }
+}
+
+/// Runs the interface on the plans in the given store until the user quits.
+pub fn run<S: PlanStore>(store: &mut S) -> Result<()> {
+    let mut plans = Vec::new();
+    let mut failures = 0;
+    for plan in store.plans()? {
+        match plan {
+            Ok(p) => plans.push(p),
+            Err(_) => failures += 1,
+        }
+    }
+    if plans.is_empty() {
+        bail!("no plans are installed (use `reading add` to add one)");
+    }
+    let mut state = State {
+        plans,
+        selected: 0,
+        message: match failures {
+            0 => String::new(),
+            1 => "1 plan could not be read".into(),
+            n => format!("{} plans could not be read", n),
+        },
+    };
+
+    let stdout = io::stdout()
+        .into_raw_mode()
+        .chain_err(|| "could not set up terminal")?;
+    let mut screen = AlternateScreen::from(stdout);
+    write!(screen, "{}", cursor::Hide).chain_err(|| "could not write to terminal")?;
+    draw(&mut screen, &state)?;
+
+    for key in io::stdin().keys() {
+        match key.chain_err(|| "could not read key")? {
+            Key::Char('q') | Key::Esc | Key::Ctrl('c') => break,
+            Key::Up | Key::Char('k') => state.selected = state.selected.saturating_sub(1),
+            Key::Down | Key::Char('j') => {
+                state.selected = (state.selected + 1).min(state.plans.len() - 1)
+            }
+            Key::Right | Key::Char('n') => state.advance(store, 1),
+            Key::Left | Key::Char('p') => state.advance(store, -1),
+            _ => {}
+        }
+        draw(&mut screen, &state)?;
+    }
+
+    write!(screen, "{}", cursor::Show).chain_err(|| "could not write to terminal")
+}
+
+/// Draws the whole interface.
+fn draw<W: Write>(w: &mut W, state: &State) -> Result<()> {
+    let (width, height) = terminal_size().unwrap_or((80, 24));
+    let width = width as usize;
+    let height = height as usize;
+    let mut lines = Vec::new();
+
+    lines.push(format!("{}{}{}", style::Bold, truncate(HELP, width), style::Reset));
+    lines.push(String::new());
+    for (i, plan) in state.plans.iter().enumerate() {
+        let position = if plan.is_ended() {
+            "end".to_owned()
+        } else {
+            format!("{}/{}", plan.current_entry_number(), plan.len())
+        };
+        let line = truncate(&format!(" {} ({}) ", plan.name(), position), width);
+        if i == state.selected {
+            lines.push(format!("{}{}{}", style::Invert, line, style::Reset));
+        } else {
+            lines.push(line);
+        }
+    }
+    lines.push(String::new());
+
+    // Show as many entries of the selected plan as will fit, keeping the
+    // current entry in view (with a little context before it)
+    let plan = &state.plans[state.selected];
+    let available = height.saturating_sub(lines.len() + 2);
+    let first = plan.current_entry_number().saturating_sub(3).min(plan.len().saturating_sub(available));
+    for (i, entry) in plan.entries().enumerate().skip(first).take(available) {
+        let line = truncate(&format!("{:>5}  {}", i + 1, entry.title()), width);
+        if i + 1 == plan.current_entry_number() {
+            lines.push(format!("{}{}{}", style::Bold, line, style::Reset));
+        } else {
+            lines.push(format!("{}{}{}", style::Faint, line, style::Reset));
+        }
+    }
+
+    write!(w, "{}", clear::All).chain_err(|| "could not write to terminal")?;
+    for (row, line) in lines.iter().enumerate() {
+        write!(w, "{}{}", cursor::Goto(1, row as u16 + 1), line)
+            .chain_err(|| "could not write to terminal")?;
+    }
+    write!(w, "{}{}", cursor::Goto(1, height as u16), truncate(&state.message, width))
+        .chain_err(|| "could not write to terminal")?;
+    w.flush().chain_err(|| "could not write to terminal")
+}
+
+/// Truncates the given text so that it fits in the given width.
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
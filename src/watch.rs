@@ -0,0 +1,76 @@
+//! The `watch` subcommand, which is only available when the `watch` feature
+//! is enabled.
+//!
+//! Watching shows a summary of the plans (the same as `reading list`) and
+//! shows it again whenever a plan file in the plans directory changes, until
+//! it is stopped. In a terminal, the screen is cleared before each summary,
+//! so that it can be kept open (e.g. in a tmux pane) as a live status
+//! display; otherwise (e.g. with `--json`), each summary simply follows the
+//! last one.
+
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use chrono::Local;
+use notify::{self, DebouncedEvent, RecursiveMode, Watcher};
+
+use reading::files;
+use reading::errors::*;
+
+/// How long to wait for changes to the plans directory to settle before
+/// showing the plans again.
+const DEBOUNCE_DELAY: Duration = Duration::from_millis(500);
+
+/// The ANSI escape codes which clear the screen and move the cursor to its
+/// top left corner.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+/// Watches the plans in the given directory, calling `show` to show them
+/// once at the start and again after every change. If `clear` is set, the
+/// screen is cleared every time they are shown.
+pub fn run<F: FnMut() -> Result<()>>(dir: &Path, clear: bool, mut show: F) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::watcher(tx, DEBOUNCE_DELAY).chain_err(|| "could not watch plans directory")?;
+    watcher.watch(dir, RecursiveMode::NonRecursive)
+        .chain_err(|| format!("could not watch plans directory {}", dir.display()))?;
+
+    loop {
+        if clear {
+            print!("{}", CLEAR_SCREEN);
+            println!("Plans in {} at {} (press Ctrl-C to stop)\n",
+                     dir.display(),
+                     Local::now().format("%H:%M:%S"));
+        }
+        // A plan may be changed in a way that makes it unreadable for a
+        // moment, which shouldn't stop the watching
+        if let Err(e) = show() {
+            eprintln!("Error: {}", e);
+        }
+        io::stdout().flush().chain_err(|| "could not write output")?;
+
+        // Wait for a change to one of the plans, ignoring other files in
+        // the directory (such as locks and undo journals)
+        loop {
+            let event = rx.recv().chain_err(|| "stopped watching plans directory")?;
+            if is_relevant(&event) {
+                break;
+            }
+        }
+    }
+}
+
+/// Returns whether an event in the plans directory might have changed a
+/// plan.
+fn is_relevant(event: &DebouncedEvent) -> bool {
+    match *event {
+        DebouncedEvent::Create(ref path) |
+        DebouncedEvent::Write(ref path) |
+        DebouncedEvent::Remove(ref path) => files::is_plan_file(path),
+        // Plans are written by renaming a temporary file over them
+        DebouncedEvent::Rename(ref from, ref to) => files::is_plan_file(from) || files::is_plan_file(to),
+        DebouncedEvent::Rescan => true,
+        _ => false,
+    }
+}